@@ -0,0 +1,17 @@
+use std::process::Command;
+
+fn command_output(cmd: &str, args: &[&str]) -> Option<String> {
+    let out = Command::new(cmd).args(args).output().ok().filter(|o| o.status.success())?;
+    Some(String::from_utf8(out.stdout).ok()?.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+fn main() {
+    // GIT_SHA may be injected by CI when building outside a checkout
+    let git_sha = std::env::var("GIT_SHA").ok().or_else(|| command_output("git", &["rev-parse", "--short=12", "HEAD"])).unwrap_or_else(|| "unknown".into());
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+    let rustc_version = command_output(&rustc, &["--version"]).unwrap_or_else(|| "unknown".into());
+    println!("cargo:rustc-env=GIT_SHA={git_sha}");
+    println!("cargo:rustc-env=RUSTC_VERSION={rustc_version}");
+    println!("cargo:rerun-if-env-changed=GIT_SHA");
+    println!("cargo:rerun-if-changed=../../../.git/HEAD");
+}
@@ -2,6 +2,16 @@
 //!
 //! Evaluates rolling resource metrics & threat volume to decide scale actions.
 //! Conservative scale-in to avoid thrash; proportional scale-out.
+//!
+//! Anti-flapping: the averaged load moves between Low / Normal / High bands with a Schmitt
+//! trigger (entering High needs `> *_scale_out`, leaving it needs `< *_scale_out - hysteresis`;
+//! mirrored for Low). A scale action fires when a band is entered, and again while staying in it
+//! only if the load is a further `hysteresis` past the threshold. No action fires within
+//! `cooldown` of `last_scaled_at`; suppressed evaluations return `NoChange { reason }`.
+//!
+//! Every step is clamped to `max_step` and to the `min_replicas..=max_replicas` range around the
+//! tracked replica count (`set_replicas`); `last_clamp()` explains the most recent clamp, and a
+//! step clamped to zero becomes `NoChange`.
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
@@ -26,6 +36,14 @@ pub struct ScalingThresholds {
     pub memory_scale_in: f32,
     pub scale_out_duration: Duration,
     pub scale_in_duration: Duration,
+    /// Width of the hysteresis band around each threshold (utilization fraction).
+    pub hysteresis: f32,
+    /// Redundancy floor; never recommend fewer replicas.
+    pub min_replicas: u32,
+    /// Budget cap.
+    pub max_replicas: u32,
+    /// Largest change a single decision may recommend.
+    pub max_step: Option<u32>,
 }
 
 impl Default for ScalingThresholds {
@@ -37,60 +55,126 @@ impl Default for ScalingThresholds {
             memory_scale_in: 0.50,
             scale_out_duration: Duration::from_secs(5 * 60),
             scale_in_duration: Duration::from_secs(15 * 60),
+            hysteresis: 0.05,
+            min_replicas: 1,
+            max_replicas: 20,
+            max_step: None,
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum ScalingDecision { ScaleOut(u32), ScaleIn(u32), NoAction }
+pub enum ScalingDecision { ScaleOut(u32), ScaleIn(u32), NoChange { reason: String } }
+
+impl ScalingDecision {
+    fn no_change(reason: impl Into<String>) -> Self { Self::NoChange { reason: reason.into() } }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LoadBand { Low, Normal, High }
 
 pub struct AutoScaler {
     thresholds: ScalingThresholds,
     metrics_history: Arc<RwLock<Vec<(Instant, ResourceMetrics)>>>,
-    last_scale_action: Arc<RwLock<Option<Instant>>>,
+    last_scaled_at: Arc<RwLock<Option<Instant>>>,
+    band: Arc<RwLock<LoadBand>>,
+    replicas: Arc<RwLock<u32>>,
+    last_clamp: Arc<RwLock<Option<String>>>,
     cooldown_period: Duration,
 }
 
 impl AutoScaler {
     pub fn new(thresholds: ScalingThresholds) -> Self {
-        Self { thresholds, metrics_history: Arc::new(RwLock::new(Vec::new())), last_scale_action: Arc::new(RwLock::new(None)), cooldown_period: Duration::from_secs(5 * 60) }
+        let replicas = Arc::new(RwLock::new(thresholds.min_replicas));
+        Self { thresholds, metrics_history: Arc::new(RwLock::new(Vec::new())), last_scaled_at: Arc::new(RwLock::new(None)), band: Arc::new(RwLock::new(LoadBand::Normal)), replicas, last_clamp: Arc::new(RwLock::new(None)), cooldown_period: Duration::from_secs(5 * 60) }
     }
 
-    pub async fn record_metrics(&self, m: ResourceMetrics) -> Result<()> {
+    /// Observed replica count. Decisions also advance it, assuming they get applied.
+    pub async fn set_replicas(&self, n: u32) { *self.replicas.write().await = n; }
+    pub async fn replicas(&self) -> u32 { *self.replicas.read().await }
+
+    /// Why the most recent scale decision was reduced, if it was.
+    pub async fn last_clamp(&self) -> Option<String> { self.last_clamp.read().await.clone() }
+
+    /// Minimum time between two scale actions (default 5m).
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self { self.cooldown_period = cooldown; self }
+
+    pub async fn last_scaled_at(&self) -> Option<Instant> { *self.last_scaled_at.read().await }
+
+    pub async fn record_metrics(&self, m: ResourceMetrics) -> Result<()> { self.record_metrics_at(Instant::now(), m).await }
+
+    pub async fn record_metrics_at(&self, now: Instant, m: ResourceMetrics) -> Result<()> {
         let mut h = self.metrics_history.write().await;
-        h.push((Instant::now(), m));
-        let cutoff = Instant::now() - Duration::from_secs(30 * 60);
+        h.push((now, m));
+        let cutoff = now.checked_sub(Duration::from_secs(30 * 60)).unwrap_or(now);
         h.retain(|(t, _)| *t > cutoff);
         Ok(())
     }
 
-    pub async fn evaluate(&self) -> Result<ScalingDecision> {
-        if let Some(last) = *self.last_scale_action.read().await { if last.elapsed() < self.cooldown_period { return Ok(ScalingDecision::NoAction); } }
+    pub async fn evaluate(&self) -> Result<ScalingDecision> { self.evaluate_at(Instant::now()).await }
+
+    pub async fn evaluate_at(&self, now: Instant) -> Result<ScalingDecision> {
         let h = self.metrics_history.read().await;
-        if h.is_empty() { return Ok(ScalingDecision::NoAction); }
-        if self.should_scale_out(&h) { let n = self.calculate_scale_out_amount(&h); *self.last_scale_action.write().await = Some(Instant::now()); return Ok(ScalingDecision::ScaleOut(n)); }
-        if self.should_scale_in(&h) { let n = self.calculate_scale_in_amount(&h); *self.last_scale_action.write().await = Some(Instant::now()); return Ok(ScalingDecision::ScaleIn(n)); }
-        Ok(ScalingDecision::NoAction)
+        if h.is_empty() { return Ok(ScalingDecision::no_change("no metrics")); }
+        let t = &self.thresholds;
+        let (out_cpu, out_mem) = Self::averages(&Self::recent(&h, now, t.scale_out_duration));
+        let (in_cpu, in_mem) = Self::averages(&Self::recent(&h, now, t.scale_in_duration));
+        let mut band = self.band.write().await;
+        // Schmitt trigger: thresholds to enter a band, thresholds minus the hysteresis to stay in it
+        let next = match *band {
+            LoadBand::High if out_cpu > t.cpu_scale_out - t.hysteresis || out_mem > t.memory_scale_out - t.hysteresis => LoadBand::High,
+            LoadBand::Low if in_cpu < t.cpu_scale_in + t.hysteresis && in_mem < t.memory_scale_in + t.hysteresis => LoadBand::Low,
+            _ if out_cpu > t.cpu_scale_out || out_mem > t.memory_scale_out => LoadBand::High,
+            _ if in_cpu < t.cpu_scale_in && in_mem < t.memory_scale_in => LoadBand::Low,
+            _ => LoadBand::Normal,
+        };
+        let wanted = match next {
+            LoadBand::Normal => { *band = next; return Ok(ScalingDecision::no_change("load within thresholds")); }
+            _ if next != *band => true,
+            LoadBand::High => out_cpu > t.cpu_scale_out + t.hysteresis || out_mem > t.memory_scale_out + t.hysteresis,
+            LoadBand::Low => in_cpu < t.cpu_scale_in - t.hysteresis && in_mem < t.memory_scale_in - t.hysteresis,
+        };
+        if !wanted { return Ok(ScalingDecision::no_change(format!("holding: {next:?} load within hysteresis band"))); }
+        let mut last = self.last_scaled_at.write().await;
+        if let Some(at) = *last {
+            let since = now.saturating_duration_since(at);
+            if since < self.cooldown_period { return Ok(ScalingDecision::no_change(format!("cooldown: {}s remaining", (self.cooldown_period - since).as_secs()))); }
+        }
+        let out = next == LoadBand::High;
+        let wanted = if out { self.calculate_scale_out_amount(&h, now) } else { self.calculate_scale_in_amount(&h) };
+        let mut replicas = self.replicas.write().await;
+        let (step, clamp) = self.clamp_step(*replicas, wanted, out);
+        *self.last_clamp.write().await = clamp.clone();
+        if step == 0 { return Ok(ScalingDecision::no_change(clamp.unwrap_or_default())); }
+        if let Some(c) = &clamp { tracing::info!(wanted, step, replicas=*replicas, clamp=%c, "autoscale decision clamped"); }
+        // the band only advances once the action is taken, so a suppressed crossing fires afterwards
+        *band = next;
+        *last = Some(now);
+        Ok(if out { *replicas += step; ScalingDecision::ScaleOut(step) } else { *replicas -= step; ScalingDecision::ScaleIn(step) })
     }
 
-    fn should_scale_out(&self, h: &[(Instant, ResourceMetrics)]) -> bool {
-        let recent = self.get_recent(h, self.thresholds.scale_out_duration);
-        if recent.is_empty() { return false; }
-        let avg_cpu = recent.iter().map(|m| m.cpu_utilization).sum::<f32>() / recent.len() as f32;
-        let avg_mem = recent.iter().map(|m| m.memory_utilization).sum::<f32>() / recent.len() as f32;
-        avg_cpu > self.thresholds.cpu_scale_out || avg_mem > self.thresholds.memory_scale_out
+    /// Bound `wanted` by `max_step` and the replica range; returns the step and why it shrank.
+    fn clamp_step(&self, replicas: u32, wanted: u32, out: bool) -> (u32, Option<String>) {
+        let t = &self.thresholds;
+        let mut step = wanted;
+        let mut why = None;
+        if let Some(max) = t.max_step { if step > max { step = max; why = Some(format!("max_step {max}")); } }
+        let room = if out { t.max_replicas.saturating_sub(replicas) } else { replicas.saturating_sub(t.min_replicas) };
+        if step > room {
+            step = room;
+            why = Some(if out { format!("max_replicas {} (at {replicas})", t.max_replicas) } else { format!("min_replicas {} (at {replicas})", t.min_replicas) });
+        }
+        (step, why.map(|w| format!("clamped {wanted} -> {step} by {w}")))
     }
 
-    fn should_scale_in(&self, h: &[(Instant, ResourceMetrics)]) -> bool {
-        let recent = self.get_recent(h, self.thresholds.scale_in_duration);
-        if recent.is_empty() { return false; }
-        let avg_cpu = recent.iter().map(|m| m.cpu_utilization).sum::<f32>() / recent.len() as f32;
-        let avg_mem = recent.iter().map(|m| m.memory_utilization).sum::<f32>() / recent.len() as f32;
-        avg_cpu < self.thresholds.cpu_scale_in && avg_mem < self.thresholds.memory_scale_in
+    fn averages(recent: &[ResourceMetrics]) -> (f32, f32) {
+        if recent.is_empty() { return (f32::NAN, f32::NAN); } // NaN never crosses a threshold
+        let n = recent.len() as f32;
+        (recent.iter().map(|m| m.cpu_utilization).sum::<f32>() / n, recent.iter().map(|m| m.memory_utilization).sum::<f32>() / n)
     }
 
-    fn calculate_scale_out_amount(&self, h: &[(Instant, ResourceMetrics)]) -> u32 {
-        let recent = self.get_recent(h, Duration::from_secs(60));
+    fn calculate_scale_out_amount(&self, h: &[(Instant, ResourceMetrics)], now: Instant) -> u32 {
+        let recent = Self::recent(h, now, Duration::from_secs(60));
         if recent.is_empty() { return 1; }
         let avg_cpu = recent.iter().map(|m| m.cpu_utilization).sum::<f32>() / recent.len() as f32;
         if avg_cpu > 0.95 { 3 } else if avg_cpu > 0.85 { 2 } else { 1 }
@@ -98,9 +182,9 @@ impl AutoScaler {
 
     fn calculate_scale_in_amount(&self, _h: &[(Instant, ResourceMetrics)]) -> u32 { 1 }
 
-    fn get_recent(&self, h: &[(Instant, ResourceMetrics)], dur: Duration) -> Vec<ResourceMetrics> {
-        let cutoff = Instant::now() - dur;
-        h.iter().filter(|(t, _)| *t > cutoff).map(|(_, m)| m.clone()).collect()
+    fn recent(h: &[(Instant, ResourceMetrics)], now: Instant, dur: Duration) -> Vec<ResourceMetrics> {
+        let cutoff = now.checked_sub(dur);
+        h.iter().filter(|(t, _)| !matches!(cutoff, Some(c) if *t <= c)).map(|(_, m)| m.clone()).collect()
     }
 }
 
@@ -114,4 +198,66 @@ mod tests {
         for _ in 0..5 { scaler.record_metrics(ResourceMetrics { cpu_utilization: 0.9, memory_utilization: 0.4, network_throughput: 0.0, threat_volume: 0, timestamp: 0 }).await.unwrap(); }
         match scaler.evaluate().await.unwrap() { ScalingDecision::ScaleOut(n) => assert!(n >= 1), _ => panic!("expected scale out") }
     }
+
+    fn cpu(cpu_utilization: f32) -> ResourceMetrics { ResourceMetrics { cpu_utilization, memory_utilization: 0.4, network_throughput: 0.0, threat_volume: 0, timestamp: 0 } }
+
+    #[tokio::test]
+    async fn oscillation_near_threshold_scales_once() {
+        let scaler = AutoScaler::new(ScalingThresholds::default()).with_cooldown(Duration::from_secs(60));
+        let start = Instant::now();
+        let mut actions = Vec::new();
+        for i in 0..120u64 { // 1h of samples every 30s, flipping around the 0.80 scale-out threshold
+            let now = start + Duration::from_secs(30 * i);
+            scaler.record_metrics_at(now, cpu(if i % 2 == 0 { 0.83 } else { 0.79 })).await.unwrap();
+            let d = scaler.evaluate_at(now).await.unwrap();
+            if !matches!(d, ScalingDecision::NoChange { .. }) { actions.push((i, d)); }
+        }
+        assert_eq!(actions.len(), 1, "{actions:?}");
+        assert!(matches!(actions[0].1, ScalingDecision::ScaleOut(_)));
+    }
+
+    #[tokio::test]
+    async fn cooldown_suppresses_and_reports_reason() {
+        let t = ScalingThresholds { scale_out_duration: Duration::from_secs(60), ..Default::default() };
+        let scaler = AutoScaler::new(t).with_cooldown(Duration::from_secs(300));
+        let start = Instant::now();
+        scaler.record_metrics_at(start, cpu(0.97)).await.unwrap();
+        assert!(matches!(scaler.evaluate_at(start).await.unwrap(), ScalingDecision::ScaleOut(3)));
+        assert_eq!(scaler.last_scaled_at().await, Some(start));
+        // still far above the band: would scale again, but not inside the cooldown
+        let soon = start + Duration::from_secs(120);
+        scaler.record_metrics_at(soon, cpu(0.97)).await.unwrap();
+        match scaler.evaluate_at(soon).await.unwrap() { ScalingDecision::NoChange { reason } => assert!(reason.starts_with("cooldown"), "{reason}"), d => panic!("unexpected {d:?}") }
+        let later = start + Duration::from_secs(301);
+        scaler.record_metrics_at(later, cpu(0.97)).await.unwrap();
+        assert!(matches!(scaler.evaluate_at(later).await.unwrap(), ScalingDecision::ScaleOut(_)));
+    }
+
+    #[tokio::test]
+    async fn spike_is_capped_by_max_step() {
+        let t = ScalingThresholds { max_step: Some(1), ..Default::default() };
+        let scaler = AutoScaler::new(t);
+        scaler.set_replicas(2).await;
+        scaler.record_metrics(cpu(0.99)).await.unwrap();
+        assert_eq!(scaler.evaluate().await.unwrap(), ScalingDecision::ScaleOut(1));
+        assert_eq!(scaler.replicas().await, 3);
+        assert!(scaler.last_clamp().await.unwrap().contains("max_step 1"));
+    }
+
+    #[tokio::test]
+    async fn scale_in_respects_min_replicas() {
+        let t = ScalingThresholds { min_replicas: 2, ..Default::default() };
+        let scaler = AutoScaler::new(t).with_cooldown(Duration::ZERO);
+        scaler.set_replicas(3).await;
+        let start = Instant::now();
+        scaler.record_metrics_at(start, cpu(0.05)).await.unwrap();
+        assert_eq!(scaler.evaluate_at(start).await.unwrap(), ScalingDecision::ScaleIn(1));
+        assert_eq!(scaler.last_clamp().await, None);
+        for i in 1..5 {
+            let now = start + Duration::from_secs(60 * i);
+            scaler.record_metrics_at(now, cpu(0.05)).await.unwrap();
+            match scaler.evaluate_at(now).await.unwrap() { ScalingDecision::NoChange { reason } => assert!(reason.contains("min_replicas 2"), "{reason}"), d => panic!("unexpected {d:?}") }
+        }
+        assert_eq!(scaler.replicas().await, 2);
+    }
 }
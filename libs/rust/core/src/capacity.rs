@@ -0,0 +1,153 @@
+//! Capacity planning report served at `/capacity`.
+//!
+//! OTel instruments cannot be read back in-process, so hot paths additionally feed `CAPACITY`:
+//! `record` for throughput, `record_latency` for latency samples and `set_queue_depth` for
+//! bounded queues. `report()` turns the last `SWARM_CAPACITY_WINDOW_SECS` (default 60) into
+//! per-second rates and p99 latencies, with headroom against `SWARM_CAPACITY_LIMITS`, e.g.
+//! `events_ingested=5000,detections=500,consensus_rounds=20` (per second).
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+
+pub const EVENTS_INGESTED: &str = "events_ingested";
+pub const DETECTIONS: &str = "detections";
+pub const CONSENSUS_ROUNDS: &str = "consensus_rounds";
+
+const MAX_LATENCY_SAMPLES: usize = 1024;
+
+pub static CAPACITY: Lazy<CapacityTracker> = Lazy::new(|| {
+    let window = std::env::var("SWARM_CAPACITY_WINDOW_SECS").ok().and_then(|v| v.parse().ok()).unwrap_or(60u64).max(1);
+    let limits = std::env::var("SWARM_CAPACITY_LIMITS").map(|s| parse_limits(&s)).unwrap_or_default();
+    CapacityTracker::new(Duration::from_secs(window), limits)
+});
+
+/// `name=per_sec,...`; malformed entries are skipped.
+pub fn parse_limits(spec: &str) -> HashMap<String, f64> {
+    spec.split(',').filter_map(|kv| {
+        let (k, v) = kv.split_once('=')?;
+        Some((k.trim().to_string(), v.trim().parse().ok()?))
+    }).collect()
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RateReport {
+    pub per_sec: f64,
+    pub limit_per_sec: Option<f64>,
+    pub headroom_ratio: Option<f64>, // 1 - rate/limit; negative when over the limit
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct QueueReport { pub depth: u64, pub capacity: u64, pub headroom_ratio: f64 }
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CapacityReport {
+    pub window_secs: f64,
+    pub rates: BTreeMap<String, RateReport>,
+    pub p99_latency_ms: BTreeMap<String, f64>,
+    pub queues: BTreeMap<String, QueueReport>,
+    /// Smallest headroom across limited rates and queues (None when nothing is limited).
+    pub min_headroom_ratio: Option<f64>,
+}
+
+pub struct CapacityTracker {
+    window: Duration,
+    started: Instant,
+    limits: HashMap<String, f64>,
+    rates: Mutex<HashMap<String, VecDeque<(Instant, u64)>>>,
+    latencies: Mutex<HashMap<String, VecDeque<(Instant, f64)>>>,
+    queues: Mutex<HashMap<String, (u64, u64)>>,
+}
+
+impl CapacityTracker {
+    pub fn new(window: Duration, limits: HashMap<String, f64>) -> Self {
+        Self { window, started: Instant::now(), limits, rates: Mutex::new(HashMap::new()), latencies: Mutex::new(HashMap::new()), queues: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn record(&self, metric: &str, n: u64) {
+        let now = Instant::now();
+        let mut rates = self.rates.lock();
+        let q = rates.entry(metric.to_string()).or_default();
+        q.push_back((now, n));
+        prune(q, now, self.window);
+    }
+
+    pub fn record_latency(&self, metric: &str, ms: f64) {
+        let now = Instant::now();
+        let mut lat = self.latencies.lock();
+        let q = lat.entry(metric.to_string()).or_default();
+        q.push_back((now, ms));
+        if q.len() > MAX_LATENCY_SAMPLES { q.pop_front(); }
+        prune(q, now, self.window);
+    }
+
+    pub fn set_queue_depth(&self, queue: &str, depth: u64, capacity: u64) { self.queues.lock().insert(queue.to_string(), (depth, capacity)); }
+
+    pub fn report(&self) -> CapacityReport {
+        let now = Instant::now();
+        // until a full window has elapsed, rate over the time actually observed (at least 1s)
+        let span = now.duration_since(self.started).min(self.window).max(Duration::from_secs(1)).as_secs_f64();
+        let mut rates = BTreeMap::new();
+        for (name, q) in self.rates.lock().iter_mut() {
+            prune(q, now, self.window);
+            let per_sec = q.iter().map(|(_, n)| *n).sum::<u64>() as f64 / span;
+            let limit = self.limits.get(name).copied().filter(|l| *l > 0.0);
+            rates.insert(name.clone(), RateReport { per_sec, limit_per_sec: limit, headroom_ratio: limit.map(|l| 1.0 - per_sec / l) });
+        }
+        for (name, limit) in self.limits.iter().filter(|(_, l)| **l > 0.0) {
+            rates.entry(name.clone()).or_insert(RateReport { per_sec: 0.0, limit_per_sec: Some(*limit), headroom_ratio: Some(1.0) });
+        }
+        let mut p99_latency_ms = BTreeMap::new();
+        for (name, q) in self.latencies.lock().iter_mut() {
+            prune(q, now, self.window);
+            if let Some(p) = p99(q.iter().map(|(_, v)| *v).collect()) { p99_latency_ms.insert(name.clone(), p); }
+        }
+        let queues: BTreeMap<String, QueueReport> = self.queues.lock().iter().map(|(name, (depth, capacity))| {
+            let headroom_ratio = if *capacity == 0 { 0.0 } else { 1.0 - *depth as f64 / *capacity as f64 };
+            (name.clone(), QueueReport { depth: *depth, capacity: *capacity, headroom_ratio })
+        }).collect();
+        let min_headroom_ratio = rates.values().filter_map(|r| r.headroom_ratio).chain(queues.values().map(|q| q.headroom_ratio)).reduce(f64::min);
+        CapacityReport { window_secs: span, rates, p99_latency_ms, queues, min_headroom_ratio }
+    }
+}
+
+fn prune<T>(q: &mut VecDeque<(Instant, T)>, now: Instant, window: Duration) {
+    while q.front().is_some_and(|(t, _)| now.duration_since(*t) > window) { q.pop_front(); }
+}
+
+/// Nearest-rank 99th percentile.
+fn p99(mut samples: Vec<f64>) -> Option<f64> {
+    if samples.is_empty() { return None; }
+    samples.sort_by(|a, b| a.total_cmp(b));
+    let rank = ((samples.len() as f64) * 0.99).ceil() as usize;
+    samples.get(rank.saturating_sub(1)).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_reflects_driven_load_and_headroom() {
+        let tracker = CapacityTracker::new(Duration::from_secs(10), parse_limits("events_ingested=1000, detections=50, consensus_rounds=5, bogus"));
+        for i in 0..200 {
+            tracker.record(EVENTS_INGESTED, 1);
+            tracker.record_latency("ingest_e2e", if i == 199 { 250.0 } else { (i % 10) as f64 });
+            if i % 20 == 0 { tracker.record(DETECTIONS, 1); }
+        }
+        tracker.set_queue_depth("consensus_events", 64, 256);
+        let r = tracker.report();
+        // load was driven in well under a second, so rates are over the 1s floor
+        assert!((r.rates[EVENTS_INGESTED].per_sec - 200.0).abs() < 1e-9);
+        assert!((r.rates[EVENTS_INGESTED].headroom_ratio.unwrap() - 0.8).abs() < 1e-9);
+        assert!((r.rates[DETECTIONS].headroom_ratio.unwrap() - 0.8).abs() < 1e-9);
+        assert_eq!(r.rates[CONSENSUS_ROUNDS], RateReport { per_sec: 0.0, limit_per_sec: Some(5.0), headroom_ratio: Some(1.0) });
+        assert_eq!(r.p99_latency_ms["ingest_e2e"], 9.0);
+        assert_eq!(r.queues["consensus_events"].headroom_ratio, 0.75);
+        assert_eq!(r.min_headroom_ratio, Some(0.75));
+        let json = serde_json::to_value(&r).unwrap();
+        assert_eq!(json["rates"]["events_ingested"]["limit_per_sec"], 1000.0);
+    }
+}
@@ -0,0 +1,105 @@
+//! Clock-skew monitoring from peer timestamps.
+//!
+//! Services feed timestamps carried by peer messages (gossip envelopes, consensus events)
+//! into `observe_peer_timestamp`; the estimated offset is the median of the last
+//! `SWARM_CLOCK_SAMPLES` (default 16) `peer_ts - local_now` samples, so one delayed message
+//! does not swing it. Exported as `swarm_clock_offset_ms`; offsets beyond
+//! `SWARM_MAX_CLOCK_SKEW_MS` (default 500) log a warning, bump `swarm_clock_skew_warnings_total`
+//! and - with `SWARM_READY_REQUIRE_CLOCK_SYNC=1` - fail `/ready`.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use once_cell::sync::Lazy;
+use opentelemetry::metrics::Counter;
+use parking_lot::Mutex;
+
+pub static CLOCK_HEALTH: Lazy<ClockHealth> = Lazy::new(|| {
+    let max_skew: i64 = std::env::var("SWARM_MAX_CLOCK_SKEW_MS").ok().and_then(|v| v.parse().ok()).unwrap_or(500);
+    let samples: usize = std::env::var("SWARM_CLOCK_SAMPLES").ok().and_then(|v| v.parse().ok()).unwrap_or(16);
+    ClockHealth::new(max_skew, samples)
+});
+
+pub struct ClockHealth {
+    max_skew_ms: i64,
+    window: usize,
+    samples: Mutex<VecDeque<i64>>,
+    offset_ms: Arc<AtomicI64>,
+    warnings: Counter<u64>,
+}
+
+impl ClockHealth {
+    pub fn new(max_skew_ms: i64, window: usize) -> Self {
+        let meter = opentelemetry::global::meter("swarm_clock");
+        let offset_ms = Arc::new(AtomicI64::new(0));
+        let gauge_src = offset_ms.clone();
+        let _gauge = meter.i64_observable_gauge("swarm_clock_offset_ms")
+            .with_description("Estimated local clock offset vs peers (ms, positive = peers ahead)")
+            .with_callback(move |obs| obs.observe(gauge_src.load(Ordering::Relaxed), &[]))
+            .init();
+        let warnings = meter.u64_counter("swarm_clock_skew_warnings_total").with_description("Offset estimates beyond SWARM_MAX_CLOCK_SKEW_MS").init();
+        Self { max_skew_ms: max_skew_ms.abs(), window: window.max(1), samples: Mutex::new(VecDeque::new()), offset_ms, warnings }
+    }
+
+    /// Feed a peer's wall-clock timestamp (unix ms). Returns true when the new estimate exceeds the threshold.
+    pub fn observe_peer_timestamp(&self, peer_ts_ms: i64) -> bool {
+        self.observe_offset(peer_ts_ms - chrono::Utc::now().timestamp_millis())
+    }
+
+    /// Feed a raw offset sample (ms). Returns true when the new estimate exceeds the threshold.
+    pub fn observe_offset(&self, sample_ms: i64) -> bool {
+        let estimate = {
+            let mut s = self.samples.lock();
+            s.push_back(sample_ms);
+            if s.len() > self.window { s.pop_front(); }
+            let mut sorted: Vec<i64> = s.iter().copied().collect();
+            sorted.sort_unstable();
+            sorted[sorted.len() / 2]
+        };
+        self.offset_ms.store(estimate, Ordering::Relaxed);
+        let skewed = estimate.abs() > self.max_skew_ms;
+        if skewed {
+            self.warnings.add(1, &[]);
+            tracing::warn!(offset_ms = estimate, max_skew_ms = self.max_skew_ms, "clock skew beyond threshold");
+        }
+        skewed
+    }
+
+    /// Current offset estimate (ms); this is what the gauge reports.
+    pub fn offset_ms(&self) -> i64 { self.offset_ms.load(Ordering::Relaxed) }
+
+    /// True while the estimate is within `max_skew_ms` (also true before any sample).
+    pub fn within_threshold(&self) -> bool { self.offset_ms().abs() <= self.max_skew_ms }
+}
+
+/// Whether `/ready` should currently report ready as far as clock health is concerned.
+pub fn clock_ready() -> bool {
+    let required = std::env::var("SWARM_READY_REQUIRE_CLOCK_SYNC").ok().map(|v| v=="1" || v.eq_ignore_ascii_case("true")).unwrap_or(false);
+    !required || CLOCK_HEALTH.within_threshold()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simulated_offset_reflected_and_warns_past_threshold() {
+        let ch = ClockHealth::new(500, 3);
+        assert!(!ch.observe_offset(120));
+        assert_eq!(ch.offset_ms(), 120);
+        assert!(ch.within_threshold());
+        // inject a clock that runs 900ms behind the peers
+        assert!(ch.observe_offset(900));
+        assert!(ch.observe_offset(900), "median of [120,900,900] is past threshold");
+        assert_eq!(ch.offset_ms(), 900);
+        assert!(!ch.within_threshold());
+    }
+
+    #[test]
+    fn single_outlier_does_not_trip() {
+        let ch = ClockHealth::new(500, 5);
+        for s in [10, -5, 3000, 8] { ch.observe_offset(s); }
+        assert!(ch.within_threshold());
+        assert!(!ch.observe_peer_timestamp(chrono::Utc::now().timestamp_millis() - 2000));
+    }
+}
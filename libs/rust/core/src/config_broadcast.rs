@@ -0,0 +1,153 @@
+//! Config update fan-out to in-process subscribers.
+//!
+//! Backed by `tokio::sync::broadcast` (capacity `SWARM_CONFIG_BROADCAST_CAP`, default 16).
+//! A subscriber that falls behind the channel would normally get `RecvError::Lagged` and
+//! silently skip updates; `ConfigSubscriber::recv` instead counts the lag
+//! (`config_broadcast_lag_total`), drops the stale backlog and re-syncs to the latest config.
+//!
+//! `subscribe_config_changes` carries only what changed: each publish is diffed against the
+//! previous config and a `ConfigChange` with just the changed fields is sent. Identical reloads
+//! and the very first config (nothing to diff against; read `latest_config`) send nothing.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use once_cell::sync::Lazy;
+use opentelemetry::metrics::Counter;
+use parking_lot::RwLock;
+use tokio::sync::broadcast::{self, error::RecvError};
+use crate::DynamicConfig;
+
+static LAG_TOTAL: Lazy<Counter<u64>> = Lazy::new(|| {
+    opentelemetry::global::meter("swarm_config")
+        .u64_counter("config_broadcast_lag_total")
+        .with_description("Config subscribers that lagged and re-synced to the latest config")
+        .init()
+});
+
+static CONFIG_BROADCAST: Lazy<ConfigBroadcast> = Lazy::new(|| {
+    let cap: usize = std::env::var("SWARM_CONFIG_BROADCAST_CAP").ok().and_then(|v| v.parse().ok()).unwrap_or(16);
+    ConfigBroadcast::new(cap)
+});
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change<T> { pub old: T, pub new: T }
+
+/// Fields that differ between two consecutive configs; unchanged fields are `None`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigChange {
+    pub service_name: Option<Change<Option<String>>>,
+    pub nats_url: Option<Change<Option<String>>>,
+    pub log_level: Option<Change<Option<String>>>,
+    pub config_version: Option<Change<Option<String>>>,
+    pub config_signature: Option<Change<Option<String>>>,
+    pub disabled_rules: Option<Change<Option<Vec<String>>>>,
+    pub rule_severity_overrides: Option<Change<Option<HashMap<String, String>>>>,
+}
+
+impl ConfigChange {
+    /// `None` when nothing changed.
+    pub fn between(old: &DynamicConfig, new: &DynamicConfig) -> Option<Self> {
+        macro_rules! diff { ($($f:ident),*) => { Self { $($f: (old.$f != new.$f).then(|| Change { old: old.$f.clone(), new: new.$f.clone() }),)* } } }
+        let change = diff!(service_name, nats_url, log_level, config_version, config_signature, disabled_rules, rule_severity_overrides);
+        (change != Self::default()).then_some(change)
+    }
+}
+
+pub struct ConfigBroadcast {
+    tx: broadcast::Sender<DynamicConfig>,
+    changes: broadcast::Sender<ConfigChange>,
+    latest: Arc<RwLock<Option<DynamicConfig>>>,
+}
+
+impl ConfigBroadcast {
+    pub fn new(cap: usize) -> Self {
+        let (tx, _) = broadcast::channel(cap.max(1));
+        let (changes, _) = broadcast::channel(cap.max(1));
+        Self { tx, changes, latest: Arc::new(RwLock::new(None)) }
+    }
+
+    pub fn publish(&self, cfg: DynamicConfig) {
+        let previous = self.latest.write().replace(cfg.clone());
+        if let Some(change) = previous.and_then(|old| ConfigChange::between(&old, &cfg)) { let _ = self.changes.send(change); }
+        let _ = self.tx.send(cfg); // no subscribers is fine
+    }
+
+    pub fn subscribe_changes(&self) -> broadcast::Receiver<ConfigChange> { self.changes.subscribe() }
+
+    pub fn subscribe(&self) -> ConfigSubscriber {
+        ConfigSubscriber { rx: self.tx.subscribe(), latest: self.latest.clone(), lag_events: 0 }
+    }
+
+    pub fn latest(&self) -> Option<DynamicConfig> { self.latest.read().clone() }
+}
+
+pub struct ConfigSubscriber {
+    rx: broadcast::Receiver<DynamicConfig>,
+    latest: Arc<RwLock<Option<DynamicConfig>>>,
+    lag_events: u64,
+}
+
+impl ConfigSubscriber {
+    /// Next config update; after a lag this yields the current config instead of the
+    /// oldest retained (stale) one. Returns `None` once the broadcaster is gone.
+    pub async fn recv(&mut self) -> Option<DynamicConfig> {
+        match self.rx.recv().await {
+            Ok(cfg) => Some(cfg),
+            Err(RecvError::Lagged(skipped)) => {
+                self.lag_events += 1;
+                LAG_TOTAL.add(1, &[]);
+                tracing::warn!(skipped, "config subscriber lagged - re-syncing to latest config");
+                self.rx = self.rx.resubscribe();
+                self.latest.read().clone()
+            }
+            Err(RecvError::Closed) => None,
+        }
+    }
+
+    /// Number of times this subscriber lagged and re-synced.
+    pub fn lag_events(&self) -> u64 { self.lag_events }
+}
+
+/// Publish a config to every process-wide subscriber.
+pub fn broadcast_config(cfg: &DynamicConfig) { CONFIG_BROADCAST.publish(cfg.clone()); }
+
+/// Subscribe to process-wide config updates.
+pub fn subscribe_config() -> ConfigSubscriber { CONFIG_BROADCAST.subscribe() }
+
+/// Subscribe to per-field diffs of process-wide config updates.
+pub fn subscribe_config_changes() -> broadcast::Receiver<ConfigChange> { CONFIG_BROADCAST.subscribe_changes() }
+
+/// Latest broadcast config, for subscribers that want to re-sync on demand.
+pub fn latest_config() -> Option<DynamicConfig> { CONFIG_BROADCAST.latest() }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cfg(version: usize) -> DynamicConfig { DynamicConfig { config_version: Some(version.to_string()), ..Default::default() } }
+
+    #[tokio::test]
+    async fn slow_subscriber_resyncs_after_lag() {
+        let bc = ConfigBroadcast::new(2);
+        let mut slow = bc.subscribe();
+        for v in 0..5 { bc.publish(cfg(v)); }
+        let got = slow.recv().await.expect("config");
+        assert_eq!(got.config_version.as_deref(), Some("4"), "must re-sync to the latest config, not a stale one");
+        assert_eq!(slow.lag_events(), 1);
+        bc.publish(cfg(5));
+        assert_eq!(slow.recv().await.unwrap().config_version.as_deref(), Some("5"));
+        assert_eq!(slow.lag_events(), 1);
+    }
+
+    #[tokio::test]
+    async fn only_changed_fields_are_reported() {
+        let bc = ConfigBroadcast::new(4);
+        let mut changes = bc.subscribe_changes();
+        bc.publish(cfg(1)); // first config: nothing to diff against
+        bc.publish(cfg(1)); // identical reload
+        bc.publish(DynamicConfig { log_level: Some("debug".into()), ..cfg(1) });
+        let change = changes.recv().await.unwrap();
+        assert_eq!(change, ConfigChange { log_level: Some(Change { old: Some("info".into()), new: Some("debug".into()) }), ..Default::default() });
+        assert!(changes.try_recv().is_err());
+    }
+}
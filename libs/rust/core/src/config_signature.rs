@@ -1,8 +1,60 @@
-//! Configuration signature verification placeholder.
-//! Future: Support Ed25519 + PQC (Dilithium) hybrid signatures, canonical YAML hashing.
+//! Ed25519 verification of signed configuration files.
+//!
+//! The signature is hex-encoded and covers the raw file bytes with any top-level
+//! `config_signature:` line removed, so it can live inside the file it signs. The public key comes
+//! from `SWARM_CONFIG_PUBKEY` (hex). With `SWARM_CONFIG_VERIFY=1`, `load_config` refuses a config
+//! that fails verification instead of only logging a warning.
+//! Future: PQC (Dilithium) hybrid signatures, canonical YAML hashing.
 
+use anyhow::{anyhow, Result};
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+
+pub fn verify_required() -> bool {
+    std::env::var("SWARM_CONFIG_VERIFY").map(|v| v == "1" || v.eq_ignore_ascii_case("true")).unwrap_or(false)
+}
+
+/// Bytes covered by the signature: `raw` minus its `config_signature:` line.
+pub fn signed_payload(raw: &str) -> String {
+    raw.split_inclusive('\n').filter(|l| !l.starts_with("config_signature:")).collect()
+}
+
+/// Verify `sig_hex` over `raw` against the hex public key `pubkey_hex`.
+pub fn verify_with_key(raw: &str, sig_hex: &str, pubkey_hex: &str) -> Result<()> {
+    let key_bytes = hex::decode(pubkey_hex.trim()).map_err(|e| anyhow!("config public key is not hex: {e}"))?;
+    let key = PublicKey::from_bytes(&key_bytes).map_err(|e| anyhow!("invalid config public key: {e}"))?;
+    let sig_bytes = hex::decode(sig_hex.trim()).map_err(|e| anyhow!("config signature is not hex: {e}"))?;
+    let sig = Signature::from_bytes(&sig_bytes).map_err(|e| anyhow!("invalid config signature: {e}"))?;
+    key.verify(signed_payload(raw).as_bytes(), &sig).map_err(|_| anyhow!("config signature does not match"))
+}
+
+/// Verify against `SWARM_CONFIG_PUBKEY`; false on a missing key or any decode/verify failure.
 pub fn verify_config_signature(raw: &str, provided_sig: &str) -> bool {
-    // Placeholder logic: accept non-empty signature, future will parse multi-alg format.
-    if raw.is_empty() { return false; }
-    !provided_sig.trim().is_empty()
+    let Ok(pubkey) = std::env::var("SWARM_CONFIG_PUBKEY") else { tracing::warn!("SWARM_CONFIG_PUBKEY not set; cannot verify config signature"); return false };
+    match verify_with_key(raw, provided_sig, &pubkey) {
+        Ok(()) => true,
+        Err(e) => { tracing::warn!(error=%e, "config signature verification failed"); false }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Keypair, SecretKey, Signer};
+
+    #[test]
+    fn valid_signature_passes_and_tampered_config_fails() {
+        let secret = SecretKey::from_bytes(&[7u8; 32]).unwrap();
+        let public: PublicKey = (&secret).into();
+        let pair = Keypair { secret, public };
+        let body = "service_name: detection\nnats_url: nats://10.0.0.5:4222\n";
+        let sig = hex::encode(pair.sign(body.as_bytes()).to_bytes());
+        let signed_file = format!("{body}config_signature: {sig}\n");
+        let pk = hex::encode(public.to_bytes());
+
+        verify_with_key(&signed_file, &sig, &pk).unwrap();
+        let tampered = signed_file.replace("10.0.0.5", "10.6.6.6");
+        assert_eq!(verify_with_key(&tampered, &sig, &pk).unwrap_err().to_string(), "config signature does not match");
+        assert!(verify_with_key(&signed_file, "zz", &pk).is_err());
+        assert!(verify_with_key(&signed_file, &sig, "abcd").is_err());
+    }
 }
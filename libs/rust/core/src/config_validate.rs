@@ -0,0 +1,61 @@
+//! Strict validation of a loaded `DynamicConfig` (`SWARM_CONFIG_STRICT=1`).
+//!
+//! Lenient loading turns a misspelt key into `None` and keeps going. Strict mode rejects the config
+//! instead, with an error naming the field: `nats_url` must be one or more comma-separated NATS
+//! URLs (scheme optional), `log_level` a tracing level and `config_version` present and non-empty.
+
+use anyhow::{anyhow, Result};
+use crate::DynamicConfig;
+
+const NATS_SCHEMES: [&str; 4] = ["nats", "tls", "ws", "wss"];
+const LOG_LEVELS: [&str; 6] = ["trace", "debug", "info", "warn", "error", "off"];
+
+pub fn strict_from_env() -> bool {
+    std::env::var("SWARM_CONFIG_STRICT").map(|v| v == "1" || v.eq_ignore_ascii_case("true")).unwrap_or(false)
+}
+
+pub fn validate(cfg: &DynamicConfig) -> Result<()> {
+    let nats_url = cfg.nats_url.as_deref().ok_or_else(|| anyhow!("config field `nats_url`: missing"))?;
+    for server in nats_url.split(',') { check_nats_url(server.trim()).map_err(|e| anyhow!("config field `nats_url`: {e}"))?; }
+    let level = cfg.log_level.as_deref().ok_or_else(|| anyhow!("config field `log_level`: missing"))?;
+    if !LOG_LEVELS.iter().any(|l| l.eq_ignore_ascii_case(level)) {
+        return Err(anyhow!("config field `log_level`: unknown level {level:?} (expected one of {})", LOG_LEVELS.join(", ")));
+    }
+    match cfg.config_version.as_deref().map(str::trim) {
+        None => Err(anyhow!("config field `config_version`: missing")),
+        Some("") => Err(anyhow!("config field `config_version`: must not be empty")),
+        Some(_) => Ok(()),
+    }
+}
+
+fn check_nats_url(server: &str) -> Result<()> {
+    let full = if server.contains("://") { server.to_string() } else { format!("nats://{server}") };
+    let url = url::Url::parse(&full).map_err(|e| anyhow!("{server:?} is not a valid URL: {e}"))?;
+    if !NATS_SCHEMES.contains(&url.scheme()) { return Err(anyhow!("{server:?} has unsupported scheme {:?}", url.scheme())); }
+    if url.host_str().is_none_or(str::is_empty) { return Err(anyhow!("{server:?} has no host")); }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn err(cfg: DynamicConfig) -> String { validate(&cfg).unwrap_err().to_string() }
+
+    #[test]
+    fn defaults_and_common_forms_pass() {
+        validate(&DynamicConfig::default()).unwrap();
+        let cfg = DynamicConfig { nats_url: Some("nats://a:4222, tls://b:4222".into()), log_level: Some("WARN".into()), ..Default::default() };
+        validate(&cfg).unwrap();
+    }
+
+    #[test]
+    fn each_invalid_field_is_named() {
+        assert_eq!(err(DynamicConfig { nats_url: None, ..Default::default() }), "config field `nats_url`: missing");
+        assert!(err(DynamicConfig { nats_url: Some("nats://:4222".into()), ..Default::default() }).starts_with("config field `nats_url`: \"nats://:4222\" is not a valid URL"));
+        assert_eq!(err(DynamicConfig { nats_url: Some("http://a:4222".into()), ..Default::default() }), "config field `nats_url`: \"http://a:4222\" has unsupported scheme \"http\"");
+        assert_eq!(err(DynamicConfig { log_level: Some("verbose".into()), ..Default::default() }), "config field `log_level`: unknown level \"verbose\" (expected one of trace, debug, info, warn, error, off)");
+        assert_eq!(err(DynamicConfig { config_version: None, ..Default::default() }), "config field `config_version`: missing");
+        assert_eq!(err(DynamicConfig { config_version: Some(" ".into()), ..Default::default() }), "config field `config_version`: must not be empty");
+    }
+}
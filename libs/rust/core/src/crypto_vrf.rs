@@ -0,0 +1,184 @@
+//! Verifiable Random Function: ECVRF-EDWARDS25519-SHA512-TAI (RFC 9381, suite 0x03).
+//!
+//! Used for stake-weighted leader selection: the prover publishes `(VrfProof, VrfOutput)`
+//! and anyone holding the prover's public key can check the output with `vrf_verify`.
+//! Proofs are bound to the secret key through real curve operations (Gamma = x*H and a
+//! Schnorr-style (c, s) pair), so they cannot be forged from public data.
+
+use curve25519_dalek::constants::ED25519_BASEPOINT_POINT;
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::scalar::{clamp_integer, Scalar};
+use rand::RngCore;
+use sha2::{Digest, Sha512};
+
+const SUITE: u8 = 0x03;
+const C_LEN: usize = 16;
+
+/// `Gamma (32) || c (16) || s (32)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VrfProof(pub [u8; 80]);
+
+/// `beta`, the 64-byte pseudorandom output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VrfOutput(pub [u8; 64]);
+
+#[derive(Clone)]
+pub struct VrfKeypair {
+    pub secret: [u8; 32], // RFC 8032 style seed
+    pub public: [u8; 32], // compressed Y = x*B
+}
+
+impl VrfKeypair {
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        let (x, _) = expand_secret(&seed);
+        let public = (ED25519_BASEPOINT_POINT * x).compress().to_bytes();
+        Self { secret: seed, public }
+    }
+
+    pub fn generate() -> Self {
+        let mut seed = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut seed);
+        Self::from_seed(seed)
+    }
+}
+
+/// Produce a proof and output for `alpha` under the secret seed.
+pub fn vrf_prove(secret: &[u8; 32], alpha: &[u8]) -> (VrfProof, VrfOutput) {
+    let (x, nonce_prefix) = expand_secret(secret);
+    let y = (ED25519_BASEPOINT_POINT * x).compress();
+    let h = encode_to_curve_tai(&y, alpha);
+    let h_bytes = h.compress();
+    let gamma = h * x;
+    let k = Scalar::from_bytes_mod_order_wide(&sha512(&[&nonce_prefix, h_bytes.as_bytes()]));
+    let c = challenge(&y, &h_bytes, &gamma.compress(), &(ED25519_BASEPOINT_POINT * k).compress(), &(h * k).compress());
+    let s = k + c * x;
+    let mut pi = [0u8; 80];
+    pi[..32].copy_from_slice(gamma.compress().as_bytes());
+    pi[32..48].copy_from_slice(&c.to_bytes()[..C_LEN]);
+    pi[48..].copy_from_slice(s.as_bytes());
+    (VrfProof(pi), proof_to_hash(&gamma))
+}
+
+/// Verify `proof` for `alpha` under `public`, returning the output only when valid.
+pub fn vrf_verify(public: &[u8; 32], alpha: &[u8], proof: &VrfProof) -> Option<VrfOutput> {
+    let y_c = CompressedEdwardsY(*public);
+    let y = y_c.decompress()?;
+    if y.is_small_order() { return None; }
+    let gamma = CompressedEdwardsY(proof.0[..32].try_into().ok()?).decompress()?;
+    let mut c_bytes = [0u8; 32];
+    c_bytes[..C_LEN].copy_from_slice(&proof.0[32..48]);
+    let c = Scalar::from_bytes_mod_order(c_bytes);
+    let s = Option::<Scalar>::from(Scalar::from_canonical_bytes(proof.0[48..].try_into().ok()?))?;
+    let h = encode_to_curve_tai(&y_c, alpha);
+    // U = s*B - c*Y ; V = s*H - c*Gamma
+    let u = EdwardsPoint::vartime_double_scalar_mul_basepoint(&(-c), &y, &s);
+    let v = h * s - gamma * c;
+    let c_prime = challenge(&y_c, &h.compress(), &gamma.compress(), &u.compress(), &v.compress());
+    if c_prime != c { return None; }
+    Some(proof_to_hash(&gamma))
+}
+
+/// Stake-weighted selection driven by a VRF output: the first 8 bytes of beta pick a
+/// point in `[0, total_stake)`. Zero-stake validators are never selected.
+pub fn select_validator_with_vrf(output: &VrfOutput, validators: &[(String, u64)]) -> Option<String> {
+    let total: u128 = validators.iter().map(|(_, s)| *s as u128).sum();
+    if total == 0 { return None; }
+    let mut head = [0u8; 8];
+    head.copy_from_slice(&output.0[..8]);
+    let mut target = u64::from_le_bytes(head) as u128 % total;
+    for (id, stake) in validators {
+        let stake = *stake as u128;
+        if target < stake { return Some(id.clone()); }
+        target -= stake;
+    }
+    None
+}
+
+fn sha512(parts: &[&[u8]]) -> [u8; 64] {
+    let mut hasher = Sha512::new();
+    for p in parts { hasher.update(p); }
+    hasher.finalize().into()
+}
+
+/// RFC 8032 key expansion: clamped scalar x plus the nonce-derivation half of the digest.
+fn expand_secret(seed: &[u8; 32]) -> (Scalar, [u8; 32]) {
+    let digest = sha512(&[seed]);
+    let mut lower = [0u8; 32];
+    lower.copy_from_slice(&digest[..32]);
+    let mut prefix = [0u8; 32];
+    prefix.copy_from_slice(&digest[32..]);
+    (Scalar::from_bytes_mod_order(clamp_integer(lower)), prefix)
+}
+
+/// ECVRF_encode_to_curve_try_and_increment; result is multiplied by the cofactor.
+fn encode_to_curve_tai(y: &CompressedEdwardsY, alpha: &[u8]) -> EdwardsPoint {
+    for ctr in 0u8..=255 {
+        let digest = sha512(&[&[SUITE, 0x01], y.as_bytes(), alpha, &[ctr, 0x00]]);
+        let mut candidate = [0u8; 32];
+        candidate.copy_from_slice(&digest[..32]);
+        if let Some(p) = CompressedEdwardsY(candidate).decompress() {
+            return p.mul_by_cofactor();
+        }
+    }
+    // Each attempt succeeds with probability ~1/2; 256 consecutive failures is not reachable in practice.
+    unreachable!("ECVRF try-and-increment exhausted")
+}
+
+fn challenge(y: &CompressedEdwardsY, h: &CompressedEdwardsY, gamma: &CompressedEdwardsY, u: &CompressedEdwardsY, v: &CompressedEdwardsY) -> Scalar {
+    let digest = sha512(&[&[SUITE, 0x02], y.as_bytes(), h.as_bytes(), gamma.as_bytes(), u.as_bytes(), v.as_bytes(), &[0x00]]);
+    let mut c = [0u8; 32];
+    c[..C_LEN].copy_from_slice(&digest[..C_LEN]);
+    Scalar::from_bytes_mod_order(c)
+}
+
+fn proof_to_hash(gamma: &EdwardsPoint) -> VrfOutput {
+    VrfOutput(sha512(&[&[SUITE, 0x03], gamma.mul_by_cofactor().compress().as_bytes(), &[0x00]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unhex<const N: usize>(s: &str) -> [u8; N] {
+        let mut out = [0u8; N];
+        for (i, b) in out.iter_mut().enumerate() { *b = u8::from_str_radix(&s[i*2..i*2+2], 16).unwrap(); }
+        out
+    }
+
+    #[test]
+    fn rfc9381_vector_empty_alpha() {
+        let kp = VrfKeypair::from_seed(unhex("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60"));
+        assert_eq!(kp.public, unhex::<32>("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a"));
+        let (proof, output) = vrf_prove(&kp.secret, b"");
+        assert_eq!(proof.0, unhex::<80>("8657106690b5526245a92b003bb079ccd1a92130477671f6fc01ad16f26f723f26f8a57ccaed74ee1b190bed1f479d9727d2d0f9b005a6e456a35d4fb0daab1268a1b0db10836d9826a528ca76567805"));
+        assert_eq!(output.0, unhex::<64>("90cf1df3b703cce59e2a35b925d411164068269d7b2d29f3301c03dd757876ff66b71dda49d2de59d03450451af026798e8f81cd2e333de5cdf4f3e140fdd8ae"));
+        assert_eq!(vrf_verify(&kp.public, b"", &proof), Some(output));
+    }
+
+    #[test]
+    fn proof_from_other_key_fails() {
+        let a = VrfKeypair::generate();
+        let b = VrfKeypair::generate();
+        let (proof, output) = vrf_prove(&a.secret, b"height:7:round:0");
+        assert_eq!(vrf_verify(&a.public, b"height:7:round:0", &proof), Some(output));
+        assert_eq!(vrf_verify(&b.public, b"height:7:round:0", &proof), None);
+        assert_eq!(vrf_verify(&a.public, b"height:7:round:1", &proof), None);
+    }
+
+    #[test]
+    fn tampered_proof_fails() {
+        let kp = VrfKeypair::generate();
+        let (mut proof, _) = vrf_prove(&kp.secret, b"alpha");
+        proof.0[40] ^= 0x01;
+        assert!(vrf_verify(&kp.public, b"alpha", &proof).is_none());
+    }
+
+    #[test]
+    fn selection_respects_stake() {
+        let vals = vec![("a".to_string(), 0u64), ("b".to_string(), 10u64)];
+        let kp = VrfKeypair::generate();
+        let (_, out) = vrf_prove(&kp.secret, b"x");
+        assert_eq!(select_validator_with_vrf(&out, &vals).as_deref(), Some("b"));
+        assert!(select_validator_with_vrf(&out, &[]).is_none());
+    }
+}
@@ -0,0 +1,67 @@
+//! Centralized on-disk layout for persistent subsystems.
+//!
+//! Every store lives under `$SWARM_DATA_DIR/<subsystem>` (default root `./data`), so services
+//! sharing a host never collide. Directories are created on demand and probed for writability,
+//! letting services fail at startup instead of silently running without persistence.
+
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Context, Result};
+
+/// Root data directory (`SWARM_DATA_DIR`, default `./data`).
+pub fn data_root() -> PathBuf {
+    std::env::var("SWARM_DATA_DIR").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("./data"))
+}
+
+/// Resolve, create and validate `$SWARM_DATA_DIR/<subsystem>`.
+pub fn resolve_data_dir(subsystem: &str) -> Result<PathBuf> { resolve_data_dir_in(&data_root(), subsystem) }
+
+/// Same as `resolve_data_dir` with an explicit root.
+pub fn resolve_data_dir_in(root: &Path, subsystem: &str) -> Result<PathBuf> {
+    if subsystem.is_empty() || subsystem.contains(['/', '\\']) || subsystem == ".." {
+        return Err(anyhow!("invalid data subsystem name {subsystem:?}"));
+    }
+    let dir = root.join(subsystem);
+    ensure_writable_dir(&dir)?;
+    Ok(dir)
+}
+
+/// Create `dir` if needed and verify a file can be written inside it.
+pub fn ensure_writable_dir(dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("cannot create data dir {}", dir.display()))?;
+    let probe = dir.join(format!(".write-probe-{}", std::process::id()));
+    std::fs::write(&probe, b"ok").with_context(|| format!("data dir {} is not writable", dir.display()))?;
+    let _ = std::fs::remove_file(&probe);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos();
+        std::env::temp_dir().join(format!("swarm-data-{name}-{}-{nanos}", std::process::id()))
+    }
+
+    #[test]
+    fn subsystems_get_distinct_subdirs() {
+        let root = scratch("shared");
+        let dirs: Vec<PathBuf> = ["consensus", "identity-ca", "validators", "audit"].iter().map(|s| resolve_data_dir_in(&root, s).unwrap()).collect();
+        for (i, d) in dirs.iter().enumerate() {
+            assert!(d.starts_with(&root) && d.is_dir());
+            assert!(dirs.iter().skip(i + 1).all(|o| o != d));
+        }
+        assert!(resolve_data_dir_in(&root, "../escape").is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn unwritable_root_fails_clearly() {
+        // a regular file as the root cannot hold subdirectories (works even when running as root)
+        let file = scratch("file");
+        std::fs::write(&file, b"not a dir").unwrap();
+        let err = resolve_data_dir_in(&file, "consensus").unwrap_err();
+        assert!(format!("{err:#}").contains("cannot create data dir"), "unexpected error: {err:#}");
+        let _ = std::fs::remove_file(&file);
+    }
+}
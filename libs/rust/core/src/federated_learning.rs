@@ -1,10 +1,30 @@
 //! Federated learning coordination primitives.
 //!
-//! Supports basic aggregation strategies (FedAvg, FedProx, FedNova placeholder).
-//! Future work: secure aggregation, differential privacy, version negotiation.
+//! Supports basic aggregation strategies (FedAvg, FedProx, FedNova placeholder), the
+//! Byzantine-robust TrimmedMean and (multi-)Krum, and SecureAggregation, where clients submit
+//! double-masked updates (see `secure_aggregation`) and only the weighted mean of a round is
+//! ever recovered. Robust methods report the clients they discarded in `GlobalModel::excluded`.
+//!
+//! Updates are validated on submit: the layer shape must match the current global model (or,
+//! before the first round, the first update of the round) and plaintext values must be finite;
+//! failures return `UpdateRejected` and count `swarm_fl_rejected_updates_total`. With
+//! `with_clip_norm`, updates whose L2 norm exceeds the bound are scaled down to it and listed in
+//! `GlobalModel::clipped`.
+//!
+//! `poll_round` closes rounds: as soon as every expected client submitted (`Completed`), or at the
+//! `with_round_deadline` deadline with whatever arrived if that meets `min_participants`
+//! (`Partial`). Below the minimum at the deadline the round is `Aborted`: its updates are dropped,
+//! the round number still advances and the previous global model stays in use.
+//!
+//! Updates carry the `base_version` of the global model they were trained from; one more than
+//! `with_staleness_tolerance` (default 0) versions behind `current_version()` is rejected as
+//! `UpdateRejected::Stale` (also counted in `swarm_fl_stale_updates_total`).
+//! Future work: differential privacy.
 
+use std::time::{Duration, Instant};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use crate::secure_aggregation::{unmask_mean, MaskShare, MaskedGradient, SelfMaskShare};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelGradient {
@@ -12,6 +32,8 @@ pub struct ModelGradient {
     pub layer_gradients: Vec<Vec<f32>>, // layers -> weights
     pub sample_count: usize,
     pub timestamp: i64,
+    #[serde(default)]
+    pub base_version: u64, // GlobalModel version the client trained from (0: initial weights)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,13 +41,50 @@ pub struct GlobalModel {
     pub version: u64,
     pub weights: Vec<Vec<f32>>, // layers -> weights
     pub updated_at: i64,
+    #[serde(default)]
+    pub excluded: Vec<String>, // clients discarded as outliers by robust aggregation
+    #[serde(default)]
+    pub clipped: Vec<String>, // clients whose update was norm-clipped
 }
 
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum UpdateRejected {
+    #[error("plaintext gradient in secure aggregation mode")]
+    PlaintextInSecureMode,
+    #[error("update from {node_id}: layer shape {got:?}, expected {expected:?}")]
+    ShapeMismatch { node_id: String, expected: Vec<usize>, got: Vec<usize> },
+    #[error("update from {node_id} contains non-finite values")]
+    NonFinite { node_id: String },
+    #[error("update from {node_id} based on model v{base_version}, current v{current}")]
+    Stale { node_id: String, base_version: u64, current: u64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RoundStatus { Completed, Partial, Aborted }
+
+/// A closed round; `model` is the new global model, or the prior one for an aborted round.
+#[derive(Debug, Clone)]
+pub struct RoundOutcome {
+    pub round: u64,
+    pub status: RoundStatus,
+    pub participants: usize,
+    pub model: Option<GlobalModel>,
+}
+
+fn shape_of(layers: &[Vec<f32>]) -> Vec<usize> { layers.iter().map(Vec::len).collect() }
+
 #[derive(Debug, Clone)]
 pub enum AggregationMethod {
     FedAvg,
     FedProx,
     FedNova,
+    SecureAggregation,
+    /// Coordinate-wise mean after dropping the `trim_fraction` lowest and highest values.
+    TrimmedMean { trim_fraction: f32 },
+    /// Multi-Krum: average the `n - f` updates closest to their `n - f - 2` nearest neighbours;
+    /// tolerates `f` Byzantine clients and needs `n >= 2f + 3`.
+    Krum { f: usize },
 }
 
 pub struct FederatedLearningCoordinator {
@@ -34,52 +93,268 @@ pub struct FederatedLearningCoordinator {
     gradient_buffer: Vec<ModelGradient>,
     round: u64,
     mu: f32, // FedProx proximal term
+    roster: Vec<String>, // SecureAggregation: clients expected this round
+    masked_buffer: Vec<MaskedGradient>,
+    mask_shares: Vec<MaskShare>,
+    self_shares: Vec<SelfMaskShare>,
+    submissions_closed: bool, // SecureAggregation: survivors announced, no more masked updates
+    expected_shape: Option<Vec<usize>>, // layer sizes of the current global model
+    clip_norm: Option<f32>,
+    clipped: Vec<String>,
+    expected_participants: usize,
+    round_deadline: Option<Duration>,
+    round_started: Instant,
+    last_model: Option<GlobalModel>,
+    staleness_tolerance: u64,
 }
 
 impl FederatedLearningCoordinator {
     pub fn new(method: AggregationMethod, min_participants: usize) -> Self {
-        Self { aggregation_method: method, min_participants, gradient_buffer: Vec::new(), round: 0, mu: 0.01 }
+        Self { aggregation_method: method, min_participants, gradient_buffer: Vec::new(), round: 0, mu: 0.01, roster: Vec::new(), masked_buffer: Vec::new(), mask_shares: Vec::new(), self_shares: Vec::new(), submissions_closed: false,
+            expected_shape: None, clip_norm: None, clipped: Vec::new(),
+            expected_participants: min_participants, round_deadline: None, round_started: Instant::now(), last_model: None, staleness_tolerance: 0 }
     }
 
-    pub fn submit_gradient(&mut self, g: ModelGradient) -> Result<()> {
-        // Basic validation
-        if !self.gradient_buffer.is_empty() && g.layer_gradients.len() != self.gradient_buffer[0].layer_gradients.len() {
-            anyhow::bail!("layer mismatch");
+    /// Close a round at `deadline` after it opened even if not everyone submitted.
+    pub fn with_round_deadline(mut self, deadline: Duration) -> Self { self.round_deadline = Some(deadline); self }
+
+    /// Clients a round waits for before completing early (at least `min_participants`; the roster
+    /// in SecureAggregation).
+    pub fn with_expected_participants(mut self, n: usize) -> Self { self.expected_participants = n.max(self.min_participants); self }
+
+    /// Accept updates trained on a global model up to `versions` behind the current one.
+    pub fn with_staleness_tolerance(mut self, versions: u64) -> Self { self.staleness_tolerance = versions; self }
+
+    /// Version of the global model clients should train from (0 before the first round).
+    pub fn current_version(&self) -> u64 { self.last_model.as_ref().map_or(0, |m| m.version) }
+
+    /// Latest global model produced by a round.
+    pub fn current_model(&self) -> Option<&GlobalModel> { self.last_model.as_ref() }
+
+    /// Scale down updates whose L2 norm (over all layers) exceeds `max_norm`.
+    pub fn with_clip_norm(mut self, max_norm: f32) -> Self { self.clip_norm = Some(max_norm); self }
+
+    /// Validate updates against `model`'s layer shape (done automatically after each round).
+    pub fn set_model_shape(&mut self, model: &GlobalModel) { self.expected_shape = Some(shape_of(&model.weights)); }
+
+    /// Start a SecureAggregation round with the clients holding pairwise seeds; returns the round
+    /// number clients must mask with.
+    pub fn begin_secure_round(&mut self, roster: Vec<String>) -> u64 {
+        self.roster = roster;
+        self.round_started = Instant::now();
+        self.clear_secure_round();
+        self.round + 1
+    }
+
+    fn clear_secure_round(&mut self) {
+        self.masked_buffer.clear();
+        self.mask_shares.clear();
+        self.self_shares.clear();
+        self.submissions_closed = false;
+    }
+
+    pub fn submit_masked(&mut self, m: MaskedGradient) -> Result<()> {
+        if !matches!(self.aggregation_method, AggregationMethod::SecureAggregation) { anyhow::bail!("masked update outside secure aggregation"); }
+        if m.round != self.round + 1 { anyhow::bail!("masked update for round {} (current {})", m.round, self.round + 1); }
+        if !self.roster.contains(&m.node_id) { anyhow::bail!("{} not in secure aggregation roster", m.node_id); }
+        if self.submissions_closed { anyhow::bail!("round {} no longer accepts masked updates", m.round); }
+        // once survivors revealed their seeds with this client its masks are exposed: too late
+        if self.mask_shares.iter().any(|s| s.dropped == m.node_id) { anyhow::bail!("{} already treated as dropped", m.node_id); }
+        if self.masked_buffer.iter().any(|b| b.node_id == m.node_id) { anyhow::bail!("duplicate masked update from {}", m.node_id); }
+        let round_shape = self.masked_buffer.first().map(|b| b.masked.iter().map(Vec::len).collect());
+        if let Err(e) = self.check_shape(&m.node_id, m.masked.iter().map(Vec::len).collect(), round_shape).and_then(|()| self.check_staleness(&m.node_id, m.base_version)) {
+            record_rejection(&m.node_id, &e);
+            return Err(e.into());
+        }
+        self.masked_buffer.push(m);
+        Ok(())
+    }
+
+    /// Stop collecting masked updates and return the clients that submitted; every client must
+    /// `acknowledge` this list before revealing any share.
+    pub fn acknowledge_survivors(&mut self) -> Vec<String> {
+        self.submissions_closed = true;
+        self.masked_buffer.iter().map(|m| m.node_id.clone()).collect()
+    }
+
+    /// Roster clients that have not submitted a masked update.
+    pub fn dropped_clients(&self) -> Vec<String> {
+        self.roster.iter().filter(|c| !self.masked_buffer.iter().any(|m| &m.node_id == *c)).cloned().collect()
+    }
+
+    pub fn submit_mask_share(&mut self, s: MaskShare) { self.mask_shares.push(s); }
+
+    pub fn submit_self_share(&mut self, s: SelfMaskShare) { self.self_shares.push(s); }
+
+    pub fn submit_gradient(&mut self, mut g: ModelGradient) -> std::result::Result<(), UpdateRejected> {
+        if let Err(e) = self.validate(&g) {
+            record_rejection(&g.node_id, &e);
+            return Err(e);
+        }
+        if let Some(max_norm) = self.clip_norm {
+            let norm = g.layer_gradients.iter().flatten().map(|v| v * v).sum::<f32>().sqrt();
+            if norm > max_norm {
+                let scale = max_norm / norm;
+                g.layer_gradients.iter_mut().flatten().for_each(|v| *v *= scale);
+                crate::EXTENDED_METRICS.fl.clipped_updates_total.add(1, &[]);
+                tracing::debug!(node_id=%g.node_id, norm, max_norm, "federated update norm-clipped");
+                self.clipped.push(g.node_id.clone());
+            }
         }
         self.gradient_buffer.push(g);
         Ok(())
     }
 
+    fn validate(&self, g: &ModelGradient) -> std::result::Result<(), UpdateRejected> {
+        if matches!(self.aggregation_method, AggregationMethod::SecureAggregation) { return Err(UpdateRejected::PlaintextInSecureMode); }
+        self.check_shape(&g.node_id, shape_of(&g.layer_gradients), self.gradient_buffer.first().map(|b| shape_of(&b.layer_gradients)))?;
+        if g.layer_gradients.iter().flatten().any(|v| !v.is_finite()) { return Err(UpdateRejected::NonFinite { node_id: g.node_id.clone() }); }
+        self.check_staleness(&g.node_id, g.base_version)
+    }
+
+    /// `got` must match the current model's shape, or before the first round `round_shape`.
+    fn check_shape(&self, node_id: &str, got: Vec<usize>, round_shape: Option<Vec<usize>>) -> std::result::Result<(), UpdateRejected> {
+        match self.expected_shape.clone().or(round_shape).filter(|e| *e != got) {
+            Some(expected) => Err(UpdateRejected::ShapeMismatch { node_id: node_id.to_string(), expected, got }),
+            None => Ok(()),
+        }
+    }
+
+    fn check_staleness(&self, node_id: &str, base_version: u64) -> std::result::Result<(), UpdateRejected> {
+        let current = self.current_version();
+        if base_version > current || current - base_version > self.staleness_tolerance {
+            return Err(UpdateRejected::Stale { node_id: node_id.to_string(), base_version, current });
+        }
+        Ok(())
+    }
+
+    fn is_secure(&self) -> bool { matches!(self.aggregation_method, AggregationMethod::SecureAggregation) }
+
+    /// Updates received in the open round.
+    pub fn submitted(&self) -> usize { if self.is_secure() { self.masked_buffer.len() } else { self.gradient_buffer.len() } }
+
+    /// Close the open round if it is complete or past its deadline; `None` while still waiting.
+    pub fn poll_round(&mut self, now: Instant) -> Result<Option<RoundOutcome>> {
+        let expected = if self.is_secure() { self.roster.len().max(self.min_participants) } else { self.expected_participants };
+        let participants = self.submitted();
+        let expired = self.round_deadline.is_some_and(|d| now.saturating_duration_since(self.round_started) >= d);
+        let status = if participants >= expected { RoundStatus::Completed }
+            else if !expired { return Ok(None) }
+            else if participants >= self.min_participants { RoundStatus::Partial }
+            else { RoundStatus::Aborted };
+        let model = match status {
+            RoundStatus::Aborted => {
+                self.round += 1;
+                self.gradient_buffer.clear();
+                self.clear_secure_round();
+                self.clipped.clear();
+                tracing::warn!(round = self.round, participants, min = self.min_participants, "federated round aborted, keeping prior model");
+                self.last_model.clone()
+            }
+            _ => match self.aggregate()? { Some(m) => Some(m), None => return Ok(None) }, // e.g. mask shares outstanding
+        };
+        self.round_started = now;
+        Ok(Some(RoundOutcome { round: self.round, status, participants, model }))
+    }
+
     pub fn aggregate(&mut self) -> Result<Option<GlobalModel>> {
+        let model = if self.is_secure() { self.secure_aggregate() } else { self.plain_aggregate()? };
+        if let Some(m) = &model { self.last_model = Some(m.clone()); }
+        Ok(model)
+    }
+
+    fn plain_aggregate(&mut self) -> Result<Option<GlobalModel>> {
         if self.gradient_buffer.len() < self.min_participants { return Ok(None); }
-        self.round += 1;
-        let model = match self.aggregation_method {
-            AggregationMethod::FedAvg => self.fed_avg()?,
-            AggregationMethod::FedProx => self.fed_prox()?,
-            AggregationMethod::FedNova => self.fed_nova()?,
+        let (weights, excluded) = match self.aggregation_method {
+            AggregationMethod::FedAvg => (self.fed_avg(), Vec::new()),
+            AggregationMethod::FedProx => (self.fed_prox(), Vec::new()),
+            AggregationMethod::FedNova => (self.fed_nova(), Vec::new()),
+            AggregationMethod::TrimmedMean { trim_fraction } => trimmed_mean(&self.gradient_buffer, trim_fraction),
+            AggregationMethod::Krum { f } => multi_krum(&self.gradient_buffer, f)?,
+            AggregationMethod::SecureAggregation => unreachable!("handled above"),
         };
+        if !excluded.is_empty() { tracing::warn!(round = self.round + 1, ?excluded, "robust aggregation discarded client updates"); }
+        self.round += 1;
         self.gradient_buffer.clear();
+        let model = GlobalModel { version: self.round, weights, updated_at: chrono::Utc::now().timestamp(), excluded, clipped: std::mem::take(&mut self.clipped) };
+        self.set_model_shape(&model);
         Ok(Some(model))
     }
 
-    fn fed_avg(&self) -> Result<GlobalModel> {
-        let total_samples: usize = self.gradient_buffer.iter().map(|g| g.sample_count).sum();
-        let num_layers = self.gradient_buffer[0].layer_gradients.len();
-        let mut agg: Vec<Vec<f32>> = Vec::with_capacity(num_layers);
-        for layer in 0..num_layers {
-            let size = self.gradient_buffer[0].layer_gradients[layer].len();
-            let mut layer_vec = vec![0.0f32; size];
-            for g in &self.gradient_buffer {
-                let w = g.sample_count as f32 / total_samples as f32;
-                for (i, val) in g.layer_gradients[layer].iter().enumerate() { layer_vec[i] += *val * w; }
-            }
-            agg.push(layer_vec);
-        }
-        Ok(GlobalModel { version: self.round, weights: agg, updated_at: chrono::Utc::now().timestamp() })
+    fn fed_avg(&self) -> Vec<Vec<f32>> { weighted_average(&self.gradient_buffer.iter().collect::<Vec<_>>()) }
+
+    /// `None` until `min_participants` submitted, every survivor sent its seeds for the dropped and
+    /// enough self-mask shares arrived for every survivor.
+    fn secure_aggregate(&mut self) -> Option<GlobalModel> {
+        if self.masked_buffer.len() < self.min_participants { return None; }
+        let weights = unmask_mean(&self.masked_buffer, &self.dropped_clients(), &self.mask_shares, &self.self_shares)?;
+        self.round += 1;
+        self.clear_secure_round();
+        let model = GlobalModel { version: self.round, weights, updated_at: chrono::Utc::now().timestamp(), excluded: Vec::new(), clipped: Vec::new() };
+        self.set_model_shape(&model);
+        Some(model)
     }
 
-    fn fed_prox(&self) -> Result<GlobalModel> { self.fed_avg() }
-    fn fed_nova(&self) -> Result<GlobalModel> { self.fed_avg() }
+    fn fed_prox(&self) -> Vec<Vec<f32>> { self.fed_avg() }
+    fn fed_nova(&self) -> Vec<Vec<f32>> { self.fed_avg() }
+}
+
+fn record_rejection(node_id: &str, e: &UpdateRejected) {
+    crate::EXTENDED_METRICS.fl.rejected_updates_total.add(1, &[]);
+    if matches!(e, UpdateRejected::Stale { .. }) { crate::EXTENDED_METRICS.fl.stale_updates_total.add(1, &[]); }
+    tracing::warn!(node_id, error=%e, "federated update rejected");
+}
+
+/// Sample-weighted mean of `grads` (all with the first one's layer shapes).
+fn weighted_average(grads: &[&ModelGradient]) -> Vec<Vec<f32>> {
+    let total_samples: usize = grads.iter().map(|g| g.sample_count).sum();
+    grads[0].layer_gradients.iter().enumerate().map(|(layer, shape)| {
+        let mut layer_vec = vec![0.0f32; shape.len()];
+        for g in grads {
+            let w = g.sample_count as f32 / total_samples as f32;
+            for (i, val) in g.layer_gradients[layer].iter().enumerate() { layer_vec[i] += *val * w; }
+        }
+        layer_vec
+    }).collect()
+}
+
+/// Coordinate-wise trimmed mean. A client counts as excluded when its value was trimmed in more
+/// than half of all coordinates.
+fn trimmed_mean(grads: &[ModelGradient], trim_fraction: f32) -> (Vec<Vec<f32>>, Vec<String>) {
+    let n = grads.len();
+    let k = ((n as f32 * trim_fraction.clamp(0.0, 0.5)) as usize).min((n - 1) / 2);
+    let mut trimmed = vec![0usize; n];
+    let mut coords = 0usize;
+    let weights = grads[0].layer_gradients.iter().enumerate().map(|(layer, shape)| {
+        (0..shape.len()).map(|i| {
+            let mut col: Vec<(f32, usize)> = grads.iter().enumerate().map(|(c, g)| (g.layer_gradients[layer][i], c)).collect();
+            col.sort_by(|a, b| a.0.total_cmp(&b.0));
+            for &(_, c) in col[..k].iter().chain(&col[n - k..]) { trimmed[c] += 1; }
+            coords += 1;
+            let kept = &col[k..n - k];
+            kept.iter().map(|(v, _)| v).sum::<f32>() / kept.len() as f32
+        }).collect()
+    }).collect();
+    let excluded = grads.iter().zip(&trimmed).filter(|(_, &t)| t * 2 > coords).map(|(g, _)| g.node_id.clone()).collect();
+    (weights, excluded)
+}
+
+/// Multi-Krum: score each update by the summed squared distance to its `n - f - 2` nearest
+/// neighbours, drop the `f` worst-scoring and average the rest.
+fn multi_krum(grads: &[ModelGradient], f: usize) -> Result<(Vec<Vec<f32>>, Vec<String>)> {
+    let n = grads.len();
+    if n < 2 * f + 3 { anyhow::bail!("krum needs at least {} updates to tolerate f={f}, got {n}", 2 * f + 3); }
+    let flat: Vec<Vec<f32>> = grads.iter().map(|g| g.layer_gradients.concat()).collect();
+    let dist = |a: &[f32], b: &[f32]| a.iter().zip(b).map(|(x, y)| ((x - y) as f64).powi(2)).sum::<f64>();
+    let mut scored: Vec<(f64, usize)> = (0..n).map(|i| {
+        let mut d: Vec<f64> = (0..n).filter(|&j| j != i).map(|j| dist(&flat[i], &flat[j])).collect();
+        d.sort_by(f64::total_cmp);
+        (d[..n - f - 2].iter().sum(), i)
+    }).collect();
+    scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let kept: Vec<&ModelGradient> = scored[..n - f].iter().map(|&(_, i)| &grads[i]).collect();
+    let excluded = scored[n - f..].iter().map(|&(_, i)| grads[i].node_id.clone()).collect();
+    Ok((weighted_average(&kept), excluded))
 }
 
 #[cfg(test)]
@@ -89,10 +364,165 @@ mod tests {
     #[test]
     fn fedavg_aggregates() {
         let mut coord = FederatedLearningCoordinator::new(AggregationMethod::FedAvg, 2);
-        coord.submit_gradient(ModelGradient { node_id: "n1".into(), layer_gradients: vec![vec![0.1,0.2,0.3]], sample_count: 10, timestamp: 0 }).unwrap();
-        coord.submit_gradient(ModelGradient { node_id: "n2".into(), layer_gradients: vec![vec![0.2,0.4,0.6]], sample_count: 30, timestamp: 0 }).unwrap();
+        coord.submit_gradient(ModelGradient { node_id: "n1".into(), layer_gradients: vec![vec![0.1,0.2,0.3]], sample_count: 10, timestamp: 0, base_version: 0 }).unwrap();
+        coord.submit_gradient(ModelGradient { node_id: "n2".into(), layer_gradients: vec![vec![0.2,0.4,0.6]], sample_count: 30, timestamp: 0, base_version: 0 }).unwrap();
         let model = coord.aggregate().unwrap().expect("should aggregate");
         // Weighted: (0.1*10 + 0.2*30)/40 = 0.175
         assert!((model.weights[0][0] - 0.175).abs() < 1e-6);
     }
+
+    #[test]
+    fn deadline_closes_round_as_partial_or_aborted() {
+        let g = |id: &str, v: f32, base_version: u64| ModelGradient { node_id: id.into(), layer_gradients: vec![vec![v]], sample_count: 1, timestamp: 0, base_version };
+        let deadline = Duration::from_secs(30);
+        let mut coord = FederatedLearningCoordinator::new(AggregationMethod::FedAvg, 2).with_expected_participants(4).with_round_deadline(deadline);
+        let t0 = Instant::now();
+        coord.submit_gradient(g("a", 1.0, 0)).unwrap();
+        coord.submit_gradient(g("b", 3.0, 0)).unwrap();
+        assert!(coord.poll_round(t0).unwrap().is_none(), "still within the deadline");
+        let partial = coord.poll_round(t0 + deadline).unwrap().expect("deadline closes the round");
+        assert_eq!((partial.round, partial.status, partial.participants), (1, RoundStatus::Partial, 2));
+        assert!((partial.model.unwrap().weights[0][0] - 2.0).abs() < 1e-6);
+
+        coord.submit_gradient(g("a", 9.0, 1)).unwrap();
+        let aborted = coord.poll_round(t0 + deadline * 2).unwrap().expect("closed");
+        assert_eq!((aborted.round, aborted.status), (2, RoundStatus::Aborted));
+        assert_eq!(aborted.model.map(|m| m.version), Some(1), "prior model reused");
+        assert_eq!(coord.submitted(), 0);
+
+        for (id, v) in [("a", 1.0), ("b", 1.0), ("c", 1.0), ("d", 1.0)] { coord.submit_gradient(g(id, v, coord.current_version())).unwrap(); }
+        assert_eq!(coord.poll_round(t0 + deadline * 2).unwrap().map(|o| o.status), Some(RoundStatus::Completed));
+    }
+
+    #[test]
+    fn stale_base_version_respects_tolerance() {
+        let at_v3 = |tolerance: u64| {
+            let mut c = FederatedLearningCoordinator::new(AggregationMethod::FedAvg, 1).with_staleness_tolerance(tolerance);
+            for _ in 0..3 {
+                let base_version = c.current_version();
+                c.submit_gradient(ModelGradient { node_id: "n".into(), layer_gradients: vec![vec![1.0]], sample_count: 1, timestamp: 0, base_version }).unwrap();
+                c.aggregate().unwrap().unwrap();
+            }
+            assert_eq!(c.current_version(), 3);
+            c
+        };
+        let update = ModelGradient { node_id: "old".into(), layer_gradients: vec![vec![1.0]], sample_count: 1, timestamp: 0, base_version: 1 };
+        assert_eq!(at_v3(1).submit_gradient(update.clone()), Err(UpdateRejected::Stale { node_id: "old".into(), base_version: 1, current: 3 }));
+        assert!(at_v3(2).submit_gradient(update.clone()).is_ok());
+        assert!(at_v3(2).submit_gradient(ModelGradient { base_version: 4, ..update }).is_err(), "future base");
+    }
+
+    #[test]
+    fn mismatched_shapes_are_rejected() {
+        let g = |id: &str, layers: Vec<Vec<f32>>| ModelGradient { node_id: id.into(), layer_gradients: layers, sample_count: 1, timestamp: 0, base_version: 0 };
+        let mut coord = FederatedLearningCoordinator::new(AggregationMethod::FedAvg, 1);
+        coord.set_model_shape(&GlobalModel { version: 1, weights: vec![vec![0.0; 3], vec![0.0]], updated_at: 0, excluded: vec![], clipped: vec![] });
+        assert_eq!(coord.submit_gradient(g("short", vec![vec![0.1, 0.2], vec![0.3]])),
+            Err(UpdateRejected::ShapeMismatch { node_id: "short".into(), expected: vec![3, 1], got: vec![2, 1] }));
+        assert!(matches!(coord.submit_gradient(g("nan", vec![vec![0.1, f32::NAN, 0.0], vec![0.3]])), Err(UpdateRejected::NonFinite { .. })));
+        assert!(coord.submit_gradient(g("ok", vec![vec![0.1, 0.2, 0.3], vec![0.4]])).is_ok());
+        assert_eq!(coord.aggregate().unwrap().unwrap().weights.len(), 2);
+    }
+
+    #[test]
+    fn oversized_update_is_norm_clipped() {
+        let mut coord = FederatedLearningCoordinator::new(AggregationMethod::FedAvg, 2).with_clip_norm(1.0);
+        coord.submit_gradient(ModelGradient { node_id: "big".into(), layer_gradients: vec![vec![30.0, 40.0]], sample_count: 1, timestamp: 0, base_version: 0 }).unwrap();
+        coord.submit_gradient(ModelGradient { node_id: "small".into(), layer_gradients: vec![vec![0.3, 0.4]], sample_count: 1, timestamp: 0, base_version: 0 }).unwrap();
+        let model = coord.aggregate().unwrap().unwrap();
+        // "big" (norm 50) scaled to norm 1: [0.6, 0.8]; mean with [0.3, 0.4]
+        assert!((model.weights[0][0] - 0.45).abs() < 1e-6 && (model.weights[0][1] - 0.6).abs() < 1e-6);
+        assert_eq!(model.clipped, vec!["big".to_string()]);
+    }
+
+    #[test]
+    fn robust_aggregation_resists_poisoned_updates() {
+        let mut grads: Vec<ModelGradient> = (0..8).map(|i| ModelGradient { node_id: format!("honest{i}"), layer_gradients: vec![vec![1.0 + 0.01 * i as f32, -1.0], vec![0.5]], sample_count: 10, timestamp: 0, base_version: 0 }).collect();
+        let honest = weighted_average(&grads.iter().collect::<Vec<_>>());
+        grads.push(ModelGradient { node_id: "evil0".into(), layer_gradients: vec![vec![1e4, 1e4], vec![-1e4]], sample_count: 10, timestamp: 0, base_version: 0 });
+        grads.push(ModelGradient { node_id: "evil1".into(), layer_gradients: vec![vec![-1e4, 5e3], vec![1e4]], sample_count: 10, timestamp: 0, base_version: 0 });
+        let off = |w: &[Vec<f32>]| w.iter().flatten().zip(honest.iter().flatten()).map(|(a, b)| (a - b).abs()).fold(0.0f32, f32::max);
+        let run = |method: AggregationMethod| {
+            let mut c = FederatedLearningCoordinator::new(method, 1);
+            for g in &grads { c.submit_gradient(g.clone()).unwrap(); }
+            c.aggregate().unwrap().unwrap()
+        };
+        assert!(off(&run(AggregationMethod::FedAvg).weights) > 100.0, "plain FedAvg is dragged by the attackers");
+        for method in [AggregationMethod::TrimmedMean { trim_fraction: 0.2 }, AggregationMethod::Krum { f: 2 }] {
+            let model = run(method.clone());
+            assert!(off(&model.weights) < 0.05, "{method:?}: {:?}", model.weights);
+            assert!(["evil0", "evil1"].iter().all(|e| model.excluded.iter().any(|x| x == e)), "{method:?}: {:?}", model.excluded);
+        }
+        // trimming is symmetric, so honest extremes may be trimmed too; Krum drops exactly f
+        let mut krum_excluded = run(AggregationMethod::Krum { f: 2 }).excluded;
+        krum_excluded.sort();
+        assert_eq!(krum_excluded, vec!["evil0".to_string(), "evil1".to_string()]);
+        let mut few = FederatedLearningCoordinator::new(AggregationMethod::Krum { f: 2 }, 1);
+        few.submit_gradient(grads[0].clone()).unwrap();
+        assert!(few.aggregate().is_err());
+    }
+
+    #[test]
+    fn secure_aggregation_matches_fedavg_with_dropout() {
+        use crate::secure_aggregation::SecureAggClient;
+        use std::collections::HashMap;
+        // every client masks and deals its self-mask shares; only `submit` reach the coordinator
+        fn deal(clients: &mut [SecureAggClient], coord: &mut FederatedLearningCoordinator, grads: &[ModelGradient], round: u64, submit: usize) {
+            for i in 0..clients.len() {
+                let (masked, shares) = clients[i].mask(&grads[i], round);
+                for s in shares { let h = clients.iter().position(|c| c.node_id == s.holder).unwrap(); clients[h].receive_self_share(s).unwrap(); }
+                if i < submit { coord.submit_masked(masked).unwrap(); }
+            }
+        }
+        fn reveal(client: &mut SecureAggClient, coord: &mut FederatedLearningCoordinator, round: u64, survivors: &[String], dropped: &[String]) {
+            client.acknowledge(round, survivors).unwrap();
+            for s in client.shares_for(round, dropped).unwrap() { coord.submit_mask_share(s); }
+            for s in client.self_shares_for(round) { coord.submit_self_share(s); }
+        }
+        let ids: Vec<String> = (0..4).map(|i| format!("n{i}")).collect();
+        let seed = |a: usize, b: usize| { let mut s = [0u8; 32]; s[0] = a.min(b) as u8; s[1] = a.max(b) as u8; s };
+        let mut clients: Vec<SecureAggClient> = (0..4).map(|i| SecureAggClient::new(ids[i].clone(), (0..4).filter(|&j| j != i).map(|j| (ids[j].clone(), seed(i, j))).collect::<HashMap<_, _>>())).collect();
+        let grads = |base_version: u64| -> Vec<ModelGradient> { (0..4).map(|i| ModelGradient { node_id: ids[i].clone(), layer_gradients: vec![vec![0.1 * (i + 1) as f32, -0.3, 2.0 / (i + 1) as f32], vec![i as f32]], sample_count: 10 * (i + 1), timestamp: 0, base_version }).collect() };
+        let plain = |n: usize| {
+            let mut c = FederatedLearningCoordinator::new(AggregationMethod::FedAvg, n);
+            for g in &grads(0)[..n] { c.submit_gradient(g.clone()).unwrap(); }
+            c.aggregate().unwrap().unwrap().weights
+        };
+        let close = |a: &[Vec<f32>], b: &[Vec<f32>]| a.iter().flatten().zip(b.iter().flatten()).all(|(x, y)| (x - y).abs() < 1e-5);
+
+        let mut coord = FederatedLearningCoordinator::new(AggregationMethod::SecureAggregation, 3);
+        let round = coord.begin_secure_round(ids.clone());
+        assert!(coord.submit_gradient(grads(0)[0].clone()).is_err(), "plaintext is refused");
+        deal(&mut clients, &mut coord, &grads(0), round, 4);
+        let survivors = coord.acknowledge_survivors();
+        assert!(coord.aggregate().unwrap().is_none(), "self masks still in place");
+        for c in clients.iter_mut() { reveal(c, &mut coord, round, &survivors, &[]); }
+        assert!(close(&coord.aggregate().unwrap().expect("all submitted").weights, &plain(4)));
+
+        // n3 drops out: the sum only unmasks once every survivor revealed its round seed with n3
+        let round = coord.begin_secure_round(ids.clone());
+        deal(&mut clients, &mut coord, &grads(1), round, 3);
+        assert_eq!(coord.dropped_clients(), vec!["n3".to_string()]);
+        let survivors = coord.acknowledge_survivors();
+        assert!(coord.submit_masked(clients[3].mask(&grads(1)[3], round).0).is_err(), "late update after survivors were announced");
+        let dropped = coord.dropped_clients();
+        for c in clients.iter_mut().take(2) { reveal(c, &mut coord, round, &survivors, &dropped); }
+        assert!(coord.aggregate().unwrap().is_none(), "n2's seed still missing");
+        reveal(&mut clients[2], &mut coord, round, &survivors, &dropped);
+        let model = coord.aggregate().unwrap().expect("survivors unmasked");
+        assert_eq!(model.version, 2);
+        assert!(close(&model.weights, &plain(3)));
+    }
+
+    #[test]
+    fn masked_updates_are_shape_and_version_checked() {
+        let mut coord = FederatedLearningCoordinator::new(AggregationMethod::SecureAggregation, 1);
+        coord.set_model_shape(&GlobalModel { version: 0, weights: vec![vec![0.0; 3]], updated_at: 0, excluded: Vec::new(), clipped: Vec::new() });
+        let round = coord.begin_secure_round(vec!["a".into()]);
+        let masked = |layers: Vec<Vec<u64>>, base_version: u64| MaskedGradient { node_id: "a".into(), masked: layers, sample_count: 1, round, base_version };
+        let rejected = |r: Result<()>| r.unwrap_err().downcast::<UpdateRejected>().unwrap();
+        assert!(matches!(rejected(coord.submit_masked(masked(vec![vec![0; 2]], 0))), UpdateRejected::ShapeMismatch { .. }));
+        assert!(matches!(rejected(coord.submit_masked(masked(vec![vec![0; 3]], 1))), UpdateRejected::Stale { .. }));
+        coord.submit_masked(masked(vec![vec![0; 3]], 0)).unwrap();
+    }
 }
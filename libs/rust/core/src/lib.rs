@@ -7,11 +7,12 @@ use parking_lot::RwLock;
 use std::time::{Duration, Instant};
 use std::path::PathBuf;
 use notify::{RecommendedWatcher, Watcher, EventKind};
-use opentelemetry::{global, sdk::{trace as sdktrace, Resource}, KeyValue};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use opentelemetry::{global, KeyValue};
+use opentelemetry_sdk::{trace as sdktrace, Resource};
 use opentelemetry_otlp::WithExportConfig;
 use axum::{routing::get, Router};
 use once_cell::sync::Lazy;
-use opentelemetry_prometheus::PrometheusExporter;
 use prometheus::{Encoder, TextEncoder};
 use std::net::SocketAddr;
 use serde::Deserialize;
@@ -20,7 +21,7 @@ use std::sync::atomic::{AtomicBool, Ordering, AtomicU64};
 static OTEL_INIT: OnceCell<()> = OnceCell::new();
 static CONFIG_CACHE: OnceCell<RwLock<CachedConfig>> = OnceCell::new();
 static PROM_INIT: OnceCell<()> = OnceCell::new();
-static EXPORTER: Lazy<RwLock<Option<PrometheusExporter>>> = Lazy::new(|| RwLock::new(None));
+static METER_PROVIDER: Lazy<RwLock<Option<opentelemetry_sdk::metrics::MeterProvider>>> = Lazy::new(|| RwLock::new(None));
 
 // --- Detection Metrics (Phase 1 observability alignment) ---
 #[derive(Clone, Debug)]
@@ -37,6 +38,9 @@ static NODE_LIVENESS: AtomicBool = AtomicBool::new(true);
 static NODE_READINESS: AtomicBool = AtomicBool::new(false);
 pub fn mark_ready() { NODE_READINESS.store(true, Ordering::SeqCst); }
 pub fn clear_ready() { NODE_READINESS.store(false, Ordering::SeqCst); }
+/// Serializes tests that flip the process-wide readiness flag.
+#[cfg(test)]
+pub(crate) static READINESS_TEST_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
 pub fn mark_not_live() { NODE_LIVENESS.store(false, Ordering::SeqCst); }
 /// Global detection metrics handle (initialized lazily).
 /// Exposed publicly for services to record detection events, while
@@ -92,13 +96,16 @@ struct CachedConfig {
 pub fn init_tracing(service: &str) -> Result<()> {
     OTEL_INIT.get_or_try_init(|| {
         let otlp_endpoint = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").unwrap_or_else(|_| "http://localhost:4317".into());
+        // bounded queue + export timeout: a slow collector drops (and counts) spans instead of stalling
+        otel_export::install_error_handler();
         let tracer = opentelemetry_otlp::new_pipeline()
             .tracing()
-            .with_endpoint(otlp_endpoint)
+            .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(otlp_endpoint).with_timeout(otel_export::exporter_timeout()))
             .with_trace_config(sdktrace::config().with_resource(Resource::new(vec![
                 KeyValue::new("service.name", service.to_string()),
             ])))
-            .install_batch(opentelemetry::runtime::Tokio)?;
+            .with_batch_config(otel_export::batch_config_from_env())
+            .install_batch(opentelemetry_sdk::runtime::Tokio)?;
         let json = std::env::var("SWARM_JSON_LOG").ok().map(|v| v=="1" || v.eq_ignore_ascii_case("true")).unwrap_or(false);
         let fmt_layer = if json {
             tracing_subscriber::fmt::layer()
@@ -117,9 +124,10 @@ pub fn init_tracing(service: &str) -> Result<()> {
                 .with_line_number(true)
         };
         let otel_layer = tracing_opentelemetry::layer().with_tracer(tracer);
-        let env_filter = tracing_subscriber::EnvFilter::from_default_env();
+        let env_filter = log_level::reloadable_filter(tracing_subscriber::EnvFilter::from_default_env());
         let registry = tracing_subscriber::registry().with(env_filter).with(fmt_layer).with(otel_layer);
         registry.try_init()?;
+        log_level::spawn_config_follower();
         Ok(())
     })?;
     info!(target: service, "Tracing + OTEL initialized");
@@ -128,32 +136,86 @@ pub fn init_tracing(service: &str) -> Result<()> {
 
 pub fn shutdown_tracer() { global::shutdown_tracer_provider(); }
 
+static OBS_SHUTDOWN: OnceCell<()> = OnceCell::new();
+
+/// Flush pending metric exports, then shut down metrics and the tracer, bounded by
+/// `SWARM_OTEL_SHUTDOWN_TIMEOUT_MS` (default 5000). Idempotent; a no-op for pipelines that
+/// were never initialized.
+pub fn shutdown_observability() {
+    OBS_SHUTDOWN.get_or_init(|| {
+        let timeout = Duration::from_millis(std::env::var("SWARM_OTEL_SHUTDOWN_TIMEOUT_MS").ok().and_then(|v| v.parse().ok()).unwrap_or(5000));
+        let provider = METER_PROVIDER.write().take();
+        let tracing_on = OTEL_INIT.get().is_some();
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        // exporters may block on a dead collector: flush on a side thread and stop waiting at the deadline
+        std::thread::spawn(move || {
+            if let Some(p) = provider {
+                if let Err(e) = p.force_flush() { tracing::warn!(error=?e, "metrics flush failed"); }
+                if let Err(e) = p.shutdown() { tracing::warn!(error=?e, "metrics shutdown failed"); }
+            }
+            if tracing_on { global::shutdown_tracer_provider(); }
+            let _ = done_tx.send(());
+        });
+        if done_rx.recv_timeout(timeout).is_err() { tracing::warn!(?timeout, "observability shutdown timed out; pending telemetry dropped"); }
+    });
+}
+
 pub fn init_metrics() -> Result<()> {
     PROM_INIT.get_or_try_init(|| {
-        let exporter = opentelemetry_prometheus::exporter().try_init()?;
-        let mut w = EXPORTER.write();
-        *w = Some(exporter);
+        let exporter = opentelemetry_prometheus::exporter().with_registry(prometheus::default_registry().clone()).build()?;
+        let mut builder = opentelemetry_sdk::metrics::MeterProvider::builder().with_reader(exporter);
+        for view in metrics_views::latency_views(&metrics_views::latency_buckets_from_env())? { builder = builder.with_view(view); }
+        let provider = builder.build();
+        global::set_meter_provider(provider.clone());
+        *METER_PROVIDER.write() = Some(provider);
+        build_info::register_metrics();
         Ok(())
     })?;
     Ok(())
 }
 
+/// `ip:port` for the health server: `SWARM_HEALTH_BIND` (default `0.0.0.0`; `::` for dual-stack).
+pub fn health_bind_addr(port: u16) -> Result<SocketAddr> {
+    parse_bind_addr(std::env::var("SWARM_HEALTH_BIND").as_deref().unwrap_or("0.0.0.0"), port)
+}
+
+fn parse_bind_addr(host: &str, port: u16) -> Result<SocketAddr> {
+    let ip: std::net::IpAddr = host.trim().trim_start_matches('[').trim_end_matches(']').parse()
+        .map_err(|_| anyhow::anyhow!("SWARM_HEALTH_BIND: {host:?} is not an IP address"))?;
+    Ok(SocketAddr::new(ip, port))
+}
+
+/// 200 when marked ready, clock-synced (if required) and every readiness check passes; else 503
+/// with the failing checks.
+async fn ready_handler() -> (axum::http::StatusCode, axum::Json<serde_json::Value>) {
+    let failing = failing_readiness_checks();
+    let ready = NODE_READINESS.load(Ordering::SeqCst) && clock_ready() && failing.is_empty();
+    let status = if ready { axum::http::StatusCode::OK } else { axum::http::StatusCode::SERVICE_UNAVAILABLE };
+    (status, axum::Json(serde_json::json!({"ready": ready, "failing": failing})))
+}
+
 pub async fn start_health_server(port: u16) -> Result<()> {
     let app = Router::new()
         .route("/live", get(|| async { axum::Json(serde_json::json!({"live": NODE_LIVENESS.load(Ordering::SeqCst)})) }))
-        .route("/ready", get(|| async { axum::Json(serde_json::json!({"ready": NODE_READINESS.load(Ordering::SeqCst)})) }))
+        .route("/ready", get(ready_handler))
         .route("/status", get(|| async {
             axum::Json(serde_json::json!({
                 "live": NODE_LIVENESS.load(Ordering::SeqCst),
                 "ready": NODE_READINESS.load(Ordering::SeqCst),
                 "config_version": CONFIG_CACHE.get().and_then(|c| c.read().cfg.config_version.clone()),
+                "clock_offset_ms": CLOCK_HEALTH.offset_ms(),
+                "bootstrap_phase": lifecycle::current_phase(),
+                "uptime_ms": build_info::uptime().as_millis() as u64,
+                "build": build_info(),
             }))
         }))
+        .route("/capacity", get(|| async { axum::Json(CAPACITY.report()) }))
         .route("/metrics", get(metrics_handler));
-    let addr = SocketAddr::from(([0,0,0,0], port));
+    let addr = health_bind_addr(port)?;
+    let listener = tokio::net::TcpListener::bind(addr).await.map_err(|e| anyhow::anyhow!("health server cannot bind {addr}: {e}"))?;
     tracing::info!(?addr, "Health server listening");
     tokio::spawn(async move {
-        if let Err(e) = axum::Server::bind(&addr).serve(app.into_make_service()).await {
+        if let Err(e) = axum::serve(listener, app).await {
             tracing::error!(error=?e, "Health server failed");
         }
     });
@@ -167,11 +229,16 @@ pub struct DynamicConfig {
     pub log_level: Option<String>,
     // --- Added for versioned & signed config roadmap alignment ---
     pub config_version: Option<String>,
-    pub config_signature: Option<String>, // placeholder (e.g., hex-encoded ed25519 or PQC signature)
+    pub config_signature: Option<String>, // hex-encoded ed25519 over the file (see config_signature)
+    // --- Detection rule overlay (applied on top of the signed rules bundle) ---
+    #[serde(default)]
+    pub disabled_rules: Option<Vec<String>>,
+    #[serde(default)]
+    pub rule_severity_overrides: Option<std::collections::HashMap<String, String>>,
 }
 
 impl Default for DynamicConfig {
-    fn default() -> Self { Self { service_name: None, nats_url: Some("127.0.0.1:4222".into()), log_level: Some("info".into()), config_version: Some("0".into()), config_signature: None } }
+    fn default() -> Self { Self { service_name: None, nats_url: Some("127.0.0.1:4222".into()), log_level: Some("info".into()), config_version: Some("0".into()), config_signature: None, disabled_rules: None, rule_severity_overrides: None } }
 }
 
 pub async fn load_config(service: &str) -> Result<DynamicConfig> {
@@ -196,13 +263,16 @@ pub async fn load_config(service: &str) -> Result<DynamicConfig> {
     builder = builder.add_source(config::Environment::with_prefix("SWARM").separator("__"));
     let cfg = builder.build()?;
     let dyn_cfg: DynamicConfig = cfg.try_deserialize()?;
-    // If signature present, attempt verification (best-effort for now)
-    if let (Some(sig), Some(file)) = (&dyn_cfg.config_signature, &file_path) {
-        if let Ok(raw) = std::fs::read_to_string(file) {
-            if !crate::config_signature::verify_config_signature(&raw, sig) {
-                tracing::warn!(?file, "Config signature verification failed");
-            }
-        }
+    if config_validate::strict_from_env() { config_validate::validate(&dyn_cfg)?; }
+    // Verify the file signature when present; SWARM_CONFIG_VERIFY=1 makes it mandatory
+    if let Some(file) = &file_path {
+        let required = config_signature::verify_required();
+        let verified = match (&dyn_cfg.config_signature, std::fs::read_to_string(file)) {
+            (Some(sig), Ok(raw)) => config_signature::verify_config_signature(&raw, sig),
+            _ => false,
+        };
+        if !verified && required { return Err(anyhow::anyhow!("config {file:?} failed signature verification (SWARM_CONFIG_VERIFY=1)")); }
+        if !verified && dyn_cfg.config_signature.is_some() { tracing::warn!(?file, "Config signature verification failed"); }
     }
     let ttl_secs: u64 = std::env::var("SWARM_CONFIG_TTL_SECS").ok().and_then(|v| v.parse().ok()).unwrap_or(30);
     let cached = CachedConfig { cfg: dyn_cfg.clone(), fetched_at: Instant::now(), ttl: Duration::from_secs(ttl_secs), file: file_path};
@@ -212,6 +282,7 @@ pub async fn load_config(service: &str) -> Result<DynamicConfig> {
         *w = cached;
     }
     if let Some(f) = lock.read().file.clone() { spawn_file_watcher(f); }
+    config_broadcast::broadcast_config(&dyn_cfg);
     Ok(dyn_cfg)
 }
 
@@ -232,11 +303,37 @@ async fn watch_loop(path: PathBuf) -> Result<()> {
     Ok(())
 }
 
+static CONFIG_RELOAD_FAILED: Lazy<Counter<u64>> = Lazy::new(|| {
+    global::meter("swarm_config").u64_counter("config_reload_failed_total").with_description("Config file reloads rejected because the file failed to parse").init()
+});
+
+/// Format of a watched config file by extension; YAML when unknown.
+fn config_file_format(path: &std::path::Path) -> config::FileFormat {
+    match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+        Some("toml") => config::FileFormat::Toml,
+        Some("json") => config::FileFormat::Json,
+        _ => config::FileFormat::Yaml,
+    }
+}
+
+fn parse_config_file(path: &std::path::Path, text: &str) -> Result<DynamicConfig> {
+    Ok(config::Config::builder().add_source(config::File::from_str(text, config_file_format(path))).build()?.try_deserialize()?)
+}
+
 async fn refresh_from_file(path: &PathBuf) -> Result<()> {
     if let Some(lock) = CONFIG_CACHE.get() {
         if let Ok(text) = tokio::fs::read_to_string(path).await {
-            let builder = config::Config::builder().add_source(config::File::from_str(&text, config::FileFormat::Yaml));
-            if let Ok(cfg) = builder.build() { if let Ok(new_cfg) = cfg.try_deserialize::<DynamicConfig>() { let mut w = lock.write(); w.cfg = new_cfg; w.fetched_at = Instant::now(); } }
+            match parse_config_file(path, &text) {
+                Err(e) => { CONFIG_RELOAD_FAILED.add(1, &[]); tracing::warn!(?path, error=%e, "config reload failed to parse"); }
+                Ok(new_cfg) => {
+                    if config_signature::verify_required() && !new_cfg.config_signature.as_deref().is_some_and(|sig| config_signature::verify_config_signature(&text, sig)) {
+                        tracing::warn!(?path, "rejected unsigned or badly signed config reload"); return Ok(());
+                    }
+                    if config_validate::strict_from_env() { if let Err(e) = config_validate::validate(&new_cfg) { tracing::warn!(error=%e, "rejected invalid config reload"); return Ok(()); } }
+                    { let mut w = lock.write(); w.cfg = new_cfg.clone(); w.fetched_at = Instant::now(); }
+                    config_broadcast::broadcast_config(&new_cfg);
+                }
+            }
         }
     }
     Ok(())
@@ -245,7 +342,7 @@ async fn refresh_from_file(path: &PathBuf) -> Result<()> {
 pub async fn force_reload(service: &str) -> Result<DynamicConfig> { load_config(service).await }
 
 async fn metrics_handler() -> axum::response::Response {
-    if EXPORTER.read().is_none() {
+    if METER_PROVIDER.read().is_none() {
         return axum::response::Response::builder().status(503).body(axum::body::Body::from("metrics not initialized")).unwrap();
     }
     let registry = prometheus::default_registry();
@@ -265,12 +362,33 @@ mod resilience; // new module providing retry & circuit breaker
 pub use resilience::{retry_async, RetryConfig, CircuitBreaker, BreakerState};
 pub mod resilience_telemetry; // telemetry metrics for resilience primitives
 pub use resilience_telemetry::{register_metrics as register_resilience_metrics, ResilienceMetrics};
-pub mod config_signature; // configuration signature verification (stub for now)
+pub mod config_signature; // ed25519 config file signatures (SWARM_CONFIG_PUBKEY / SWARM_CONFIG_VERIFY)
 pub use config_signature::verify_config_signature;
+pub mod config_broadcast; // fan-out of config updates with lag detection / re-sync
+pub use config_broadcast::{broadcast_config, subscribe_config, subscribe_config_changes, latest_config, ConfigBroadcast, ConfigSubscriber, ConfigChange, Change};
+pub mod config_validate; // strict DynamicConfig field checks behind SWARM_CONFIG_STRICT
+pub mod log_level; // reloadable EnvFilter driven by DynamicConfig.log_level
+pub use log_level::apply_log_level;
+pub mod metrics_views; // explicit latency histogram buckets (SWARM_LATENCY_BUCKETS_MS)
+pub mod build_info; // uptime + build metadata gauges (swarm_uptime_seconds, swarm_build_info)
+pub use build_info::{build_info, BuildInfo, START_INSTANT};
+pub mod readiness; // named dependency checks gating /ready
+pub use readiness::{register_readiness_check, unregister_readiness_check, failing_readiness_checks, ReadinessCheck};
+pub mod data_dir; // per-subsystem persistence layout under SWARM_DATA_DIR
+pub use data_dir::{data_root, resolve_data_dir, ensure_writable_dir};
+pub mod clock_health; // peer-timestamp clock offset estimate + skew gauge
+pub use clock_health::{CLOCK_HEALTH, ClockHealth, clock_ready};
+pub mod otel_export; // OTLP batch/timeout config + dropped-span accounting
+pub use otel_export::{spans_dropped, export_errors};
+pub mod capacity; // /capacity report: windowed rates, p99 latencies, headroom vs limits
+pub use capacity::{CAPACITY, CapacityTracker, CapacityReport};
+pub mod crypto_vrf; // ECVRF-EDWARDS25519-SHA512-TAI for leader selection
+pub use crypto_vrf::{vrf_prove, vrf_verify, select_validator_with_vrf, VrfKeypair, VrfProof, VrfOutput};
 
 // Advanced swarm intelligence modules
 pub mod ml_detection;
 pub mod federated_learning;
+pub mod secure_aggregation; // double-masked updates for federated SecureAggregation
 pub mod consensus;
 pub mod autoscaling;
 pub mod gossip;
@@ -279,12 +397,72 @@ pub mod lifecycle;
 pub mod reputation;
 mod metrics_ext; // extended metrics groups
 
-pub use ml_detection::{MLDetectionPipeline, ThreatEvent, DetectionResult, ThreatLevel};
-pub use federated_learning::{FederatedLearningCoordinator, ModelGradient, GlobalModel, AggregationMethod};
+pub use ml_detection::{MLDetectionPipeline, ThreatEvent, DetectionResult, ThreatLevel, DetectionModel, Calibration, ExtractedFeatures};
+pub use federated_learning::{FederatedLearningCoordinator, ModelGradient, GlobalModel, AggregationMethod, UpdateRejected, RoundStatus, RoundOutcome};
+pub use secure_aggregation::{SecureAggClient, MaskedGradient, MaskShare, SelfMaskShare};
 pub use consensus::{PBFTConsensus, ConsensusMessage, NodeId};
 pub use autoscaling::{AutoScaler, ResourceMetrics, ScalingDecision, ScalingThresholds};
 pub use gossip::{GossipEngine, GossipMessage, GossipKind, GossipId};
-pub use transport_quic::{QuicTransport, QuicConfig, QuicConnectionHandle};
+pub use transport_quic::{QuicTransport, QuicConfig, QuicConnectionHandle, StreamHandle, StreamKind};
 pub use lifecycle::{BootstrapState, BootstrapPhase};
 pub use reputation::{ReputationService, ReputationConfig};
 pub use metrics_ext::{EXTENDED_METRICS, ExtendedMetrics};
+
+#[cfg(test)]
+mod config_reload_tests {
+    use super::*;
+
+    #[test]
+    fn reload_parses_each_file_format() {
+        let files = [
+            ("swarm.yaml", "nats_url: nats://10.0.0.5:4222\nlog_level: debug\nconfig_version: \"7\"\ndisabled_rules: [r1]\n"),
+            ("swarm.yml", "nats_url: nats://10.0.0.5:4222\nlog_level: debug\nconfig_version: \"7\"\ndisabled_rules: [r1]\n"),
+            ("swarm.toml", "nats_url = \"nats://10.0.0.5:4222\"\nlog_level = \"debug\"\nconfig_version = \"7\"\ndisabled_rules = [\"r1\"]\n"),
+            ("swarm.json", r#"{"nats_url": "nats://10.0.0.5:4222", "log_level": "debug", "config_version": "7", "disabled_rules": ["r1"]}"#),
+        ];
+        for (name, text) in files {
+            let cfg = parse_config_file(std::path::Path::new(name), text).unwrap_or_else(|e| panic!("{name}: {e}"));
+            assert_eq!(cfg.nats_url.as_deref(), Some("nats://10.0.0.5:4222"), "{name}");
+            assert_eq!(cfg.log_level.as_deref(), Some("debug"), "{name}");
+            assert_eq!(cfg.config_version.as_deref(), Some("7"), "{name}");
+            assert_eq!(cfg.disabled_rules, Some(vec!["r1".to_string()]), "{name}");
+        }
+        assert!(parse_config_file(std::path::Path::new("swarm.json"), "nats_url: x\n").is_err());
+    }
+}
+
+#[cfg(test)]
+mod health_tests {
+    use super::*;
+
+    #[test]
+    fn shutdown_observability_is_idempotent_without_init() {
+        shutdown_observability();
+        shutdown_observability();
+    }
+
+    #[tokio::test]
+    async fn failing_check_makes_ready_return_503() {
+        let _guard = READINESS_TEST_LOCK.lock().await;
+        mark_ready();
+        register_readiness_check("nats", Box::new(|| false));
+        let (status, body) = ready_handler().await;
+        assert_eq!(status, axum::http::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body.0, serde_json::json!({"ready": false, "failing": ["nats"]}));
+
+        register_readiness_check("nats", Box::new(|| true));
+        assert_eq!(ready_handler().await.0, axum::http::StatusCode::OK);
+        clear_ready();
+        assert_eq!(ready_handler().await.0, axum::http::StatusCode::SERVICE_UNAVAILABLE);
+        unregister_readiness_check("nats");
+    }
+
+    #[test]
+    fn parses_ipv4_ipv6_and_rejects_garbage() {
+        assert_eq!(parse_bind_addr("0.0.0.0", 8080).unwrap().to_string(), "0.0.0.0:8080");
+        assert_eq!(parse_bind_addr("10.1.2.3", 9091).unwrap().to_string(), "10.1.2.3:9091");
+        assert_eq!(parse_bind_addr("::", 8080).unwrap().to_string(), "[::]:8080");
+        assert_eq!(parse_bind_addr("[fe80::1]", 8080).unwrap().to_string(), "[fe80::1]:8080");
+        assert_eq!(parse_bind_addr("localhost", 8080).unwrap_err().to_string(), "SWARM_HEALTH_BIND: \"localhost\" is not an IP address");
+    }
+}
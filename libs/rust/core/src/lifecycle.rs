@@ -6,34 +6,85 @@
 //! - KnowledgeSync
 //! - Operational
 //!
-//! Tracks timestamps for each transition and offers readiness evaluation.
+//! Tracks timestamps for each transition and offers readiness evaluation. Phase changes are
+//! published on a watch channel: `await_phase` waits for a phase, and `spawn_readiness_observer`
+//! keeps `mark_ready`/`clear_ready` in step with the FSM (ready exactly while Operational) and
+//! reports the phase on `/status`.
 
 use std::time::{Instant, Duration};
 use serde::{Serialize, Deserialize};
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use tokio::sync::watch;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum BootstrapPhase { HardwareInit, NetworkDiscovery, KnowledgeSync, Operational }
 
+/// Phase last seen by the readiness observer, for `/status`.
+static OBSERVED_PHASE: Lazy<RwLock<Option<BootstrapPhase>>> = Lazy::new(|| RwLock::new(None));
+
+pub fn current_phase() -> Option<BootstrapPhase> { *OBSERVED_PHASE.read() }
+
 #[derive(Debug)]
 pub struct BootstrapState {
     phase: BootstrapPhase,
     started_at: Instant,
     phase_started_at: Instant,
     phase_durations: Vec<(BootstrapPhase, Duration)>,
+    phase_tx: watch::Sender<BootstrapPhase>,
 }
 
 impl BootstrapState {
-    pub fn new() -> Self { Self { phase: BootstrapPhase::HardwareInit, started_at: Instant::now(), phase_started_at: Instant::now(), phase_durations: Vec::new() } }
+    pub fn new() -> Self { Self { phase: BootstrapPhase::HardwareInit, started_at: Instant::now(), phase_started_at: Instant::now(), phase_durations: Vec::new(), phase_tx: watch::channel(BootstrapPhase::HardwareInit).0 } }
     pub fn phase(&self) -> BootstrapPhase { self.phase }
     pub fn advance(&mut self) {
+        let next = match self.phase { BootstrapPhase::HardwareInit => BootstrapPhase::NetworkDiscovery, BootstrapPhase::NetworkDiscovery => BootstrapPhase::KnowledgeSync, BootstrapPhase::KnowledgeSync => BootstrapPhase::Operational, BootstrapPhase::Operational => BootstrapPhase::Operational };
+        self.enter(next);
+    }
+    /// Fall back to an earlier phase (e.g. lost all peers -> NetworkDiscovery).
+    pub fn regress_to(&mut self, phase: BootstrapPhase) { if phase < self.phase { self.enter(phase); } }
+    fn enter(&mut self, phase: BootstrapPhase) {
         let now = Instant::now();
-        let dur = now - self.phase_started_at;
-        self.phase_durations.push((self.phase, dur));
-        self.phase = match self.phase { BootstrapPhase::HardwareInit => BootstrapPhase::NetworkDiscovery, BootstrapPhase::NetworkDiscovery => BootstrapPhase::KnowledgeSync, BootstrapPhase::KnowledgeSync => BootstrapPhase::Operational, BootstrapPhase::Operational => BootstrapPhase::Operational };
+        self.phase_durations.push((self.phase, now - self.phase_started_at));
+        self.phase = phase;
         self.phase_started_at = now;
+        self.phase_tx.send_replace(phase);
     }
     pub fn is_ready(&self) -> bool { self.phase == BootstrapPhase::Operational }
     pub fn durations(&self) -> &Vec<(BootstrapPhase, Duration)> { &self.phase_durations }
+    pub fn elapsed(&self) -> Duration { self.started_at.elapsed() }
+
+    pub fn subscribe(&self) -> watch::Receiver<BootstrapPhase> { self.phase_tx.subscribe() }
+
+    /// Resolves once the FSM is at or past `phase`; does not borrow the state while waiting.
+    pub fn await_phase(&self, phase: BootstrapPhase) -> impl std::future::Future<Output = ()> + Send + 'static {
+        let mut rx = self.subscribe();
+        async move { let _ = rx.wait_for(|p| *p >= phase).await; }
+    }
+
+    /// Call `on_change` with every phase the FSM moves to (latest wins if changes race ahead).
+    pub fn spawn_phase_observer(&self, on_change: impl Fn(BootstrapPhase) + Send + 'static) -> tokio::task::JoinHandle<()> {
+        let mut rx = self.subscribe();
+        tokio::spawn(async move {
+            loop {
+                let phase = *rx.borrow_and_update();
+                on_change(phase);
+                if rx.changed().await.is_err() { break; }
+            }
+        })
+    }
+
+    /// Drive node readiness from the FSM: ready in Operational, not ready on any regression.
+    pub fn spawn_readiness_observer(&self) -> tokio::task::JoinHandle<()> {
+        self.spawn_phase_observer(|phase| {
+            *OBSERVED_PHASE.write() = Some(phase);
+            if phase == BootstrapPhase::Operational { crate::mark_ready() } else { crate::clear_ready() }
+        })
+    }
+}
+
+impl Default for BootstrapState {
+    fn default() -> Self { Self::new() }
 }
 
 #[cfg(test)]
@@ -48,4 +99,35 @@ mod tests {
         st.advance();
         assert!(st.is_ready());
     }
+
+    /// Wait until the readiness observer has handled `phase`.
+    async fn observed(phase: BootstrapPhase) {
+        tokio::time::timeout(Duration::from_secs(1), async { while current_phase() != Some(phase) { tokio::task::yield_now().await; } })
+            .await
+            .unwrap_or_else(|_| panic!("observer never saw {phase:?}"));
+    }
+
+    #[tokio::test]
+    async fn readiness_flips_exactly_at_operational() {
+        let _guard = crate::READINESS_TEST_LOCK.lock().await;
+        let ready = || crate::NODE_READINESS.load(std::sync::atomic::Ordering::SeqCst);
+        crate::mark_ready(); // stale flag from before the FSM took over
+        let mut st = BootstrapState::new();
+        let observer = st.spawn_readiness_observer();
+        observed(BootstrapPhase::HardwareInit).await;
+        assert!(!ready(), "HardwareInit must clear readiness");
+        let operational = st.await_phase(BootstrapPhase::Operational);
+        for expect in [BootstrapPhase::NetworkDiscovery, BootstrapPhase::KnowledgeSync, BootstrapPhase::Operational] {
+            assert!(!ready(), "ready before {expect:?}");
+            st.advance();
+            observed(expect).await;
+        }
+        assert!(ready());
+        operational.await;
+
+        st.regress_to(BootstrapPhase::NetworkDiscovery);
+        observed(BootstrapPhase::NetworkDiscovery).await;
+        assert!(!ready(), "regression must clear readiness");
+        observer.abort();
+    }
 }
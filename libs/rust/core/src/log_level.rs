@@ -0,0 +1,85 @@
+//! Runtime log level changes.
+//!
+//! `init_tracing` installs its `EnvFilter` behind a reload layer; `apply_log_level` swaps it, and a
+//! follower task applies `log_level` at startup and whenever a config reload changes it. Only the
+//! default level is replaced: per-target directives from `RUST_LOG` stay in effect. Invalid levels
+//! are rejected with a warning and the current filter stays in place.
+
+use anyhow::{anyhow, Result};
+use once_cell::sync::OnceCell;
+use tracing_subscriber::{filter::LevelFilter, reload, EnvFilter, Registry};
+
+static FILTER_HANDLE: OnceCell<reload::Handle<EnvFilter, Registry>> = OnceCell::new();
+/// Directives of the filter passed to `reloadable_filter`, rebuilt on every level change.
+static BASE_DIRECTIVES: OnceCell<String> = OnceCell::new();
+
+/// Wrap `filter` in a reload layer whose handle `apply_log_level` uses (first caller wins).
+pub fn reloadable_filter(filter: EnvFilter) -> reload::Layer<EnvFilter, Registry> {
+    let base = filter.to_string();
+    let (layer, handle) = reload::Layer::new(filter);
+    if FILTER_HANDLE.set(handle).is_ok() { let _ = BASE_DIRECTIVES.set(base); }
+    layer
+}
+
+/// `base` with its bare default-level directives replaced by `level`.
+fn with_default_level(base: &str, level: LevelFilter) -> Result<EnvFilter> {
+    let kept = base.split(',').map(str::trim).filter(|d| !d.is_empty() && d.parse::<LevelFilter>().is_err());
+    let directives: Vec<String> = std::iter::once(level.to_string()).chain(kept.map(str::to_string)).collect();
+    EnvFilter::try_new(directives.join(",")).map_err(|e| anyhow!("invalid log directives: {e}"))
+}
+
+/// Replace the live filter with `level` (trace, debug, info, warn, error or off).
+pub fn apply_log_level(level: &str) -> Result<()> {
+    let res = level.trim().parse::<LevelFilter>().map_err(|_| anyhow!("invalid log level {level:?}")).and_then(|lf| {
+        let handle = FILTER_HANDLE.get().ok_or_else(|| anyhow!("tracing not initialized"))?;
+        let filter = with_default_level(BASE_DIRECTIVES.get().map_or("", String::as_str), lf)?;
+        handle.reload(filter).map_err(|e| anyhow!("log filter reload failed: {e}"))
+    });
+    match &res {
+        Ok(()) => tracing::info!(level, "log level applied"),
+        Err(e) => tracing::warn!(error=%e, "log level not applied"),
+    }
+    res
+}
+
+/// Apply the current `log_level`, then every config change to it (needs a Tokio runtime; a no-op
+/// without one).
+pub fn spawn_config_follower() {
+    let Ok(rt) = tokio::runtime::Handle::try_current() else { return };
+    let mut changes = crate::subscribe_config_changes();
+    rt.spawn(async move {
+        if let Some(level) = crate::latest_config().and_then(|c| c.log_level) { let _ = apply_log_level(&level); }
+        loop {
+            match changes.recv().await {
+                Ok(change) => if let Some(level) = change.log_level.and_then(|c| c.new) { let _ = apply_log_level(&level); },
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
+                    if let Some(level) = crate::latest_config().and_then(|c| c.log_level) { let _ = apply_log_level(&level); }
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn apply_log_level_changes_effective_level() {
+        let subscriber = tracing_subscriber::registry().with(reloadable_filter(EnvFilter::new("info,noisy=error,swarm_rules=trace")));
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(!tracing::enabled!(tracing::Level::DEBUG));
+            apply_log_level("debug").unwrap();
+            assert!(tracing::enabled!(tracing::Level::DEBUG));
+            assert!(apply_log_level("verbose").is_err());
+            assert!(tracing::enabled!(tracing::Level::DEBUG), "invalid level must leave the filter alone");
+
+            apply_log_level("warn").unwrap();
+            assert!(!tracing::enabled!(tracing::Level::INFO));
+            assert!(tracing::enabled!(target: "swarm_rules", tracing::Level::TRACE), "RUST_LOG target directives survive");
+            assert!(!tracing::enabled!(target: "noisy", tracing::Level::WARN));
+        });
+    }
+}
@@ -16,7 +16,9 @@ pub struct ConsensusMetrics {
 pub struct FederatedLearningMetrics {
     pub rounds_total: Counter<u64>,
     pub participants_total: Counter<u64>,
-    pub rejected_gradients_total: Counter<u64>,
+    pub rejected_updates_total: Counter<u64>,
+    pub clipped_updates_total: Counter<u64>,
+    pub stale_updates_total: Counter<u64>,
     pub aggregation_latency_ms: Histogram<f64>,
 }
 
@@ -50,13 +52,15 @@ pub static EXTENDED_METRICS: Lazy<ExtendedMetrics> = Lazy::new(|| {
             proposals_total: EXT_METER.u64_counter("swarm_consensus_proposals_total").with_description("Total consensus proposals initiated").init(),
             prepares_total: EXT_METER.u64_counter("swarm_consensus_prepares_total").with_description("Total prepare messages handled").init(),
             commits_total: EXT_METER.u64_counter("swarm_consensus_commits_total").with_description("Total commit messages handled").init(),
-            view_changes_total: EXT_METER.u64_counter("swarm_consensus_view_changes_total").with_description("Total view changes (future)").init(),
+            view_changes_total: EXT_METER.u64_counter("swarm_consensus_view_changes_total").with_description("Total view changes triggered by round timeout").init(),
             phase_latency_ms: EXT_METER.f64_histogram("swarm_consensus_phase_latency_ms").with_description("Latency per consensus phase ms").with_unit(Unit::new("ms")).init(),
         },
         fl: FederatedLearningMetrics {
             rounds_total: EXT_METER.u64_counter("swarm_fl_rounds_total").with_description("Total federated learning rounds").init(),
             participants_total: EXT_METER.u64_counter("swarm_fl_participants_total").with_description("Total participants aggregated").init(),
-            rejected_gradients_total: EXT_METER.u64_counter("swarm_fl_rejected_gradients_total").with_description("Gradients rejected (validation/security)").init(),
+            rejected_updates_total: EXT_METER.u64_counter("swarm_fl_rejected_updates_total").with_description("Client updates rejected by validation (shape, non-finite)").init(),
+            clipped_updates_total: EXT_METER.u64_counter("swarm_fl_clipped_updates_total").with_description("Client updates scaled down by L2-norm clipping").init(),
+            stale_updates_total: EXT_METER.u64_counter("swarm_fl_stale_updates_total").with_description("Client updates rejected for training on a stale global model").init(),
             aggregation_latency_ms: EXT_METER.f64_histogram("swarm_fl_aggregation_latency_ms").with_description("Aggregation latency ms").with_unit(Unit::new("ms")).init(),
         },
         autoscale: AutoscaleMetrics {
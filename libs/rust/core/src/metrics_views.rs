@@ -0,0 +1,59 @@
+//! Explicit histogram buckets for the detection latency histograms.
+//!
+//! The SDK default boundaries top out coarsely and start at 0/5ms, which flattens our
+//! sub-millisecond-to-seconds spread. `SWARM_LATENCY_BUCKETS_MS` (comma list, ms) replaces them for
+//! `swarm_detection_alert_latency_ms` and `swarm_ingest_e2e_latency_ms`; unset or unparsable falls
+//! back to `DEFAULT_LATENCY_BUCKETS_MS`. Applied as views when `init_metrics` builds the provider.
+
+use anyhow::Result;
+use opentelemetry_sdk::metrics::{new_view, Aggregation, Instrument, Stream, View};
+
+pub const DEFAULT_LATENCY_BUCKETS_MS: [f64; 11] = [0.5, 1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0];
+pub const LATENCY_HISTOGRAMS: [&str; 2] = ["swarm_detection_alert_latency_ms", "swarm_ingest_e2e_latency_ms"];
+
+/// Parse a comma list of boundaries into a sorted, de-duplicated set; `None` if any entry is
+/// not a finite number or the list is empty.
+pub fn parse_buckets(raw: &str) -> Option<Vec<f64>> {
+    let mut b = raw.split(',').map(|s| s.trim().parse::<f64>().ok().filter(|v| v.is_finite())).collect::<Option<Vec<_>>>()?;
+    b.sort_by(f64::total_cmp);
+    b.dedup();
+    (!b.is_empty()).then_some(b)
+}
+
+pub fn latency_buckets_from_env() -> Vec<f64> {
+    match std::env::var("SWARM_LATENCY_BUCKETS_MS") {
+        Ok(raw) => parse_buckets(&raw).unwrap_or_else(|| {
+            tracing::warn!(%raw, "invalid SWARM_LATENCY_BUCKETS_MS, using default latency buckets");
+            DEFAULT_LATENCY_BUCKETS_MS.to_vec()
+        }),
+        Err(_) => DEFAULT_LATENCY_BUCKETS_MS.to_vec(),
+    }
+}
+
+/// One view per latency histogram using `buckets` as explicit boundaries.
+pub fn latency_views(buckets: &[f64]) -> Result<Vec<Box<dyn View>>> {
+    LATENCY_HISTOGRAMS.iter().map(|name| {
+        let aggregation = Aggregation::ExplicitBucketHistogram { boundaries: buckets.to_vec(), record_min_max: true };
+        Ok(new_view(Instrument::new().name(*name), Stream::new().aggregation(aggregation))?)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configured_buckets_reach_the_views() {
+        let buckets = parse_buckets("250, 0.25,1,1, 5").unwrap();
+        assert_eq!(buckets, vec![0.25, 1.0, 5.0, 250.0]);
+        assert!(parse_buckets("1,fast").is_none());
+
+        let views = latency_views(&buckets).unwrap();
+        for name in LATENCY_HISTOGRAMS {
+            let inst = Instrument::new().name(name);
+            let stream = views.iter().find_map(|v| v.match_inst(&inst)).expect("view for latency histogram");
+            assert_eq!(stream.aggregation, Some(Aggregation::ExplicitBucketHistogram { boundaries: buckets.clone(), record_min_max: true }));
+        }
+        assert!(views.iter().all(|v| v.match_inst(&Instrument::new().name("swarm_ingest_events_total")).is_none()));
+    }
+}
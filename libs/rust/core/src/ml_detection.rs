@@ -5,17 +5,44 @@
 //! 2. Statistical anomaly detection (simple distribution deviation)
 //! 3. ML classification (placeholder heuristic until model inference integrated)
 //!
+//! The stage-3 model sits behind an `ArcSwap`: `swap_model` replaces it atomically while
+//! detections keep running. Each classification loads the model once, so it is scored entirely by
+//! either the old or the new model, whose version is reported in `DetectionResult::model_version`.
+//!
+//! Each model carries its `Calibration`: optional Platt scaling of the raw score into a
+//! probability, then the boundaries mapping that probability to a `ThreatLevel`. Models with
+//! different score distributions thus alert consistently; the boundaries used are returned with
+//! every stage-3 result.
+//!
+//! With `with_feature_cache(cap)`, features extracted for stage 2 are kept in an LRU keyed by a
+//! SHA-256 of the event content (everything but the timestamp) and the model's `feature_version`,
+//! so retried or gossip-duplicated events skip re-extraction. Swapping in a model with another
+//! `feature_version` empties the cache. Lookups count `swarm_detection_feature_cache_total{result}`.
+//!
 //! Metrics integration:
 //! - Increments signature/anomaly counters when early exits
 //! - Records alert latency per stage and end-to-end latency
 
 use anyhow::Result;
+use arc_swap::ArcSwap;
+use lru::LruCache;
+use once_cell::sync::Lazy;
+use opentelemetry::{metrics::Counter, KeyValue};
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::Instant;
 
 use crate::DETECTION_METRICS;
 
+static FEATURE_CACHE_LOOKUPS: Lazy<Counter<u64>> = Lazy::new(|| {
+    opentelemetry::global::meter("swarm_detection").u64_counter("swarm_detection_feature_cache_total").with_description("Feature cache lookups by result (hit|miss)").init()
+});
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ThreatEvent {
     pub timestamp: i64,
@@ -40,12 +67,97 @@ pub struct DetectionResult {
     pub confidence: f32,
     pub attack_type: Option<String>,
     pub latency_ms: f64,
+    /// Model that produced a stage-3 result; `None` for signature/anomaly early exits.
+    pub model_version: Option<String>,
+    /// Calibration that mapped the stage-3 score to `level`.
+    pub calibration: Option<Calibration>,
+}
+
+/// Per-model mapping from raw score to `ThreatLevel`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Calibration {
+    /// Calibrated scores above these are at least Suspicious / Malicious / Critical.
+    pub suspicious: f32,
+    pub malicious: f32,
+    #[serde(default)]
+    pub critical: Option<f32>,
+    /// Platt scaling `1 / (1 + exp(a * raw + b))`, if fitted for the model.
+    #[serde(default)]
+    pub platt: Option<(f32, f32)>,
+}
+
+impl Default for Calibration {
+    fn default() -> Self { Self { suspicious: 0.5, malicious: 0.8, critical: None, platt: None } }
+}
+
+impl Calibration {
+    pub fn calibrate(&self, raw: f32) -> f32 {
+        match self.platt { Some((a, b)) => 1.0 / (1.0 + (a * raw + b).exp()), None => raw }
+    }
+
+    pub fn level(&self, score: f32) -> ThreatLevel {
+        if self.critical.is_some_and(|c| score > c) { ThreatLevel::Critical }
+        else if score > self.malicious { ThreatLevel::Malicious }
+        else if score > self.suspicious { ThreatLevel::Suspicious }
+        else { ThreatLevel::Benign }
+    }
+}
+
+/// Stage-3 scoring model: a linear layer over the features, or the feature mean without weights.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetectionModel {
+    pub version: String,
+    #[serde(default)]
+    pub weights: Option<Vec<f32>>,
+    #[serde(default)]
+    pub bias: f32,
+    #[serde(default)]
+    pub calibration: Calibration,
+    /// Version of the feature extraction this model expects; part of the feature cache key.
+    #[serde(default = "default_feature_version")]
+    pub feature_version: u32,
+}
+
+fn default_feature_version() -> u32 { 1 }
+
+impl Default for DetectionModel {
+    fn default() -> Self { Self { version: "heuristic-v0".into(), weights: None, bias: 0.0, calibration: Calibration::default(), feature_version: default_feature_version() } }
+}
+
+/// Derived per-event statistics used by the anomaly stage.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractedFeatures {
+    pub mean: f32,
+    pub std_dev: f32,
+    pub anomaly_score: f32,
+}
+
+/// Stable content hash of `event` (timestamp excluded) under `feature_version`.
+pub fn feature_cache_key(event: &ThreatEvent, feature_version: u32) -> [u8; 32] {
+    let mut h = Sha256::new();
+    h.update(feature_version.to_le_bytes());
+    for field in [&event.source_ip, &event.dest_ip, &event.protocol] { h.update((field.len() as u64).to_le_bytes()); h.update(field.as_bytes()); }
+    h.update((event.payload_size as u64).to_le_bytes());
+    for f in &event.features { h.update(f.to_bits().to_le_bytes()); }
+    h.finalize().into()
+}
+
+impl DetectionModel {
+    fn score(&self, features: &[f32]) -> f32 {
+        match &self.weights {
+            Some(w) => (w.iter().zip(features).map(|(w, x)| w * x).sum::<f32>() + self.bias).clamp(0.0, 1.0),
+            None => features.iter().sum::<f32>() / features.len() as f32,
+        }
+    }
 }
 
 pub struct MLDetectionPipeline {
     signature_cache: HashMap<String, bool>,
     anomaly_threshold: f32,
-    ml_threshold: f32,
+    model: ArcSwap<DetectionModel>,
+    feature_cache: Option<Mutex<LruCache<[u8; 32], Arc<ExtractedFeatures>>>>,
+    feature_cache_hits: AtomicU64,
+    feature_cache_misses: AtomicU64,
 }
 
 impl MLDetectionPipeline {
@@ -53,10 +165,37 @@ impl MLDetectionPipeline {
         Self {
             signature_cache: HashMap::new(),
             anomaly_threshold: 0.7,
-            ml_threshold: 0.8,
+            model: ArcSwap::from_pointee(DetectionModel::default()),
+            feature_cache: None,
+            feature_cache_hits: AtomicU64::new(0),
+            feature_cache_misses: AtomicU64::new(0),
         }
     }
 
+    /// Cache extracted features for up to `capacity` distinct events.
+    pub fn with_feature_cache(mut self, capacity: usize) -> Self {
+        self.feature_cache = Some(Mutex::new(LruCache::new(NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN))));
+        self
+    }
+
+    /// (hits, misses) of the feature cache.
+    pub fn feature_cache_stats(&self) -> (u64, u64) { (self.feature_cache_hits.load(Ordering::Relaxed), self.feature_cache_misses.load(Ordering::Relaxed)) }
+
+    pub fn with_model(self, model: DetectionModel) -> Self { self.model.store(Arc::new(model)); self }
+
+    /// Install `model` for all subsequent classifications; in-flight ones finish on the old model,
+    /// which is returned.
+    pub fn swap_model(&self, model: DetectionModel) -> Arc<DetectionModel> {
+        let to = model.version.clone();
+        let feature_version = model.feature_version;
+        let old = self.model.swap(Arc::new(model));
+        if old.feature_version != feature_version { if let Some(cache) = &self.feature_cache { cache.lock().clear(); } }
+        tracing::info!(from=%old.version, %to, "detection model swapped");
+        old
+    }
+
+    pub fn model_version(&self) -> String { self.model.load().version.clone() }
+
     /// Stage 1: Signature-based detection (< 10ms)
     pub async fn signature_match(&self, event: &ThreatEvent) -> Result<Option<DetectionResult>> {
         let start = Instant::now();
@@ -71,6 +210,8 @@ impl MLDetectionPipeline {
                 confidence: 1.0,
                 attack_type: Some("known_threat".to_string()),
                 latency_ms: latency,
+                model_version: None,
+                calibration: None,
             }));
         }
         Ok(None)
@@ -80,7 +221,7 @@ impl MLDetectionPipeline {
     pub async fn anomaly_detect(&self, event: &ThreatEvent) -> Result<Option<DetectionResult>> {
         let start = Instant::now();
         if event.features.is_empty() { return Ok(None); }
-        let anomaly_score = self.calculate_anomaly_score(&event.features);
+        let anomaly_score = self.features_for(event).anomaly_score;
         if anomaly_score > self.anomaly_threshold {
             let latency = start.elapsed().as_secs_f64() * 1000.0;
             DETECTION_METRICS.anomaly_total.add(1, &[]);
@@ -90,6 +231,8 @@ impl MLDetectionPipeline {
                 confidence: anomaly_score,
                 attack_type: Some("anomaly".to_string()),
                 latency_ms: latency,
+                model_version: None,
+                calibration: None,
             }));
         }
         Ok(None)
@@ -98,22 +241,20 @@ impl MLDetectionPipeline {
     /// Stage 3: ML classification (< 1s) placeholder heuristic
     pub async fn ml_classify(&self, event: &ThreatEvent) -> Result<DetectionResult> {
         let start = Instant::now();
+        let model = self.model.load_full(); // one snapshot for the whole classification
         if event.features.is_empty() {
-            return Ok(DetectionResult { level: ThreatLevel::Benign, confidence: 0.0, attack_type: None, latency_ms: 0.0 });
+            return Ok(DetectionResult { level: ThreatLevel::Benign, confidence: 0.0, attack_type: None, latency_ms: 0.0, model_version: Some(model.version.clone()), calibration: Some(model.calibration.clone()) });
         }
-        let (confidence, attack_type) = self.neural_inference(&event.features);
-        let level = if confidence > self.ml_threshold {
-            ThreatLevel::Malicious
-        } else if confidence > 0.5 {
-            ThreatLevel::Suspicious
-        } else {
-            ThreatLevel::Benign
-        };
+        let (raw, attack_type) = self.neural_inference(&model, &event.features);
+        let confidence = model.calibration.calibrate(raw);
+        let level = model.calibration.level(confidence);
         Ok(DetectionResult {
             level,
             confidence,
             attack_type: Some(attack_type),
             latency_ms: start.elapsed().as_secs_f64() * 1000.0,
+            model_version: Some(model.version.clone()),
+            calibration: Some(model.calibration.clone()),
         })
     }
 
@@ -127,15 +268,32 @@ impl MLDetectionPipeline {
         Ok(res)
     }
 
-    fn calculate_anomaly_score(&self, features: &[f32]) -> f32 {
+    /// Extracted features for `event`, from the cache when enabled.
+    fn features_for(&self, event: &ThreatEvent) -> Arc<ExtractedFeatures> {
+        let Some(cache) = &self.feature_cache else { return Arc::new(Self::extract_features(&event.features)) };
+        let key = feature_cache_key(event, self.model.load().feature_version);
+        if let Some(hit) = cache.lock().get(&key).cloned() {
+            self.feature_cache_hits.fetch_add(1, Ordering::Relaxed);
+            FEATURE_CACHE_LOOKUPS.add(1, &[KeyValue::new("result", "hit")]);
+            return hit;
+        }
+        self.feature_cache_misses.fetch_add(1, Ordering::Relaxed);
+        FEATURE_CACHE_LOOKUPS.add(1, &[KeyValue::new("result", "miss")]);
+        let extracted = Arc::new(Self::extract_features(&event.features));
+        cache.lock().put(key, extracted.clone());
+        extracted
+    }
+
+    fn extract_features(features: &[f32]) -> ExtractedFeatures {
         let mean: f32 = features.iter().sum::<f32>() / features.len() as f32;
         let variance: f32 = features.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / features.len() as f32;
         let std_dev = variance.sqrt().max(1e-6);
-        features.iter().map(|x| ((x - mean) / std_dev).abs()).sum::<f32>() / features.len() as f32 / 3.0
+        let anomaly_score = features.iter().map(|x| ((x - mean) / std_dev).abs()).sum::<f32>() / features.len() as f32 / 3.0;
+        ExtractedFeatures { mean, std_dev, anomaly_score }
     }
 
-    fn neural_inference(&self, features: &[f32]) -> (f32, String) {
-        let score: f32 = features.iter().sum::<f32>() / features.len() as f32;
+    fn neural_inference(&self, model: &DetectionModel, features: &[f32]) -> (f32, String) {
+        let score = model.score(features);
         let attack_type = if score > 0.8 { "ddos" } else if score > 0.6 { "port_scan" } else { "unknown" };
         (score, attack_type.to_string())
     }
@@ -152,4 +310,60 @@ mod tests {
         let res = pipeline.detect(&evt).await.unwrap();
         assert!(matches!(res.level, ThreatLevel::Benign | ThreatLevel::Suspicious | ThreatLevel::Malicious));
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn detections_during_swap_see_one_model() {
+        let pipeline = Arc::new(MLDetectionPipeline::new().with_model(DetectionModel { version: "v1".into(), ..Default::default() }));
+        let evt = ThreatEvent { timestamp: 0, source_ip: "1.1.1.1".into(), dest_ip: "2.2.2.2".into(), protocol: "TCP".into(), payload_size: 128, features: vec![0.5; 4] };
+        let workers: Vec<_> = (0..4).map(|_| {
+            let (p, evt) = (pipeline.clone(), evt.clone());
+            tokio::spawn(async move {
+                let mut seen = Vec::new();
+                for _ in 0..2000 { let r = p.detect(&evt).await.unwrap(); seen.push((r.model_version.unwrap(), r.confidence)); tokio::task::yield_now().await; }
+                seen
+            })
+        }).collect();
+        tokio::task::yield_now().await;
+        let old = pipeline.swap_model(DetectionModel { version: "v2".into(), weights: Some(vec![0.25; 4]), bias: 0.3, ..Default::default() });
+        assert_eq!(old.version, "v1");
+        for w in workers {
+            for (version, confidence) in w.await.unwrap() {
+                match version.as_str() { "v1" => assert_eq!(confidence, 0.5), "v2" => assert!((confidence - 0.8).abs() < 1e-6), v => panic!("unknown version {v}") }
+            }
+        }
+        assert_eq!(pipeline.detect(&evt).await.unwrap().model_version.as_deref(), Some("v2"));
+    }
+
+    #[tokio::test]
+    async fn calibration_decides_threat_level() {
+        let evt = ThreatEvent { timestamp: 0, source_ip: "1.1.1.1".into(), dest_ip: "2.2.2.2".into(), protocol: "TCP".into(), payload_size: 128, features: vec![0.7; 4] };
+        let strict = Calibration { suspicious: 0.3, malicious: 0.6, critical: Some(0.65), platt: None };
+        let default = MLDetectionPipeline::new().detect(&evt).await.unwrap();
+        let tuned = MLDetectionPipeline::new().with_model(DetectionModel { calibration: strict.clone(), ..Default::default() }).detect(&evt).await.unwrap();
+        assert_eq!((default.level, default.confidence), (ThreatLevel::Suspicious, 0.7));
+        assert_eq!((tuned.level, tuned.confidence), (ThreatLevel::Critical, 0.7));
+        assert_eq!(tuned.calibration, Some(strict));
+
+        // Platt scaling: 1 / (1 + e^(-10 * 0.7 + 5)) ~ 0.88
+        let platt = MLDetectionPipeline::new().with_model(DetectionModel { calibration: Calibration { platt: Some((-10.0, 5.0)), ..Default::default() }, ..Default::default() }).detect(&evt).await.unwrap();
+        assert!((platt.confidence - 0.8808).abs() < 1e-3, "{}", platt.confidence);
+        assert_eq!(platt.level, ThreatLevel::Malicious);
+    }
+
+    #[tokio::test]
+    async fn repeated_event_hits_feature_cache() {
+        let pipeline = MLDetectionPipeline::new().with_feature_cache(16);
+        let evt = ThreatEvent { timestamp: 0, source_ip: "1.1.1.1".into(), dest_ip: "2.2.2.2".into(), protocol: "TCP".into(), payload_size: 128, features: vec![0.1, 0.9, 0.2, 0.8] };
+        let first = pipeline.detect(&evt).await.unwrap();
+        assert_eq!(pipeline.feature_cache_stats(), (0, 1));
+        let again = pipeline.detect(&ThreatEvent { timestamp: 99, ..evt.clone() }).await.unwrap(); // retry, re-stamped
+        assert_eq!(pipeline.feature_cache_stats(), (1, 1));
+        assert_eq!((first.level, first.confidence), (again.level, again.confidence));
+
+        pipeline.detect(&ThreatEvent { features: vec![0.1, 0.9, 0.2, 0.7], ..evt.clone() }).await.unwrap();
+        assert_eq!(pipeline.feature_cache_stats(), (1, 2), "different content is a miss");
+        pipeline.swap_model(DetectionModel { feature_version: 2, ..Default::default() });
+        pipeline.detect(&evt).await.unwrap();
+        assert_eq!(pipeline.feature_cache_stats(), (1, 3), "new extractor version invalidates");
+    }
 }
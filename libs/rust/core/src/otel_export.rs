@@ -0,0 +1,138 @@
+//! Span export tuning for the OTLP batch pipeline.
+//!
+//! The batch processor never blocks span recording: when its queue is full spans are dropped,
+//! and an export that exceeds the timeout is abandoned. Both are reported through the global
+//! OTel error handler, which `install_error_handler` turns into counters
+//! (`swarm_otel_spans_dropped_total`, `swarm_otel_export_errors_total`) instead of log spam.
+//!
+//! Env (standard `OTEL_BSP_*` names, millis for durations):
+//!   `OTEL_BSP_MAX_QUEUE_SIZE` (2048) | `OTEL_BSP_MAX_EXPORT_BATCH_SIZE` (512, capped at queue) |
+//!   `OTEL_BSP_SCHEDULE_DELAY` (5000) | `OTEL_BSP_EXPORT_TIMEOUT` (5000) |
+//!   `OTEL_EXPORTER_OTLP_TIMEOUT` (3000, per gRPC request).
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use once_cell::sync::{Lazy, OnceCell};
+use opentelemetry::global;
+use opentelemetry::metrics::Counter;
+use opentelemetry::trace::TraceError;
+use opentelemetry_sdk::runtime::TrySendError;
+use opentelemetry_sdk::trace::BatchConfig;
+
+static SPANS_DROPPED: AtomicU64 = AtomicU64::new(0);
+static EXPORT_ERRORS: AtomicU64 = AtomicU64::new(0);
+static HANDLER_INSTALLED: OnceCell<()> = OnceCell::new();
+
+static DROPPED_CTR: Lazy<Counter<u64>> = Lazy::new(|| {
+    global::meter("swarm-otel").u64_counter("swarm_otel_spans_dropped_total").with_description("Spans dropped because the export queue was full").init()
+});
+static EXPORT_ERR_CTR: Lazy<Counter<u64>> = Lazy::new(|| {
+    global::meter("swarm-otel").u64_counter("swarm_otel_export_errors_total").with_description("Span export batches that failed or timed out").init()
+});
+
+fn env_u64(key: &str, default: u64) -> u64 { std::env::var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(default) }
+
+/// Batch processor settings from `OTEL_BSP_*`.
+pub fn batch_config_from_env() -> BatchConfig {
+    let queue = env_u64("OTEL_BSP_MAX_QUEUE_SIZE", 2048).max(1) as usize;
+    let batch = (env_u64("OTEL_BSP_MAX_EXPORT_BATCH_SIZE", 512).max(1) as usize).min(queue);
+    BatchConfig::default()
+        .with_max_queue_size(queue)
+        .with_max_export_batch_size(batch)
+        .with_scheduled_delay(Duration::from_millis(env_u64("OTEL_BSP_SCHEDULE_DELAY", 5000)))
+        .with_max_export_timeout(Duration::from_millis(env_u64("OTEL_BSP_EXPORT_TIMEOUT", 5000)))
+}
+
+/// Per-request timeout for the OTLP gRPC exporter.
+pub fn exporter_timeout() -> Duration { Duration::from_millis(env_u64("OTEL_EXPORTER_OTLP_TIMEOUT", 3000)) }
+
+/// Route OTel SDK errors into the drop/error counters (idempotent).
+pub fn install_error_handler() {
+    HANDLER_INSTALLED.get_or_init(|| {
+        let _ = global::set_error_handler(|err| match err {
+            global::Error::Trace(e) => record_trace_error(&e),
+            other => tracing::debug!(error=%other, "otel error"),
+        });
+    });
+}
+
+/// The batch processor failed to enqueue a span because its queue was full.
+fn is_queue_full(err: &TraceError) -> bool {
+    matches!(err, TraceError::Other(e) if matches!(e.downcast_ref::<TrySendError>(), Some(TrySendError::ChannelFull)))
+}
+
+fn record_trace_error(err: &TraceError) {
+    if is_queue_full(err) {
+        SPANS_DROPPED.fetch_add(1, Ordering::Relaxed);
+        DROPPED_CTR.add(1, &[]);
+    } else {
+        EXPORT_ERRORS.fetch_add(1, Ordering::Relaxed);
+        EXPORT_ERR_CTR.add(1, &[]);
+        tracing::debug!(error=%err, "span export failed");
+    }
+}
+
+/// Spans dropped since start (queue full).
+pub fn spans_dropped() -> u64 { SPANS_DROPPED.load(Ordering::Relaxed) }
+
+/// Failed or timed-out export batches since start.
+pub fn export_errors() -> u64 { EXPORT_ERRORS.load(Ordering::Relaxed) }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::future::BoxFuture;
+    use opentelemetry::trace::{Tracer, TracerProvider as _};
+    use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+    use opentelemetry_sdk::trace::{BatchSpanProcessor, TracerProvider};
+
+    /// Exporter stuck on a dead collector: every export hangs far past the timeout.
+    #[derive(Debug)]
+    struct StuckExporter;
+    impl SpanExporter for StuckExporter {
+        fn export(&mut self, _batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+            Box::pin(async { tokio::time::sleep(Duration::from_secs(60)).await; Ok(()) })
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn stuck_exporter_drops_and_counts_without_blocking() {
+        install_error_handler();
+        let config = BatchConfig::default().with_max_queue_size(8).with_max_export_batch_size(4)
+            .with_scheduled_delay(Duration::from_millis(10)).with_max_export_timeout(Duration::from_millis(50));
+        let processor = BatchSpanProcessor::builder(StuckExporter, opentelemetry_sdk::runtime::Tokio).with_batch_config(config).build();
+        let provider = TracerProvider::builder().with_span_processor(processor).build();
+        let tracer = provider.tracer("otel-export-test");
+        let (dropped0, errors0) = (spans_dropped(), export_errors());
+
+        let start = std::time::Instant::now();
+        for i in 0..500 { tracer.in_span(format!("span-{i}"), |_| {}); }
+        assert!(start.elapsed() < Duration::from_secs(1), "span recording blocked for {:?}", start.elapsed());
+        assert!(spans_dropped() > dropped0, "queue overflow not counted");
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(export_errors() > errors0, "export timeout not counted");
+    }
+
+    /// Serializes tests that mutate the process environment.
+    static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    #[test]
+    fn only_a_full_queue_counts_as_dropped() {
+        assert!(is_queue_full(&TraceError::Other(TrySendError::ChannelFull.into())));
+        assert!(!is_queue_full(&TraceError::Other(TrySendError::ChannelClosed.into())));
+        assert!(!is_queue_full(&TraceError::from("export buffer full")), "message text is not matched");
+        assert!(!is_queue_full(&TraceError::ExportTimedOut(Duration::from_millis(50))));
+    }
+
+    #[test]
+    fn batch_size_is_capped_by_queue() {
+        let _env = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        std::env::set_var("OTEL_BSP_MAX_QUEUE_SIZE", "16");
+        std::env::set_var("OTEL_BSP_MAX_EXPORT_BATCH_SIZE", "64");
+        let cfg = format!("{:?}", batch_config_from_env());
+        std::env::remove_var("OTEL_BSP_MAX_QUEUE_SIZE");
+        std::env::remove_var("OTEL_BSP_MAX_EXPORT_BATCH_SIZE");
+        assert!(cfg.contains("max_queue_size: 16") && cfg.contains("max_export_batch_size: 16"), "{cfg}");
+    }
+}
@@ -0,0 +1,26 @@
+//! Named dependency checks gating `/ready`.
+//!
+//! Services register cheap, non-blocking probes (e.g. "NATS connected", "rules loaded"); `/ready`
+//! answers 503 listing the failing ones unless every probe passes. `mark_ready`/`clear_ready`
+//! remain a manual override on top. Registering an existing name replaces that check.
+
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+
+pub type ReadinessCheck = Box<dyn Fn() -> bool + Send + Sync>;
+
+static CHECKS: Lazy<RwLock<Vec<(String, ReadinessCheck)>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+pub fn register_readiness_check(name: impl Into<String>, check: ReadinessCheck) {
+    let name = name.into();
+    let mut checks = CHECKS.write();
+    checks.retain(|(n, _)| *n != name);
+    checks.push((name, check));
+}
+
+pub fn unregister_readiness_check(name: &str) { CHECKS.write().retain(|(n, _)| n != name); }
+
+/// Names of registered checks currently failing.
+pub fn failing_readiness_checks() -> Vec<String> {
+    CHECKS.read().iter().filter(|(_, check)| !check()).map(|(n, _)| n.clone()).collect()
+}
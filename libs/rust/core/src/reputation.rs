@@ -1,6 +1,10 @@
 //! Reputation scoring service per design (weighted voting, decay, misbehavior penalty).
 //!
-//! This skeleton provides in-memory scoring with exponential decay placeholder.
+//! Scores decay exponentially toward a neutral `baseline`, asymmetrically: trust earned above the
+//! baseline fades with `half_life_secs`, while a penalized node climbs back with the slower
+//! `recovery_half_life_secs`. Combined with `penalty > reward`, repeated offenses sink a node far
+//! faster than it can recover. Decay is computed lazily on read and folded into the stored entries
+//! by `decay_all` (run periodically via `spawn_decay_task`).
 //! Future additions: persistence, cryptographic attestation linkage, consensus integration.
 
 use std::{collections::HashMap, time::{Instant, Duration}};
@@ -13,16 +17,21 @@ pub struct ReputationEntry {
     pub last_update: Instant,
 }
 
+#[derive(Debug, Clone)]
 pub struct ReputationConfig {
     pub half_life_secs: u64,
+    pub recovery_half_life_secs: u64,
+    pub baseline: f64,
     pub min_score: f64,
     pub max_score: f64,
     pub penalty: f64,
     pub reward: f64,
 }
 
-impl Default for ReputationConfig { fn default() -> Self { Self { half_life_secs: 3600, min_score: 0.0, max_score: 1000.0, penalty: 50.0, reward: 10.0 } } }
+impl Default for ReputationConfig { fn default() -> Self { Self { half_life_secs: 3600, recovery_half_life_secs: 4 * 3600, baseline: 500.0, min_score: 0.0, max_score: 1000.0, penalty: 50.0, reward: 10.0 } } }
 
+/// Clones share the same scores.
+#[derive(Clone)]
 pub struct ReputationService {
     cfg: ReputationConfig,
     entries: Arc<RwLock<HashMap<String, ReputationEntry>>>,
@@ -31,24 +40,47 @@ pub struct ReputationService {
 impl ReputationService {
     pub fn new(cfg: ReputationConfig) -> Self { Self { cfg, entries: Arc::new(RwLock::new(HashMap::new())) } }
 
-    pub fn get(&self, node: &str) -> f64 { self.entries.read().get(node).map(|e| self.decayed_score(e)).unwrap_or(self.cfg.max_score / 2.0) }
+    pub fn config(&self) -> &ReputationConfig { &self.cfg }
+
+    /// Current (decayed) score; unknown nodes sit at the baseline.
+    pub fn score(&self, node: &str) -> f64 { self.score_at(node, Instant::now()) }
+    pub fn score_at(&self, node: &str, now: Instant) -> f64 { self.entries.read().get(node).map(|e| self.decayed_score(e, now)).unwrap_or(self.cfg.baseline) }
+    pub fn get(&self, node: &str) -> f64 { self.score(node) }
 
-    fn decayed_score(&self, e: &ReputationEntry) -> f64 {
-        let elapsed = e.last_update.elapsed().as_secs_f64();
-        let hl = self.cfg.half_life_secs as f64;
-        let decay_factor = 0.5_f64.powf(elapsed / hl);
-        (e.score * decay_factor).clamp(self.cfg.min_score, self.cfg.max_score)
+    fn decayed_score(&self, e: &ReputationEntry, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(e.last_update).as_secs_f64();
+        let offset = e.score - self.cfg.baseline;
+        let hl = if offset < 0.0 { self.cfg.recovery_half_life_secs } else { self.cfg.half_life_secs } as f64;
+        let decay_factor = 0.5_f64.powf(elapsed / hl.max(1.0));
+        (self.cfg.baseline + offset * decay_factor).clamp(self.cfg.min_score, self.cfg.max_score)
     }
 
-    pub fn reward(&self, node: &str) { self.adjust(node, self.cfg.reward); }
-    pub fn penalize(&self, node: &str) { self.adjust(node, -self.cfg.penalty); }
+    pub fn reward(&self, node: &str) { self.reward_at(node, Instant::now()); }
+    pub fn penalize(&self, node: &str) { self.penalize_at(node, Instant::now()); }
+    pub fn reward_at(&self, node: &str, now: Instant) { self.adjust(node, self.cfg.reward, now); }
+    pub fn penalize_at(&self, node: &str, now: Instant) { self.adjust(node, -self.cfg.penalty, now); }
 
-    fn adjust(&self, node: &str, delta: f64) {
+    fn adjust(&self, node: &str, delta: f64, now: Instant) {
         let mut map = self.entries.write();
-        let entry = map.entry(node.to_string()).or_insert(ReputationEntry { score: self.cfg.max_score / 2.0, last_update: Instant::now() });
-        let current = self.decayed_score(entry);
+        let entry = map.entry(node.to_string()).or_insert(ReputationEntry { score: self.cfg.baseline, last_update: now });
+        let current = self.decayed_score(entry, now);
         entry.score = (current + delta).clamp(self.cfg.min_score, self.cfg.max_score);
-        entry.last_update = Instant::now();
+        entry.last_update = now;
+    }
+
+    /// Fold elapsed decay into every entry; nodes back at the baseline are forgotten.
+    pub fn decay_all(&self, now: Instant) {
+        let mut map = self.entries.write();
+        for e in map.values_mut() { e.score = self.decayed_score(e, now); e.last_update = now; }
+        map.retain(|_, e| (e.score - self.cfg.baseline).abs() > 1e-3);
+    }
+
+    pub fn spawn_decay_task(&self, every: Duration) -> tokio::task::JoinHandle<()> {
+        let svc = self.clone();
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(every);
+            loop { tick.tick().await; svc.decay_all(Instant::now()); }
+        })
     }
 }
 
@@ -66,4 +98,37 @@ mod tests {
         let after_penalty = svc.get("n1");
         assert!(after_penalty < after);
     }
+
+    #[test]
+    fn penalized_node_recovers_toward_baseline() {
+        let svc = ReputationService::new(ReputationConfig::default());
+        let t0 = Instant::now();
+        svc.penalize_at("n1", t0);
+        assert_eq!(svc.score_at("n1", t0), 450.0);
+        let hl = Duration::from_secs(svc.config().recovery_half_life_secs);
+        assert!((svc.score_at("n1", t0 + hl) - 475.0).abs() < 1e-9);
+        svc.decay_all(t0 + hl * 2);
+        assert!((svc.score_at("n1", t0 + hl * 2) - 487.5).abs() < 1e-9, "decay_all must not change the value");
+        svc.decay_all(t0 + hl * 40);
+        assert_eq!(svc.score_at("n1", t0 + hl * 40), 500.0);
+        assert!(svc.entries.read().is_empty());
+    }
+
+    #[test]
+    fn re_offense_drops_faster_than_recovery() {
+        let svc = ReputationService::new(ReputationConfig::default());
+        let t0 = Instant::now();
+        for i in 0..3 { svc.penalize_at("bad", t0 + Duration::from_secs(10 * i)); }
+        let after = svc.score_at("bad", t0 + Duration::from_secs(20));
+        assert!(500.0 - after > 140.0, "three offenses in 20s: {after}");
+        let recovered = svc.score_at("bad", t0 + Duration::from_secs(40)) - after;
+        assert!(recovered > 0.0 && recovered < 1.0, "20s of recovery: {recovered}");
+
+        // asymmetric: earned trust fades faster than a penalty heals
+        svc.reward_at("good", t0);
+        svc.penalize_at("meh", t0);
+        let later = t0 + Duration::from_secs(3600);
+        assert!(svc.score_at("good", later) - 500.0 < 0.5 * 10.0 + 1e-9);
+        assert!(500.0 - svc.score_at("meh", later) > 0.8 * 50.0);
+    }
 }
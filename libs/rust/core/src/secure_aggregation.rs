@@ -0,0 +1,291 @@
+//! Double-masked additive aggregation for federated secure aggregation (Bonawitz et al.).
+//!
+//! Every pair of clients shares a 32-byte seed (agreed out of band, e.g. via DH). Each client
+//! encodes `gradient * sample_count` as fixed point and adds two kinds of pseudo-random mask:
+//! - per peer, a mask expanded from their pair seed hashed with the round: the lexicographically
+//!   smaller id adds it, the larger subtracts it, so over `u64` wrapping arithmetic the pair masks
+//!   cancel in the sum;
+//! - a fresh per-round self mask, whose seed is Shamir-split (`threshold` of the peers) into
+//!   `SelfMaskShare`s handed to the other clients.
+//!
+//! Once the coordinator stops collecting it announces the survivors, which every client
+//! `acknowledge`s. Survivors then reveal their held self-mask shares for acknowledged clients only,
+//! and their round pair seeds (`MaskShare`) for dropped clients only; `shares_for` refuses any
+//! acknowledged id. For any one client the coordinator learns either its self mask or its pair
+//! masks, never both, so it only ever recovers the aggregate.
+
+use std::collections::{HashMap, HashSet};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::federated_learning::ModelGradient;
+
+/// Fixed-point scale for masked values (24 fractional bits).
+pub const FIXED_POINT_SCALE: f64 = (1u64 << 24) as f64;
+
+/// A client's update with every weight masked; meaningless on its own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaskedGradient {
+    pub node_id: String,
+    pub masked: Vec<Vec<u64>>, // layers -> fixed-point (weight * sample_count) + masks
+    pub sample_count: usize,
+    pub round: u64,
+    pub base_version: u64, // GlobalModel version the client trained from
+}
+
+/// A survivor's round pair seed with a dropped client, revealed so the coordinator can remove it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaskShare {
+    pub from: String,
+    pub dropped: String,
+    pub round: u64,
+    pub seed: [u8; 32], // `round_seed` of the pair: useless for any other round
+}
+
+/// One Shamir share of `owner`'s self-mask seed for `round`, dealt to `holder`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelfMaskShare {
+    pub owner: String,
+    pub holder: String,
+    pub round: u64,
+    pub threshold: usize, // shares needed to rebuild the seed
+    pub x: u8,
+    pub y: [u8; 32],
+}
+
+pub fn encode(v: f64) -> u64 { (v * FIXED_POINT_SCALE).round() as i64 as u64 }
+pub fn decode(v: u64) -> f64 { v as i64 as f64 / FIXED_POINT_SCALE }
+
+/// Mask stream for one layer of one seed, identical wherever the seed is known.
+pub fn mask_stream(seed: &[u8; 32], round: u64, layer: usize, len: usize) -> Vec<u64> {
+    let mut out = Vec::with_capacity(len);
+    let mut counter = 0u64;
+    while out.len() < len {
+        let block = Sha256::new().chain_update(seed).chain_update(round.to_be_bytes()).chain_update((layer as u64).to_be_bytes()).chain_update(counter.to_be_bytes()).finalize();
+        out.extend(block.chunks_exact(8).map(|c| u64::from_be_bytes(c.try_into().expect("8-byte chunk"))).take(len - out.len()));
+        counter += 1;
+    }
+    out
+}
+
+/// Pair seed for one round; revealing it exposes no other round's masks.
+pub fn round_seed(seed: &[u8; 32], round: u64) -> [u8; 32] {
+    Sha256::new().chain_update(b"swarm-secagg-round").chain_update(seed).chain_update(round.to_be_bytes()).finalize().into()
+}
+
+fn apply_mask(values: &mut [Vec<u64>], seed: &[u8; 32], round: u64, add: bool) {
+    for (layer, vals) in values.iter_mut().enumerate() {
+        let mask = mask_stream(seed, round, layer, vals.len());
+        for (v, m) in vals.iter_mut().zip(mask) {
+            *v = if add { v.wrapping_add(m) } else { v.wrapping_sub(m) };
+        }
+    }
+}
+
+/// Apply (or with `remove`, undo) `owner`'s side of the pair mask with `peer` (`seed` is the
+/// round seed).
+fn apply_pair_mask(values: &mut [Vec<u64>], owner: &str, peer: &str, seed: &[u8; 32], round: u64, remove: bool) {
+    apply_mask(values, seed, round, (owner < peer) != remove);
+}
+
+// Shamir secret sharing over GF(2^8) (AES polynomial), byte-wise over the 32-byte seed.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut p = 0u8;
+    while b != 0 {
+        if b & 1 != 0 { p ^= a; }
+        let carry = a & 0x80;
+        a <<= 1;
+        if carry != 0 { a ^= 0x1b; }
+        b >>= 1;
+    }
+    p
+}
+
+fn gf_inv(a: u8) -> u8 { (0..7).fold((1, a), |(acc, sq), _| { let sq = gf_mul(sq, sq); (gf_mul(acc, sq), sq) }).0 } // a^(2+4+..+128) = a^254
+
+/// Split `secret` into `n` shares (x = 1..=n), any `threshold` of which rebuild it.
+fn split_secret(secret: &[u8; 32], n: usize, threshold: usize) -> Vec<(u8, [u8; 32])> {
+    let coeffs: Vec<[u8; 32]> = (1..threshold).map(|_| rand::random()).collect();
+    (1..=n as u8).map(|x| {
+        let mut y = *secret;
+        let mut xp = 1u8;
+        for c in &coeffs {
+            xp = gf_mul(xp, x);
+            for (yb, cb) in y.iter_mut().zip(c) { *yb ^= gf_mul(*cb, xp); }
+        }
+        (x, y)
+    }).collect()
+}
+
+/// Lagrange interpolation at 0 over distinct-x shares.
+fn combine_shares(shares: &[(u8, [u8; 32])]) -> [u8; 32] {
+    let mut secret = [0u8; 32];
+    for (i, (xi, yi)) in shares.iter().enumerate() {
+        let basis = shares.iter().enumerate().filter(|(j, _)| *j != i).fold(1u8, |acc, (_, (xj, _))| gf_mul(acc, gf_mul(*xj, gf_inv(xj ^ xi))));
+        for (s, b) in secret.iter_mut().zip(yi) { *s ^= gf_mul(basis, *b); }
+    }
+    secret
+}
+
+/// What a client dealt, acknowledged and revealed in its latest round.
+#[derive(Default)]
+struct RoundState {
+    round: u64,
+    held: Vec<SelfMaskShare>,
+    acknowledged: HashSet<String>,
+    revealed: HashSet<String>,
+}
+
+/// Client side: holds the pairwise seeds shared with every other participant.
+pub struct SecureAggClient {
+    pub node_id: String,
+    seeds: HashMap<String, [u8; 32]>,
+    threshold: usize,
+    state: RoundState,
+}
+
+impl SecureAggClient {
+    /// Self-mask seeds need a majority of the peers to rebuild (see `with_threshold`).
+    pub fn new(node_id: impl Into<String>, seeds: HashMap<String, [u8; 32]>) -> Self {
+        let threshold = seeds.len() / 2 + 1;
+        Self { node_id: node_id.into(), seeds, threshold, state: RoundState::default() }
+    }
+
+    /// Peers whose shares are needed to rebuild this client's self-mask seed (1..=peers).
+    pub fn with_threshold(mut self, threshold: usize) -> Self { self.threshold = threshold.clamp(1, self.seeds.len().max(1)); self }
+
+    /// Masked update plus the self-mask seed shares to deliver to each peer (out of band).
+    pub fn mask(&self, g: &ModelGradient, round: u64) -> (MaskedGradient, Vec<SelfMaskShare>) {
+        let w = g.sample_count as f64;
+        let mut masked: Vec<Vec<u64>> = g.layer_gradients.iter().map(|l| l.iter().map(|&x| encode(x as f64 * w)).collect()).collect();
+        for (peer, seed) in &self.seeds { apply_pair_mask(&mut masked, &self.node_id, peer, &round_seed(seed, round), round, false); }
+        let self_seed: [u8; 32] = rand::random();
+        apply_mask(&mut masked, &self_seed, round, true);
+        let mut peers: Vec<&String> = self.seeds.keys().collect();
+        peers.sort();
+        let shares = peers.into_iter().zip(split_secret(&self_seed, self.seeds.len(), self.threshold))
+            .map(|(holder, (x, y))| SelfMaskShare { owner: self.node_id.clone(), holder: holder.clone(), round, threshold: self.threshold, x, y })
+            .collect();
+        (MaskedGradient { node_id: self.node_id.clone(), masked, sample_count: g.sample_count, round, base_version: g.base_version }, shares)
+    }
+
+    fn round_state(&mut self, round: u64) -> Result<&mut RoundState> {
+        if round < self.state.round { bail!("round {round} is over (current {})", self.state.round); }
+        if round > self.state.round { self.state = RoundState { round, ..RoundState::default() }; }
+        Ok(&mut self.state)
+    }
+
+    /// Keep a peer's self-mask share dealt to this client.
+    pub fn receive_self_share(&mut self, share: SelfMaskShare) -> Result<()> {
+        if share.holder != self.node_id { bail!("self-mask share for {} delivered to {}", share.holder, self.node_id); }
+        self.round_state(share.round)?.held.push(share);
+        Ok(())
+    }
+
+    /// Record the clients whose masked update the coordinator accepted in `round`.
+    pub fn acknowledge(&mut self, round: u64, survivors: &[String]) -> Result<()> {
+        let state = self.round_state(round)?;
+        if let Some(id) = survivors.iter().find(|id| state.revealed.contains(*id)) { bail!("{id} was already reported dropped"); }
+        state.acknowledged.extend(survivors.iter().cloned());
+        Ok(())
+    }
+
+    /// Round pair seeds with the clients that dropped out; refuses any acknowledged client.
+    pub fn shares_for(&mut self, round: u64, dropped: &[String]) -> Result<Vec<MaskShare>> {
+        let state = self.round_state(round)?;
+        if let Some(id) = dropped.iter().find(|id| state.acknowledged.contains(*id)) { bail!("{id} submitted its update, refusing to reveal its pair seed"); }
+        let shares: Vec<MaskShare> = dropped.iter().filter_map(|d| self.seeds.get(d).map(|seed| MaskShare { from: self.node_id.clone(), dropped: d.clone(), round, seed: round_seed(seed, round) })).collect();
+        self.state.revealed.extend(shares.iter().map(|s| s.dropped.clone()));
+        Ok(shares)
+    }
+
+    /// Held self-mask shares of the acknowledged clients in `round`.
+    pub fn self_shares_for(&self, round: u64) -> Vec<SelfMaskShare> {
+        if self.state.round != round { return Vec::new(); }
+        self.state.held.iter().filter(|s| self.state.acknowledged.contains(&s.owner)).cloned().collect()
+    }
+}
+
+/// Sum the survivors' masked `updates`, strip their self masks (rebuilt from `self_shares`) and
+/// the pair masks shared with `dropped` clients. Returns the sample-weighted mean, or `None` while
+/// a survivor's pair seed for a dropped client or enough shares of a self-mask seed are missing.
+pub fn unmask_mean(updates: &[MaskedGradient], dropped: &[String], shares: &[MaskShare], self_shares: &[SelfMaskShare]) -> Option<Vec<Vec<f32>>> {
+    let first = updates.first()?;
+    let round = first.round;
+    let mut sum: Vec<Vec<u64>> = first.masked.iter().map(|l| vec![0u64; l.len()]).collect();
+    for u in updates {
+        for (acc, vals) in sum.iter_mut().zip(&u.masked) {
+            for (a, v) in acc.iter_mut().zip(vals) { *a = a.wrapping_add(*v); }
+        }
+    }
+    for u in updates {
+        let mut points: Vec<(u8, [u8; 32])> = Vec::new();
+        let mut threshold = usize::MAX;
+        for s in self_shares.iter().filter(|s| s.owner == u.node_id && s.round == round) {
+            threshold = threshold.min(s.threshold);
+            if !points.iter().any(|(x, _)| *x == s.x) { points.push((s.x, s.y)); }
+        }
+        if points.len() < threshold { return None; }
+        apply_mask(&mut sum, &combine_shares(&points), round, false);
+        for d in dropped {
+            let share = shares.iter().find(|s| s.from == u.node_id && s.dropped == *d && s.round == round)?;
+            apply_pair_mask(&mut sum, &u.node_id, d, &share.seed, round, true);
+        }
+    }
+    let total = updates.iter().map(|u| u.sample_count).sum::<usize>().max(1) as f64;
+    Some(sum.into_iter().map(|l| l.into_iter().map(|v| (decode(v) / total) as f32).collect()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_cancel_pairwise() {
+        let seed = [7u8; 32];
+        let mut a = vec![vec![encode(1.5); 5]];
+        let mut b = vec![vec![encode(-0.25); 5]];
+        apply_pair_mask(&mut a, "a", "b", &seed, 3, false);
+        apply_pair_mask(&mut b, "b", "a", &seed, 3, false);
+        assert_ne!(decode(a[0][0]), 1.5, "a single update must be hidden");
+        let sum: Vec<f64> = a[0].iter().zip(&b[0]).map(|(x, y)| decode(x.wrapping_add(*y))).collect();
+        assert!(sum.iter().all(|v| (v - 1.25).abs() < 1e-6));
+    }
+
+    #[test]
+    fn any_threshold_shares_rebuild_the_seed() {
+        let secret: [u8; 32] = rand::random();
+        let shares = split_secret(&secret, 5, 3);
+        assert_eq!(combine_shares(&shares[..3]), secret);
+        assert_eq!(combine_shares(&[shares[4], shares[1], shares[2]]), secret);
+        assert_eq!(combine_shares(&shares), secret, "extra shares are consistent");
+        assert_ne!(combine_shares(&shares[..2]), secret, "below threshold");
+    }
+
+    #[test]
+    fn claiming_a_survivor_dropped_does_not_expose_its_update() {
+        let ids = ["a", "b", "c"];
+        let mut clients: Vec<SecureAggClient> = ids.iter().map(|me| SecureAggClient::new(*me, ids.iter().filter(|p| *p != me).map(|p| {
+            let (lo, hi) = if me < p { (me, p) } else { (p, me) };
+            (p.to_string(), Sha256::digest(format!("{lo}{hi}")).into())
+        }).collect())).collect();
+        let g = ModelGradient { node_id: "a".into(), layer_gradients: vec![vec![0.5, -2.0]], sample_count: 1, timestamp: 0, base_version: 0 };
+        let (masked, dealt) = clients[0].mask(&g, 1);
+        for s in dealt { let holder = ids.iter().position(|id| *id == s.holder).unwrap(); clients[holder].receive_self_share(s).unwrap(); }
+
+        // b acknowledged a: it will not hand over its pair seed with a
+        clients[1].acknowledge(1, &["a".to_string()]).unwrap();
+        assert!(clients[1].shares_for(1, &["a".to_string()]).is_err());
+        // c was told a dropped: it reveals its pair seed but then never a's self-mask share
+        let pair = clients[2].shares_for(1, &["a".to_string()]).unwrap();
+        assert!(clients[2].acknowledge(1, &["a".to_string()]).is_err());
+        assert!(clients[2].self_shares_for(1).is_empty());
+
+        // even with every pair seed of a, the self mask still hides its update
+        let mut stripped = masked.masked.clone();
+        let b_seed = round_seed(&clients[1].seeds["a"], 1);
+        apply_pair_mask(&mut stripped, "a", "b", &b_seed, 1, true);
+        apply_pair_mask(&mut stripped, "a", "c", &pair[0].seed, 1, true);
+        assert!((decode(stripped[0][0]) - 0.5).abs() > 1.0, "self mask must hide the update");
+    }
+}
@@ -1,31 +1,324 @@
-//! QUIC transport abstraction skeleton.
+//! QUIC transport (quinn) per design Section 2.3.3 Streaming Protocol.
 //!
-//! Placeholder to align with design (Section 2.3.3 Streaming Protocol / QUIC).
-//! Real implementation will use the `quinn` crate and integrate OTEL tracing.
+//! Each `QuicTransport` is one UDP endpoint that both accepts and dials, with a self-signed
+//! certificate for `server_name`; peers are trusted by pinning each other's certificate (`trust`).
+//! A connection carries many logical channels: `open_stream(kind)` opens a bidirectional QUIC
+//! stream whose first byte tags its `StreamKind`. Streams have their own flow-control window
+//! (`stream_receive_window`) and the connection window fits many of them, so a stalled bulk
+//! stream cannot starve control traffic. `StreamHandle` frames messages (u32 length prefix) and
+//! buffers at most `stream_buffer` frames each way: a reader that stops draining fills its buffer,
+//! then the QUIC window, then the sender's buffer, at which point `send` waits and `try_send` fails.
+//!
+//! `enable_0rtt` lets a client resuming a cached TLS session send before the handshake completes.
+//! 0-RTT data can be replayed by an attacker, so only idempotent stream kinds (`Bulk`: content-
+//! addressed, deduplicated payloads) are opened early; other kinds wait for the handshake. If the
+//! server rejects the early data, an early stream is reopened once the handshake completes and the
+//! frames written so far are sent again.
+//! `enable_migration` lets peers keep a connection across address changes (see `rebind`).
 
-#[derive(Debug)]
+use anyhow::{anyhow, bail, Context, Result};
+use parking_lot::Mutex;
+use quinn::crypto::rustls::{QuicClientConfig, QuicServerConfig};
+use quinn::{ClientConfig, Connection, Endpoint, ReadError, ReadExactError, RecvStream, SendStream, ServerConfig, TransportConfig, VarInt, WriteError};
+use rustls::pki_types::{CertificateDer, PrivatePkcs8KeyDer};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use tokio::sync::{mpsc::{self, error::TrySendError}, oneshot, watch};
+
+#[derive(Debug, Clone)]
 pub struct QuicConfig {
     pub alpn: Vec<String>,
     pub idle_timeout_secs: u64,
+    /// Name in the self-signed certificate and the one clients verify.
+    pub server_name: String,
+    /// Flow-control window per stream (bytes).
+    pub stream_receive_window: u32,
+    /// Frames buffered per direction in a `StreamHandle`.
+    pub stream_buffer: usize,
+    pub max_frame_bytes: usize,
+    pub enable_0rtt: bool,
+    pub enable_migration: bool,
 }
 
 impl Default for QuicConfig {
-    fn default() -> Self { Self { alpn: vec!["swarm/1".into()], idle_timeout_secs: 30 } }
+    fn default() -> Self { Self { alpn: vec!["swarm/1".into()], idle_timeout_secs: 30, server_name: "swarm.local".into(), stream_receive_window: 256 * 1024, stream_buffer: 64, max_frame_bytes: 16 * 1024 * 1024, enable_0rtt: false, enable_migration: true } }
+}
+
+/// Purpose of a logical channel, sent as the first byte of the stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamKind { Control, Bulk, Other(u8) }
+
+impl From<u8> for StreamKind {
+    fn from(b: u8) -> Self { match b { 0 => Self::Control, 1 => Self::Bulk, other => Self::Other(other) } }
+}
+
+impl From<StreamKind> for u8 {
+    fn from(k: StreamKind) -> u8 { match k { StreamKind::Control => 0, StreamKind::Bulk => 1, StreamKind::Other(b) => b } }
+}
+
+impl StreamKind {
+    /// Safe to replay, so allowed in 0-RTT data.
+    pub fn is_idempotent(self) -> bool { self == Self::Bulk }
 }
 
 #[derive(Debug)]
 pub struct QuicConnectionHandle {
     pub peer_id: String,
+    conn: Connection,
+    cfg: Arc<QuicConfig>,
+    zero_rtt: watch::Receiver<Option<bool>>, // None until the handshake completes
+}
+
+impl QuicConnectionHandle {
+    /// Wrap `conn`; with `accepted` the handshake is still running and resolves whether 0-RTT was accepted.
+    fn new(peer_id: String, conn: Connection, cfg: Arc<QuicConfig>, accepted: Option<quinn::ZeroRttAccepted>) -> Self {
+        let (tx, zero_rtt) = watch::channel(if accepted.is_some() { None } else { Some(false) });
+        if let Some(accepted) = accepted { tokio::spawn(async move { let _ = tx.send(Some(accepted.await)); }); }
+        Self { peer_id, conn, cfg, zero_rtt }
+    }
+
+    pub fn remote_address(&self) -> SocketAddr { self.conn.remote_address() }
+
+    /// Whether the handshake used (and the server accepted) 0-RTT; waits for the handshake.
+    pub async fn used_0rtt(&self) -> bool {
+        self.zero_rtt.clone().wait_for(Option::is_some).await.map(|v| *v == Some(true)).unwrap_or(false)
+    }
+
+    pub async fn open_stream(&self, kind: StreamKind) -> Result<StreamHandle> {
+        if !kind.is_idempotent() { self.used_0rtt().await; } // not replay-safe: no early data
+        let (mut send, recv) = self.conn.open_bi().await.context("open quic stream")?;
+        let early = self.zero_rtt.borrow().is_none(); // handshake still running: this is 0-RTT data
+        let (replaced, replacement) = oneshot::channel();
+        let early = early.then(|| EarlyStream { conn: self.conn.clone(), kind, zero_rtt: self.zero_rtt.clone(), replaced });
+        match send.write_all(&[kind.into()]).await { // the peer only sees the stream once it carries data
+            Ok(()) | Err(WriteError::ZeroRttRejected) if early.is_some() => {}
+            res => res?,
+        }
+        Ok(StreamHandle::spawn(kind, send, recv, &self.cfg, early.map(|e| (e, replacement))))
+    }
+
+    /// Next stream opened by the peer.
+    pub async fn accept_stream(&self) -> Result<StreamHandle> {
+        let (send, mut recv) = self.conn.accept_bi().await.context("accept quic stream")?;
+        let mut tag = [0u8; 1];
+        recv.read_exact(&mut tag).await.context("read stream kind")?;
+        Ok(StreamHandle::spawn(tag[0].into(), send, recv, &self.cfg, None))
+    }
+
+    pub fn close(&self) { self.conn.close(VarInt::from_u32(0), b"close"); }
+}
+
+/// A stream opened in 0-RTT data. A server that rejects early data never sees it, so after the
+/// handshake the kind tag and every frame written so far go out again on a fresh stream, whose
+/// receive half is handed to the reader through `replaced`.
+struct EarlyStream {
+    conn: Connection,
+    kind: StreamKind,
+    zero_rtt: watch::Receiver<Option<bool>>,
+    replaced: oneshot::Sender<RecvStream>,
+}
+
+impl EarlyStream {
+    /// Waits for the handshake; true if the server accepted the early data.
+    async fn accepted(&mut self) -> bool {
+        self.zero_rtt.wait_for(Option::is_some).await.map(|v| *v == Some(true)).unwrap_or(false)
+    }
+
+    async fn reopen(self, frames: &[Vec<u8>]) -> Option<SendStream> {
+        let (mut send, recv) = self.conn.open_bi().await.ok()?;
+        send.write_all(&[self.kind.into()]).await.ok()?;
+        for frame in frames { write_frame(&mut send, frame).await.ok()?; }
+        self.replaced.send(recv).ok()?;
+        Some(send)
+    }
+}
+
+async fn write_frame(send: &mut SendStream, frame: &[u8]) -> std::result::Result<(), WriteError> {
+    send.write_all(&(frame.len() as u32).to_be_bytes()).await?;
+    send.write_all(frame).await
+}
+
+/// Writer half of a `StreamHandle`. While `early` is set, written frames are kept for a replay.
+async fn write_frames(mut send: SendStream, mut outbound: mpsc::Receiver<Vec<u8>>, mut early: Option<EarlyStream>) {
+    enum Next { Frame(Option<Vec<u8>>), Settled(bool) }
+    let mut sent_early: Vec<Vec<u8>> = Vec::new();
+    loop {
+        let next = match early.as_mut() {
+            Some(e) => tokio::select! {
+                frame = outbound.recv() => Next::Frame(frame),
+                accepted = e.accepted() => Next::Settled(accepted),
+            },
+            None => Next::Frame(outbound.recv().await),
+        };
+        match next {
+            Next::Settled(accepted) => {
+                let e = early.take().expect("settled only while early");
+                if !accepted { match e.reopen(&sent_early).await { Some(reopened) => send = reopened, None => return } }
+                sent_early = Vec::new();
+            }
+            Next::Frame(None) => break,
+            Next::Frame(Some(frame)) => match write_frame(&mut send, &frame).await {
+                Ok(()) | Err(WriteError::ZeroRttRejected) if early.is_some() => sent_early.push(frame), // replayed if rejected
+                Ok(()) => {}
+                Err(_) => return,
+            },
+        }
+    }
+    if let Some(mut e) = early { // closed before the handshake settled
+        if !e.accepted().await { match e.reopen(&sent_early).await { Some(reopened) => send = reopened, None => return } }
+    }
+    let _ = send.finish();
+}
+
+/// One logical channel. Dropping it finishes the outgoing side once buffered frames are written.
+pub struct StreamHandle {
+    pub kind: StreamKind,
+    max_frame_bytes: usize,
+    tx: mpsc::Sender<Vec<u8>>,
+    rx: mpsc::Receiver<Vec<u8>>,
+}
+
+impl StreamHandle {
+    fn spawn(kind: StreamKind, send: SendStream, mut recv: RecvStream, cfg: &QuicConfig, early: Option<(EarlyStream, oneshot::Receiver<RecvStream>)>) -> Self {
+        let (early, mut replacement) = early.unzip();
+        let (tx, outbound) = mpsc::channel::<Vec<u8>>(cfg.stream_buffer.max(1));
+        tokio::spawn(write_frames(send, outbound, early));
+        let (inbound, rx) = mpsc::channel::<Vec<u8>>(cfg.stream_buffer.max(1));
+        let max = cfg.max_frame_bytes;
+        tokio::spawn(async move {
+            let mut len = [0u8; 4];
+            loop {
+                match recv.read_exact(&mut len).await {
+                    Ok(()) => {}
+                    // early data rejected: continue on the stream the writer reopens
+                    Err(ReadExactError::ReadError(ReadError::ZeroRttRejected)) if replacement.is_some() => {
+                        match replacement.take().expect("checked").await { Ok(reopened) => { recv = reopened; continue; } Err(_) => return }
+                    }
+                    Err(_) => return,
+                }
+                let n = u32::from_be_bytes(len) as usize;
+                if n > max { let _ = recv.stop(VarInt::from_u32(1)); return; }
+                let mut buf = vec![0u8; n];
+                if recv.read_exact(&mut buf).await.is_err() { return; }
+                // waits while our buffer is full, which stops crediting the peer's window
+                if inbound.send(buf).await.is_err() { return; }
+            }
+        });
+        Self { kind, max_frame_bytes: cfg.max_frame_bytes, tx, rx }
+    }
+
+    /// Queue a frame, waiting while the stream is backpressured.
+    pub async fn send(&self, data: Vec<u8>) -> Result<()> {
+        if data.len() > self.max_frame_bytes { bail!("frame of {} bytes exceeds max_frame_bytes {}", data.len(), self.max_frame_bytes); }
+        self.tx.send(data).await.map_err(|_| anyhow!("quic stream closed"))
+    }
+
+    /// Queue a frame without waiting; `Full` means the stream is backpressured.
+    pub fn try_send(&self, data: Vec<u8>) -> std::result::Result<(), TrySendError<Vec<u8>>> { self.tx.try_send(data) }
+
+    /// Next frame from the peer; `None` once the peer finished the stream.
+    pub async fn recv(&mut self) -> Option<Vec<u8>> { self.rx.recv().await }
 }
 
 pub struct QuicTransport {
-    cfg: QuicConfig,
+    cfg: Arc<QuicConfig>,
+    endpoint: Endpoint,
+    cert: CertificateDer<'static>,
+    trusted: Mutex<Vec<CertificateDer<'static>>>,
+    client: Mutex<ClientConfig>, // rebuilt on `trust`; clones share the TLS session cache
 }
 
+fn crypto_provider() -> Arc<rustls::crypto::CryptoProvider> { Arc::new(rustls::crypto::ring::default_provider()) }
+
 impl QuicTransport {
-    pub fn new(cfg: QuicConfig) -> Self { Self { cfg } }
-    pub async fn connect(&self, peer: &str) -> anyhow::Result<QuicConnectionHandle> { Ok(QuicConnectionHandle { peer_id: peer.to_string() }) }
-    pub async fn open_stream(&self, _conn: &QuicConnectionHandle, _logical: &str) -> anyhow::Result<()> { Ok(()) }
-    pub async fn send(&self, _conn: &QuicConnectionHandle, _data: &[u8]) -> anyhow::Result<()> { Ok(()) }
-    pub async fn recv(&self, _conn: &QuicConnectionHandle) -> anyhow::Result<Vec<u8>> { Ok(Vec::new()) }
+    /// Listen on `addr` (port 0 picks one); must be called within a tokio runtime.
+    pub fn bind(cfg: QuicConfig, addr: SocketAddr) -> Result<Self> {
+        let cfg = Arc::new(cfg);
+        let certified = rcgen::generate_simple_self_signed(vec![cfg.server_name.clone()])?;
+        let cert = certified.cert.der().clone();
+        let key = PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der());
+        let mut tls = rustls::ServerConfig::builder_with_provider(crypto_provider()).with_protocol_versions(&[&rustls::version::TLS13])?
+            .with_no_client_auth().with_single_cert(vec![cert.clone()], key.into())?;
+        tls.alpn_protocols = cfg.alpn.iter().map(|a| a.as_bytes().to_vec()).collect();
+        if cfg.enable_0rtt { tls.max_early_data_size = u32::MAX; } // QUIC requires exactly 0xffffffff
+        let mut server = ServerConfig::with_crypto(Arc::new(QuicServerConfig::try_from(tls)?));
+        server.transport_config(Self::transport_config(&cfg)?);
+        server.migration(cfg.enable_migration);
+        let endpoint = Endpoint::server(server, addr).with_context(|| format!("bind quic endpoint {addr}"))?;
+        let client = Mutex::new(Self::client_config(&cfg, &[])?);
+        Ok(Self { cfg, endpoint, cert, trusted: Mutex::new(Vec::new()), client })
+    }
+
+    fn transport_config(cfg: &QuicConfig) -> Result<Arc<TransportConfig>> {
+        let mut t = TransportConfig::default();
+        t.max_idle_timeout(Some(Duration::from_secs(cfg.idle_timeout_secs).try_into()?));
+        t.stream_receive_window(VarInt::from_u32(cfg.stream_receive_window));
+        // room for 16 full stream windows, so one stalled stream never exhausts the connection
+        t.receive_window(VarInt::from_u64(cfg.stream_receive_window as u64 * 16)?);
+        Ok(Arc::new(t))
+    }
+
+    fn client_config(cfg: &QuicConfig, trusted: &[CertificateDer<'static>]) -> Result<ClientConfig> {
+        let mut roots = rustls::RootCertStore::empty();
+        for c in trusted { roots.add(c.clone())?; }
+        let mut tls = rustls::ClientConfig::builder_with_provider(crypto_provider()).with_protocol_versions(&[&rustls::version::TLS13])?
+            .with_root_certificates(roots).with_no_client_auth();
+        tls.alpn_protocols = cfg.alpn.iter().map(|a| a.as_bytes().to_vec()).collect();
+        tls.enable_early_data = cfg.enable_0rtt;
+        let mut client = ClientConfig::new(Arc::new(QuicClientConfig::try_from(tls)?));
+        client.transport_config(Self::transport_config(cfg)?);
+        Ok(client)
+    }
+
+    /// This endpoint's certificate, for peers to `trust`.
+    pub fn certificate(&self) -> CertificateDer<'static> { self.cert.clone() }
+
+    /// Accept servers presenting `cert` on outgoing connections.
+    pub fn trust(&self, cert: CertificateDer<'static>) -> Result<()> {
+        let mut trusted = self.trusted.lock();
+        trusted.push(cert);
+        *self.client.lock() = Self::client_config(&self.cfg, &trusted)?;
+        Ok(())
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> { Ok(self.endpoint.local_addr()?) }
+
+    /// Move the endpoint to a new local address (e.g. after a network change); open connections follow.
+    pub fn rebind(&self, addr: SocketAddr) -> Result<()> {
+        if !self.cfg.enable_migration { bail!("connection migration disabled (enable_migration = false)"); }
+        self.endpoint.rebind(std::net::UdpSocket::bind(addr).with_context(|| format!("bind {addr}"))?)?;
+        Ok(())
+    }
+
+    /// Dial `peer` (`host:port`).
+    pub async fn connect(&self, peer: &str) -> Result<QuicConnectionHandle> {
+        let addr: SocketAddr = peer.parse().with_context(|| format!("quic peer address {peer}"))?;
+        let client = self.client.lock().clone();
+        let connecting = self.endpoint.connect_with(client, addr, &self.cfg.server_name)?;
+        if self.cfg.enable_0rtt {
+            match connecting.into_0rtt() {
+                Ok((conn, accepted)) => return Ok(QuicConnectionHandle::new(peer.to_string(), conn, self.cfg.clone(), Some(accepted))),
+                Err(connecting) => { // no resumable session yet: full handshake
+                    let conn = connecting.await.with_context(|| format!("quic handshake with {peer}"))?;
+                    return Ok(QuicConnectionHandle::new(peer.to_string(), conn, self.cfg.clone(), None));
+                }
+            }
+        }
+        let conn = connecting.await.with_context(|| format!("quic handshake with {peer}"))?;
+        Ok(QuicConnectionHandle::new(peer.to_string(), conn, self.cfg.clone(), None))
+    }
+
+    /// Next incoming connection.
+    pub async fn accept(&self) -> Result<QuicConnectionHandle> {
+        let incoming = self.endpoint.accept().await.ok_or_else(|| anyhow!("quic endpoint closed"))?;
+        let connecting = incoming.accept().context("quic handshake")?;
+        if self.cfg.enable_0rtt {
+            // always succeeds server-side; `accepted` reports whether the client's early data was taken
+            match connecting.into_0rtt() {
+                Ok((conn, accepted)) => return Ok(QuicConnectionHandle::new(conn.remote_address().to_string(), conn, self.cfg.clone(), Some(accepted))),
+                Err(connecting) => { let conn = connecting.await.context("quic handshake")?; return Ok(QuicConnectionHandle::new(conn.remote_address().to_string(), conn, self.cfg.clone(), None)); }
+            }
+        }
+        let conn = connecting.await.context("quic handshake")?;
+        Ok(QuicConnectionHandle::new(conn.remote_address().to_string(), conn, self.cfg.clone(), None))
+    }
 }
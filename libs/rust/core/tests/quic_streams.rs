@@ -0,0 +1,40 @@
+use std::time::Duration;
+use swarm_core::transport_quic::{QuicConfig, QuicTransport, StreamKind};
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn control_stream_flows_while_bulk_is_backpressured() {
+    let cfg = QuicConfig { stream_receive_window: 64 * 1024, stream_buffer: 4, ..Default::default() };
+    let server = QuicTransport::bind(cfg.clone(), "127.0.0.1:0".parse().unwrap()).unwrap();
+    let client = QuicTransport::bind(cfg, "127.0.0.1:0".parse().unwrap()).unwrap();
+    client.trust(server.certificate()).unwrap();
+    let addr = server.local_addr().unwrap().to_string();
+
+    let peer = tokio::spawn(async move {
+        let conn = server.accept().await.unwrap();
+        let bulk = conn.accept_stream().await.unwrap(); // never read
+        let mut control = conn.accept_stream().await.unwrap();
+        assert_eq!((bulk.kind, control.kind), (StreamKind::Bulk, StreamKind::Control));
+        while let Some(msg) = control.recv().await { control.send(msg).await.unwrap(); }
+        drop((bulk, server));
+    });
+
+    let conn = client.connect(&addr).await.unwrap();
+    let bulk = conn.open_stream(StreamKind::Bulk).await.unwrap();
+    let chunk = vec![7u8; 16 * 1024];
+    let mut sent = 0usize;
+    while timeout(Duration::from_millis(200), bulk.send(chunk.clone())).await.is_ok() {
+        sent += chunk.len();
+        assert!(sent < 8 * 1024 * 1024, "bulk stream never backpressured");
+    }
+    assert!(sent >= 64 * 1024, "stalled before filling the stream window: {sent}");
+    assert!(bulk.try_send(chunk.clone()).is_err());
+
+    let mut control = conn.open_stream(StreamKind::Control).await.unwrap();
+    for i in 0..10u8 {
+        control.send(vec![i; 32]).await.unwrap();
+        assert_eq!(timeout(Duration::from_secs(2), control.recv()).await.expect("control stalled behind bulk"), Some(vec![i; 32]));
+    }
+    assert!(timeout(Duration::from_millis(200), bulk.send(chunk)).await.is_err(), "bulk is still blocked");
+    peer.abort();
+}
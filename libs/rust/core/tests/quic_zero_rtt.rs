@@ -0,0 +1,70 @@
+use std::time::Duration;
+use swarm_core::transport_quic::{QuicConfig, QuicTransport, StreamKind};
+use tokio::time::timeout;
+
+/// Echo server; returns its address. Runs until the test's runtime shuts down.
+fn spawn_echo(cfg: QuicConfig, client: &QuicTransport) -> String {
+    let server = QuicTransport::bind(cfg, "127.0.0.1:0".parse().unwrap()).unwrap();
+    client.trust(server.certificate()).unwrap();
+    let addr = server.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        while let Ok(conn) = server.accept().await {
+            tokio::spawn(async move {
+                while let Ok(mut stream) = conn.accept_stream().await {
+                    tokio::spawn(async move { while let Some(m) = stream.recv().await { if stream.send(m).await.is_err() { break; } } });
+                }
+            });
+        }
+    });
+    addr
+}
+
+/// Connect and round-trip one bulk message; returns whether the handshake used 0-RTT.
+async fn round_trip(client: &QuicTransport, addr: &str) -> bool {
+    let conn = client.connect(addr).await.unwrap();
+    let mut s = conn.open_stream(StreamKind::Bulk).await.unwrap();
+    s.send(b"ping".to_vec()).await.unwrap();
+    assert_eq!(timeout(Duration::from_secs(5), s.recv()).await.unwrap(), Some(b"ping".to_vec()));
+    let used = conn.used_0rtt().await;
+    conn.close();
+    used
+}
+
+#[tokio::test]
+async fn resumed_session_uses_0rtt_when_enabled() {
+    let cfg = QuicConfig { enable_0rtt: true, ..Default::default() };
+    let client = QuicTransport::bind(cfg.clone(), "127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = spawn_echo(cfg, &client);
+    assert!(!round_trip(&client, &addr).await, "first connection has no cached session");
+    assert!(round_trip(&client, &addr).await, "resumed connection should use 0-RTT");
+}
+
+#[tokio::test]
+async fn no_0rtt_when_disabled() {
+    let cfg = QuicConfig::default();
+    let client = QuicTransport::bind(cfg.clone(), "127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = spawn_echo(cfg, &client);
+    assert!(!round_trip(&client, &addr).await);
+    assert!(!round_trip(&client, &addr).await);
+}
+
+#[tokio::test]
+async fn rejected_early_data_is_resent_after_the_handshake() {
+    let cfg = QuicConfig { enable_0rtt: true, ..Default::default() };
+    let client = QuicTransport::bind(cfg.clone(), "127.0.0.1:0".parse().unwrap()).unwrap();
+    // both trusted up front: `trust` starts a fresh session cache
+    let first = spawn_echo(cfg.clone(), &client);
+    let other = spawn_echo(cfg, &client);
+    assert!(!round_trip(&client, &first).await);
+    // the ticket from `first` is offered to `other` (same server name), which cannot resume it
+    // and so rejects the 0-RTT data
+    let conn = client.connect(&other).await.unwrap();
+    let mut s = conn.open_stream(StreamKind::Bulk).await.unwrap();
+    for m in ["one", "two"] { s.send(m.into()).await.unwrap(); }
+    assert!(!conn.used_0rtt().await, "early data should have been rejected");
+    s.send("three".into()).await.unwrap();
+    for want in ["one", "two", "three"] {
+        assert_eq!(timeout(Duration::from_secs(5), s.recv()).await.unwrap(), Some(want.as_bytes().to_vec()));
+    }
+    conn.close();
+}
@@ -8,6 +8,7 @@ pub mod common { tonic::include_proto!("swarm.common"); }
 pub mod consensus { tonic::include_proto!("swarm.consensus"); }
 pub mod events { tonic::include_proto!("swarm.events"); }
 pub mod federation { tonic::include_proto!("swarm.federation"); }
+pub mod identity { tonic::include_proto!("swarm.identity"); }
 pub mod ingestion { tonic::include_proto!("swarm.ingestion"); }
 
 // Re-export frequently used consensus types at crate root (optional convenience)
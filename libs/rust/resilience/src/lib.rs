@@ -1,11 +1,13 @@
 //! Resilience utilities: retry + circuit breaker (minimal MVP)
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 use thiserror::Error;
 use parking_lot::Mutex;
 use futures::Future;
 use tracing::{warn, debug};
 use opentelemetry::{global, metrics::Counter};
 use once_cell::sync::Lazy;
+use rand::Rng;
+use tokio::sync::broadcast;
 
 static RETRY_ATTEMPTS: Lazy<Counter<u64>> = Lazy::new(|| {
     global::meter("swarm-resilience")
@@ -14,6 +16,20 @@ static RETRY_ATTEMPTS: Lazy<Counter<u64>> = Lazy::new(|| {
         .init()
 });
 
+static RETRY_EXHAUSTED: Lazy<Counter<u64>> = Lazy::new(|| {
+    global::meter("swarm-resilience")
+        .u64_counter("swarm_resilience_retry_exhausted_total")
+        .with_description("Retry loops that failed on every attempt")
+        .init()
+});
+
+static NON_RETRIABLE: Lazy<Counter<u64>> = Lazy::new(|| {
+    global::meter("swarm-resilience")
+        .u64_counter("swarm_resilience_non_retriable_total")
+        .with_description("Retry loops aborted early on a non-retriable error")
+        .init()
+});
+
 static CIRCUIT_OPEN: Lazy<Counter<u64>> = Lazy::new(|| {
     global::meter("swarm-resilience")
         .u64_counter("swarm_resilience_circuit_open_total")
@@ -24,34 +40,185 @@ static CIRCUIT_OPEN: Lazy<Counter<u64>> = Lazy::new(|| {
 #[derive(Debug, Error)]
 pub enum ResilienceError { #[error("circuit open")] CircuitOpen }
 
-pub async fn retry_async<F, Fut, T, E>(mut f: F, attempts: usize, delay: Duration) -> Result<T, E>
+/// Delay schedule between retry attempts: `base_delay * multiplier^n` capped at `max_delay`,
+/// then scaled by a uniform factor in `[1 - jitter, 1 + jitter]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryConfig {
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    pub jitter: f64, // 0.0 - 1.0
+}
+
+impl RetryConfig {
+    /// Constant delay, no jitter (the original `retry_async` behaviour).
+    pub fn fixed(delay: Duration) -> Self { Self { base_delay: delay, max_delay: delay, multiplier: 1.0, jitter: 0.0 } }
+
+    pub fn exponential(base_delay: Duration, max_delay: Duration) -> Self { Self { base_delay, max_delay, multiplier: 2.0, jitter: 0.2 } }
+
+    /// Delay before retry number `retry` (0 = after the first failure), before jitter.
+    pub fn nominal_delay(&self, retry: u32) -> Duration {
+        let secs = self.base_delay.as_secs_f64() * self.multiplier.max(1.0).powi(retry.min(i32::MAX as u32) as i32);
+        Duration::from_secs_f64(secs.min(self.max_delay.as_secs_f64()))
+    }
+
+    pub fn delay(&self, retry: u32) -> Duration {
+        let nominal = self.nominal_delay(retry);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 { return nominal; }
+        nominal.mul_f64(1.0 + rand::thread_rng().gen_range(-jitter..=jitter))
+    }
+}
+
+impl From<Duration> for RetryConfig {
+    fn from(delay: Duration) -> Self { Self::fixed(delay) }
+}
+
+/// Run `f` up to `attempts` times, sleeping per `delay` (a `Duration` for a fixed delay or a
+/// `RetryConfig` for backoff) between failures.
+pub async fn retry_async<F, Fut, T, E>(f: F, attempts: usize, delay: impl Into<RetryConfig>) -> Result<T, E>
 where F: FnMut() -> Fut, Fut: Future<Output = Result<T, E>> {
+    retry_async_if(f, attempts, delay, |_| true).await
+}
+
+/// Like `retry_async`, but an error for which `is_retriable` returns false is returned at once.
+pub async fn retry_async_if<F, Fut, T, E, P>(mut f: F, attempts: usize, delay: impl Into<RetryConfig>, is_retriable: P) -> Result<T, E>
+where F: FnMut() -> Fut, Fut: Future<Output = Result<T, E>>, P: Fn(&E) -> bool {
+    let cfg = delay.into();
     let mut last_err = None;
     for i in 0..attempts {
         RETRY_ATTEMPTS.add(1, &[]);
-        match f().await { Ok(v) => return Ok(v), Err(e) => { last_err = Some(e); if i+1 < attempts { tokio::time::sleep(delay).await; } } }
+        match f().await {
+            Ok(v) => return Ok(v),
+            Err(e) if !is_retriable(&e) => { NON_RETRIABLE.add(1, &[]); debug!(attempt = i + 1, "non-retriable error, giving up"); return Err(e); }
+            Err(e) => { last_err = Some(e); if i+1 < attempts { tokio::time::sleep(cfg.delay(i as u32)).await; } }
+        }
     }
-    Err(last_err.unwrap())
+    RETRY_EXHAUSTED.add(1, &[]);
+    Err(last_err.expect("retry_async called with zero attempts"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState { Closed, Open, HalfOpen }
+
+/// One state transition of a named breaker, as delivered by `CircuitBreaker::subscribe`.
+#[derive(Debug, Clone)]
+pub struct CircuitEvent {
+    pub name: String,
+    pub from: CircuitState,
+    pub to: CircuitState,
+    pub at: SystemTime,
 }
 
+const CIRCUIT_EVENT_BUFFER: usize = 16;
+
+/// Opens after `failure_threshold` consecutive failures; once `half_open_after` has passed,
+/// `allow` lets calls through half-open until a success closes it or a failure reopens it.
 pub struct CircuitBreaker {
+    name: String,
     state: Mutex<State>,
     half_open_after: Duration,
     failure_threshold: u32,
+    events: broadcast::Sender<CircuitEvent>,
 }
 
-struct State { failures: u32, opened_at: Option<Instant> }
+struct State { failures: u32, opened_at: Option<Instant>, current: CircuitState }
 
 impl CircuitBreaker {
-    pub fn new(failure_threshold: u32, half_open_after: Duration) -> Self { Self { state: Mutex::new(State { failures:0, opened_at: None }), half_open_after, failure_threshold } }
+    pub fn new(failure_threshold: u32, half_open_after: Duration) -> Self { Self::named("default", failure_threshold, half_open_after) }
+
+    /// `name` identifies the breaker in its `CircuitEvent`s and logs.
+    pub fn named(name: impl Into<String>, failure_threshold: u32, half_open_after: Duration) -> Self {
+        Self { name: name.into(), state: Mutex::new(State { failures:0, opened_at: None, current: CircuitState::Closed }), half_open_after, failure_threshold, events: broadcast::channel(CIRCUIT_EVENT_BUFFER).0 }
+    }
+
+    /// Transitions from now on; a receiver that falls more than 16 events behind gets `Lagged`.
+    pub fn subscribe(&self) -> broadcast::Receiver<CircuitEvent> { self.events.subscribe() }
+
+    pub fn state(&self) -> CircuitState { self.state.lock().current }
+
     pub fn allow(&self) -> bool {
         let mut st = self.state.lock();
-        if let Some(opened) = st.opened_at { if opened.elapsed() >= self.half_open_after { debug!("half-open trial"); st.opened_at=None; st.failures=0; return true; } else { return false; } }
+        if let Some(opened) = st.opened_at { if opened.elapsed() >= self.half_open_after { debug!(circuit=%self.name, "half-open trial"); st.opened_at=None; st.failures=0; self.transition(&mut st, CircuitState::HalfOpen); return true; } else { return false; } }
         true
     }
-    pub fn record_success(&self) { let mut st = self.state.lock(); st.failures=0; }
-    pub fn record_failure(&self) { let mut st = self.state.lock(); st.failures+=1; if st.failures >= self.failure_threshold { if st.opened_at.is_none() { st.opened_at = Some(Instant::now()); CIRCUIT_OPEN.add(1, &[]); warn!("circuit opened"); } } }
+    pub fn record_success(&self) { let mut st = self.state.lock(); st.failures=0; if st.current == CircuitState::HalfOpen { self.transition(&mut st, CircuitState::Closed); } }
+    pub fn record_failure(&self) {
+        let mut st = self.state.lock();
+        st.failures+=1;
+        // a failed half-open trial reopens at once
+        if (st.failures >= self.failure_threshold || st.current == CircuitState::HalfOpen) && st.opened_at.is_none() {
+            st.opened_at = Some(Instant::now()); CIRCUIT_OPEN.add(1, &[]); warn!(circuit=%self.name, "circuit opened");
+            self.transition(&mut st, CircuitState::Open);
+        }
+    }
+
+    fn transition(&self, st: &mut State, to: CircuitState) {
+        let from = std::mem::replace(&mut st.current, to);
+        let _ = self.events.send(CircuitEvent { name: self.name.clone(), from, to, at: SystemTime::now() }); // no subscribers is fine
+    }
 }
 
 #[cfg(test)]
-mod tests { use super::*; #[tokio::test] async fn test_retry() { let mut c = 0; let res: Result<u32, &'static str> = retry_async(|| { c+=1; async move { if c<3 { Err("e") } else { Ok(42) } } }, 5, Duration::from_millis(1)).await; assert_eq!(res.unwrap(), 42); } }
+mod tests {
+    use super::*;
+
+    #[tokio::test] async fn test_retry() { let mut c = 0; let res: Result<u32, &'static str> = retry_async(|| { c+=1; async move { if c<3 { Err("e") } else { Ok(42) } } }, 5, Duration::from_millis(1)).await; assert_eq!(res.unwrap(), 42); }
+
+    #[tokio::test]
+    async fn permanent_errors_abort_transient_errors_retry() {
+        #[derive(Debug, PartialEq)] enum E { Transient, Permanent }
+        let mut calls = 0;
+        let res: Result<u32, E> = retry_async_if(|| { calls += 1; async { Err(E::Permanent) } }, 5, Duration::from_millis(1), |e| *e == E::Transient).await;
+        assert_eq!((res, calls), (Err(E::Permanent), 1));
+        let mut calls = 0;
+        let res: Result<u32, E> = retry_async_if(|| { calls += 1; let c = calls; async move { if c < 3 { Err(E::Transient) } else { Ok(7) } } }, 5, Duration::from_millis(1), |e| *e == E::Transient).await;
+        assert_eq!((res, calls), (Ok(7), 3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn backoff_delays_grow() {
+        let cfg = RetryConfig { base_delay: Duration::from_millis(10), max_delay: Duration::from_millis(50), multiplier: 2.0, jitter: 0.0 };
+        let start = tokio::time::Instant::now();
+        let mut stamps = Vec::new();
+        let res: Result<(), &str> = retry_async(|| { stamps.push(start.elapsed()); async { Err("e") } }, 5, cfg).await;
+        assert!(res.is_err());
+        let gaps: Vec<u128> = stamps.windows(2).map(|w| (w[1] - w[0]).as_millis()).collect();
+        assert_eq!(gaps, vec![10, 20, 40, 50]); // doubled, then capped
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let cfg = RetryConfig { base_delay: Duration::from_millis(100), max_delay: Duration::from_secs(1), multiplier: 2.0, jitter: 0.25 };
+        for retry in 0..4 {
+            let nominal = cfg.nominal_delay(retry);
+            for _ in 0..200 {
+                let d = cfg.delay(retry);
+                assert!(d >= nominal.mul_f64(0.75) && d <= nominal.mul_f64(1.25), "{d:?} vs {nominal:?}");
+            }
+        }
+        assert_eq!(RetryConfig::from(Duration::from_millis(7)).delay(3), Duration::from_millis(7));
+    }
+
+    #[test]
+    fn breaker_broadcasts_each_transition() {
+        let cb = CircuitBreaker::named("nats", 2, Duration::from_millis(5));
+        let mut events = cb.subscribe();
+        cb.record_failure();
+        assert!(cb.allow(), "below threshold stays closed");
+        cb.record_failure();
+        assert!(!cb.allow());
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(cb.allow()); // half-open trial
+        cb.record_failure();
+        assert_eq!(cb.state(), CircuitState::Open, "failed trial reopens");
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(cb.allow());
+        cb.record_success();
+        assert_eq!(cb.state(), CircuitState::Closed);
+        use CircuitState::*;
+        let mut seen = Vec::new();
+        while let Ok(ev) = events.try_recv() { assert_eq!(ev.name, "nats"); seen.push((ev.from, ev.to)); }
+        assert_eq!(seen, vec![(Closed, Open), (Open, HalfOpen), (HalfOpen, Open), (Open, HalfOpen), (HalfOpen, Closed)]);
+    }
+}
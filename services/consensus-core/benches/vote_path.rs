@@ -0,0 +1,41 @@
+use criterion::{criterion_group, criterion_main, Criterion, BenchmarkId};
+use consensus_core::PbftService;
+use swarm_proto::consensus::{pbft_server::Pbft, Vote};
+use tonic::Request;
+
+// Concurrent cast_vote throughput with mostly re-sent votes (the PBFT rebroadcast case): after the
+// first vote per validator, every call is answered under the shared votes lock. Only two of the
+// four validators vote, so the round never reaches quorum and the measurement stays on the vote path.
+fn vote_path(c: &mut Criterion) {
+    std::env::set_var("CONSENSUS_VIEW_CHANGE_ENABLED", "0");
+    std::env::set_var("SWARM_DATA_DIR", std::env::temp_dir().join(format!("consensus-bench-{}", std::process::id())));
+    let rt = tokio::runtime::Builder::new_multi_thread().worker_threads(4).enable_all().build().unwrap();
+    let svc = rt.block_on(async { PbftService::new() });
+    let mut group = c.benchmark_group("vote_path");
+    for &tasks in &[1usize, 4, 16] {
+        group.bench_with_input(BenchmarkId::new("concurrent_votes", tasks), &tasks, |b, &tasks| {
+            b.to_async(&rt).iter(|| {
+                let svc = svc.clone();
+                async move {
+                    let handles: Vec<_> = (0..tasks).map(|t| {
+                        let svc = svc.clone();
+                        tokio::spawn(async move {
+                            for i in 0..64 {
+                                let vote = Vote { proposal_id: "bench".into(), node_id: format!("node-{}", (t + i) % 2), height: 1, round: 0, vote_type: 0 };
+                                svc.cast_vote(Request::new(vote)).await.unwrap();
+                            }
+                        })
+                    }).collect();
+                    for h in handles { h.await.unwrap(); }
+                }
+            })
+        });
+    }
+    group.finish();
+    let (fast, inserted) = svc.vote_path_stats();
+    assert_eq!(inserted, 2, "only the first vote per validator should take the write lock");
+    assert!(fast > 0);
+}
+
+criterion_group!(benches, vote_path);
+criterion_main!(benches);
@@ -0,0 +1,13 @@
+//! Deployment smoke test: drive one proposal to quorum on a consensus node and report pass/fail.
+//! Usage: `swarm-smoke [endpoint]` (see `consensus_core::smoke` for env settings). Exit code 1 on failure.
+
+use consensus_core::smoke::{run_smoke, SmokeConfig};
+
+#[tokio::main]
+async fn main() {
+    let mut cfg = SmokeConfig::from_env();
+    if let Some(endpoint) = std::env::args().nth(1) { cfg.endpoint = endpoint; }
+    let report = run_smoke(&cfg).await;
+    println!("{report}");
+    if !report.passed() { std::process::exit(1); }
+}
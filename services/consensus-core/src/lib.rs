@@ -1,77 +1,311 @@
 use std::collections::{HashMap, HashSet};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::num::NonZeroUsize;
+use lru::LruCache;
 use std::time::Instant;
 use once_cell::sync::Lazy;
 static DB: Lazy<Option<sled::Db>> = Lazy::new(|| {
-    let path = std::env::var("CONSENSUS_DB_PATH").unwrap_or_else(|_| "./data/consensus".into());
+    let path = match db_path() { Ok(p) => p, Err(e) => { tracing::warn!(error=?e, "consensus data dir unusable - running ephemeral"); return None; } };
     match sled::open(path) { Ok(db) => Some(db), Err(e) => { tracing::warn!(error=?e, "sled open failed - running ephemeral"); None } }
 });
+
+/// Consensus store location: `CONSENSUS_DB_PATH` if set, else `$SWARM_DATA_DIR/consensus`.
+/// Created and probed for writability; main() calls this at startup to fail fast.
+pub fn db_path() -> anyhow::Result<std::path::PathBuf> {
+    match std::env::var("CONSENSUS_DB_PATH") {
+        Ok(p) => { let p = std::path::PathBuf::from(p); swarm_core::ensure_writable_dir(&p)?; Ok(p) }
+        Err(_) => swarm_core::resolve_data_dir("consensus"),
+    }
+}
 use async_trait::async_trait;
 use tonic::{Request, Response, Status};
-use swarm_proto::consensus::{pbft_server::Pbft, Proposal, Vote, Ack, ConsensusStateQuery, ConsensusState};
+use swarm_proto::consensus::{pbft_server::Pbft, Proposal, Vote, Ack, ConsensusStateQuery, ConsensusState, SubscribeEventsRequest, ConsensusEvent, consensus_event::Kind as EventKind};
+use std::pin::Pin;
+use tokio::sync::broadcast;
+use tokio_stream::{Stream, StreamExt, wrappers::{BroadcastStream, errors::BroadcastStreamRecvError}};
+use swarm_core::{vrf_prove, vrf_verify, select_validator_with_vrf, VrfProof, VrfOutput};
 use tracing::instrument;
+pub mod smoke;
 mod view_change;
+mod participation;
+pub mod restore;
+mod lock_order;
+pub mod vrf_keys;
+pub mod publish;
+use participation::ParticipationTracker;
+use publish::{NatsPublisher, RoundPublisher};
+use vrf_keys::VrfKeys;
+use lock_order::{LockRank, Ordered};
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct PbftState {
     pub height: u64,
     pub round: u64,
     pub leader: String,
     pub validators: Vec<String>,
+    pub stakes: HashMap<String, u64>, // validator -> stake (uniform 1 until staking lands)
+    pub last_leader_proof: Option<VrfProof>, // proof behind `leader`; verify against the key of leader_beacon(height, round)
+}
+
+/// VRF input for a (height,round).
+pub fn leader_vrf_alpha(height: u64, round: u64) -> Vec<u8> { format!("height:{height}:round:{round}").into_bytes() }
+
+/// Validator whose VRF key draws the leader of (height,round); rotates round-robin.
+pub fn leader_beacon(height: u64, round: u64, validators: &[String]) -> Option<&String> {
+    (!validators.is_empty()).then(|| &validators[(height + round) as usize % validators.len()])
+}
+
+fn stake_weights(validators: &[String], stakes: &HashMap<String, u64>) -> Vec<(String, u64)> {
+    validators.iter().map(|v| (v.clone(), stakes.get(v).copied().unwrap_or(0))).collect()
+}
+
+/// Stake-weighted leader for (height,round) drawn with the beacon's VRF `secret`; validators
+/// without a stake entry count as zero.
+pub fn weighted_leader(height: u64, round: u64, secret: &[u8; 32], validators: &[String], stakes: &HashMap<String, u64>) -> Option<String> {
+    weighted_leader_with_proof(height, round, secret, validators, stakes).map(|(leader, _, _)| leader)
+}
+
+/// Like `weighted_leader` but also returns the VRF proof and output, so other validators can check
+/// the draw against the beacon's public key with `verify_leader_proof`.
+pub fn weighted_leader_with_proof(height: u64, round: u64, secret: &[u8; 32], validators: &[String], stakes: &HashMap<String, u64>) -> Option<(String, VrfProof, VrfOutput)> {
+    let (proof, vrf_output) = vrf_prove(secret, &leader_vrf_alpha(height, round));
+    select_validator_with_vrf(&vrf_output, &stake_weights(validators, stakes)).map(|leader| (leader, proof, vrf_output))
+}
+
+/// Leader selected by `proof` when it verifies for (height,round) under the beacon's `public` key.
+pub fn verify_leader_proof(height: u64, round: u64, public: &[u8; 32], proof: &VrfProof, validators: &[String], stakes: &HashMap<String, u64>) -> Option<String> {
+    let vrf_output = vrf_verify(public, &leader_vrf_alpha(height, round), proof)?;
+    select_validator_with_vrf(&vrf_output, &stake_weights(validators, stakes))
 }
 
 #[derive(Clone)]
 pub struct PbftService {
     state: Arc<RwLock<PbftState>>,
-    votes: Arc<RwLock<HashMap<(u64,u64), HashSet<String>>>>, // (height,round) -> voters
+    votes: Arc<RwLock<HashMap<(u64,u64), RoundVotes>>>, // (height,round) -> voters + tally
     round_starts: Arc<RwLock<HashMap<(u64,u64), Instant>>>, // track start time for (height,round)
+    participation: Arc<ParticipationTracker>,
+    events: broadcast::Sender<ConsensusEvent>, // fan-out for SubscribeEvents streams
+    event_buffer: usize,
+    leader_cache: Arc<RwLock<LruCache<(u64,u64), (String, Option<VrfProof>)>>>, // (height,round) -> elected leader; cleared on reconfigure
+    leader_cache_hits: Arc<AtomicU64>,
+    leader_cache_misses: Arc<AtomicU64>,
+    quorum: Arc<AtomicUsize>, // 2f+1 for the current validator set; kept in step with `state.validators`
+    vote_stats: Arc<VoteStats>,
+    vrf: Arc<VrfKeys>,
+    publisher: Arc<dyn RoundPublisher>,
+}
+
+/// Voters of one (height,round): the set is authoritative for dedup, `tally` mirrors its size so
+/// the quorum check reads an atomic under the shared lock. `finalized` is claimed by exactly one
+/// caller once the tally reaches quorum.
+#[derive(Default)]
+struct RoundVotes { voters: HashSet<String>, tally: AtomicUsize, finalized: AtomicBool }
+
+impl From<HashSet<String>> for RoundVotes {
+    fn from(voters: HashSet<String>) -> Self { Self { tally: AtomicUsize::new(voters.len()), voters, finalized: AtomicBool::new(false) } }
+}
+
+/// Counters for the vote hot path: how many votes were settled under the shared lock.
+#[derive(Default)]
+struct VoteStats { fast: AtomicU64, inserted: AtomicU64 }
+
+fn quorum_for(validators: usize) -> usize { ((validators * 2) / 3) + 1 }
+
+fn event(kind: EventKind, height: u64, round: u64) -> ConsensusEvent {
+    ConsensusEvent { kind: kind as i32, height, round, ..Default::default() }
+}
+
+fn leader_changed_event(height: u64, round: u64, st: &PbftState) -> ConsensusEvent {
+    ConsensusEvent { leader: st.leader.clone(), leader_proof: st.last_leader_proof.map(|p| p.0.to_vec()).unwrap_or_default(), ..event(EventKind::LeaderChanged, height, round) }
 }
 
 impl PbftService {
     pub fn new() -> Self {
+        let keys = VrfKeys::from_env().unwrap_or_else(|e| {
+            tracing::warn!(error=?e, "VRF keys unusable - following other beacons' leader proofs only");
+            VrfKeys::default()
+        });
+        Self::with_vrf_keys(keys)
+    }
+
+    pub fn with_vrf_keys(vrf: VrfKeys) -> Self { Self::with_publisher(vrf, Arc::new(NatsPublisher)) }
+
+    pub fn with_publisher(vrf: VrfKeys, publisher: Arc<dyn RoundPublisher>) -> Self {
         let size: usize = std::env::var("VALIDATOR_SET_SIZE").ok().and_then(|v| v.parse().ok()).unwrap_or(4);
         let validators = (0..size).map(|i| format!("node-{}", i)).collect::<Vec<_>>();
         let leader = validators.first().cloned().unwrap_or_default();
-    let svc = Self { state: Arc::new(RwLock::new(PbftState { validators: validators.clone(), leader, ..Default::default() })), votes: Arc::new(RwLock::new(HashMap::new())), round_starts: Arc::new(RwLock::new(HashMap::new())) };
+        let stakes = validators.iter().map(|v| (v.clone(), 1u64)).collect();
+        let leader_cache_cap: usize = std::env::var("CONSENSUS_LEADER_CACHE_CAP").ok().and_then(|v| v.parse().ok()).unwrap_or(256);
+        let event_buffer: usize = std::env::var("CONSENSUS_EVENT_BUFFER").ok().and_then(|v| v.parse().ok()).unwrap_or(256);
+        let window: usize = std::env::var("CONSENSUS_PARTICIPATION_WINDOW").ok().and_then(|v| v.parse().ok()).unwrap_or(100);
+        let svc = Self { state: Arc::new(RwLock::new(PbftState { validators: validators.clone(), leader, stakes, ..Default::default() })), votes: Arc::new(RwLock::new(HashMap::new())), round_starts: Arc::new(RwLock::new(HashMap::new())), participation: Arc::new(ParticipationTracker::new(window)), events: broadcast::channel(event_buffer).0, event_buffer,
+            leader_cache: Arc::new(RwLock::new(LruCache::new(NonZeroUsize::new(leader_cache_cap).unwrap_or(NonZeroUsize::MIN)))), leader_cache_hits: Arc::new(AtomicU64::new(0)), leader_cache_misses: Arc::new(AtomicU64::new(0)),
+            quorum: Arc::new(AtomicUsize::new(quorum_for(size))), vote_stats: Arc::new(VoteStats::default()), vrf: Arc::new(vrf), publisher };
         svc.load_votes();
         // spawn view change timer task
         svc.spawn_view_change_task();
         svc
     }
-    pub fn snapshot(&self) -> PbftState { self.state.read().unwrap().clone() }
+    pub fn snapshot(&self) -> PbftState { self.state_read().clone() }
 
-    fn quorum(&self) -> usize {
-        let st = self.state.read().unwrap();
-        ((st.validators.len() * 2) / 3) + 1
+    // Lock accessors; nested acquisition must follow state -> votes -> round_starts -> leader_cache (see lock_order).
+    fn state_read(&self) -> Ordered<RwLockReadGuard<'_, PbftState>> { lock_order::read(&self.state, LockRank::State) }
+    fn state_write(&self) -> Ordered<RwLockWriteGuard<'_, PbftState>> { lock_order::write(&self.state, LockRank::State) }
+    fn votes_read(&self) -> Ordered<RwLockReadGuard<'_, HashMap<(u64,u64), RoundVotes>>> { lock_order::read(&self.votes, LockRank::Votes) }
+    fn votes_write(&self) -> Ordered<RwLockWriteGuard<'_, HashMap<(u64,u64), RoundVotes>>> { lock_order::write(&self.votes, LockRank::Votes) }
+    fn round_starts_write(&self) -> Ordered<RwLockWriteGuard<'_, HashMap<(u64,u64), Instant>>> { lock_order::write(&self.round_starts, LockRank::RoundStarts) }
+    fn leader_cache_write(&self) -> Ordered<RwLockWriteGuard<'_, LruCache<(u64,u64), (String, Option<VrfProof>)>>> { lock_order::write(&self.leader_cache, LockRank::LeaderCache) }
+
+    /// Per-validator participation ratio over the rolling window (backs the dashboard gauge).
+    pub fn participation_snapshot(&self) -> HashMap<String, f64> { self.participation.snapshot() }
+
+    /// Close a superseded (height,round), recording which validators voted in it.
+    fn close_round(&self, height: u64, round: u64) {
+        if height == 0 { return; }
+        let validators = self.state_read().validators.clone();
+        let voters = self.votes_read().get(&(height, round)).map(|v| v.voters.clone()).unwrap_or_default();
+        self.participation.record_round(&validators, &voters);
     }
 
-    fn record_vote(&self, height: u64, round: u64, node: &str) -> usize {
-        let mut map = self.votes.write().unwrap();
-        let entry = map.entry((height, round)).or_insert_with(HashSet::new);
-        entry.insert(node.to_string());
+    fn quorum(&self) -> usize { self.quorum.load(Ordering::Acquire) }
+
+    /// Records `node`'s vote for (height,round); returns the tally and whether this call inserted
+    /// the voter. Re-sent votes are answered from the atomic tally under the shared lock; only a
+    /// genuinely new voter takes the write lock, so each tally in 1..=n is reported as newly
+    /// inserted exactly once.
+    fn record_vote(&self, height: u64, round: u64, node: &str) -> (usize, bool) {
+        if let Some(count) = self.votes_read().get(&(height, round)).and_then(|v| v.voters.contains(node).then(|| v.tally.load(Ordering::Acquire))) {
+            self.vote_stats.fast.fetch_add(1, Ordering::Relaxed);
+            return (count, false);
+        }
+        let mut map = self.votes_write();
+        let entry = map.entry((height, round)).or_default();
+        if !entry.voters.insert(node.to_string()) { return (entry.tally.load(Ordering::Acquire), false); } // raced another writer
+        self.vote_stats.inserted.fetch_add(1, Ordering::Relaxed);
         // persist single vote (idempotent based on key)
         if let Some(db) = &*DB { let _ = db.insert(format!("vote:{}:{}:{}", height, round, node), &[]); }
-        entry.len()
+        (entry.tally.fetch_add(1, Ordering::AcqRel) + 1, true)
     }
 
+    /// (votes settled without the write lock, new voters inserted).
+    pub fn vote_path_stats(&self) -> (u64, u64) { (self.vote_stats.fast.load(Ordering::Relaxed), self.vote_stats.inserted.load(Ordering::Relaxed)) }
+
     fn elect_leader(&self, height: u64, round: u64) {
-        let mut st = self.state.write().unwrap();
-        if st.validators.is_empty() { return; }
-        let idx = (height + round) as usize % st.validators.len();
-        st.leader = st.validators[idx].clone();
+        let changed = {
+            let mut st = self.state_write();
+            if st.validators.is_empty() { return; }
+            let previous = st.leader.clone();
+            let (leader, proof) = self.leader_for(&st, height, round);
+            st.leader = leader;
+            st.last_leader_proof = proof;
+            (st.leader != previous).then(|| leader_changed_event(height, round, &st))
+        };
+        if let Some(ev) = changed { self.emit(ev); }
+    }
+
+    /// Leader (and VRF proof) for (height,round), memoized so repeated elections skip VRF derivation.
+    /// Only our own draws and verified proofs are cached: until the beacon's proof has been
+    /// accepted (`accept_leader_proof`) the beacon itself leads provisionally.
+    fn leader_for(&self, st: &PbftState, height: u64, round: u64) -> (String, Option<VrfProof>) {
+        let mut cache = self.leader_cache_write();
+        if let Some(hit) = cache.get(&(height, round)) {
+            self.leader_cache_hits.fetch_add(1, Ordering::Relaxed);
+            return hit.clone();
+        }
+        self.leader_cache_misses.fetch_add(1, Ordering::Relaxed);
+        let beacon = leader_beacon(height, round, &st.validators).cloned().unwrap_or_default();
+        match self.vrf.secret_for(&beacon).and_then(|secret| weighted_leader_with_proof(height, round, secret, &st.validators, &st.stakes)) {
+            Some((leader, proof, _)) => {
+                cache.put((height, round), (leader.clone(), Some(proof)));
+                (leader, Some(proof))
+            }
+            None => (beacon, None), // another validator's draw, or no stake at all
+        }
+    }
+
+    /// Adopt the leader drawn by (height,round)'s beacon once `proof` verifies against the beacon's
+    /// registered VRF key; returns that leader.
+    pub fn accept_leader_proof(&self, height: u64, round: u64, proof: VrfProof) -> anyhow::Result<String> {
+        let (leader, changed) = {
+            let mut st = self.state_write();
+            let beacon = leader_beacon(height, round, &st.validators).ok_or_else(|| anyhow::anyhow!("empty validator set"))?;
+            let public = self.vrf.public_key(beacon).ok_or_else(|| anyhow::anyhow!("no VRF key registered for beacon {beacon}"))?;
+            let leader = verify_leader_proof(height, round, public, &proof, &st.validators, &st.stakes)
+                .ok_or_else(|| anyhow::anyhow!("leader proof for ({height},{round}) does not verify against beacon {beacon}"))?;
+            self.leader_cache_write().put((height, round), (leader.clone(), Some(proof)));
+            let changed = ((st.height, st.round) == (height, round) && (st.leader != leader || st.last_leader_proof != Some(proof))).then(|| {
+                st.leader = leader.clone();
+                st.last_leader_proof = Some(proof);
+                leader_changed_event(height, round, &st)
+            });
+            (leader, changed)
+        };
+        if let Some(ev) = changed { self.emit(ev); }
+        Ok(leader)
+    }
+
+    /// Replace the validator set / stakes; cached leaders were computed for the old set and are dropped.
+    /// A lowered quorum that the current round's votes already meet finalizes it here, since no
+    /// further vote may arrive to do so.
+    pub fn reconfigure(&self, validators: Vec<String>, stakes: HashMap<String, u64>) {
+        let (height, round) = {
+            let mut st = self.state_write();
+            self.quorum.store(quorum_for(validators.len()), Ordering::Release);
+            st.validators = validators;
+            st.stakes = stakes;
+            self.leader_cache_write().clear();
+            (st.height, st.round)
+        };
+        let tally = self.votes_read().get(&(height, round)).map(|v| v.tally.load(Ordering::Acquire));
+        if let Some(count) = tally { self.finalize_if_quorum(height, round, count); }
+    }
+
+    /// Finalize (height,round) if `count` votes meet the current quorum and nobody has yet; returns
+    /// whether this call finalized it.
+    fn finalize_if_quorum(&self, height: u64, round: u64, count: usize) -> bool {
+        let quorum = self.quorum();
+        if count < quorum { return false; }
+        let claimed = self.votes_read().get(&(height, round)).is_some_and(|v| !v.finalized.swap(true, Ordering::AcqRel));
+        if !claimed { return false; }
+        self.emit(event(EventKind::Finalized, height, round));
+        swarm_core::CAPACITY.record(swarm_core::capacity::CONSENSUS_ROUNDS, 1);
+        self.elect_leader(height, round);
+        tracing::info!(height, round, quorum=%quorum, votes=%count, leader=%self.snapshot().leader, "quorum_reached");
+        // record round progress duration metric
+        let started = self.round_starts_write().remove(&(height, round));
+        if let Some(start) = started {
+            let dur_ms = start.elapsed().as_secs_f64() * 1000.0;
+            let meter = opentelemetry::global::meter("consensus-core");
+            let hist = meter.f64_histogram("consensus_round_progress_ms").with_description("Time from propose to quorum for a (height,round)").init();
+            hist.record(dur_ms, &[]);
+            swarm_core::CAPACITY.record_latency("consensus_round_progress", dur_ms);
+        }
+        self.publish_round_changed(height, round);
+        true
+    }
+
+    /// (hits, misses) of the per-(height,round) leader cache.
+    pub fn leader_cache_stats(&self) -> (u64, u64) { (self.leader_cache_hits.load(Ordering::Relaxed), self.leader_cache_misses.load(Ordering::Relaxed)) }
+
+    /// Announce (height,round) with its current leader and proof.
+    fn publish_round_changed(&self, height: u64, round: u64) {
+        let (leader, proof) = { let st = self.state_read(); (st.leader.clone(), st.last_leader_proof) };
+        self.publisher.round_changed(height, round, leader, proof);
+    }
+
+    fn emit(&self, ev: ConsensusEvent) {
+        let _ = self.events.send(ev); // no subscribers is fine
+        swarm_core::CAPACITY.set_queue_depth("consensus_events", self.events.len() as u64, self.event_buffer as u64);
     }
 
     fn load_votes(&self) {
         if let Some(db) = &*DB {
-            let mut map = self.votes.write().unwrap();
-            for kv in db.scan_prefix("vote:") { if let Ok((k,_)) = kv {
-                if let Ok(s) = std::str::from_utf8(&k) { // vote:height:round:node
-                    let parts: Vec<&str> = s.split(':').collect();
-                    if parts.len()==4 { if let (Ok(h), Ok(r)) = (parts[1].parse::<u64>(), parts[2].parse::<u64>()) {
-                        map.entry((h,r)).or_insert_with(HashSet::new).insert(parts[3].to_string());
-                    }}
-                }
-            }}
+            let mut map = self.votes_write();
+            *map = restore::restore_votes(db, restore::CorruptPolicy::from_env()).into_iter().map(|(hr, voters)| (hr, voters.into())).collect();
+            // rounds that reached quorum before the restart were already finalized
+            let quorum = self.quorum();
+            for round in map.values() { if round.voters.len() >= quorum { round.finalized.store(true, Ordering::Release); } }
             tracing::info!(restored_votes=map.len(), "restored_votes_from_persistence");
         }
     }
@@ -79,56 +313,59 @@ impl PbftService {
 
 #[async_trait]
 impl Pbft for PbftService {
+    type SubscribeEventsStream = Pin<Box<dyn Stream<Item = Result<ConsensusEvent, Status>> + Send + 'static>>;
+
     #[instrument(skip(self, request), fields(proposal.id = %request.get_ref().id))]
     async fn propose(&self, request: Request<Proposal>) -> Result<Response<Ack>, Status> {
         let prop = request.into_inner();
         let mut broadcast = None;
+        let mut closed = None;
         {
-            let mut st = self.state.write().unwrap();
-            if prop.height > st.height { st.height = prop.height; st.round = prop.round; broadcast = Some((st.height, st.round)); }
+            let mut st = self.state_write();
+            if prop.height > st.height { closed = Some((st.height, st.round)); st.height = prop.height; st.round = prop.round; broadcast = Some((st.height, st.round)); }
         }
+        if let Some((h,r)) = closed { self.close_round(h, r); }
+        if let Some((h,r)) = broadcast { self.emit(event(EventKind::HeightChanged, h, r)); }
         // record round start time (height,round)
         if let Some((h,r)) = broadcast {
-            self.round_starts.write().unwrap().insert((h,r), Instant::now());
+            self.round_starts_write().insert((h,r), Instant::now());
         }
         // Leader re-elected on new height
         if let Some((h,r)) = broadcast { self.elect_leader(h, r); }
-        if let Some((h,r)) = broadcast { tokio::spawn(async move { super::publish_height_changed_versioned(h,r).await; }); }
+        if let Some((h,r)) = broadcast { self.publisher.height_changed(h, r); }
         Ok(Response::new(Ack { accepted: true, reason: "accepted".into() }))
     }
 
     #[instrument(skip(self, request), fields(vote.proposal_id = %request.get_ref().proposal_id))]
     async fn cast_vote(&self, request: Request<Vote>) -> Result<Response<Ack>, Status> {
         let vote = request.into_inner();
-        {
-            let mut st = self.state.write().unwrap();
-            if vote.height > st.height { st.height = vote.height; st.round = vote.round; }
-        }
-        let count = self.record_vote(vote.height, vote.round, &vote.node_id);
-        let quorum = self.quorum();
-        if count >= quorum {
-            self.elect_leader(vote.height, vote.round);
-            tracing::info!(height=vote.height, round=vote.round, quorum=%quorum, votes=%count, leader=%self.snapshot().leader, "quorum_reached");
-            // record round progress duration metric
-            if let Some(start) = self.round_starts.write().unwrap().remove(&(vote.height, vote.round)) {
-                let dur_ms = start.elapsed().as_secs_f64() * 1000.0;
-                let meter = opentelemetry::global::meter("consensus-core");
-                let hist = meter.f64_histogram("consensus_round_progress_ms").with_description("Time from propose to quorum for a (height,round)").init();
-                hist.record(dur_ms, &[]);
-            }
-            let h = vote.height; let r = vote.round;
-            tokio::spawn(async move { super::publish_round_changed(h,r).await; });
-        }
+        let advanced = {
+            let mut st = self.state_write();
+            if vote.height > st.height { st.height = vote.height; st.round = vote.round; true } else { false }
+        };
+        if advanced { self.emit(event(EventKind::HeightChanged, vote.height, vote.round)); }
+        let (count, _) = self.record_vote(vote.height, vote.round, &vote.node_id);
+        // the first vote to see the tally at or past quorum finalizes (the quorum may have been
+        // lowered by a reconfigure since the last vote); re-sent and late votes find it claimed
+        self.finalize_if_quorum(vote.height, vote.round, count);
         Ok(Response::new(Ack { accepted: true, reason: "vote recorded".into() }))
     }
 
     #[instrument(skip(self), fields(query.height = %request.get_ref().height))]
     async fn get_state(&self, request: Request<ConsensusStateQuery>) -> Result<Response<ConsensusState>, Status> {
         let q = request.into_inner();
-        let st = self.state.read().unwrap();
+        let st = self.state_read();
         if q.height != 0 && q.height != st.height { return Err(Status::not_found("height not found")); }
         Ok(Response::new(ConsensusState { height: st.height, round: st.round, leader: st.leader.clone() }))
     }
+
+    async fn subscribe_events(&self, _request: Request<SubscribeEventsRequest>) -> Result<Response<Self::SubscribeEventsStream>, Status> {
+        let stream = BroadcastStream::new(self.events.subscribe()).filter_map(|ev| match ev {
+            Ok(ev) => Some(Ok(ev)),
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => { tracing::warn!(skipped, "consensus event subscriber lagged"); None }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
 }
 
 #[cfg(test)]
@@ -162,4 +399,171 @@ mod tests {
         assert_eq!(snap.round, 2);
         assert_eq!(snap.height, 1);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn participation_reflects_partial_votes() {
+        let svc = PbftService::new();
+        let vote = |node: &str, h: u64| Request::new(Vote { proposal_id: format!("p{h}"), node_id: node.into(), height: h, round: 0, vote_type: 0 });
+        // round 1001: three of four validators vote; round 1002: only node-0 (plus an unknown voter)
+        let _ = svc.propose(Request::new(Proposal { id: "p1001".into(), payload: vec![], height: 1001, round: 0 })).await.unwrap();
+        for n in ["node-0", "node-1", "node-2"] { let _ = svc.cast_vote(vote(n, 1001)).await.unwrap(); }
+        let _ = svc.propose(Request::new(Proposal { id: "p1002".into(), payload: vec![], height: 1002, round: 0 })).await.unwrap();
+        for n in ["node-0", "outsider"] { let _ = svc.cast_vote(vote(n, 1002)).await.unwrap(); }
+        // proposing the next height closes round 1002
+        let _ = svc.propose(Request::new(Proposal { id: "p1003".into(), payload: vec![], height: 1003, round: 0 })).await.unwrap();
+        let snap = svc.participation_snapshot();
+        assert_eq!(snap["node-0"], 1.0);
+        assert_eq!(snap["node-1"], 0.5);
+        assert_eq!(snap["node-2"], 0.5);
+        assert_eq!(snap["node-3"], 0.0);
+        assert!(!snap.contains_key("outsider"), "unknown voters must not add label cardinality");
+    }
+
+    #[test]
+    fn leader_proof_verifies_against_beacon_key() {
+        let validators: Vec<String> = (0..4).map(|i| format!("node-{i}")).collect();
+        let stakes: HashMap<String, u64> = validators.iter().cloned().zip([5, 1, 0, 10]).collect();
+        assert_eq!(leader_beacon(42, 3, &validators).map(String::as_str), Some("node-1"));
+        let keys = vrf_keys::test_keys("node-1");
+        let (leader, proof, _) = weighted_leader_with_proof(42, 3, &vrf_keys::test_secret("node-1"), &validators, &stakes).expect("leader");
+        assert_eq!(verify_leader_proof(42, 3, keys.public_key("node-1").unwrap(), &proof, &validators, &stakes), Some(leader.clone()));
+        assert!(verify_leader_proof(42, 4, keys.public_key("node-1").unwrap(), &proof, &validators, &stakes).is_none());
+        // another validator cannot stand in for the beacon
+        let (_, forged, _) = weighted_leader_with_proof(42, 3, &vrf_keys::test_secret("node-3"), &validators, &stakes).expect("leader");
+        assert!(verify_leader_proof(42, 3, keys.public_key("node-1").unwrap(), &forged, &validators, &stakes).is_none());
+        assert_ne!(leader, "node-2", "zero-stake validator must never lead");
+        assert_eq!(weighted_leader(42, 3, &vrf_keys::test_secret("node-1"), &validators, &stakes), Some(leader));
+    }
+
+    #[tokio::test]
+    async fn elect_leader_stores_proof() {
+        // node-0 is the beacon of (7,1) with four validators
+        let svc = PbftService::with_vrf_keys(vrf_keys::test_keys("node-0"));
+        svc.elect_leader(7, 1);
+        let snap = svc.snapshot();
+        let proof = snap.last_leader_proof.expect("proof stored");
+        let public = vrf_keys::test_keys("node-0").public_key("node-0").copied().unwrap();
+        assert_eq!(verify_leader_proof(7, 1, &public, &proof, &snap.validators, &snap.stakes), Some(snap.leader.clone()));
+        assert!(snap.validators.contains(&snap.leader));
+    }
+
+    #[tokio::test]
+    async fn other_beacons_leader_needs_a_verified_proof() {
+        let svc = PbftService::with_vrf_keys(vrf_keys::test_keys("node-1"));
+        let mut events = svc.events.subscribe();
+        svc.propose(Request::new(Proposal { id: "vrf".into(), payload: vec![], height: 7, round: 1 })).await.unwrap();
+        let snap = svc.snapshot();
+        assert_eq!((snap.leader.as_str(), snap.last_leader_proof), ("node-0", None), "beacon leads until its proof arrives");
+
+        let (_, forged, _) = weighted_leader_with_proof(7, 1, &vrf_keys::test_secret("node-1"), &snap.validators, &snap.stakes).unwrap();
+        assert!(svc.accept_leader_proof(7, 1, forged).is_err());
+        let (leader, proof, _) = weighted_leader_with_proof(7, 1, &vrf_keys::test_secret("node-0"), &snap.validators, &snap.stakes).unwrap();
+        assert_eq!(svc.accept_leader_proof(7, 1, proof).unwrap(), leader);
+        let snap = svc.snapshot();
+        assert_eq!((snap.leader, snap.last_leader_proof), (leader.clone(), Some(proof)));
+        let mut announced = None;
+        while let Ok(ev) = events.try_recv() { if ev.kind == EventKind::LeaderChanged as i32 { announced = Some((ev.leader, ev.leader_proof)); } }
+        assert_eq!(announced, Some((leader.clone(), proof.0.to_vec())));
+        // the accepted proof is served from the cache on re-election
+        svc.elect_leader(7, 1);
+        assert_eq!(svc.snapshot().leader, leader);
+    }
+
+    #[tokio::test]
+    async fn repeated_election_hits_leader_cache() {
+        // node-3 is the beacon of (50,1)
+        let svc = PbftService::with_vrf_keys(vrf_keys::test_keys("node-3"));
+        let (h0, m0) = svc.leader_cache_stats();
+        svc.elect_leader(50, 1);
+        let first = svc.snapshot();
+        svc.elect_leader(50, 1);
+        let second = svc.snapshot();
+        assert_eq!(svc.leader_cache_stats(), (h0 + 1, m0 + 1));
+        assert_eq!(first.leader, second.leader);
+        assert_eq!(first.last_leader_proof, second.last_leader_proof);
+
+        // stake moves entirely to node-3: the cached leader must not survive the reconfigure
+        let validators = first.validators.clone();
+        let stakes = validators.iter().map(|v| (v.clone(), if v == "node-3" { 1 } else { 0 })).collect();
+        svc.reconfigure(validators, stakes);
+        svc.elect_leader(50, 1);
+        assert_eq!(svc.snapshot().leader, "node-3");
+        assert_eq!(svc.leader_cache_stats(), (h0 + 1, m0 + 2));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_methods_respect_lock_order() {
+        let svc = PbftService::new();
+        let mut handles = Vec::new();
+        for t in 0..8u64 {
+            let svc = svc.clone();
+            handles.push(tokio::spawn(async move {
+                for i in 0..200u64 {
+                    let h = 2000 + i;
+                    let _ = svc.propose(Request::new(Proposal { id: format!("s{t}-{i}"), payload: vec![], height: h, round: 0 })).await;
+                    let _ = svc.cast_vote(Request::new(Vote { proposal_id: format!("s{t}-{i}"), node_id: format!("node-{}", t % 4), height: h, round: 0, vote_type: 0 })).await;
+                    let _ = svc.get_state(Request::new(ConsensusStateQuery { height: 0 })).await;
+                    let _ = svc.participation_snapshot();
+                }
+            }));
+        }
+        let joined = tokio::time::timeout(std::time::Duration::from_secs(20), async {
+            let mut out = Vec::with_capacity(handles.len());
+            for h in handles { out.push(h.await); }
+            out
+        }).await.expect("deadlock: stress test timed out");
+        for r in joined { assert!(r.is_ok(), "task panicked (lock order violation?)"); }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_duplicate_votes_finalize_once() {
+        let svc = PbftService::new();
+        // fresh height so votes persisted by earlier runs cannot pre-fill the round
+        let h = 1_000_000 + std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos() as u64 % 1_000_000_000;
+        let mut events = svc.events.subscribe();
+        let vote = move |node: &str| Request::new(Vote { proposal_id: "dup".into(), node_id: node.into(), height: h, round: 0, vote_type: 0 });
+        // 16 tasks race the same three voters (quorum for four validators)
+        let mut handles = Vec::new();
+        for t in 0..16u64 {
+            let svc = svc.clone();
+            handles.push(tokio::spawn(async move {
+                for n in 0..3 { svc.cast_vote(vote(&format!("node-{}", (n + t) % 3))).await.unwrap(); }
+            }));
+        }
+        for handle in handles { handle.await.unwrap(); }
+        // re-sent votes after quorum land on tally == quorum again; a late fourth voter goes past it
+        for node in ["node-0", "node-1", "node-2", "node-3", "node-3"] { svc.cast_vote(vote(node)).await.unwrap(); }
+        let votes = svc.votes_read();
+        let round = votes.get(&(h, 0)).unwrap();
+        assert_eq!((round.voters.len(), round.tally.load(Ordering::Acquire)), (4, 4));
+        drop(votes);
+        let (fast, inserted) = svc.vote_path_stats();
+        assert_eq!(inserted, 4);
+        assert!((4..=16 * 3 + 4).contains(&fast), "re-sent votes take the shared-lock path: {fast}");
+        let mut finalized = 0;
+        while let Ok(ev) = events.try_recv() { if ev.kind == EventKind::Finalized as i32 && ev.height == h { finalized += 1; } }
+        assert_eq!(finalized, 1);
+    }
+
+    #[tokio::test]
+    async fn lowering_quorum_mid_round_finalizes_once() {
+        let svc = PbftService::new();
+        let h = 3_000_000 + std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos() as u64 % 1_000_000_000;
+        let seven: Vec<String> = (0..7).map(|i| format!("node-{i}")).collect();
+        let stakes = |vals: &[String]| vals.iter().map(|v| (v.clone(), 1)).collect::<HashMap<_, _>>();
+        svc.reconfigure(seven.clone(), stakes(&seven)); // quorum 5
+        let mut events = svc.events.subscribe();
+        let vote = move |node: &str| Request::new(Vote { proposal_id: "lower".into(), node_id: node.into(), height: h, round: 0, vote_type: 0 });
+        for n in 0..3 { svc.cast_vote(vote(&format!("node-{n}"))).await.unwrap(); }
+        // node-3's vote is tallied against quorum 5, then the set shrinks to four (quorum 3) before
+        // the vote reaches its quorum check
+        let (count, inserted) = svc.record_vote(h, 0, "node-3");
+        assert_eq!((count, inserted), (4, true));
+        svc.reconfigure(seven[..4].to_vec(), stakes(&seven[..4]));
+        assert!(!svc.finalize_if_quorum(h, 0, count), "reconfigure already finalized the round");
+        for node in ["node-3", "node-4"] { svc.cast_vote(vote(node)).await.unwrap(); }
+        let mut finalized = 0;
+        while let Ok(ev) = events.try_recv() { if ev.kind == EventKind::Finalized as i32 && ev.height == h { finalized += 1; } }
+        assert_eq!(finalized, 1);
+    }
+}
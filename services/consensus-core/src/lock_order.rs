@@ -0,0 +1,88 @@
+//! Canonical lock-acquisition order for `PbftService`.
+//!
+//! Order: `state` -> `votes` -> `round_starts` -> `leader_cache`. A lock may only be taken while every
+//! lock already held by the thread has a strictly lower rank. With the audit enabled
+//! (debug builds by default, `CONSENSUS_LOCK_AUDIT=0|1` overrides) an out-of-order
+//! acquisition panics *before* blocking, turning a latent deadlock into a test failure.
+
+use std::cell::RefCell;
+use std::ops::{Deref, DerefMut};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use once_cell::sync::Lazy;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum LockRank { State = 1, Votes = 2, RoundStarts = 3, LeaderCache = 4 }
+
+static AUDIT_ENABLED: Lazy<bool> = Lazy::new(|| {
+    std::env::var("CONSENSUS_LOCK_AUDIT").ok().map(|v| v=="1" || v.eq_ignore_ascii_case("true")).unwrap_or(cfg!(debug_assertions))
+});
+
+thread_local! {
+    static HELD: RefCell<Vec<LockRank>> = RefCell::new(Vec::new());
+}
+
+fn enter(rank: LockRank) -> bool {
+    if !*AUDIT_ENABLED { return false; }
+    HELD.with(|held| {
+        let mut held = held.borrow_mut();
+        if let Some(top) = held.iter().max() {
+            if *top >= rank { panic!("lock order violation: acquiring {:?} while holding {:?}", rank, top); }
+        }
+        held.push(rank);
+    });
+    true
+}
+
+fn exit(rank: LockRank) {
+    HELD.with(|held| {
+        let mut held = held.borrow_mut();
+        if let Some(pos) = held.iter().rposition(|r| *r == rank) { held.remove(pos); }
+    });
+}
+
+/// Guard wrapper that records the held rank for the lifetime of the inner lock guard.
+pub(crate) struct Ordered<G> { guard: G, rank: LockRank, tracked: bool }
+
+impl<G> Drop for Ordered<G> {
+    fn drop(&mut self) { if self.tracked { exit(self.rank); } }
+}
+
+impl<G: Deref> Deref for Ordered<G> {
+    type Target = G::Target;
+    fn deref(&self) -> &Self::Target { &self.guard }
+}
+
+impl<G: DerefMut> DerefMut for Ordered<G> {
+    fn deref_mut(&mut self) -> &mut Self::Target { &mut self.guard }
+}
+
+pub(crate) fn read<T>(lock: &RwLock<T>, rank: LockRank) -> Ordered<RwLockReadGuard<'_, T>> {
+    let tracked = enter(rank);
+    Ordered { guard: lock.read().unwrap(), rank, tracked }
+}
+
+pub(crate) fn write<T>(lock: &RwLock<T>, rank: LockRank) -> Ordered<RwLockWriteGuard<'_, T>> {
+    let tracked = enter(rank);
+    Ordered { guard: lock.write().unwrap(), rank, tracked }
+}
+
+#[cfg(all(test, debug_assertions))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn in_order_nesting_is_allowed() {
+        let a = RwLock::new(1u32); let b = RwLock::new(2u32);
+        let ga = read(&a, LockRank::State);
+        let gb = write(&b, LockRank::Votes);
+        assert_eq!(*ga + *gb, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "lock order violation")]
+    fn out_of_order_nesting_panics() {
+        let a = RwLock::new(()); let b = RwLock::new(());
+        let _votes = write(&b, LockRank::Votes);
+        let _state = read(&a, LockRank::State);
+    }
+}
@@ -1,5 +1,5 @@
-use anyhow::Result;
-use swarm_core::{init_tracing, start_health_server, shutdown_tracer};
+use anyhow::{Context, Result};
+use swarm_core::{init_tracing, start_health_server, shutdown_observability};
 use swarm_proto::consensus::pbft_server::PbftServer;
 use tonic::transport::Server;
 use tracing::info;
@@ -7,7 +7,9 @@ use std::net::SocketAddr;
 use axum::{routing::get, Router};
 use opentelemetry_prometheus::PrometheusExporter;
 use opentelemetry::{metrics::MeterProvider as _, sdk::metrics::{controllers, processors, selectors}};
-use consensus_core::{PbftService};
+use consensus_core::PbftService;
+use swarm_core::VrfProof;
+use tokio_stream::StreamExt;
 
 
 fn spawn_metrics_server(port: u16) {
@@ -26,33 +28,34 @@ fn spawn_metrics_server(port: u16) {
     });
 }
 
-pub async fn publish_height_changed_versioned(height: u64, round: u64) {
-    let nats_url = std::env::var("NATS_URL").unwrap_or_else(|_| "127.0.0.1:4222".into());
-    let proto_version = std::env::var("PROTO_SCHEMA_VERSION").unwrap_or_else(|_| "v1".into());
-    if let Ok(conn) = async_nats::connect(nats_url).await {
-        let payload = serde_json::json!({"height": height, "round": round, "proto_schema_version": proto_version});
-        let _ = conn.publish("consensus.v1.height.changed".into(), payload.to_string().into()).await;
-        tracing::info!(height, round, proto_schema_version=?proto_version, "broadcast consensus.v1.height.changed");
-    } else { tracing::debug!("NATS unavailable - skip broadcast"); }
-}
-
-pub async fn publish_round_changed(height: u64, round: u64) {
-    let nats_url = std::env::var("NATS_URL").unwrap_or_else(|_| "127.0.0.1:4222".into());
-    let proto_version = std::env::var("PROTO_SCHEMA_VERSION").unwrap_or_else(|_| "v1".into());
-    if let Ok(conn) = async_nats::connect(nats_url).await {
-        let payload = serde_json::json!({"height": height, "round": round, "proto_schema_version": proto_version});
-        let _ = conn.publish("consensus.v1.round.changed".into(), payload.to_string().into()).await;
-        tracing::info!(height, round, proto_schema_version=?proto_version, "broadcast consensus.v1.round.changed");
-    } else { tracing::debug!("NATS unavailable - skip broadcast"); }
+/// Hand leader proofs published by other beacons to `svc`, which adopts those that verify.
+fn spawn_leader_proof_listener(svc: PbftService) {
+    tokio::spawn(async move {
+        let nats_url = std::env::var("NATS_URL").unwrap_or_else(|_| "127.0.0.1:4222".into());
+        let Ok(conn) = async_nats::connect(nats_url).await else { tracing::debug!("NATS unavailable - leader proofs from other beacons not received"); return; };
+        let mut sub = match conn.subscribe("consensus.v1.round.changed").await {
+            Ok(sub) => sub,
+            Err(e) => { tracing::warn!(error=?e, "subscribe consensus.v1.round.changed failed"); return; }
+        };
+        while let Some(msg) = sub.next().await {
+            let Ok(v) = serde_json::from_slice::<serde_json::Value>(&msg.payload) else { continue };
+            let (Some(height), Some(round)) = (v["height"].as_u64(), v["round"].as_u64()) else { continue };
+            let Some(proof) = v["leader_proof"].as_str().and_then(|p| hex::decode(p).ok()).and_then(|b| <[u8; 80]>::try_from(b).ok()) else { continue };
+            if let Err(e) = svc.accept_leader_proof(height, round, VrfProof(proof)) { tracing::warn!(height, round, error=%e, "leader proof rejected"); }
+        }
+    });
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     init_tracing("consensus-core")?;
     start_health_server(8081).await?; // separate health port
+    let db_dir = consensus_core::db_path().context("consensus persistence unavailable")?;
+    info!(?db_dir, "consensus data dir ready");
     let grpc_port: u16 = std::env::var("CONSENSUS_GRPC_PORT").ok().and_then(|v| v.parse().ok()).unwrap_or(50051);
     let addr = ([0,0,0,0], grpc_port).into();
     let svc = PbftService::new();
+    spawn_leader_proof_listener(svc.clone());
     spawn_metrics_server(9102);
     info!(?addr, "Starting consensus-core gRPC server");
     let server = Server::builder()
@@ -65,6 +68,6 @@ async fn main() -> Result<()> {
             tracing::info!("Shutdown signal received");
         });
     if let Err(e) = server.await { tracing::error!(error=?e, "gRPC server error"); }
-    shutdown_tracer();
+    shutdown_observability();
     Ok(())
 }
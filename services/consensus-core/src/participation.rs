@@ -0,0 +1,58 @@
+//! Rolling per-validator participation tracking for the consensus dashboard.
+//!
+//! Each closed (height,round) records whether every known validator voted in it.
+//! Exported as `swarm_consensus_validator_participation{validator}` (ratio over the
+//! window) plus `swarm_consensus_validator_missed_rounds_total{validator}`.
+//! Only validators from the configured set are tracked so label cardinality stays bounded.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, RwLock};
+use opentelemetry::{KeyValue, metrics::Counter};
+
+pub struct ParticipationTracker {
+    window: usize,
+    history: Arc<RwLock<HashMap<String, VecDeque<bool>>>>, // validator -> participated flags (oldest first)
+    missed_total: Counter<u64>,
+}
+
+impl ParticipationTracker {
+    pub fn new(window: usize) -> Self {
+        let history: Arc<RwLock<HashMap<String, VecDeque<bool>>>> = Arc::new(RwLock::new(HashMap::new()));
+        let meter = opentelemetry::global::meter("consensus-core");
+        let missed_total = meter.u64_counter("swarm_consensus_validator_missed_rounds_total").with_description("Rounds closed without a vote from the validator").init();
+        let gauge_history = history.clone();
+        let _gauge = meter.f64_observable_gauge("swarm_consensus_validator_participation")
+            .with_description("Fraction of recent rounds the validator voted in (0..1)")
+            .with_callback(move |obs| {
+                for (validator, ratio) in ratios(&gauge_history.read().unwrap()) {
+                    obs.observe(ratio, &[KeyValue::new("validator", validator)]);
+                }
+            })
+            .init();
+        Self { window: window.max(1), history, missed_total }
+    }
+
+    /// Record a closed round. Voters outside `validators` are ignored and validators
+    /// that left the set are dropped, capping cardinality to the current set.
+    pub fn record_round(&self, validators: &[String], voters: &HashSet<String>) {
+        let mut hist = self.history.write().unwrap();
+        hist.retain(|v, _| validators.contains(v));
+        for v in validators {
+            let participated = voters.contains(v);
+            let entry = hist.entry(v.clone()).or_insert_with(VecDeque::new);
+            entry.push_back(participated);
+            if entry.len() > self.window { entry.pop_front(); }
+            if !participated { self.missed_total.add(1, &[KeyValue::new("validator", v.clone())]); }
+        }
+    }
+
+    /// Current participation ratio per validator over the rolling window.
+    pub fn snapshot(&self) -> HashMap<String, f64> { ratios(&self.history.read().unwrap()) }
+}
+
+fn ratios(hist: &HashMap<String, VecDeque<bool>>) -> HashMap<String, f64> {
+    hist.iter()
+        .filter(|(_, rounds)| !rounds.is_empty())
+        .map(|(v, rounds)| (v.clone(), rounds.iter().filter(|p| **p).count() as f64 / rounds.len() as f64))
+        .collect()
+}
@@ -0,0 +1,47 @@
+//! NATS notifications for consensus progress.
+//!
+//! `PbftService` reports height changes, finalized rounds and view changes through a
+//! `RoundPublisher`; `NatsPublisher` (the default) sends them as `consensus.v1.height.changed` /
+//! `consensus.v1.round.changed` on `NATS_URL`, tagged with `PROTO_SCHEMA_VERSION`.
+
+use swarm_core::VrfProof;
+
+pub trait RoundPublisher: Send + Sync {
+    fn height_changed(&self, height: u64, round: u64);
+    fn round_changed(&self, height: u64, round: u64, leader: String, leader_proof: Option<VrfProof>);
+}
+
+/// Publishes on NATS from a spawned task; a missing broker only skips the message.
+pub struct NatsPublisher;
+
+impl RoundPublisher for NatsPublisher {
+    fn height_changed(&self, height: u64, round: u64) {
+        tokio::spawn(publish_height_changed_versioned(height, round));
+    }
+
+    fn round_changed(&self, height: u64, round: u64, leader: String, leader_proof: Option<VrfProof>) {
+        tokio::spawn(publish_round_changed(height, round, leader, leader_proof));
+    }
+}
+
+pub async fn publish_height_changed_versioned(height: u64, round: u64) {
+    let nats_url = std::env::var("NATS_URL").unwrap_or_else(|_| "127.0.0.1:4222".into());
+    let proto_version = std::env::var("PROTO_SCHEMA_VERSION").unwrap_or_else(|_| "v1".into());
+    if let Ok(conn) = async_nats::connect(nats_url).await {
+        let payload = serde_json::json!({"height": height, "round": round, "proto_schema_version": proto_version});
+        let _ = conn.publish("consensus.v1.height.changed", payload.to_string().into()).await;
+        tracing::info!(height, round, proto_schema_version=?proto_version, "broadcast consensus.v1.height.changed");
+    } else { tracing::debug!("NATS unavailable - skip broadcast"); }
+}
+
+pub async fn publish_round_changed(height: u64, round: u64, leader: String, leader_proof: Option<VrfProof>) {
+    let nats_url = std::env::var("NATS_URL").unwrap_or_else(|_| "127.0.0.1:4222".into());
+    let proto_version = std::env::var("PROTO_SCHEMA_VERSION").unwrap_or_else(|_| "v1".into());
+    if let Ok(conn) = async_nats::connect(nats_url).await {
+        // observers check leader_proof against the registered VRF key of leader_beacon(height, round)
+        let payload = serde_json::json!({"height": height, "round": round, "leader": leader,
+            "leader_proof": leader_proof.map(|p| hex::encode(p.0)), "proto_schema_version": proto_version});
+        let _ = conn.publish("consensus.v1.round.changed", payload.to_string().into()).await;
+        tracing::info!(height, round, proto_schema_version=?proto_version, "broadcast consensus.v1.round.changed");
+    } else { tracing::debug!("NATS unavailable - skip broadcast"); }
+}
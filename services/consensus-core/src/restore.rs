@@ -0,0 +1,118 @@
+//! Tolerant restore of persisted votes.
+//!
+//! A vote is stored as key `vote:{height}:{round}:{node}` with an empty value. Entries that do
+//! not match (non-UTF-8 or malformed key, non-empty value) are skipped, logged with their key and
+//! counted in `persistence_corrupt_entries_total`; valid votes keep loading. What happens to a
+//! corrupt entry is set by `CONSENSUS_CORRUPT_POLICY`: `quarantine` (default, moved to the
+//! `quarantine` tree for inspection), `skip` (left in place) or `delete`.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use once_cell::sync::Lazy;
+use opentelemetry::metrics::Counter;
+
+static CORRUPT_ENTRIES: AtomicU64 = AtomicU64::new(0);
+static CORRUPT_CTR: Lazy<Counter<u64>> = Lazy::new(|| {
+    opentelemetry::global::meter("consensus-core").u64_counter("persistence_corrupt_entries_total").with_description("Persisted entries skipped on restore because they failed to decode").init()
+});
+
+pub type VoteMap = HashMap<(u64,u64), HashSet<String>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CorruptPolicy { Skip, Quarantine, Delete }
+
+impl CorruptPolicy {
+    pub fn from_env() -> Self {
+        match std::env::var("CONSENSUS_CORRUPT_POLICY").as_deref() {
+            Ok("skip") => Self::Skip,
+            Ok("delete") => Self::Delete,
+            _ => Self::Quarantine,
+        }
+    }
+}
+
+/// Corrupt entries skipped since start.
+pub fn corrupt_entries() -> u64 { CORRUPT_ENTRIES.load(Ordering::Relaxed) }
+
+fn parse_vote(key: &[u8], value: &[u8]) -> Option<((u64,u64), String)> {
+    if !value.is_empty() { return None; }
+    let mut parts = std::str::from_utf8(key).ok()?.splitn(4, ':');
+    let (tag, h, r, node) = (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+    if tag != "vote" || node.is_empty() || node.contains(':') { return None; }
+    Some(((h.parse().ok()?, r.parse().ok()?), node.to_string()))
+}
+
+fn mark_corrupt(key: &[u8]) {
+    CORRUPT_ENTRIES.fetch_add(1, Ordering::Relaxed);
+    CORRUPT_CTR.add(1, &[]);
+    tracing::warn!(key=%String::from_utf8_lossy(key), "skipping corrupt persisted vote");
+}
+
+/// Load every decodable vote; corrupt entries are handled per `policy` and never abort startup.
+pub fn restore_votes(db: &sled::Db, policy: CorruptPolicy) -> VoteMap {
+    let mut votes = VoteMap::new();
+    let mut corrupt = Vec::new();
+    for kv in db.scan_prefix("vote:") {
+        match kv {
+            Ok((k, v)) => match parse_vote(&k, &v) {
+                Some((hr, node)) => { votes.entry(hr).or_default().insert(node); }
+                None => { mark_corrupt(&k); corrupt.push((k, v)); }
+            },
+            // an unreadable page: the cursor cannot move past it, keep what was loaded so far
+            Err(e) => { mark_corrupt(b"<unreadable>"); tracing::error!(error=?e, "vote scan aborted"); break; }
+        }
+    }
+    if policy != CorruptPolicy::Skip && !corrupt.is_empty() {
+        let quarantine = match policy { CorruptPolicy::Quarantine => db.open_tree("quarantine").map_err(|e| tracing::warn!(error=?e, "quarantine tree unavailable")).ok(), _ => None };
+        if policy == CorruptPolicy::Delete || quarantine.is_some() {
+            for (k, v) in &corrupt {
+                if let Some(q) = &quarantine { if let Err(e) = q.insert(k, v.clone()) { tracing::warn!(error=?e, "quarantine insert failed"); continue; } }
+                let _ = db.remove(k);
+            }
+            let _ = db.flush();
+        }
+    }
+    votes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corrupt_entries_are_skipped_counted_and_quarantined() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        db.insert("vote:1:0:node-0", &[]).unwrap();
+        db.insert("vote:1:0:node-1", &[]).unwrap();
+        db.insert("vote:2:0:node-2", &[]).unwrap();
+        db.insert("vote:x:0:node-3", &[]).unwrap(); // bad height
+        db.insert(b"vote:\xff\xfe", &[]).unwrap(); // not UTF-8
+        db.insert("vote:3:0:node-4", b"garbage").unwrap(); // votes carry no value
+        let before = corrupt_entries();
+
+        let votes = restore_votes(&db, CorruptPolicy::Quarantine);
+        assert_eq!(votes.len(), 2);
+        assert_eq!(votes[&(1, 0)].len(), 2);
+        assert!(votes[&(2, 0)].contains("node-2"));
+        assert!(corrupt_entries() >= before + 3);
+        let quarantine = db.open_tree("quarantine").unwrap();
+        assert_eq!(quarantine.len(), 3);
+        assert_eq!(quarantine.get("vote:3:0:node-4").unwrap().as_deref(), Some(&b"garbage"[..]));
+        assert_eq!(db.scan_prefix("vote:").count(), 3);
+
+        // a second start sees a clean store
+        assert_eq!(restore_votes(&db, CorruptPolicy::Quarantine), votes);
+        assert_eq!(quarantine.len(), 3);
+    }
+
+    #[test]
+    fn skip_leaves_corrupt_entries_in_place() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        db.insert("vote:1:0:node-0", &[]).unwrap();
+        db.insert("vote:1", &[]).unwrap();
+        assert_eq!(restore_votes(&db, CorruptPolicy::Skip).len(), 1);
+        assert!(db.contains_key("vote:1").unwrap());
+        assert_eq!(restore_votes(&db, CorruptPolicy::Delete).len(), 1);
+        assert!(!db.contains_key("vote:1").unwrap());
+    }
+}
@@ -0,0 +1,103 @@
+//! End-to-end consensus smoke check used by the `swarm-smoke` binary.
+//!
+//! Against a live node: read the current height, propose `height + 1`, cast votes from
+//! `node-0..node-{voters-1}` and wait for the FINALIZED event (the node's quorum certificate)
+//! on `SubscribeEvents`, then confirm `GetState` reports the new height.
+//! Env: `SMOKE_ENDPOINT` (http://127.0.0.1:50051), `SMOKE_VOTERS` (`VALIDATOR_SET_SIZE`, else 1),
+//! `SMOKE_TIMEOUT_SECS` (10).
+
+use std::fmt;
+use std::time::{Duration, Instant};
+use swarm_proto::consensus::{pbft_client::PbftClient, consensus_event::Kind as EventKind, ConsensusStateQuery, Proposal, SubscribeEventsRequest, Vote};
+use tonic::transport::Channel;
+
+#[derive(Debug, Clone)]
+pub struct SmokeConfig {
+    pub endpoint: String,
+    pub voters: usize,
+    pub timeout: Duration,
+}
+
+impl SmokeConfig {
+    pub fn from_env() -> Self {
+        let env_usize = |k: &str| std::env::var(k).ok().and_then(|v| v.parse::<usize>().ok());
+        Self {
+            endpoint: std::env::var("SMOKE_ENDPOINT").unwrap_or_else(|_| "http://127.0.0.1:50051".into()),
+            voters: env_usize("SMOKE_VOTERS").or_else(|| env_usize("VALIDATOR_SET_SIZE")).unwrap_or(1).max(1),
+            timeout: Duration::from_secs(env_usize("SMOKE_TIMEOUT_SECS").unwrap_or(10) as u64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SmokeReport {
+    pub endpoint: String,
+    pub start_height: u64,
+    pub target_height: u64,
+    pub final_height: u64,
+    pub votes_cast: usize,
+    pub finalized: bool, // FINALIZED observed for target_height
+    pub leader: String,
+    pub elapsed: Duration,
+    pub failure: Option<String>,
+}
+
+impl SmokeReport {
+    pub fn passed(&self) -> bool { self.failure.is_none() && self.finalized && self.final_height >= self.target_height }
+}
+
+impl fmt::Display for SmokeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "swarm-smoke {} [{}]", if self.passed() { "PASS" } else { "FAIL" }, self.endpoint)?;
+        writeln!(f, "  height        {} -> {} (target {})", self.start_height, self.final_height, self.target_height)?;
+        writeln!(f, "  votes cast    {}", self.votes_cast)?;
+        writeln!(f, "  quorum cert   {}", if self.finalized { "finalized" } else { "missing" })?;
+        writeln!(f, "  leader        {}", if self.leader.is_empty() { "-" } else { &self.leader })?;
+        write!(f, "  elapsed       {} ms", self.elapsed.as_millis())?;
+        if let Some(reason) = &self.failure { write!(f, "\n  failure       {reason}")?; }
+        Ok(())
+    }
+}
+
+/// Run the smoke flow; never panics, failures are reported in the result.
+pub async fn run_smoke(cfg: &SmokeConfig) -> SmokeReport {
+    let started = Instant::now();
+    let mut report = SmokeReport { endpoint: cfg.endpoint.clone(), ..Default::default() };
+    let outcome = tokio::time::timeout(cfg.timeout, drive(cfg, &mut report)).await;
+    report.failure = match outcome {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e),
+        Err(_) => Some(format!("timed out after {:?}", cfg.timeout)),
+    };
+    report.elapsed = started.elapsed();
+    report
+}
+
+async fn drive(cfg: &SmokeConfig, report: &mut SmokeReport) -> Result<(), String> {
+    let mut client: PbftClient<Channel> = PbftClient::connect(cfg.endpoint.clone()).await.map_err(|e| format!("connect: {e}"))?;
+    let mut events = client.subscribe_events(SubscribeEventsRequest {}).await.map_err(|e| format!("subscribe events: {e}"))?.into_inner();
+    let state = client.get_state(ConsensusStateQuery { height: 0 }).await.map_err(|e| format!("get state: {e}"))?.into_inner();
+    report.start_height = state.height;
+    report.target_height = state.height + 1;
+    let (h, id) = (report.target_height, format!("smoke-{}", report.target_height));
+
+    let ack = client.propose(Proposal { id: id.clone(), payload: b"swarm-smoke".to_vec(), height: h, round: 0 }).await.map_err(|e| format!("propose: {e}"))?.into_inner();
+    if !ack.accepted { return Err(format!("proposal rejected: {}", ack.reason)); }
+    for i in 0..cfg.voters {
+        client.cast_vote(Vote { proposal_id: id.clone(), node_id: format!("node-{i}"), height: h, round: 0, vote_type: 0 }).await.map_err(|e| format!("vote node-{i}: {e}"))?;
+        report.votes_cast += 1;
+    }
+
+    while !report.finalized {
+        match events.message().await.map_err(|e| format!("event stream: {e}"))? {
+            Some(ev) if ev.kind == EventKind::Finalized as i32 && ev.height == h => report.finalized = true,
+            Some(_) => {}
+            None => return Err("event stream closed before FINALIZED".into()),
+        }
+    }
+    let state = client.get_state(ConsensusStateQuery { height: 0 }).await.map_err(|e| format!("get state: {e}"))?.into_inner();
+    report.final_height = state.height;
+    report.leader = state.leader;
+    if report.final_height < h { return Err(format!("height did not advance to {h}")); }
+    Ok(())
+}
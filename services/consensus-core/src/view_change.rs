@@ -1,7 +1,17 @@
+//! Timeout-driven view change.
+//!
+//! Every proposed (height,round) gets a start time in `round_starts`; reaching COMMIT quorum removes
+//! it (cancelling the timer). If it is still there after `CONSENSUS_VIEW_TIMEOUT_MS` (default 3000)
+//! the leader is presumed stalled: the round is bumped, a leader re-elected for (height, round+1),
+//! `RoundChanged`/`LeaderChanged` are broadcast to event subscribers and the new round is published
+//! like a finalized one. The new round starts its own timer, so a leader that stays silent keeps
+//! rotating out.
+
 use std::time::{Duration, Instant};
-use std::sync::{Arc, RwLock};
-use crate::{PbftState, PbftService};
-use tracing::{info, warn};
+use once_cell::sync::Lazy;
+use opentelemetry::metrics::Histogram;
+use crate::PbftService;
+use tracing::info;
 
 /// Configuration for view change timeouts.
 pub struct ViewChangeConfig {
@@ -12,47 +22,122 @@ impl Default for ViewChangeConfig {
     fn default() -> Self { Self { round_timeout_ms: 3000 } }
 }
 
+impl ViewChangeConfig {
+    pub fn from_env() -> Self {
+        let round_timeout_ms = std::env::var("CONSENSUS_VIEW_TIMEOUT_MS").or_else(|_| std::env::var("CONSENSUS_ROUND_TIMEOUT_MS")).ok()
+            .and_then(|v| v.parse().ok()).unwrap_or(Self::default().round_timeout_ms);
+        Self { round_timeout_ms }
+    }
+}
+
+static VIEW_CHANGE_AGE: Lazy<Histogram<f64>> = Lazy::new(|| {
+    opentelemetry::global::meter("consensus-core").f64_histogram("consensus_view_change_interval_ms").with_description("Age of the stalled round when its view change fired (ms)").init()
+});
+
 impl PbftService {
     pub fn spawn_view_change_task(&self) {
         let enabled = std::env::var("CONSENSUS_VIEW_CHANGE_ENABLED").ok().map(|v| v=="1" || v.eq_ignore_ascii_case("true"))
             .unwrap_or(true);
         if !enabled { tracing::info!("view change task disabled via CONSENSUS_VIEW_CHANGE_ENABLED"); return; }
-        let timeout: u64 = std::env::var("CONSENSUS_ROUND_TIMEOUT_MS").ok().and_then(|v| v.parse().ok()).unwrap_or(ViewChangeConfig::default().round_timeout_ms);
-        let state = self.state.clone();
-        // Metrics setup
-        let meter = opentelemetry::global::meter("consensus-core");
-        let vc_counter = meter.u64_counter("consensus_view_changes_total").with_description("Total view changes triggered by timeout").init();
-        let vc_hist = meter.f64_histogram("consensus_view_change_interval_ms").with_description("Interval between view changes (ms)").init();
+        let timeout = Duration::from_millis(ViewChangeConfig::from_env().round_timeout_ms.max(1));
+        let svc = self.clone();
         tokio::spawn(async move {
-            let mut last_change = Instant::now();
-            let dur = Duration::from_millis(timeout);
+            let mut tick = tokio::time::interval((timeout / 4).max(Duration::from_millis(10)));
             loop {
-                tokio::time::sleep(dur).await;
-                let (h_before, r_before, leader_before, validators) = {
-                    let st = state.read().unwrap();
-                    (st.height, st.round, st.leader.clone(), st.validators.clone())
-                };
-                let mut changed = false;
-                {
-                    let mut st = state.write().unwrap();
-                    if !validators.is_empty() {
-                        let idx = (st.round + 1) as usize % validators.len();
-                        st.round += 1;
-                        st.leader = validators[idx].clone();
-                        changed = true;
-                    }
-                }
-                if changed {
-                    let st = state.read().unwrap();
-                    let elapsed_ms = last_change.elapsed().as_secs_f64() * 1000.0;
-                    vc_counter.add(1, &[]);
-                    vc_hist.record(elapsed_ms, &[]);
-                    last_change = Instant::now();
-                    info!(height=st.height, round=st.round, leader=%st.leader, interval_ms=elapsed_ms, "view_change_timeout_triggered");
-                } else {
-                    warn!(height=h_before, round=r_before, leader=%leader_before, "view_change_timeout_noop");
-                }
+                tick.tick().await;
+                svc.check_view_timeout(Instant::now(), timeout);
             }
         });
     }
+
+    /// Move to the next round if the current (height,round) has waited longer than `timeout`
+    /// for quorum. Returns the new (height,round) when a view change happened.
+    pub(crate) fn check_view_timeout(&self, now: Instant, timeout: Duration) -> Option<(u64, u64)> {
+        let (h, r) = { let st = self.state_read(); (st.height, st.round) };
+        let started = {
+            let mut starts = self.round_starts_write();
+            match starts.get(&(h, r)) {
+                Some(s) if now.saturating_duration_since(*s) >= timeout => starts.remove(&(h, r)),
+                _ => None,
+            }
+        }?;
+        {
+            let mut st = self.state_write();
+            if (st.height, st.round) != (h, r) { return None; } // moved on concurrently
+            st.round = r + 1;
+        }
+        self.close_round(h, r);
+        self.elect_leader(h, r + 1);
+        self.emit(crate::event(crate::EventKind::RoundChanged, h, r + 1));
+        self.publish_round_changed(h, r + 1);
+        self.round_starts_write().insert((h, r + 1), now);
+        let stalled_ms = now.saturating_duration_since(started).as_secs_f64() * 1000.0;
+        swarm_core::EXTENDED_METRICS.consensus.view_changes_total.add(1, &[]);
+        VIEW_CHANGE_AGE.record(stalled_ms, &[]);
+        info!(height=h, round=r + 1, leader=%self.snapshot().leader, stalled_ms, "view_change_timeout_triggered");
+        Some((h, r + 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use swarm_core::VrfProof;
+    use swarm_proto::consensus::{pbft_server::Pbft, Proposal, Vote};
+    use tonic::Request;
+    use crate::publish::RoundPublisher;
+
+    #[derive(Default)]
+    struct RecordingPublisher { rounds: Mutex<Vec<(u64, u64, String, Option<VrfProof>)>> }
+
+    impl RoundPublisher for RecordingPublisher {
+        fn height_changed(&self, _height: u64, _round: u64) {}
+        fn round_changed(&self, height: u64, round: u64, leader: String, leader_proof: Option<VrfProof>) {
+            self.rounds.lock().unwrap().push((height, round, leader, leader_proof));
+        }
+    }
+
+    fn fresh_height() -> u64 { 2_000_000_000 + std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos() as u64 % 1_000_000_000 }
+
+    #[tokio::test]
+    async fn stalled_round_triggers_one_view_change() {
+        let h = fresh_height();
+        let beacon = format!("node-{}", (h + 1) % 4);
+        let svc = PbftService::with_vrf_keys(crate::vrf_keys::test_keys(&beacon));
+        let mut events = svc.events.subscribe();
+        svc.propose(Request::new(Proposal { id: "stall".into(), payload: vec![], height: h, round: 0 })).await.unwrap();
+        let timeout = Duration::from_millis(3000);
+        let now = Instant::now();
+        assert_eq!(svc.check_view_timeout(now, timeout), None, "not stalled yet");
+        let late = now + timeout + Duration::from_millis(1);
+        assert_eq!(svc.check_view_timeout(late, timeout), Some((h, 1)));
+        assert_eq!(svc.check_view_timeout(late, timeout), None, "new round starts its own timer");
+        let snap = svc.snapshot();
+        assert_eq!((snap.height, snap.round), (h, 1));
+        assert_eq!(Some(snap.leader), crate::weighted_leader(h, 1, &crate::vrf_keys::test_secret(&beacon), &snap.validators, &snap.stakes));
+        let mut round_changes = 0;
+        while let Ok(ev) = events.try_recv() { if ev.kind == crate::EventKind::RoundChanged as i32 && ev.height == h { round_changes += 1; assert_eq!(ev.round, 1); } }
+        assert_eq!(round_changes, 1);
+
+        // quorum in round 1 cancels its timer
+        for n in 0..3 { svc.cast_vote(Request::new(Vote { proposal_id: "stall".into(), node_id: format!("node-{n}"), height: h, round: 1, vote_type: 0 })).await.unwrap(); }
+        assert_eq!(svc.check_view_timeout(late + timeout * 2, timeout), None);
+        assert_eq!(svc.snapshot().round, 1);
+    }
+
+    #[tokio::test]
+    async fn view_change_publishes_the_new_round() {
+        let h = fresh_height();
+        let beacon = format!("node-{}", (h + 1) % 4);
+        let publisher = Arc::new(RecordingPublisher::default());
+        let svc = PbftService::with_publisher(crate::vrf_keys::test_keys(&beacon), publisher.clone());
+        svc.propose(Request::new(Proposal { id: "publish".into(), payload: vec![], height: h, round: 0 })).await.unwrap();
+        let timeout = Duration::from_millis(3000);
+        assert_eq!(svc.check_view_timeout(Instant::now() + timeout, timeout), Some((h, 1)));
+        let snap = svc.snapshot();
+        let published = publisher.rounds.lock().unwrap().clone();
+        assert_eq!(published, vec![(h, 1, snap.leader, snap.last_leader_proof)]);
+        assert!(published[0].3.is_some(), "the beacon's proof travels with the announcement");
+    }
 }
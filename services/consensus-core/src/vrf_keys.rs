@@ -0,0 +1,72 @@
+//! Per-validator VRF keys for leader election.
+//!
+//! The leader of (height,round) is drawn from the VRF output of that round's beacon validator
+//! (`leader_beacon`, round-robin over the set), proved with the beacon's own secret key. The node's
+//! id comes from `CONSENSUS_NODE_ID`, its secret seed (hex, 32 bytes) from the file named by
+//! `CONSENSUS_VRF_KEY`, and validators' public keys from `CONSENSUS_VRF_PUBLIC_KEYS` (JSON
+//! `{"node_id": "pubkey hex"}`). A proof from another beacon is accepted only if it verifies
+//! against that beacon's registered key.
+
+use std::collections::HashMap;
+use anyhow::{anyhow, Context, Result};
+use swarm_core::VrfKeypair;
+
+#[derive(Default)]
+pub struct VrfKeys {
+    node_id: Option<String>,
+    keypair: Option<VrfKeypair>,
+    public: HashMap<String, [u8; 32]>,
+}
+
+fn decode_32(hex_key: &str) -> Option<[u8; 32]> { hex::decode(hex_key.trim()).ok()?.try_into().ok() }
+
+impl VrfKeys {
+    /// `keypair` belongs to `node_id`; its public half is registered alongside `public`.
+    pub fn new(node_id: Option<String>, keypair: Option<VrfKeypair>, mut public: HashMap<String, [u8; 32]>) -> Self {
+        if let (Some(id), Some(kp)) = (&node_id, &keypair) { public.insert(id.clone(), kp.public); }
+        Self { node_id, keypair, public }
+    }
+
+    /// Load keys from `CONSENSUS_NODE_ID` / `CONSENSUS_VRF_KEY` / `CONSENSUS_VRF_PUBLIC_KEYS`.
+    pub fn from_env() -> Result<Self> {
+        let node_id = std::env::var("CONSENSUS_NODE_ID").ok();
+        let keypair = match std::env::var("CONSENSUS_VRF_KEY") {
+            Ok(path) => {
+                let hex_key = std::fs::read_to_string(&path).with_context(|| format!("read CONSENSUS_VRF_KEY {path}"))?;
+                let seed = decode_32(&hex_key).ok_or_else(|| anyhow!("invalid VRF key in {path}: expected 32 hex-encoded bytes"))?;
+                if node_id.is_none() { return Err(anyhow!("CONSENSUS_VRF_KEY set without CONSENSUS_NODE_ID")); }
+                Some(VrfKeypair::from_seed(seed))
+            }
+            Err(_) => None,
+        };
+        let mut public = HashMap::new();
+        if let Ok(path) = std::env::var("CONSENSUS_VRF_PUBLIC_KEYS") {
+            let raw: HashMap<String, String> = serde_json::from_str(&std::fs::read_to_string(&path).with_context(|| format!("read CONSENSUS_VRF_PUBLIC_KEYS {path}"))?)?;
+            for (node, pk) in raw {
+                public.insert(node.clone(), decode_32(&pk).ok_or_else(|| anyhow!("invalid VRF public key for validator {node}"))?);
+            }
+        }
+        Ok(Self::new(node_id, keypair, public))
+    }
+
+    pub fn node_id(&self) -> Option<&str> { self.node_id.as_deref() }
+
+    /// Registered public key of `validator`.
+    pub fn public_key(&self, validator: &str) -> Option<&[u8; 32]> { self.public.get(validator) }
+
+    /// Our secret seed, if we are `validator`.
+    pub(crate) fn secret_for(&self, validator: &str) -> Option<&[u8; 32]> {
+        self.keypair.as_ref().filter(|_| self.node_id.as_deref() == Some(validator)).map(|kp| &kp.secret)
+    }
+}
+
+/// Secret seed the test keys assign to `node`.
+#[cfg(test)]
+pub(crate) fn test_secret(node: &str) -> [u8; 32] { let mut seed = [0u8; 32]; seed[..node.len()].copy_from_slice(node.as_bytes()); seed }
+
+/// Keys for `node-0..node-3` (seeded by `test_secret`), held as validator `me`.
+#[cfg(test)]
+pub(crate) fn test_keys(me: &str) -> VrfKeys {
+    let public = (0..4).map(|i| format!("node-{i}")).map(|n| { let pk = VrfKeypair::from_seed(test_secret(&n)).public; (n, pk) }).collect();
+    VrfKeys::new(Some(me.to_string()), Some(VrfKeypair::from_seed(test_secret(me))), public)
+}
@@ -0,0 +1,43 @@
+// In-process gRPC test for the SubscribeEvents server stream (no NATS needed).
+
+use consensus_core::PbftService;
+use swarm_proto::consensus::{pbft_client::PbftClient, pbft_server::{Pbft, PbftServer}, Proposal, Vote, SubscribeEventsRequest, consensus_event::Kind};
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::Request;
+use std::time::Duration;
+
+#[tokio::test]
+async fn subscribe_events_streams_round_in_order() {
+    std::env::set_var("CONSENSUS_VIEW_CHANGE_ENABLED", "0"); // no timer-driven round changes mid-test
+    std::env::set_var("SWARM_DATA_DIR", std::env::temp_dir().join(format!("consensus-events-{}", std::process::id())));
+    let svc = PbftService::new();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = svc.clone();
+    tokio::spawn(async move {
+        tonic::transport::Server::builder().add_service(PbftServer::new(server)).serve_with_incoming(TcpListenerStream::new(listener)).await.unwrap();
+    });
+
+    let mut client = PbftClient::connect(format!("http://{addr}")).await.unwrap();
+    let mut stream = client.subscribe_events(SubscribeEventsRequest {}).await.unwrap().into_inner();
+
+    // drive one round to quorum, then move to the next height
+    svc.propose(Request::new(Proposal { id: "p1".into(), payload: vec![], height: 1, round: 0 })).await.unwrap();
+    for n in ["node-0", "node-1", "node-2", "node-3"] {
+        svc.cast_vote(Request::new(Vote { proposal_id: "p1".into(), node_id: n.into(), height: 1, round: 0, vote_type: 0 })).await.unwrap();
+    }
+    svc.propose(Request::new(Proposal { id: "p2".into(), payload: vec![], height: 2, round: 0 })).await.unwrap();
+
+    let mut seen = Vec::new();
+    while seen.len() < 3 {
+        let ev = tokio::time::timeout(Duration::from_secs(5), stream.message()).await.expect("event timeout").unwrap().expect("stream open");
+        let kind = Kind::try_from(ev.kind).unwrap();
+        if kind == Kind::LeaderChanged {
+            assert!(ev.leader.starts_with("node-") && ev.leader_proof.len() == 80);
+            continue;
+        }
+        seen.push((kind, ev.height));
+    }
+    // one FINALIZED despite the fourth (post-quorum) vote
+    assert_eq!(seen, vec![(Kind::HeightChanged, 1), (Kind::Finalized, 1), (Kind::HeightChanged, 2)]);
+}
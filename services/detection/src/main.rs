@@ -26,5 +26,6 @@ async fn main() -> Result<()> {
 
     signal::ctrl_c().await?;
     info!("shutdown");
+    swarm_core::shutdown_observability();
     Ok(())
 }
@@ -6,18 +6,29 @@ use opentelemetry::metrics::Unit;
 use tonic::{transport::Server, Request, Response, Status};
 use swarm_proto::common::health_server::{Health, HealthServer};
 use swarm_proto::common::HealthCheckResponse;
-use std::collections::HashMap;
+use swarm_proto::identity::{certificate_authority_server::{CertificateAuthority, CertificateAuthorityServer}, CertStatusRequest, CertStatusResponse, cert_status_response::Status as ProtoCertStatus};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 use tokio::sync::RwLock;
+use serde::{Deserialize, Serialize};
+pub mod pqc;
+pub mod store;
+use store::CertStore;
 
 // Certificate Authority service with PQC support
+#[derive(Clone)]
 pub struct IdentityCAService {
     certificates: Arc<RwLock<HashMap<String, Certificate>>>,
-    crl: Arc<RwLock<Vec<String>>>, // Certificate Revocation List
-    ca_keypair: Option<Vec<u8>>,
+    crl: Arc<RwLock<Vec<(String, u64)>>>, // Certificate Revocation List: (cert id, revoked at unix secs)
+    ca_keypair: Option<(Vec<u8>, Vec<u8>)>, // Dilithium3 (public, secret)
+    expired: Arc<RwLock<HashSet<String>>>, // moved out of `certificates` by the expiry sweep
+    store: Option<Arc<CertStore>>, // None = in-memory only
+    validity_secs: u64,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Certificate {
     pub id: String,
     pub subject: String,
@@ -28,19 +39,79 @@ pub struct Certificate {
     pub cert_type: CertificateType,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CertificateType {
     Node,
     Service,
     User,
 }
 
+impl Certificate {
+    /// Canonical to-be-signed bytes: every field except the signature, length-prefixed.
+    pub fn tbs_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for field in [self.id.as_bytes(), self.subject.as_bytes(), &self.public_key] {
+            out.extend_from_slice(&(field.len() as u32).to_be_bytes());
+            out.extend_from_slice(field);
+        }
+        out.extend_from_slice(&self.not_before.to_be_bytes());
+        out.extend_from_slice(&self.not_after.to_be_bytes());
+        out.push(match self.cert_type { CertificateType::Node => 0, CertificateType::Service => 1, CertificateType::User => 2 });
+        out
+    }
+}
+
 impl IdentityCAService {
+    /// Opens the store at `store::db_path()`; if that fails the CA runs in memory only.
     pub fn new() -> Self {
+        let store = store::db_path().and_then(|p| CertStore::open(&p));
+        if let Err(e) = &store { warn!(error=?e, "identity-ca store unavailable - running ephemeral"); }
+        Self::with_store(store.ok())
+    }
+
+    /// Load certificates, CRL and the CA key from `store`, generating (and persisting) the key on first start.
+    /// If a stored key cannot be loaded the CA comes up without one instead of re-keying.
+    pub fn with_store(store: Option<CertStore>) -> Self {
+        let mut certificates = HashMap::new();
+        let mut crl = Vec::new();
+        let mut expired = HashSet::new();
+        let mut ca_keypair = None;
+        let mut key_failed = false;
+        if let Some(s) = &store {
+            match s.load_expired() { Ok(ids) => expired.extend(ids), Err(e) => warn!(error=?e, "failed to load expired set") }
+            match s.load_certificates() { Ok(certs) => certificates.extend(certs.into_iter().map(|c| (c.id.clone(), c))), Err(e) => warn!(error=?e, "failed to load certificates") }
+            match s.load_crl() { Ok(entries) => crl = entries, Err(e) => warn!(error=?e, "failed to load CRL") }
+            ca_keypair = s.ca_keypair().unwrap_or_else(|e| { warn!(error=?e, "failed to load CA key"); key_failed = true; None });
+        }
+        if ca_keypair.is_none() && !key_failed {
+            ca_keypair = pqc::generate_dilithium_keypair().ok();
+            if let (Some(s), Some((pk, sk))) = (&store, &ca_keypair) {
+                if let Err(e) = s.put_ca_keypair(pk, sk) { warn!(error=?e, "failed to persist CA key"); }
+            }
+        }
+        info!(certificates = certificates.len(), revoked = crl.len(), persistent = store.is_some(), "identity-ca state loaded");
         Self {
-            certificates: Arc::new(RwLock::new(HashMap::new())),
-            crl: Arc::new(RwLock::new(Vec::new())),
-            ca_keypair: None,
+            certificates: Arc::new(RwLock::new(certificates)),
+            crl: Arc::new(RwLock::new(crl)),
+            ca_keypair,
+            expired: Arc::new(RwLock::new(expired)),
+            store: store.map(Arc::new),
+            validity_secs: std::env::var("IDENTITY_CA_CERT_VALIDITY_SECS").ok().and_then(|v| v.parse().ok()).unwrap_or(365 * 24 * 60 * 60),
+        }
+    }
+
+    /// Override the validity period of newly issued certificates.
+    pub fn with_validity(mut self, validity: Duration) -> Self { self.validity_secs = validity.as_secs(); self }
+
+    /// CA Dilithium3 public key for out-of-band certificate verification.
+    pub fn ca_public_key(&self) -> Option<&[u8]> { self.ca_keypair.as_ref().map(|(pk, _)| pk.as_slice()) }
+
+    /// True when `cert` carries a valid CA signature over its fields.
+    pub fn verify_signature(&self, cert: &Certificate) -> bool {
+        match &self.ca_keypair {
+            Some((pk, _)) => pqc::dilithium_verify(&cert.tbs_bytes(), &cert.signature, pk).unwrap_or(false),
+            None => false,
         }
     }
 
@@ -59,17 +130,20 @@ impl IdentityCAService {
             .duration_since(std::time::UNIX_EPOCH)?
             .as_secs();
         
-        let cert = Certificate {
+        let (_, ca_secret) = self.ca_keypair.as_ref().ok_or_else(|| anyhow::anyhow!("CA signing key unavailable"))?;
+        let mut cert = Certificate {
             id: cert_id.clone(),
             subject: csr.subject.clone(),
             public_key: csr.public_key.clone(),
             not_before: now,
-            not_after: now + (365 * 24 * 60 * 60), // 1 year
-            signature: vec![0; 64], // TODO: Real signature
+            not_after: now + self.validity_secs, // default 1 year
+            signature: Vec::new(),
             cert_type: csr.cert_type.clone(),
         };
+        cert.signature = pqc::dilithium_sign(&cert.tbs_bytes(), ca_secret)?;
         
-        // Store certificate
+        // Store certificate (durably first)
+        if let Some(store) = &self.store { store.put_certificate(&cert)?; }
         let mut certs = self.certificates.write().await;
         certs.insert(cert_id.clone(), cert.clone());
         
@@ -80,39 +154,96 @@ impl IdentityCAService {
 
     pub async fn revoke_certificate(&self, cert_id: &str) -> Result<()> {
         let mut certs = self.certificates.write().await;
-        if certs.remove(cert_id).is_some() {
+        if certs.contains_key(cert_id) {
+            // revocation must be on disk before it is acknowledged
+            let revoked_at = unix_now();
+            if let Some(store) = &self.store { store.revoke(cert_id, revoked_at)?; }
+            certs.remove(cert_id);
             let mut crl = self.crl.write().await;
-            crl.push(cert_id.to_string());
+            crl.push((cert_id.to_string(), revoked_at));
             info!("Certificate revoked: {}", cert_id);
         }
         Ok(())
     }
 
     pub async fn verify_certificate(&self, cert_id: &str) -> Result<bool> {
-        // Check if revoked
-        let crl = self.crl.read().await;
-        if crl.contains(&cert_id.to_string()) {
-            return Ok(false);
+        Ok(matches!(self.cert_status(cert_id).await, CertStatus::Good { .. }))
+    }
+
+    /// Revocation list first, then existence, CA signature and validity window.
+    pub async fn cert_status(&self, cert_id: &str) -> CertStatus {
+        if let Some((_, revoked_at)) = self.crl.read().await.iter().find(|(id, _)| id == cert_id) {
+            return CertStatus::Revoked { revoked_at: *revoked_at };
         }
-        
-        // Check if exists and valid
         let certs = self.certificates.read().await;
-        if let Some(cert) = certs.get(cert_id) {
-            let now = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)?
-                .as_secs();
-            
-            return Ok(now >= cert.not_before && now <= cert.not_after);
+        let Some(cert) = certs.get(cert_id) else { return CertStatus::Unknown };
+        if !self.verify_signature(cert) {
+            warn!(cert_id, "certificate signature invalid");
+            return CertStatus::Unknown;
         }
-        
-        Ok(false)
+        let now = unix_now();
+        if now >= cert.not_before && now <= cert.not_after { CertStatus::Good { not_after: cert.not_after } } else { CertStatus::Unknown }
     }
 
     pub async fn get_crl(&self) -> Vec<String> {
-        self.crl.read().await.clone()
+        self.crl.read().await.iter().map(|(id, _)| id.clone()).collect()
+    }
+
+    /// Ids of active certificates whose `not_after` falls within `window` from now.
+    pub async fn expiring_within(&self, window: Duration) -> Vec<String> {
+        let now = unix_now();
+        let horizon = now.saturating_add(window.as_secs());
+        let mut ids: Vec<String> = self.certificates.read().await.values().filter(|c| c.not_after <= horizon).map(|c| c.id.clone()).collect();
+        ids.sort();
+        ids
+    }
+
+    pub async fn is_expired(&self, cert_id: &str) -> bool { self.expired.read().await.contains(cert_id) }
+
+    /// Move certificates past `not_after` (as of `now`, unix secs) into the expired set and
+    /// refresh the PKI gauges. Returns the ids moved.
+    pub async fn sweep_expired_at(&self, now: u64) -> Result<Vec<String>> {
+        let mut certs = self.certificates.write().await;
+        let mut moved: Vec<String> = certs.values().filter(|c| c.not_after < now).map(|c| c.id.clone()).collect();
+        moved.sort();
+        if !moved.is_empty() {
+            let mut expired = self.expired.write().await;
+            for id in &moved {
+                if let Some(store) = &self.store { store.expire(id)?; }
+                certs.remove(id);
+                expired.insert(id.clone());
+            }
+            info!(count = moved.len(), "expired certificates swept");
+        }
+        ACTIVE_CERTS.store(certs.len() as u64, Ordering::Relaxed);
+        EXPIRING_24H.store(certs.values().filter(|c| c.not_after <= now + 24 * 60 * 60).count() as u64, Ordering::Relaxed);
+        Ok(moved)
+    }
+
+    pub async fn sweep_expired(&self) -> Result<Vec<String>> { self.sweep_expired_at(unix_now()).await }
+
+    /// Periodic expiry sweep (`IDENTITY_CA_SWEEP_SECS`, default 60).
+    pub fn spawn_expiry_sweep(&self) -> tokio::task::JoinHandle<()> {
+        let ca = self.clone();
+        let every = std::env::var("IDENTITY_CA_SWEEP_SECS").ok().and_then(|v| v.parse().ok()).unwrap_or(60u64).max(1);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(every));
+            loop {
+                ticker.tick().await;
+                if let Err(e) = ca.sweep_expired().await { warn!(error=?e, "certificate expiry sweep failed"); }
+            }
+        })
     }
 }
 
+/// OCSP-style status of a certificate id.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CertStatus {
+    Good { not_after: u64 },
+    Revoked { revoked_at: u64 },
+    Unknown, // never issued, expired, not yet valid or failing signature verification
+}
+
 #[derive(Clone, Debug)]
 pub struct CertificateSigningRequest {
     pub subject: String,
@@ -133,55 +264,15 @@ impl Health for IdentityCAService {
     }
 }
 
-// Post-Quantum Cryptography module (placeholder)
-pub mod pqc {
-    use anyhow::Result;
-    
-    /// Generate Kyber768 keypair for key encapsulation
-    pub fn generate_kyber_keypair() -> Result<(Vec<u8>, Vec<u8>)> {
-        // TODO: Implement actual Kyber768 key generation
-        // For now, return placeholder
-        let public_key = vec![0u8; 1184]; // Kyber768 public key size
-        let secret_key = vec![0u8; 2400]; // Kyber768 secret key size
-        Ok((public_key, secret_key))
-    }
-    
-    /// Generate Dilithium3 keypair for digital signatures
-    pub fn generate_dilithium_keypair() -> Result<(Vec<u8>, Vec<u8>)> {
-        // TODO: Implement actual Dilithium3 key generation
-        let public_key = vec![0u8; 1952]; // Dilithium3 public key size
-        let secret_key = vec![0u8; 4000]; // Dilithium3 secret key size
-        Ok((public_key, secret_key))
-    }
-    
-    /// Sign data with Dilithium
-    pub fn dilithium_sign(data: &[u8], secret_key: &[u8]) -> Result<Vec<u8>> {
-        // TODO: Implement actual Dilithium signature
-        let _ = secret_key;
-        Ok(vec![0u8; 3293]) // Dilithium3 signature size
-    }
-    
-    /// Verify Dilithium signature
-    pub fn dilithium_verify(data: &[u8], signature: &[u8], public_key: &[u8]) -> Result<bool> {
-        // TODO: Implement actual verification
-        let _ = (data, signature, public_key);
-        Ok(true)
-    }
-    
-    /// Kyber key encapsulation
-    pub fn kyber_encapsulate(public_key: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
-        // TODO: Implement actual Kyber encapsulation
-        let _ = public_key;
-        let ciphertext = vec![0u8; 1088]; // Kyber768 ciphertext size
-        let shared_secret = vec![0u8; 32]; // Shared secret size
-        Ok((ciphertext, shared_secret))
-    }
-    
-    /// Kyber key decapsulation
-    pub fn kyber_decapsulate(ciphertext: &[u8], secret_key: &[u8]) -> Result<Vec<u8>> {
-        // TODO: Implement actual Kyber decapsulation
-        let _ = (ciphertext, secret_key);
-        Ok(vec![0u8; 32]) // Shared secret
+#[tonic::async_trait]
+impl CertificateAuthority for IdentityCAService {
+    async fn check_status(&self, request: Request<CertStatusRequest>) -> Result<Response<CertStatusResponse>, Status> {
+        let resp = match self.cert_status(&request.into_inner().cert_id).await {
+            CertStatus::Good { not_after } => CertStatusResponse { status: ProtoCertStatus::Good as i32, revoked_at: 0, not_after },
+            CertStatus::Revoked { revoked_at } => CertStatusResponse { status: ProtoCertStatus::Revoked as i32, revoked_at, not_after: 0 },
+            CertStatus::Unknown => CertStatusResponse { status: ProtoCertStatus::Unknown as i32, revoked_at: 0, not_after: 0 },
+        };
+        Ok(Response::new(resp))
     }
 }
 
@@ -196,19 +287,22 @@ async fn main() -> Result<()> {
     // Initialize CA service
     let ca_service = IdentityCAService::new();
     
-    // Generate root CA keypair (PQC)
-    let (kyber_pk, kyber_sk) = pqc::generate_kyber_keypair()?;
-    let (dilithium_pk, dilithium_sk) = pqc::generate_dilithium_keypair()?;
+    // Root CA signing key (Dilithium3) is generated by the service; Kyber768 for key exchange
+    let dilithium_pk = ca_service.ca_public_key().ok_or_else(|| anyhow::anyhow!("CA Dilithium3 key unavailable"))?;
+    let (kyber_pk, _kyber_sk) = pqc::generate_kyber_keypair()?;
     
     info!("Generated PQC keypairs:");
     info!("  Kyber768 public key: {} bytes", kyber_pk.len());
     info!("  Dilithium3 public key: {} bytes", dilithium_pk.len());
     
+    ca_service.spawn_expiry_sweep();
+    
     // Start gRPC server
     let addr = "[::]:50052".parse()?;
     info!("Identity CA gRPC server listening on {}", addr);
     
     Server::builder()
+        .add_service(CertificateAuthorityServer::new(ca_service.clone()))
         .add_service(HealthServer::new(ca_service))
         .serve(addr)
         .await?;
@@ -226,7 +320,25 @@ static RECORD_ISSUE_LATENCY: Lazy<Histogram<f64>> = Lazy::new(|| {
         .init()
 });
 
-fn init_metrics_once() { Lazy::force(&RECORD_ISSUE_LATENCY); }
+static ACTIVE_CERTS: AtomicU64 = AtomicU64::new(0);
+static EXPIRING_24H: AtomicU64 = AtomicU64::new(0);
+static PKI_GAUGES: Lazy<()> = Lazy::new(|| {
+    let meter = otel_global::meter("identity-ca");
+    let _active = meter.u64_observable_gauge("swarm_pki_active_certs")
+        .with_description("Issued, unrevoked, unexpired certificates")
+        .with_callback(|obs| obs.observe(ACTIVE_CERTS.load(Ordering::Relaxed), &[]))
+        .init();
+    let _expiring = meter.u64_observable_gauge("swarm_pki_expiring_24h")
+        .with_description("Active certificates expiring within 24h")
+        .with_callback(|obs| obs.observe(EXPIRING_24H.load(Ordering::Relaxed), &[]))
+        .init();
+});
+
+fn init_metrics_once() { Lazy::force(&RECORD_ISSUE_LATENCY); Lazy::force(&PKI_GAUGES); }
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
 
 fn init_tracing() -> Result<()> {
     tracing_subscriber::fmt()
@@ -234,3 +346,119 @@ fn init_tracing() -> Result<()> {
         .try_init()
         .map_err(|e| anyhow::anyhow!("Failed to initialize tracing: {}", e))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn csr(subject: &str) -> CertificateSigningRequest {
+        CertificateSigningRequest { subject: subject.into(), public_key: vec![7; 32], cert_type: CertificateType::Node }
+    }
+
+    #[tokio::test]
+    async fn issued_certificate_signature_verifies() {
+        let ca = IdentityCAService::with_store(None);
+        let cert = ca.issue_certificate(&csr("node-1")).await.unwrap();
+        assert!(ca.verify_signature(&cert));
+        assert!(ca.verify_certificate(&cert.id).await.unwrap());
+        assert!(pqc::dilithium_verify(&cert.tbs_bytes(), &cert.signature, ca.ca_public_key().unwrap()).unwrap());
+    }
+
+    #[tokio::test]
+    async fn tampered_certificate_fails_verification() {
+        let ca = IdentityCAService::with_store(None);
+        let cert = ca.issue_certificate(&csr("node-1")).await.unwrap();
+        ca.certificates.write().await.get_mut(&cert.id).unwrap().subject = "node-evil".into();
+        assert!(!ca.verify_certificate(&cert.id).await.unwrap());
+        // a certificate signed by a different CA is rejected as well
+        let other = IdentityCAService::with_store(None).issue_certificate(&csr("node-1")).await.unwrap();
+        assert!(!ca.verify_signature(&other));
+    }
+
+    #[tokio::test]
+    async fn certificates_and_revocations_survive_restart() {
+        let dir = std::env::temp_dir().join(format!("identity-ca-store-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (kept, revoked) = {
+            let ca = IdentityCAService::with_store(Some(CertStore::open(&dir).unwrap()));
+            let kept = ca.issue_certificate(&csr("node-1")).await.unwrap();
+            let revoked = ca.issue_certificate(&csr("node-2")).await.unwrap();
+            ca.revoke_certificate(&revoked.id).await.unwrap();
+            (kept, revoked)
+        };
+        let ca = IdentityCAService::with_store(Some(CertStore::open(&dir).unwrap()));
+        assert!(ca.verify_certificate(&kept.id).await.unwrap());
+        assert_eq!(ca.certificates.read().await[&kept.id].subject, "node-1");
+        assert!(!ca.verify_certificate(&revoked.id).await.unwrap());
+        assert_eq!(ca.get_crl().await, vec![revoked.id]);
+        assert!(ca.issue_certificate(&csr("node-3")).await.is_ok(), "signing key reloaded from the key file");
+        drop(ca);
+        let _ = std::fs::remove_dir_all(&dir);
+        let _ = std::fs::remove_file(dir.with_extension("key"));
+    }
+
+    #[tokio::test]
+    async fn ca_secret_key_is_kept_out_of_the_store() {
+        let dir = std::env::temp_dir().join(format!("identity-ca-key-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let key_file = dir.with_extension("key");
+        let _ = std::fs::remove_file(&key_file);
+        let secret = {
+            let ca = IdentityCAService::with_store(Some(CertStore::open(&dir).unwrap()));
+            ca.ca_keypair.clone().unwrap().1
+        };
+        assert_eq!(std::fs::read(&key_file).unwrap(), secret);
+        #[cfg(unix)]
+        assert_eq!(std::os::unix::fs::PermissionsExt::mode(&std::fs::metadata(&key_file).unwrap().permissions()) & 0o777, 0o600);
+        let db = sled::open(&dir).unwrap();
+        let leaked = db.tree_names().into_iter().flat_map(|t| db.open_tree(t).unwrap().iter().values().collect::<Vec<_>>())
+            .any(|v| v.unwrap().windows(secret.len()).any(|w| w == secret.as_slice()));
+        assert!(!leaked, "CA secret key found in the sled store");
+        drop(db);
+
+        std::fs::remove_file(&key_file).unwrap();
+        let ca = IdentityCAService::with_store(Some(CertStore::open(&dir).unwrap()));
+        assert!(ca.ca_public_key().is_none(), "a missing key file must not silently re-key the CA");
+        drop(ca);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn short_lived_certificate_is_reported_then_swept() {
+        let ca = IdentityCAService::with_store(None).with_validity(Duration::from_secs(60));
+        let cert = ca.issue_certificate(&csr("node-short")).await.unwrap();
+        assert_eq!(ca.expiring_within(Duration::from_secs(24 * 60 * 60)).await, vec![cert.id.clone()]);
+        assert!(ca.expiring_within(Duration::from_secs(10)).await.is_empty());
+        assert!(ca.sweep_expired().await.unwrap().is_empty());
+        assert_eq!(ca.sweep_expired_at(cert.not_after + 1).await.unwrap(), vec![cert.id.clone()]);
+        assert!(ca.is_expired(&cert.id).await);
+        assert!(ca.expiring_within(Duration::from_secs(24 * 60 * 60)).await.is_empty());
+        assert!(!ca.verify_certificate(&cert.id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn check_status_over_grpc() {
+        use swarm_proto::identity::certificate_authority_client::CertificateAuthorityClient;
+        let ca = IdentityCAService::with_store(None);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = ca.clone();
+        tokio::spawn(async move {
+            Server::builder().add_service(CertificateAuthorityServer::new(server)).serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)).await.unwrap();
+        });
+        let mut client = CertificateAuthorityClient::connect(format!("http://{addr}")).await.unwrap();
+
+        let good = ca.issue_certificate(&csr("node-good")).await.unwrap();
+        let revoked = ca.issue_certificate(&csr("node-revoked")).await.unwrap();
+        let before = unix_now();
+        ca.revoke_certificate(&revoked.id).await.unwrap();
+
+        let r = client.check_status(CertStatusRequest { cert_id: good.id.clone() }).await.unwrap().into_inner();
+        assert_eq!((r.status(), r.not_after), (ProtoCertStatus::Good, good.not_after));
+        let r = client.check_status(CertStatusRequest { cert_id: revoked.id.clone() }).await.unwrap().into_inner();
+        assert_eq!(r.status(), ProtoCertStatus::Revoked);
+        assert!(r.revoked_at >= before && r.revoked_at <= unix_now());
+        let r = client.check_status(CertStatusRequest { cert_id: "no-such-cert".into() }).await.unwrap().into_inner();
+        assert_eq!(r.status(), ProtoCertStatus::Unknown);
+    }
+}
@@ -0,0 +1,71 @@
+//! Post-Quantum Cryptography: Kyber768 (KEM) and Dilithium3 (signatures) via `pqcrypto`.
+//! Keys, ciphertexts and signatures travel as raw bytes so callers stay crate-agnostic.
+
+use anyhow::{anyhow, Result};
+use pqcrypto_dilithium::dilithium3;
+use pqcrypto_kyber::kyber768;
+use pqcrypto_traits::kem::{Ciphertext as _, PublicKey as _, SecretKey as _, SharedSecret as _};
+use pqcrypto_traits::sign::{DetachedSignature as _, PublicKey as _, SecretKey as _};
+
+/// Generate Kyber768 keypair for key encapsulation: (public, secret)
+pub fn generate_kyber_keypair() -> Result<(Vec<u8>, Vec<u8>)> {
+    let (pk, sk) = kyber768::keypair();
+    Ok((pk.as_bytes().to_vec(), sk.as_bytes().to_vec()))
+}
+
+/// Generate Dilithium3 keypair for digital signatures: (public, secret)
+pub fn generate_dilithium_keypair() -> Result<(Vec<u8>, Vec<u8>)> {
+    let (pk, sk) = dilithium3::keypair();
+    Ok((pk.as_bytes().to_vec(), sk.as_bytes().to_vec()))
+}
+
+/// Sign data with Dilithium (detached signature)
+pub fn dilithium_sign(data: &[u8], secret_key: &[u8]) -> Result<Vec<u8>> {
+    let sk = dilithium3::SecretKey::from_bytes(secret_key).map_err(|e| anyhow!("invalid Dilithium3 secret key: {e}"))?;
+    Ok(dilithium3::detached_sign(data, &sk).as_bytes().to_vec())
+}
+
+/// Verify Dilithium signature. Malformed keys are an error; a bad signature is `Ok(false)`.
+pub fn dilithium_verify(data: &[u8], signature: &[u8], public_key: &[u8]) -> Result<bool> {
+    let pk = dilithium3::PublicKey::from_bytes(public_key).map_err(|e| anyhow!("invalid Dilithium3 public key: {e}"))?;
+    let Ok(sig) = dilithium3::DetachedSignature::from_bytes(signature) else { return Ok(false) };
+    Ok(dilithium3::verify_detached_signature(&sig, data, &pk).is_ok())
+}
+
+/// Kyber key encapsulation: (ciphertext, shared secret)
+pub fn kyber_encapsulate(public_key: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+    let pk = kyber768::PublicKey::from_bytes(public_key).map_err(|e| anyhow!("invalid Kyber768 public key: {e}"))?;
+    let (ss, ct) = kyber768::encapsulate(&pk);
+    Ok((ct.as_bytes().to_vec(), ss.as_bytes().to_vec()))
+}
+
+/// Kyber key decapsulation: shared secret
+pub fn kyber_decapsulate(ciphertext: &[u8], secret_key: &[u8]) -> Result<Vec<u8>> {
+    let ct = kyber768::Ciphertext::from_bytes(ciphertext).map_err(|e| anyhow!("invalid Kyber768 ciphertext: {e}"))?;
+    let sk = kyber768::SecretKey::from_bytes(secret_key).map_err(|e| anyhow!("invalid Kyber768 secret key: {e}"))?;
+    Ok(kyber768::decapsulate(&ct, &sk).as_bytes().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dilithium_round_trip_and_tamper() {
+        let (pk, sk) = generate_dilithium_keypair().unwrap();
+        let sig = dilithium_sign(b"cert-fields", &sk).unwrap();
+        assert!(dilithium_verify(b"cert-fields", &sig, &pk).unwrap());
+        assert!(!dilithium_verify(b"cert-fieldz", &sig, &pk).unwrap());
+        let (other_pk, _) = generate_dilithium_keypair().unwrap();
+        assert!(!dilithium_verify(b"cert-fields", &sig, &other_pk).unwrap());
+        assert!(!dilithium_verify(b"cert-fields", &sig[..10], &pk).unwrap());
+    }
+
+    #[test]
+    fn kyber_shared_secret_matches() {
+        let (pk, sk) = generate_kyber_keypair().unwrap();
+        let (ct, ss) = kyber_encapsulate(&pk).unwrap();
+        assert_eq!(kyber_decapsulate(&ct, &sk).unwrap(), ss);
+        assert_eq!(ss.len(), 32);
+    }
+}
@@ -0,0 +1,137 @@
+//! Durable certificate store (sled).
+//!
+//! Trees: `certs` (cert id -> JSON `Certificate`), `crl` (append-only, big-endian sequence ->
+//! JSON `[cert id, revoked at]`), `expired` (swept certificates, same encoding as `certs`) and `meta` (CA
+//! public key, so issued certificates stay verifiable across restarts).
+//! Location: `IDENTITY_CA_DB_PATH` if set, else `$SWARM_DATA_DIR/identity-ca`.
+//!
+//! The CA secret key never enters the database: it lives in its own file, created with mode 0600,
+//! at `IDENTITY_CA_KEY_PATH` if set, else beside the store as `<store dir>.key`. A secret key
+//! left in `meta` by older versions is moved to that file on load.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use anyhow::{bail, Context, Result};
+use crate::Certificate;
+
+const CA_PUBLIC_KEY: &[u8] = b"ca_dilithium_pk";
+const LEGACY_CA_SECRET_KEY: &[u8] = b"ca_dilithium_sk"; // pre key-file stores only
+
+/// CA secret key file for the store at `db`.
+pub fn key_path(db: &Path) -> PathBuf {
+    std::env::var("IDENTITY_CA_KEY_PATH").map(PathBuf::from).unwrap_or_else(|_| db.with_extension("key"))
+}
+
+fn write_secret(path: &Path, secret: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("key.tmp");
+    let mut opts = std::fs::OpenOptions::new();
+    opts.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut opts, 0o600);
+    let mut f = opts.open(&tmp).with_context(|| format!("create CA key file {}", tmp.display()))?;
+    f.write_all(secret)?;
+    f.sync_all()?;
+    std::fs::rename(&tmp, path).with_context(|| format!("install CA key file {}", path.display()))?;
+    Ok(())
+}
+
+fn read_secret(path: &Path) -> Result<Option<Vec<u8>>> {
+    match std::fs::read(path) {
+        Ok(sk) => {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = std::fs::metadata(path)?.permissions().mode() & 0o777;
+                if mode & 0o077 != 0 { bail!("CA key file {} is accessible to group/other (mode {mode:o}); chmod 600 it", path.display()); }
+            }
+            Ok(Some(sk))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("read CA key file {}", path.display())),
+    }
+}
+
+/// Store location; created and probed for writability.
+pub fn db_path() -> Result<PathBuf> {
+    match std::env::var("IDENTITY_CA_DB_PATH") {
+        Ok(p) => { let p = PathBuf::from(p); swarm_core::ensure_writable_dir(&p)?; Ok(p) }
+        Err(_) => swarm_core::resolve_data_dir("identity-ca"),
+    }
+}
+
+pub struct CertStore {
+    db: sled::Db,
+    certs: sled::Tree,
+    crl: sled::Tree,
+    expired: sled::Tree,
+    meta: sled::Tree,
+    key_path: PathBuf,
+}
+
+impl CertStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        let db = sled::open(path).with_context(|| format!("open identity-ca store {}", path.display()))?;
+        Ok(Self { certs: db.open_tree("certs")?, crl: db.open_tree("crl")?, expired: db.open_tree("expired")?, meta: db.open_tree("meta")?, db, key_path: key_path(path) })
+    }
+
+    pub fn load_certificates(&self) -> Result<Vec<Certificate>> {
+        self.certs.iter().values().map(|v| Ok(serde_json::from_slice(&v?)?)).collect()
+    }
+
+    /// (revoked id, revocation unix secs) in revocation order.
+    pub fn load_crl(&self) -> Result<Vec<(String, u64)>> {
+        self.crl.iter().values().map(|v| Ok(serde_json::from_slice(&v?)?)).collect()
+    }
+
+    pub fn put_certificate(&self, cert: &Certificate) -> Result<()> {
+        self.certs.insert(cert.id.as_bytes(), serde_json::to_vec(cert)?)?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /// Append to the CRL and drop the certificate; flushed before returning.
+    pub fn revoke(&self, cert_id: &str, revoked_at: u64) -> Result<()> {
+        let seq = self.db.generate_id()?;
+        self.crl.insert(seq.to_be_bytes(), serde_json::to_vec(&(cert_id, revoked_at))?)?;
+        self.certs.remove(cert_id.as_bytes())?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    pub fn load_expired(&self) -> Result<Vec<String>> {
+        self.expired.iter().keys().map(|k| Ok(String::from_utf8(k?.to_vec())?)).collect()
+    }
+
+    /// Move a certificate from `certs` to `expired`.
+    pub fn expire(&self, cert_id: &str) -> Result<()> {
+        if let Some(v) = self.certs.remove(cert_id.as_bytes())? { self.expired.insert(cert_id.as_bytes(), v)?; }
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /// CA (public, secret) key; `None` on first start. A public key without its secret key file is
+    /// an error rather than `None`, so a lost key file never silently re-keys the CA.
+    pub fn ca_keypair(&self) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        let Some(pk) = self.meta.get(CA_PUBLIC_KEY)? else { return Ok(None) };
+        if let Some(legacy) = self.meta.get(LEGACY_CA_SECRET_KEY)? {
+            if read_secret(&self.key_path)?.is_none() { write_secret(&self.key_path, &legacy)?; }
+            self.meta.remove(LEGACY_CA_SECRET_KEY)?;
+            self.db.flush()?;
+            tracing::info!(key_path=%self.key_path.display(), "moved CA secret key out of the store");
+        }
+        match read_secret(&self.key_path)? {
+            Some(sk) => Ok(Some((pk.to_vec(), sk))),
+            None => bail!("CA public key is stored but its secret key file {} is missing", self.key_path.display()),
+        }
+    }
+
+    /// Persist the public key in `meta` and the secret key in the key file.
+    pub fn put_ca_keypair(&self, public: &[u8], secret: &[u8]) -> Result<()> {
+        write_secret(&self.key_path, secret)?;
+        self.meta.insert(CA_PUBLIC_KEY, public)?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    pub fn key_path(&self) -> &Path { &self.key_path }
+}
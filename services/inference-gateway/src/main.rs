@@ -5,11 +5,52 @@ use tonic::{transport::Server, Request, Response, Status};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use lru::LruCache;
+pub mod onnx;
+pub mod quantize;
+use onnx::{InferenceError, OnnxSession};
 
 // Inference Gateway với ONNX Runtime support
 pub struct InferenceGateway {
     models: Arc<RwLock<HashMap<String, LoadedModel>>>,
-    cache: Arc<RwLock<HashMap<String, Vec<f32>>>>,
+    cache: Arc<RwLock<LruCache<CacheKey, CachedOutput>>>, // bounded by INFERENCE_CACHE_CAP
+    cache_hits: Arc<AtomicU64>,
+    cache_misses: Arc<AtomicU64>,
+    max_batch: usize, // INFERENCE_MAX_BATCH; larger batch_infer requests are chunked
+}
+
+/// Cache entries keep the exact input bits so a key-hash collision is a miss, not a wrong answer.
+struct CachedOutput {
+    model: String,
+    input_bits: Vec<u32>,
+    output: Vec<f32>,
+}
+
+/// SHA-256 of the model name and input, truncated to 128 bits.
+type CacheKey = [u8; 16];
+
+/// Stable key over model name + raw f32 bits (NaN-safe, no float formatting). SHA-256 keeps keys
+/// identical across Rust releases and processes, unlike `DefaultHasher`.
+fn cache_key(model_name: &str, input_bits: &[u32]) -> CacheKey {
+    let mut h = Sha256::new();
+    h.update((model_name.len() as u64).to_le_bytes()); // length prefix: name bytes never run into input bytes
+    h.update(model_name.as_bytes());
+    for bits in input_bits { h.update(bits.to_le_bytes()); }
+    let mut key = [0u8; 16];
+    key.copy_from_slice(&h.finalize()[..16]);
+    key
+}
+
+fn cache_get(cache: &mut LruCache<CacheKey, CachedOutput>, key: CacheKey, model_name: &str, input_bits: &[u32]) -> Option<Vec<f32>> {
+    cache.get(&key).filter(|c| c.model == model_name && c.input_bits == input_bits).map(|c| c.output.clone())
+}
+
+/// Run a blocking ONNX session call on the blocking pool instead of an async worker.
+async fn run_blocking<T: Send + 'static>(f: impl FnOnce() -> Result<T, InferenceError> + Send + 'static) -> Result<T, InferenceError> {
+    tokio::task::spawn_blocking(f).await.map_err(|e| InferenceError::Onnx(format!("session task failed: {e}")))?
 }
 
 #[derive(Clone)]
@@ -20,13 +61,22 @@ pub struct LoadedModel {
     pub input_shape: Vec<i64>,
     pub output_shape: Vec<i64>,
     pub quantized: bool,
+    pub session: Option<Arc<OnnxSession>>, // None when the bytes are not a loadable ONNX model
 }
 
 impl InferenceGateway {
     pub fn new() -> Self {
+        let cap: usize = std::env::var("INFERENCE_CACHE_CAP").ok().and_then(|v| v.parse().ok()).unwrap_or(10_000);
+        Self::with_cache_capacity(cap)
+    }
+
+    pub fn with_cache_capacity(cap: usize) -> Self {
         Self {
             models: Arc::new(RwLock::new(HashMap::new())),
-            cache: Arc::new(RwLock::new(HashMap::new())),
+            cache: Arc::new(RwLock::new(LruCache::new(NonZeroUsize::new(cap).unwrap_or(NonZeroUsize::MIN)))),
+            cache_hits: Arc::new(AtomicU64::new(0)),
+            cache_misses: Arc::new(AtomicU64::new(0)),
+            max_batch: std::env::var("INFERENCE_MAX_BATCH").ok().and_then(|v| v.parse().ok()).unwrap_or(32usize).max(1),
         }
     }
 
@@ -34,13 +84,22 @@ impl InferenceGateway {
     pub async fn load_model(&self, name: &str, version: &str, model_data: Vec<u8>) -> Result<()> {
         info!("Loading model: {} version {}", name, version);
         
+        let session = match OnnxSession::from_bytes(&model_data) {
+            Ok(s) => Some(Arc::new(s)),
+            Err(e) => { tracing::warn!(model=%name, error=%e, "ONNX session build failed"); None }
+        };
+        let (input_shape, output_shape) = match &session {
+            Some(s) => (s.input_shape().to_vec(), s.output_shape().to_vec()),
+            None => (vec![1, 128], vec![1, 10]), // Example shape (mock path)
+        };
         let model = LoadedModel {
             name: name.to_string(),
             version: version.to_string(),
             model_data,
-            input_shape: vec![1, 128], // Example shape
-            output_shape: vec![1, 10], // Example shape
+            input_shape,
+            output_shape,
             quantized: false,
+            session,
         };
         
         let mut models = self.models.write().await;
@@ -51,38 +110,50 @@ impl InferenceGateway {
     }
 
     /// Inference với caching
-    pub async fn infer(&self, model_name: &str, input: Vec<f32>) -> Result<Vec<f32>> {
+    pub async fn infer(&self, model_name: &str, input: Vec<f32>) -> Result<Vec<f32>, InferenceError> {
         debug!("Running inference on model: {}", model_name);
         
         // Check cache first
-        let cache_key = format!("{}:{:?}", model_name, input);
-        {
-            let cache = self.cache.read().await;
-            if let Some(cached) = cache.get(&cache_key) {
-                debug!("Cache hit for model: {}", model_name);
-                return Ok(cached.clone());
-            }
+        let input_bits: Vec<u32> = input.iter().map(|x| x.to_bits()).collect();
+        let cache_key = cache_key(model_name, &input_bits);
+        // LRU lookup updates recency, hence the write lock
+        if let Some(cached) = cache_get(&mut *self.cache.write().await, cache_key, model_name, &input_bits) {
+            debug!("Cache hit for model: {}", model_name);
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(cached);
         }
+        self.cache_misses.fetch_add(1, Ordering::Relaxed);
         
         // Get model
-        let models = self.models.read().await;
-        let model = models.get(model_name)
-            .ok_or_else(|| anyhow::anyhow!("Model not found: {}", model_name))?;
+        let (session, _output_shape) = self.model_handle(model_name).await?;
         
-        // Run inference (mock implementation)
-        // TODO: Integrate actual ONNX Runtime
-        let output = self.mock_inference(&input, &model.output_shape);
+        let output = match session {
+            Some(session) => run_blocking(move || session.run(&input)).await?,
+            #[cfg(feature = "mock")]
+            None => self.mock_inference(&input, &_output_shape),
+            #[cfg(not(feature = "mock"))]
+            None => return Err(InferenceError::NoSession(model_name.to_string())),
+        };
         
         // Cache result
         {
             let mut cache = self.cache.write().await;
-            cache.insert(cache_key, output.clone());
+            cache.put(cache_key, CachedOutput { model: model_name.to_string(), input_bits, output: output.clone() });
         }
         
         Ok(output)
     }
 
+    /// Session and output shape of a model; the models lock is released before any session run.
+    async fn model_handle(&self, model_name: &str) -> Result<(Option<Arc<OnnxSession>>, Vec<i64>), InferenceError> {
+        let models = self.models.read().await;
+        let model = models.get(model_name)
+            .ok_or_else(|| InferenceError::ModelNotFound(model_name.to_string()))?;
+        Ok((model.session.clone(), model.output_shape.clone()))
+    }
+
     /// Mock inference cho testing
+    #[cfg(feature = "mock")]
     fn mock_inference(&self, input: &[f32], output_shape: &[i64]) -> Vec<f32> {
         let output_size = output_shape.iter().product::<i64>() as usize;
         let mut output = vec![0.0f32; output_size];
@@ -99,30 +170,78 @@ impl InferenceGateway {
     }
 
     /// Batch inference cho hiệu suất cao hơn
-    pub async fn batch_infer(&self, model_name: &str, inputs: Vec<Vec<f32>>) -> Result<Vec<Vec<f32>>> {
+    /// Cached samples are answered from the cache; the rest run as `[N, ..input_dims]`
+    /// tensors, at most `max_batch` samples per session call.
+    pub async fn batch_infer(&self, model_name: &str, inputs: Vec<Vec<f32>>) -> Result<Vec<Vec<f32>>, InferenceError> {
         info!("Running batch inference: {} samples", inputs.len());
         
-        let mut results = Vec::with_capacity(inputs.len());
+        let keyed: Vec<(Vec<u32>, CacheKey)> = inputs.iter().map(|input| {
+            let bits: Vec<u32> = input.iter().map(|x| x.to_bits()).collect();
+            let key = cache_key(model_name, &bits);
+            (bits, key)
+        }).collect();
+        let mut results: Vec<Option<Vec<f32>>> = vec![None; inputs.len()];
+        let mut pending = Vec::new();
+        {
+            let mut cache = self.cache.write().await;
+            for (idx, (bits, key)) in keyed.iter().enumerate() {
+                match cache_get(&mut cache, *key, model_name, bits) {
+                    Some(out) => { self.cache_hits.fetch_add(1, Ordering::Relaxed); results[idx] = Some(out); }
+                    None => { self.cache_misses.fetch_add(1, Ordering::Relaxed); pending.push(idx); }
+                }
+            }
+        }
         
-        for input in inputs {
-            let output = self.infer(model_name, input).await?;
-            results.push(output);
+        if !pending.is_empty() {
+            let (session, _output_shape) = self.model_handle(model_name).await?;
+            let inputs = Arc::new(inputs);
+            let mut fresh = Vec::with_capacity(pending.len());
+            for chunk in pending.chunks(self.max_batch) {
+                let outputs = match &session {
+                    Some(session) => {
+                        let (session, inputs, chunk) = (session.clone(), inputs.clone(), chunk.to_vec());
+                        run_blocking(move || session.run_batch(&chunk.iter().map(|i| inputs[*i].as_slice()).collect::<Vec<_>>())).await?
+                    }
+                    #[cfg(feature = "mock")]
+                    None => chunk.iter().map(|i| self.mock_inference(&inputs[*i], &_output_shape)).collect(),
+                    #[cfg(not(feature = "mock"))]
+                    None => return Err(InferenceError::NoSession(model_name.to_string())),
+                };
+                fresh.extend(chunk.iter().copied().zip(outputs));
+            }
+            let mut cache = self.cache.write().await;
+            for (idx, output) in fresh {
+                cache.put(keyed[idx].1, CachedOutput { model: model_name.to_string(), input_bits: keyed[idx].0.clone(), output: output.clone() });
+                results[idx] = Some(output);
+            }
         }
         
-        Ok(results)
+        Ok(results.into_iter().map(Option::unwrap_or_default).collect())
     }
 
-    /// Model optimization: Quantization
-    pub async fn quantize_model(&self, model_name: &str) -> Result<()> {
+    /// Session invocations for a model (None when it has no ONNX session).
+    pub async fn session_runs(&self, model_name: &str) -> Option<u64> {
+        self.models.read().await.get(model_name).and_then(|m| m.session.as_ref()).map(|s| s.run_count())
+    }
+
+    /// Model optimization: Quantization (FP32 weights -> INT8 + DequantizeLinear)
+    pub async fn quantize_model(&self, model_name: &str) -> Result<(), InferenceError> {
         info!("Quantizing model: {}", model_name);
+        let min_elements: usize = std::env::var("INFERENCE_QUANTIZE_MIN_ELEMENTS").ok().and_then(|v| v.parse().ok()).unwrap_or(256);
         
         let mut models = self.models.write().await;
-        if let Some(model) = models.get_mut(model_name) {
-            // TODO: Implement actual quantization (FP32 -> INT8)
-            model.quantized = true;
-            info!("Model quantized: {}", model_name);
-        }
-        
+        let model = models.get_mut(model_name).ok_or_else(|| InferenceError::ModelNotFound(model_name.to_string()))?;
+        if model.quantized { return Ok(()); }
+        // build everything first; the model is only replaced once the quantized graph loads
+        let quantized = quantize::quantize_int8(&model.model_data, min_elements)?;
+        let session = OnnxSession::from_bytes(&quantized)?;
+        let before = model.model_data.len();
+        model.model_data = quantized;
+        model.session = Some(Arc::new(session));
+        model.quantized = true;
+        info!(model=%model_name, size_before=before, size_after=model.model_data.len(), "Model quantized");
+        drop(models);
+        self.clear_cache().await; // cached outputs came from the FP32 graph
         Ok(())
     }
 
@@ -133,6 +252,11 @@ impl InferenceGateway {
         info!("Inference cache cleared");
     }
 
+    /// (hits, misses, current entries)
+    pub async fn cache_stats(&self) -> (u64, u64, usize) {
+        (self.cache_hits.load(Ordering::Relaxed), self.cache_misses.load(Ordering::Relaxed), self.cache.read().await.len())
+    }
+
     /// Get model info
     pub async fn get_model_info(&self, model_name: &str) -> Option<ModelInfo> {
         let models = self.models.read().await;
@@ -157,27 +281,6 @@ pub struct ModelInfo {
     pub size_bytes: usize,
 }
 
-// ONNX Runtime integration (placeholder)
-pub mod onnx {
-    use anyhow::Result;
-    
-    pub struct OnnxSession {
-        model_data: Vec<u8>,
-    }
-    
-    impl OnnxSession {
-        pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
-            Ok(Self { model_data: data })
-        }
-        
-        pub fn run(&self, input: &[f32]) -> Result<Vec<f32>> {
-            // TODO: Integrate actual ONNX Runtime
-            let _ = input;
-            Ok(vec![0.0; 10])
-        }
-    }
-}
-
 #[tokio::main]
 async fn main() -> Result<()> {
     init_tracing("inference-gateway")?;
@@ -187,12 +290,16 @@ async fn main() -> Result<()> {
     
     let gateway = InferenceGateway::new();
     
-    // Load sample model
-    let sample_model = vec![0u8; 1024]; // Mock model data
-    gateway.load_model("threat-classifier", "v1.0", sample_model).await?;
+    // Load model (INFERENCE_MODEL_PATH); falls back to dummy bytes which only serve with the `mock` feature
+    let model_bytes = match std::env::var("INFERENCE_MODEL_PATH") {
+        Ok(path) => std::fs::read(&path)?,
+        Err(_) => vec![0u8; 1024],
+    };
+    gateway.load_model("threat-classifier", "v1.0", model_bytes).await?;
     
     // Test inference
-    let test_input = vec![0.5f32; 128];
+    let input_len = gateway.get_model_info("threat-classifier").await.map(|m| m.input_shape.iter().product::<i64>() as usize).unwrap_or(128);
+    let test_input = vec![0.5f32; input_len];
     match gateway.infer("threat-classifier", test_input).await {
         Ok(output) => {
             info!("Inference test successful. Output size: {}", output.len());
@@ -205,6 +312,7 @@ async fn main() -> Result<()> {
     // Keep service running
     tokio::signal::ctrl_c().await?;
     info!("Shutting down inference-gateway");
+    swarm_core::shutdown_observability();
     
     Ok(())
 }
@@ -215,3 +323,100 @@ fn init_tracing() -> Result<()> {
         .try_init()
         .map_err(|e| anyhow::anyhow!("Failed to initialize tracing: {}", e))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // y = x * [1, 2, 3, 4], input/output shape [1, 4]
+    const TINY_MUL: &[u8] = include_bytes!("../tests/fixtures/tiny_mul.onnx");
+
+    #[tokio::test]
+    async fn onnx_model_runs_deterministically() {
+        let gw = InferenceGateway::new();
+        gw.load_model("tiny", "v1", TINY_MUL.to_vec()).await.unwrap();
+        assert_eq!(gw.get_model_info("tiny").await.unwrap().input_shape, vec![1, 4]);
+        let out = gw.infer("tiny", vec![1.0, 1.0, 0.5, -1.0]).await.unwrap();
+        assert_eq!(out, vec![1.0, 2.0, 1.5, -4.0]);
+    }
+
+    #[tokio::test]
+    async fn input_length_mismatch_is_typed_error() {
+        let gw = InferenceGateway::new();
+        gw.load_model("tiny", "v1", TINY_MUL.to_vec()).await.unwrap();
+        let err = gw.infer("tiny", vec![1.0; 3]).await.unwrap_err();
+        assert!(matches!(err, InferenceError::ShapeMismatch { expected: 4, got: 3, .. }));
+        assert!(matches!(gw.infer("missing", vec![]).await, Err(InferenceError::ModelNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn quantized_model_is_smaller_and_close_to_fp32() {
+        let gw = InferenceGateway::new();
+        gw.load_model("mm", "v1", include_bytes!("../tests/fixtures/tiny_matmul.onnx").to_vec()).await.unwrap();
+        let input: Vec<f32> = (0..64).map(|i| (i as f32 * 0.1).cos()).collect();
+        let fp32 = gw.infer("mm", input.clone()).await.unwrap();
+        let size_fp32 = gw.get_model_info("mm").await.unwrap().size_bytes;
+
+        gw.quantize_model("mm").await.unwrap();
+        let info = gw.get_model_info("mm").await.unwrap();
+        assert!(info.quantized);
+        assert!(info.size_bytes < size_fp32, "{} !< {}", info.size_bytes, size_fp32);
+        let int8 = gw.infer("mm", input).await.unwrap();
+        assert_eq!(int8.len(), fp32.len());
+        for (a, b) in fp32.iter().zip(&int8) { assert!((a - b).abs() < 0.05, "{a} vs {b}"); }
+    }
+
+    #[tokio::test]
+    async fn failed_quantization_leaves_model_intact() {
+        let gw = InferenceGateway::new();
+        gw.load_model("tiny", "v1", TINY_MUL.to_vec()).await.unwrap();
+        assert!(matches!(gw.quantize_model("tiny").await, Err(InferenceError::Quantization(_))));
+        let info = gw.get_model_info("tiny").await.unwrap();
+        assert!(!info.quantized);
+        assert_eq!(info.size_bytes, TINY_MUL.len());
+    }
+
+    #[tokio::test]
+    async fn batch_uses_fewer_session_runs_than_serial() {
+        let model = include_bytes!("../tests/fixtures/tiny_matmul.onnx").to_vec(); // dynamic batch dim
+        let samples: Vec<Vec<f32>> = (0..64).map(|n| (0..64).map(|i| ((n * 64 + i) as f32 * 0.01).sin()).collect()).collect();
+
+        let serial = InferenceGateway::new();
+        serial.load_model("mm", "v1", model.clone()).await.unwrap();
+        let mut serial_out = Vec::new();
+        for s in &samples { serial_out.push(serial.infer("mm", s.clone()).await.unwrap()); }
+
+        let batched = InferenceGateway::new();
+        batched.load_model("mm", "v1", model).await.unwrap();
+        batched.infer("mm", samples[0].clone()).await.unwrap(); // cached sample is skipped in the batch
+        let batch_out = batched.batch_infer("mm", samples.clone()).await.unwrap();
+
+        assert_eq!(serial.session_runs("mm").await, Some(64));
+        // 1 warm-up + 63 uncached samples in chunks of INFERENCE_MAX_BATCH (32) -> 2 runs
+        assert_eq!(batched.session_runs("mm").await, Some(3));
+        for (a, b) in serial_out.iter().zip(&batch_out) {
+            assert_eq!(a.len(), 8);
+            for (x, y) in a.iter().zip(b) { assert!((x - y).abs() < 1e-5); }
+        }
+    }
+
+    #[tokio::test]
+    async fn cache_evicts_least_recently_used() {
+        let gw = InferenceGateway::with_cache_capacity(2);
+        gw.load_model("tiny", "v1", TINY_MUL.to_vec()).await.unwrap();
+        let input = |v: f32| vec![v, 0.0, 0.0, f32::NAN];
+        for v in [1.0, 2.0, 3.0] { gw.infer("tiny", input(v)).await.unwrap(); }
+        assert_eq!(gw.cache_stats().await, (0, 3, 2));
+        gw.infer("tiny", input(3.0)).await.unwrap(); // newest entry still cached (NaN input hashes stably)
+        assert_eq!(gw.cache_stats().await, (1, 3, 2));
+        gw.infer("tiny", input(1.0)).await.unwrap(); // oldest was evicted
+        assert_eq!(gw.cache_stats().await, (1, 4, 2));
+    }
+
+    #[test]
+    fn cache_key_is_truncated_sha256() {
+        // sha256(len("m") as u64 LE || "m" || f32 bits LE)[..16]
+        assert_eq!(cache_key("m", &[1.0f32.to_bits(), 2.0f32.to_bits()]), [0xd3, 0x93, 0xb6, 0xd8, 0xd4, 0xfa, 0xe5, 0x47, 0xcc, 0x25, 0x92, 0x18, 0x4b, 0xf3, 0x43, 0x4b]);
+        assert_ne!(cache_key("m", &[1]), cache_key("n", &[1]));
+    }
+}
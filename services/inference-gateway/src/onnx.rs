@@ -0,0 +1,97 @@
+//! ONNX Runtime integration (via the `ort` crate).
+//!
+//! A session is built straight from the registry's model bytes; `run` feeds a single f32
+//! tensor shaped by the model's declared input and returns the first output flattened.
+//! `run_batch` stacks samples along a dynamic leading dimension for one session call.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use parking_lot::Mutex;
+use ort::session::Session;
+use ort::value::{Tensor, ValueType};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum InferenceError {
+    #[error("model not found: {0}")] ModelNotFound(String),
+    #[error("input length {got} does not match input shape {shape:?} ({expected} values)")]
+    ShapeMismatch { shape: Vec<i64>, expected: usize, got: usize },
+    #[error("no ONNX session for model {0} (build with the `mock` feature for synthetic output)")]
+    NoSession(String),
+    #[error("onnx runtime: {0}")] Onnx(String),
+    #[error("quantization failed: {0}")] Quantization(#[from] crate::quantize::QuantizeError),
+}
+
+impl From<ort::Error> for InferenceError {
+    fn from(e: ort::Error) -> Self { InferenceError::Onnx(e.to_string()) }
+}
+
+pub struct OnnxSession {
+    session: Mutex<Session>, // ort needs &mut for run
+    input_name: String,
+    input_shape: Vec<i64>,
+    output_shape: Vec<i64>,
+    dynamic_batch: bool, // leading input dim is symbolic, so N samples fit in one run
+    runs: AtomicU64,
+}
+
+impl OnnxSession {
+    pub fn from_bytes(data: &[u8]) -> Result<Self, InferenceError> {
+        let session = Session::builder()?.commit_from_memory(data)?;
+        let input = session.inputs.first().ok_or_else(|| InferenceError::Onnx("model declares no inputs".into()))?;
+        let input_name = input.name.clone();
+        let dynamic_batch = matches!(&input.input_type, ValueType::Tensor { shape, .. } if shape.first().is_some_and(|d| *d < 0));
+        let input_shape = declared_shape(&input.input_type);
+        let output_shape = session.outputs.first().map(|o| declared_shape(&o.output_type)).unwrap_or_default();
+        Ok(Self { session: Mutex::new(session), input_name, input_shape, output_shape, dynamic_batch, runs: AtomicU64::new(0) })
+    }
+
+    /// Input shape with dynamic dimensions pinned to 1 (single-sample inference).
+    pub fn input_shape(&self) -> &[i64] { &self.input_shape }
+    pub fn output_shape(&self) -> &[i64] { &self.output_shape }
+    /// Number of session invocations so far.
+    pub fn run_count(&self) -> u64 { self.runs.load(Ordering::Relaxed) }
+
+    pub fn run(&self, input: &[f32]) -> Result<Vec<f32>, InferenceError> {
+        let expected = self.input_shape.iter().product::<i64>() as usize;
+        if input.len() != expected {
+            return Err(InferenceError::ShapeMismatch { shape: self.input_shape.clone(), expected, got: input.len() });
+        }
+        self.invoke(self.input_shape.clone(), input.to_vec())
+    }
+
+    /// Run N samples. With a dynamic batch dim this is a single `[N, ..input_dims]` call,
+    /// otherwise one call per sample.
+    pub fn run_batch(&self, inputs: &[&[f32]]) -> Result<Vec<Vec<f32>>, InferenceError> {
+        if inputs.is_empty() { return Ok(Vec::new()); }
+        if !self.dynamic_batch { return inputs.iter().map(|i| self.run(i)).collect(); }
+        let per_sample = self.input_shape.iter().skip(1).product::<i64>() as usize;
+        let mut data = Vec::with_capacity(per_sample * inputs.len());
+        for input in inputs {
+            if input.len() != per_sample {
+                return Err(InferenceError::ShapeMismatch { shape: self.input_shape.clone(), expected: per_sample, got: input.len() });
+            }
+            data.extend_from_slice(input);
+        }
+        let mut shape = self.input_shape.clone();
+        shape[0] = inputs.len() as i64;
+        let flat = self.invoke(shape, data)?;
+        if flat.len() % inputs.len() != 0 { return Err(InferenceError::Onnx(format!("batch output of {} values does not split into {} samples", flat.len(), inputs.len()))); }
+        Ok(flat.chunks(flat.len() / inputs.len()).map(|c| c.to_vec()).collect())
+    }
+
+    fn invoke(&self, shape: Vec<i64>, data: Vec<f32>) -> Result<Vec<f32>, InferenceError> {
+        let tensor = Tensor::from_array((shape, data))?;
+        let mut session = self.session.lock();
+        self.runs.fetch_add(1, Ordering::Relaxed);
+        let outputs = session.run(ort::inputs![self.input_name.as_str() => tensor])?;
+        let (_, data) = outputs[0].try_extract_tensor::<f32>()?;
+        Ok(data.to_vec())
+    }
+}
+
+fn declared_shape(ty: &ValueType) -> Vec<i64> {
+    match ty {
+        ValueType::Tensor { shape, .. } => shape.iter().map(|d| if *d < 0 { 1 } else { *d }).collect(),
+        _ => Vec::new(),
+    }
+}
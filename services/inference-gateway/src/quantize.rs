@@ -0,0 +1,204 @@
+//! FP32 -> INT8 weight quantization of an ONNX model.
+//!
+//! Works directly on the protobuf wire format so every field we don't touch (attributes,
+//! metadata, opsets) is carried over byte-for-byte. Each FLOAT initializer with at least
+//! `min_elements` values is replaced by a symmetric per-tensor INT8 tensor plus a
+//! `DequantizeLinear` node that recreates the original name, so consumers are unchanged and
+//! the stored weights shrink ~4x. Requires default-domain opset >= 10.
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum QuantizeError {
+    #[error("malformed ONNX protobuf")] Malformed,
+    #[error("model has no graph")] NoGraph,
+    #[error("default-domain opset {0} predates DequantizeLinear (needs >= 10)")] OpsetTooOld(u64),
+    #[error("no FP32 initializer with >= {0} elements to quantize")] NothingToQuantize(usize),
+}
+
+// ModelProto / GraphProto / TensorProto / NodeProto field numbers
+const MODEL_GRAPH: u32 = 7;
+const MODEL_OPSET_IMPORT: u32 = 8;
+const GRAPH_NODE: u32 = 1;
+const GRAPH_INITIALIZER: u32 = 5;
+const GRAPH_INPUT: u32 = 11;
+const TENSOR_DIMS: u32 = 1;
+const TENSOR_DATA_TYPE: u32 = 2;
+const TENSOR_FLOAT_DATA: u32 = 4;
+const TENSOR_NAME: u32 = 8;
+const TENSOR_RAW_DATA: u32 = 9;
+const TENSOR_DOC_STRING: u32 = 12;
+const DT_FLOAT: u64 = 1;
+const DT_INT8: u64 = 3;
+const WIRE_VARINT: u8 = 0;
+const WIRE_I64: u8 = 1;
+const WIRE_LEN: u8 = 2;
+const WIRE_I32: u8 = 5;
+
+enum Value<'a> { Varint(u64), Bytes(&'a [u8]), Fixed32([u8; 4]), Fixed64 }
+
+struct Field<'a> { number: u32, raw: &'a [u8], value: Value<'a> }
+
+fn read_varint(buf: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut out = 0u64;
+    for shift in (0..64).step_by(7) {
+        let b = *buf.get(*pos)?;
+        *pos += 1;
+        out |= ((b & 0x7f) as u64) << shift;
+        if b & 0x80 == 0 { return Some(out); }
+    }
+    None
+}
+
+fn parse(buf: &[u8]) -> Result<Vec<Field<'_>>, QuantizeError> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < buf.len() {
+        let start = pos;
+        let key = read_varint(buf, &mut pos).ok_or(QuantizeError::Malformed)?;
+        let value = match (key & 7) as u8 {
+            WIRE_VARINT => Value::Varint(read_varint(buf, &mut pos).ok_or(QuantizeError::Malformed)?),
+            WIRE_LEN => {
+                let len = read_varint(buf, &mut pos).ok_or(QuantizeError::Malformed)? as usize;
+                let body = buf.get(pos..pos.checked_add(len).ok_or(QuantizeError::Malformed)?).ok_or(QuantizeError::Malformed)?;
+                pos += len;
+                Value::Bytes(body)
+            }
+            WIRE_I32 => { let b = buf.get(pos..pos + 4).ok_or(QuantizeError::Malformed)?; pos += 4; Value::Fixed32(b.try_into().unwrap()) }
+            WIRE_I64 => { buf.get(pos..pos + 8).ok_or(QuantizeError::Malformed)?; pos += 8; Value::Fixed64 }
+            _ => return Err(QuantizeError::Malformed),
+        };
+        out.push(Field { number: (key >> 3) as u32, raw: &buf[start..pos], value });
+    }
+    Ok(out)
+}
+
+fn put_varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 { out.push((v as u8) | 0x80); v >>= 7; }
+    out.push(v as u8);
+}
+fn put_uint(out: &mut Vec<u8>, field: u32, v: u64) { put_varint(out, ((field as u64) << 3) | WIRE_VARINT as u64); put_varint(out, v); }
+fn put_bytes(out: &mut Vec<u8>, field: u32, b: &[u8]) { put_varint(out, ((field as u64) << 3) | WIRE_LEN as u64); put_varint(out, b.len() as u64); out.extend_from_slice(b); }
+
+fn tensor(name: &str, dims: &[u64], data_type: u64, raw: &[u8]) -> Vec<u8> {
+    let mut t = Vec::new();
+    for d in dims { put_uint(&mut t, TENSOR_DIMS, *d); }
+    put_uint(&mut t, TENSOR_DATA_TYPE, data_type);
+    put_bytes(&mut t, TENSOR_NAME, name.as_bytes());
+    put_bytes(&mut t, TENSOR_RAW_DATA, raw);
+    t
+}
+
+fn dequantize_node(name: &str) -> Vec<u8> {
+    let mut n = Vec::new();
+    for input in [format!("{name}_quantized"), format!("{name}_scale"), format!("{name}_zero_point")] { put_bytes(&mut n, 1, input.as_bytes()); }
+    put_bytes(&mut n, 2, name.as_bytes());
+    put_bytes(&mut n, 3, format!("{name}_dequantize").as_bytes());
+    put_bytes(&mut n, 4, b"DequantizeLinear");
+    n
+}
+
+/// FP32 initializer contents, or None when the tensor is not a plain inline float tensor.
+fn float_initializer(fields: &[Field<'_>]) -> Option<(String, Vec<u64>, Vec<f32>)> {
+    let (mut name, mut dims, mut dtype, mut data) = (None, Vec::new(), None, Vec::new());
+    for f in fields {
+        match (f.number, &f.value) {
+            (TENSOR_DIMS, Value::Varint(d)) => dims.push(*d),
+            (TENSOR_DATA_TYPE, Value::Varint(t)) => dtype = Some(*t),
+            (TENSOR_NAME, Value::Bytes(b)) => name = Some(String::from_utf8(b.to_vec()).ok()?),
+            (TENSOR_RAW_DATA, Value::Bytes(b)) | (TENSOR_FLOAT_DATA, Value::Bytes(b)) => {
+                if b.len() % 4 != 0 { return None; }
+                data.extend(b.chunks_exact(4).map(|c| f32::from_le_bytes(c.try_into().unwrap())));
+            }
+            (TENSOR_FLOAT_DATA, Value::Fixed32(b)) => data.push(f32::from_le_bytes(*b)),
+            (TENSOR_DOC_STRING, _) => {}
+            _ => return None, // external data, segments, other encodings: leave alone
+        }
+    }
+    if dtype != Some(DT_FLOAT) { return None; }
+    if dims.iter().product::<u64>() as usize != data.len() { return None; }
+    Some((name?, dims, data))
+}
+
+fn default_opset(model: &[Field<'_>]) -> Result<Option<u64>, QuantizeError> {
+    for f in model.iter().filter(|f| f.number == MODEL_OPSET_IMPORT) {
+        let Value::Bytes(body) = f.value else { return Err(QuantizeError::Malformed) };
+        let entry = parse(body)?;
+        let domain = entry.iter().find_map(|e| match (e.number, &e.value) { (1, Value::Bytes(d)) => Some(*d), _ => None }).unwrap_or(b"");
+        let version = entry.iter().find_map(|e| match (e.number, &e.value) { (2, Value::Varint(v)) => Some(*v), _ => None });
+        if domain.is_empty() || domain == b"ai.onnx" { return Ok(version); }
+    }
+    Ok(None)
+}
+
+/// Rewrite `model` with INT8 weights. Returns the new model bytes or an error (input untouched).
+pub fn quantize_int8(model: &[u8], min_elements: usize) -> Result<Vec<u8>, QuantizeError> {
+    let top = parse(model)?;
+    match default_opset(&top)? {
+        Some(v) if v < 10 => return Err(QuantizeError::OpsetTooOld(v)),
+        _ => {}
+    }
+    let graph_field = top.iter().find(|f| f.number == MODEL_GRAPH).ok_or(QuantizeError::NoGraph)?;
+    let Value::Bytes(graph_body) = graph_field.value else { return Err(QuantizeError::Malformed) };
+    let graph = parse(graph_body)?;
+    // initializers that double as graph inputs (old IR style) can be overridden by callers; keep them FP32
+    let mut graph_inputs = Vec::new();
+    for f in graph.iter().filter(|f| f.number == GRAPH_INPUT) {
+        if let Value::Bytes(b) = f.value {
+            if let Some(Value::Bytes(n)) = parse(b)?.into_iter().find(|e| e.number == 1).map(|e| e.value) { graph_inputs.push(n.to_vec()); }
+        }
+    }
+
+    let mut new_nodes = Vec::new();
+    let mut rest = Vec::new();
+    let mut quantized = 0usize;
+    for f in &graph {
+        if f.number == GRAPH_INITIALIZER {
+            if let Value::Bytes(body) = f.value {
+                if let Some((name, dims, data)) = float_initializer(&parse(body)?) {
+                    if data.len() >= min_elements && !graph_inputs.iter().any(|n| n == name.as_bytes()) {
+                        let max_abs = data.iter().fold(0f32, |m, v| m.max(v.abs()));
+                        let scale = if max_abs > 0.0 && max_abs.is_finite() { max_abs / 127.0 } else { 1.0 };
+                        let q: Vec<u8> = data.iter().map(|v| ((v / scale).round().clamp(-127.0, 127.0) as i8) as u8).collect();
+                        put_bytes(&mut rest, GRAPH_INITIALIZER, &tensor(&format!("{name}_quantized"), &dims, DT_INT8, &q));
+                        put_bytes(&mut rest, GRAPH_INITIALIZER, &tensor(&format!("{name}_scale"), &[], DT_FLOAT, &scale.to_le_bytes()));
+                        put_bytes(&mut rest, GRAPH_INITIALIZER, &tensor(&format!("{name}_zero_point"), &[], DT_INT8, &[0]));
+                        put_bytes(&mut new_nodes, GRAPH_NODE, &dequantize_node(&name));
+                        quantized += 1;
+                        continue;
+                    }
+                }
+            }
+        }
+        rest.extend_from_slice(f.raw);
+    }
+    if quantized == 0 { return Err(QuantizeError::NothingToQuantize(min_elements)); }
+
+    // DequantizeLinear nodes go first so the node list stays topologically sorted
+    new_nodes.extend_from_slice(&rest);
+    let mut out = Vec::with_capacity(model.len() / 2);
+    for f in &top {
+        if f.number == MODEL_GRAPH { put_bytes(&mut out, MODEL_GRAPH, &new_nodes); } else { out.extend_from_slice(f.raw); }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_or_malformed_models_are_rejected() {
+        let tiny = include_bytes!("../tests/fixtures/tiny_mul.onnx");
+        assert!(matches!(quantize_int8(tiny, 256), Err(QuantizeError::NothingToQuantize(256))));
+        assert!(matches!(quantize_int8(&tiny[..tiny.len() - 3], 1), Err(QuantizeError::Malformed)));
+    }
+
+    #[test]
+    fn weights_shrink_and_dequantize_node_is_added() {
+        let model = include_bytes!("../tests/fixtures/tiny_matmul.onnx");
+        let q = quantize_int8(model, 256).unwrap();
+        assert!(q.len() < model.len() / 2, "{} vs {}", q.len(), model.len());
+        assert!(q.windows(16).any(|w| w == b"DequantizeLinear"));
+    }
+}
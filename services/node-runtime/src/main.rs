@@ -1,12 +1,37 @@
-use anyhow::{Result, Context};
-use tracing::{info, warn};
+use anyhow::Result;
+use std::sync::Arc;
+use tracing::info;
+
+// Module APIs beyond what the pipeline drives (peer management, learning, rollback) are not wired yet.
+#[allow(dead_code)]
+mod modules;
+mod pipeline;
+
+#[cfg(feature = "wasm_plugins")]
+mod plugins;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     init_tracing();
     info!(target: "node-runtime", "Starting node-runtime service");
     #[cfg(feature = "wasm_plugins")]
-    if let Err(e) = load_wasm_plugins().await { warn!(error=?e, "WASM plugin load failed"); }
+    let inspector = match load_wasm_plugins().await {
+        Ok(host) if host.is_empty() => None,
+        Ok(host) => Some(plugins::inspector(host)),
+        Err(e) => { tracing::warn!(error=?e, "WASM plugin load failed"); None }
+    };
+    #[cfg(not(feature = "wasm_plugins"))]
+    let inspector = None;
+
+    let node_id = std::env::var("NODE_ID").unwrap_or_else(|_| format!("node-{}", uuid::Uuid::new_v4()));
+    let nats_url = std::env::var("NATS_URL").unwrap_or_else(|_| "nats://127.0.0.1:4222".into());
+    let comm = Arc::new(modules::CommunicationModule::new(node_id, &nats_url).await?);
+    let pipeline = pipeline::Pipeline::start(&pipeline::PipelineConfig::from_env(), Arc::new(modules::BrainModule::new()), comm, Arc::new(modules::ActionModule::new()), inspector);
+    modules::SensorModule::new(modules::SensorConfig::default()).with_sink(pipeline.input()).start().await?;
+
+    tokio::signal::ctrl_c().await?;
+    info!("shutdown signal received");
+    pipeline.shutdown().await;
     Ok(())
 }
 
@@ -17,31 +42,9 @@ fn init_tracing() {
 }
 
 #[cfg(feature = "wasm_plugins")]
-async fn load_wasm_plugins() -> Result<()> {
-    use wasmtime::{Engine, Module, Store, Linker};
+async fn load_wasm_plugins() -> Result<plugins::PluginHost> {
     let dir = std::env::var("WASM_PLUGIN_DIR").unwrap_or_else(|_| "./wasm-plugins".into());
-    let engine = Engine::default();
-    let mut loaded = 0u32;
-    if let Ok(read_dir) = std::fs::read_dir(&dir) {
-        for entry in read_dir.flatten() {
-            let path = entry.path();
-            if path.extension().and_then(|s| s.to_str()) == Some("wasm") {
-                let module = Module::from_file(&engine, &path).with_context(|| format!("compile wasm {:?}", path))?;
-                let mut linker = Linker::new(&engine);
-                let mut store = Store::new(&engine, ());
-                let instance = linker.instantiate(&mut store, &module)?;
-                // Expect optional exported function process_event(payload_ptr, payload_len)
-                if let Some(func) = instance.get_func(&mut store, "process_event") {
-                    info!(?path, "Loaded plugin with process_event export");
-                    // For now just call with empty payload to validate
-                    let _ = func.call(&mut store, &[], &mut []);
-                } else {
-                    warn!(?path, "Plugin missing process_event export");
-                }
-                loaded += 1;
-            }
-        }
-    }
-    info!(dir=%dir, loaded, "WASM plugin load complete");
-    Ok(())
+    let host = plugins::PluginHost::load_dir(&dir, plugins::SandboxLimits::from_env()).await?;
+    info!(dir=%dir, loaded=host.len(), "WASM plugin load complete");
+    Ok(host)
 }
@@ -1,7 +1,7 @@
 //! Action Module - Immune Response của Node
 //! Traffic filtering, countermeasures, honeypot, forensics
 use anyhow::Result;
-use tracing::{info, warn, debug};
+use tracing::info;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -34,7 +34,7 @@ pub enum ActionStatus {
     InProgress,
     Completed,
     Failed,
-    Rolled Back,
+    RolledBack,
 }
 
 pub struct ActionModule {
@@ -117,7 +117,7 @@ impl BrainModule {
     }
 
     /// Update model từ federated learning
-    pub async fn update_model(&mut self, model_data: &[u8], version: String) -> Result<()> {
+    pub async fn update_model(&mut self, _model_data: &[u8], version: String) -> Result<()> {
         info!("Updating model to version: {}", version);
         self.model_version = version;
         // TODO: Load actual ONNX model
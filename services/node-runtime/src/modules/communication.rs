@@ -4,6 +4,7 @@ use anyhow::Result;
 use tracing::{info, debug, warn};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use futures::StreamExt;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
@@ -54,6 +55,11 @@ impl CommunicationModule {
         })
     }
 
+    /// Without NATS: broadcasts and direct sends are dropped (tests, standalone nodes).
+    pub fn offline(node_id: String) -> Self {
+        Self { node_id, peers: Arc::new(tokio::sync::RwLock::new(Vec::new())), message_queue: Arc::new(tokio::sync::RwLock::new(Vec::new())), nats_client: None }
+    }
+
     /// Broadcast message tới tất cả peers (Gossip protocol)
     pub async fn broadcast(&self, msg_type: MessageType, payload: Vec<u8>) -> Result<()> {
         let msg = Message {
@@ -107,12 +113,12 @@ impl CommunicationModule {
     /// Subscribe và xử lý incoming messages
     pub async fn start_listening(&self) -> Result<()> {
         if let Some(client) = &self.nats_client {
-            let node_id = self.node_id.clone();
             let client = client.clone();
+            let client2 = client.clone();
             
             tokio::spawn(async move {
                 // Subscribe to gossip messages
-                if let Ok(mut sub) = client.subscribe("swarm.gossip.>".into()).await {
+                if let Ok(mut sub) = client.subscribe("swarm.gossip.>").await {
                     while let Some(msg) = sub.next().await {
                         if let Ok(message) = serde_json::from_slice::<Message>(&msg.payload) {
                             debug!("Received gossip message: {:?}", message.msg_type);
@@ -123,12 +129,11 @@ impl CommunicationModule {
             });
 
             let node_id2 = self.node_id.clone();
-            let client2 = client.clone();
             
             tokio::spawn(async move {
                 // Subscribe to direct messages
                 let subject = format!("swarm.direct.{}", node_id2);
-                if let Ok(mut sub) = client2.subscribe(subject.into()).await {
+                if let Ok(mut sub) = client2.subscribe(subject).await {
                     while let Some(msg) = sub.next().await {
                         if let Ok(message) = serde_json::from_slice::<Message>(&msg.payload) {
                             debug!("Received direct message from: {}", message.from);
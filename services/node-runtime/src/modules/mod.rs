@@ -6,5 +6,5 @@ pub mod action;
 
 pub use sensor::{SensorModule, SensorConfig, SensorReading};
 pub use brain::{BrainModule, Threat, Decision, ActionType as BrainActionType};
-pub use communication::{CommunicationModule, Message, MessageType};
-pub use action::{ActionModule, Action, ActionType, ActionStatus};
+pub use communication::{CommunicationModule, MessageType};
+pub use action::{ActionModule, ActionType};
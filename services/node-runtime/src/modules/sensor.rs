@@ -1,9 +1,9 @@
 //! Sensor Module - Eyes & Ears của Node
 //! Thu thập dữ liệu network traffic, system behavior, user activity
 use anyhow::Result;
-use tracing::{info, debug, warn};
+use tracing::{info, warn};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{mpsc, RwLock};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,6 +26,7 @@ pub struct SensorModule {
     config: SensorConfig,
     readings: Arc<RwLock<Vec<SensorReading>>>,
     enabled: bool,
+    sink: Option<mpsc::Sender<SensorReading>>, // downstream pipeline stage
 }
 
 #[derive(Debug, Clone)]
@@ -55,21 +56,35 @@ impl SensorModule {
             config,
             readings: Arc::new(RwLock::new(Vec::with_capacity(1000))),
             enabled: true,
+            sink: None,
         }
     }
 
+    /// Also forward every reading to `tx`; collection stops once the receiver is gone.
+    pub fn with_sink(mut self, tx: mpsc::Sender<SensorReading>) -> Self {
+        self.sink = Some(tx);
+        self
+    }
+
     pub async fn start(&self) -> Result<()> {
         info!("Starting sensor module");
         let readings = self.readings.clone();
         let config = self.config.clone();
+        let sink = self.sink.clone();
         
         tokio::spawn(async move {
             loop {
+                let mut batch = Vec::new();
                 if config.enable_network {
-                    Self::collect_network_data(&readings).await;
+                    batch.push(Self::collect_network_data(&readings).await);
                 }
                 if config.enable_system {
-                    Self::collect_system_data(&readings).await;
+                    batch.push(Self::collect_system_data(&readings).await);
+                }
+                if let Some(tx) = &sink {
+                    for reading in batch {
+                        if tx.send(reading).await.is_err() { info!("Sensor sink closed, stopping collection"); return; }
+                    }
                 }
                 tokio::time::sleep(std::time::Duration::from_millis(config.sampling_rate_ms)).await;
             }
@@ -78,7 +93,7 @@ impl SensorModule {
         Ok(())
     }
 
-    async fn collect_network_data(readings: &Arc<RwLock<Vec<SensorReading>>>) {
+    async fn collect_network_data(readings: &Arc<RwLock<Vec<SensorReading>>>) -> SensorReading {
         let reading = SensorReading {
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
@@ -93,10 +108,11 @@ impl SensorModule {
         if r.len() >= 1000 {
             r.remove(0);
         }
-        r.push(reading);
+        r.push(reading.clone());
+        reading
     }
 
-    async fn collect_system_data(readings: &Arc<RwLock<Vec<SensorReading>>>) {
+    async fn collect_system_data(readings: &Arc<RwLock<Vec<SensorReading>>>) -> SensorReading {
         let reading = SensorReading {
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
@@ -111,7 +127,8 @@ impl SensorModule {
         if r.len() >= 1000 {
             r.remove(0);
         }
-        r.push(reading);
+        r.push(reading.clone());
+        reading
     }
 
     pub async fn get_recent_readings(&self, count: usize) -> Vec<SensorReading> {
@@ -0,0 +1,180 @@
+//! Sensor -> Brain -> Communication -> Action pipeline.
+//!
+//! Stages are joined by bounded channels (`NODE_PIPELINE_CAPACITY`, default 1024), so a slow stage
+//! backpressures the ones before it. Brain, communication and action run `NODE_BRAIN_WORKERS`,
+//! `NODE_COMM_WORKERS` and `NODE_ACTION_WORKERS` workers (default 2/1/2) over a shared receiver.
+//! Shutdown closes the input channel (producers such as the sensor see their sends fail and stop);
+//! each stage drains its queue, exits, and closing its output stops the next stage.
+//!
+//! An optional `Inspector` (the WASM plugin host with `wasm_plugins`) sees every raw reading in the
+//! brain stage; a verdict it returns becomes a threat of type `plugin` next to the brain's own.
+
+use crate::modules::{ActionModule, ActionType, BrainActionType, BrainModule, CommunicationModule, Decision, MessageType, SensorReading, Threat};
+use crate::modules::brain::ThreatSeverity;
+use futures::future::BoxFuture;
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch, Mutex};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+#[derive(Debug, Clone)]
+pub struct PipelineConfig {
+    pub capacity: usize,
+    pub brain_workers: usize,
+    pub comm_workers: usize,
+    pub action_workers: usize,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self { Self { capacity: 1024, brain_workers: 2, comm_workers: 1, action_workers: 2 } }
+}
+
+impl PipelineConfig {
+    pub fn from_env() -> Self {
+        let d = Self::default();
+        let env_usize = |k: &str, d: usize| std::env::var(k).ok().and_then(|v| v.parse().ok()).unwrap_or(d).max(1);
+        Self {
+            capacity: env_usize("NODE_PIPELINE_CAPACITY", d.capacity),
+            brain_workers: env_usize("NODE_BRAIN_WORKERS", d.brain_workers),
+            comm_workers: env_usize("NODE_COMM_WORKERS", d.comm_workers),
+            action_workers: env_usize("NODE_ACTION_WORKERS", d.action_workers),
+        }
+    }
+}
+
+/// Action for a brain decision; `None` when the decision needs no local response.
+pub fn action_for(decision: &BrainActionType) -> Option<ActionType> {
+    match decision {
+        BrainActionType::Block => Some(ActionType::BlockIP),
+        BrainActionType::Quarantine => Some(ActionType::QuarantineFile),
+        BrainActionType::Monitor => Some(ActionType::CollectForensics),
+        BrainActionType::Alert | BrainActionType::Allow => None,
+    }
+}
+
+/// Out-of-band verdict on a reading's raw bytes; `None` means no opinion.
+pub type Inspector = Arc<dyn Fn(Vec<u8>) -> BoxFuture<'static, Option<BrainActionType>> + Send + Sync>;
+
+/// Threat + decision for an inspector verdict.
+fn inspected(action: BrainActionType) -> (Threat, Decision) {
+    let severity = match action {
+        BrainActionType::Block => ThreatSeverity::Critical,
+        BrainActionType::Quarantine => ThreatSeverity::High,
+        BrainActionType::Monitor => ThreatSeverity::Medium,
+        BrainActionType::Alert => ThreatSeverity::Low,
+        BrainActionType::Allow => ThreatSeverity::Info,
+    };
+    let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+    let threat = Threat { id: format!("threat-{}", uuid::Uuid::new_v4()), threat_type: "plugin".into(), confidence: 1.0, severity, timestamp, metadata: Default::default() };
+    (threat, Decision { action, confidence: 1.0, reasoning: "plugin verdict".into() })
+}
+
+type Shared<T> = Arc<Mutex<mpsc::Receiver<T>>>;
+
+/// Next queued item; once `stop` fires the channel is closed and only what is already buffered is returned.
+async fn next<T>(rx: &mut mpsc::Receiver<T>, stop: &mut Option<watch::Receiver<bool>>) -> Option<T> {
+    let Some(stop) = stop else { return rx.recv().await };
+    tokio::select! {
+        biased;
+        item = rx.recv() => return item,
+        _ = stop.wait_for(|s| *s) => {}
+    }
+    rx.close();
+    rx.recv().await
+}
+
+/// Spawn `n` workers pulling from one receiver; the receiver lock is held only while waiting.
+fn spawn_workers<T, F, Fut>(n: usize, rx: mpsc::Receiver<T>, stop: Option<watch::Receiver<bool>>, f: F) -> Vec<JoinHandle<()>>
+where T: Send + 'static, F: Fn(T) -> Fut + Clone + Send + 'static, Fut: std::future::Future<Output = ()> + Send {
+    let rx: Shared<T> = Arc::new(Mutex::new(rx));
+    (0..n).map(|_| {
+        let (rx, mut stop, f) = (rx.clone(), stop.clone(), f.clone());
+        tokio::spawn(async move {
+            loop {
+                let Some(item) = next(&mut *rx.lock().await, &mut stop).await else { break };
+                f(item).await;
+            }
+        })
+    }).collect()
+}
+
+pub struct Pipeline {
+    input: mpsc::Sender<SensorReading>,
+    stop: watch::Sender<bool>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Pipeline {
+    pub fn start(cfg: &PipelineConfig, brain: Arc<BrainModule>, comm: Arc<CommunicationModule>, action: Arc<ActionModule>, inspector: Option<Inspector>) -> Self {
+        let (input, readings) = mpsc::channel::<SensorReading>(cfg.capacity);
+        let (stop, stop_rx) = watch::channel(false);
+        let (decided_tx, decided) = mpsc::channel::<(Threat, Decision)>(cfg.capacity);
+        let (published_tx, published) = mpsc::channel::<(Threat, Decision)>(cfg.capacity);
+        let mut tasks = spawn_workers(cfg.brain_workers, readings, Some(stop_rx), move |reading: SensorReading| {
+            let (brain, out, inspector) = (brain.clone(), decided_tx.clone(), inspector.clone());
+            async move {
+                let verdict = match &inspector { Some(inspect) => inspect(reading.data.clone()).await, None => None };
+                let threats = match brain.analyze(&reading.data).await { Ok(t) => t, Err(e) => { warn!(error=?e, "brain analyze failed"); return } };
+                let decisions = match brain.decide(&threats).await { Ok(d) => d, Err(e) => { warn!(error=?e, "brain decide failed"); return } };
+                for (mut threat, decision) in threats.into_iter().zip(decisions).chain(verdict.map(inspected)) {
+                    for (k, v) in &reading.metadata { threat.metadata.entry(k.clone()).or_insert_with(|| v.clone()); }
+                    let _ = brain.remember(threat.clone()).await;
+                    if out.send((threat, decision)).await.is_err() { return; }
+                }
+            }
+        });
+        tasks.extend(spawn_workers(cfg.comm_workers, decided, None, move |(threat, decision): (Threat, Decision)| {
+            let (comm, out) = (comm.clone(), published_tx.clone());
+            async move {
+                match serde_json::to_vec(&(&threat, &decision)) {
+                    Ok(payload) => if let Err(e) = comm.broadcast(MessageType::Alert, payload).await { warn!(threat=%threat.id, error=?e, "decision broadcast failed") },
+                    Err(e) => warn!(threat=%threat.id, error=?e, "decision encode failed"),
+                }
+                let _ = out.send((threat, decision)).await;
+            }
+        }));
+        tasks.extend(spawn_workers(cfg.action_workers, published, None, move |(threat, decision): (Threat, Decision)| {
+            let action = action.clone();
+            async move {
+                let Some(kind) = action_for(&decision.action) else { debug!(threat=%threat.id, decision=?decision.action, "no local action"); return };
+                let target = threat.metadata.get("source").cloned().unwrap_or_else(|| threat.id.clone());
+                if let Err(e) = action.execute(kind, target).await { warn!(threat=%threat.id, error=?e, "action failed"); }
+            }
+        }));
+        info!(?cfg, "node pipeline started");
+        Self { input, stop, tasks }
+    }
+
+    /// Sender for sensor readings (see `SensorModule::with_sink`).
+    pub fn input(&self) -> mpsc::Sender<SensorReading> { self.input.clone() }
+
+    /// Stop accepting readings and wait until everything already queued has been acted on.
+    pub async fn shutdown(self) {
+        let _ = self.stop.send(true);
+        for t in self.tasks { let _ = t.await; }
+        info!("node pipeline stopped");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::sensor::SensorType;
+
+    #[tokio::test]
+    async fn reading_flows_through_all_stages_to_an_action() {
+        let action = Arc::new(ActionModule::new());
+        let pipeline = Pipeline::start(&PipelineConfig::default(), Arc::new(BrainModule::new()), Arc::new(CommunicationModule::offline("node-test".into())), action.clone(), None);
+        let reading = SensorReading { timestamp: 0, sensor_type: SensorType::NetworkTraffic, data: vec![0u8; 20_000], metadata: [("source".to_string(), "10.0.0.7".to_string())].into() };
+        let input = pipeline.input();
+        input.send(reading.clone()).await.unwrap();
+        input.send(SensorReading { data: vec![0u8; 10], ..reading }).await.unwrap(); // benign, no action
+        pipeline.shutdown().await;
+        assert!(input.is_closed(), "producers must see the pipeline stop");
+
+        let actions = action.get_active_actions().await;
+        assert_eq!(actions.len(), 1);
+        assert!(matches!(actions[0].action_type, ActionType::CollectForensics));
+        assert_eq!(actions[0].target, "10.0.0.7");
+    }
+}
@@ -0,0 +1,222 @@
+//! WASM plugin host.
+//!
+//! ABI (v1): a plugin exports `memory`, `alloc(len: i32) -> i32` and
+//! `process_event(ptr: i32, len: i32) -> i32`. For each event the host calls `alloc`, copies the
+//! event bytes to the returned offset and calls `process_event`, whose result is the verdict code
+//! (see `PluginVerdict`). Every `.wasm` in `WASM_PLUGIN_DIR` is instantiated once at load and the
+//! instance is reused for all events.
+//!
+//! Plugins are untrusted: each call gets `WASM_FUEL` fuel (default 10M), memory is capped at
+//! `WASM_MAX_MEMORY_MB` (default 64; growing past it traps) and the call is abandoned after
+//! `WASM_CALL_TIMEOUT_MS` (default 100). Traps and timeouts count `swarm_wasm_plugin_trap_total`
+//! and only fail that plugin's result; the other plugins still see the event.
+//!
+//! `inspector` plugs the host into the node pipeline: the strictest verdict across plugins
+//! (`Block` > `Alert` > `Allow`) drives the action; failed calls and unknown codes are no verdict.
+
+use crate::modules::BrainActionType;
+use crate::pipeline::Inspector;
+use anyhow::{anyhow, Context, Result};
+use opentelemetry::{global, metrics::Counter, KeyValue};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+use wasmtime::{Config, Engine, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+
+/// Fuel between cooperative yields, so a running call can be timed out.
+const FUEL_YIELD_INTERVAL: u64 = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginVerdict {
+    Allow,
+    Alert,
+    Block,
+    Unknown(i32),
+}
+
+impl PluginVerdict {
+    /// Pipeline action for this verdict; `None` for `Allow` and unknown codes.
+    pub fn action(self) -> Option<BrainActionType> {
+        match self { Self::Block => Some(BrainActionType::Block), Self::Alert => Some(BrainActionType::Alert), Self::Allow | Self::Unknown(_) => None }
+    }
+}
+
+impl From<i32> for PluginVerdict {
+    fn from(code: i32) -> Self {
+        match code { 0 => Self::Allow, 1 => Self::Alert, 2 => Self::Block, other => Self::Unknown(other) }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SandboxLimits {
+    pub fuel: u64,
+    pub max_memory_bytes: usize,
+    pub call_timeout: Duration,
+}
+
+impl SandboxLimits {
+    pub fn from_env() -> Self {
+        let env_u64 = |k: &str, d: u64| std::env::var(k).ok().and_then(|v| v.parse().ok()).unwrap_or(d);
+        Self {
+            fuel: env_u64("WASM_FUEL", 10_000_000),
+            max_memory_bytes: env_u64("WASM_MAX_MEMORY_MB", 64) as usize * 1024 * 1024,
+            call_timeout: Duration::from_millis(env_u64("WASM_CALL_TIMEOUT_MS", 100)),
+        }
+    }
+}
+
+pub struct WasmPlugin {
+    pub path: PathBuf,
+    store: Store<StoreLimits>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    process: TypedFunc<(i32, i32), i32>,
+}
+
+impl WasmPlugin {
+    async fn instantiate(engine: &Engine, path: &Path, limits: &SandboxLimits) -> Result<Self> {
+        let module = Module::from_file(engine, path).with_context(|| format!("compile wasm {path:?}"))?;
+        let mut store = Store::new(engine, StoreLimitsBuilder::new().memory_size(limits.max_memory_bytes).trap_on_grow_failure(true).build());
+        store.limiter(|l| l);
+        store.fuel_async_yield_interval(Some(FUEL_YIELD_INTERVAL))?;
+        store.set_fuel(limits.fuel)?;
+        let instance = Linker::new(engine).instantiate_async(&mut store, &module).await?;
+        let memory = instance.get_memory(&mut store, "memory").context("plugin does not export memory")?;
+        let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc").context("plugin export alloc(i32) -> i32")?;
+        let process = instance.get_typed_func::<(i32, i32), i32>(&mut store, "process_event").context("plugin export process_event(i32, i32) -> i32")?;
+        Ok(Self { path: path.to_path_buf(), store, memory, alloc, process })
+    }
+
+    /// One event through the plugin with a fresh fuel budget (shared by `alloc` and `process_event`).
+    pub async fn process_event(&mut self, event: &[u8], fuel: u64) -> Result<PluginVerdict> {
+        let len = i32::try_from(event.len()).context("event too large for plugin ABI")?;
+        self.store.set_fuel(fuel)?;
+        let ptr = self.alloc.call_async(&mut self.store, len).await?;
+        self.memory.write(&mut self.store, ptr as u32 as usize, event).context("plugin alloc returned out-of-bounds pointer")?;
+        Ok(self.process.call_async(&mut self.store, (ptr, len)).await?.into())
+    }
+}
+
+pub struct PluginHost {
+    plugins: Vec<WasmPlugin>,
+    limits: SandboxLimits,
+    traps: Counter<u64>,
+}
+
+impl PluginHost {
+    /// Instantiate every `.wasm` in `dir`; plugins that fail to load or lack the ABI are skipped.
+    pub async fn load_dir(dir: impl AsRef<Path>, limits: SandboxLimits) -> Result<Self> {
+        let engine = Engine::new(Config::new().async_support(true).consume_fuel(true))?;
+        let mut plugins = Vec::new();
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir.as_ref()).with_context(|| format!("read plugin dir {:?}", dir.as_ref()))?
+            .flatten().map(|e| e.path()).filter(|p| p.extension().and_then(|s| s.to_str()) == Some("wasm")).collect();
+        paths.sort();
+        for path in paths {
+            match WasmPlugin::instantiate(&engine, &path, &limits).await {
+                Ok(p) => { info!(?path, "loaded wasm plugin"); plugins.push(p); }
+                Err(e) => warn!(?path, error=?e, "skipping wasm plugin"),
+            }
+        }
+        let traps = global::meter("node-runtime").u64_counter("swarm_wasm_plugin_trap_total").with_description("WASM plugin calls aborted by a trap (incl. fuel/memory limits) or timeout").init();
+        Ok(Self { plugins, limits, traps })
+    }
+
+    pub fn len(&self) -> usize { self.plugins.len() }
+    pub fn is_empty(&self) -> bool { self.plugins.is_empty() }
+
+    /// Run `event` through every plugin, in load order.
+    pub async fn process_event(&mut self, event: &[u8]) -> Vec<(PathBuf, Result<PluginVerdict>)> {
+        let SandboxLimits { fuel, call_timeout, .. } = self.limits;
+        let mut results = Vec::with_capacity(self.plugins.len());
+        for p in &mut self.plugins {
+            let res = match tokio::time::timeout(call_timeout, p.process_event(event, fuel)).await {
+                Ok(Ok(v)) => Ok(v),
+                Ok(Err(e)) => { self.traps.add(1, &[KeyValue::new("reason", "trap")]); Err(e) }
+                Err(_) => { self.traps.add(1, &[KeyValue::new("reason", "timeout")]); Err(anyhow!("plugin call timed out after {call_timeout:?}")) }
+            };
+            if let Err(e) = &res { warn!(path=?p.path, error=%e, "wasm plugin call aborted"); }
+            results.push((p.path.clone(), res));
+        }
+        results
+    }
+}
+
+/// Pipeline hook running every reading through `host` (calls are serialized on the host).
+pub fn inspector(host: PluginHost) -> Inspector {
+    let host = Arc::new(tokio::sync::Mutex::new(host));
+    Arc::new(move |event: Vec<u8>| {
+        let host = host.clone();
+        Box::pin(async move {
+            let results = host.lock().await.process_event(&event).await;
+            let verdicts = results.into_iter().filter_map(|(_, r)| r.ok());
+            verdicts.max_by_key(|v| match v { PluginVerdict::Block => 2, PluginVerdict::Alert => 1, _ => 0 }).and_then(PluginVerdict::action)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn host_with(name: &str, plugins: &[(&str, &[u8])], limits: SandboxLimits) -> (PluginHost, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("node-runtime-plugins-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, bytes) in plugins { std::fs::write(dir.join(file), bytes).unwrap(); }
+        (PluginHost::load_dir(&dir, limits).await.unwrap(), dir)
+    }
+
+    const ECHO: &[u8] = include_bytes!("../tests/fixtures/echo_verdict.wasm");
+    const LIMITS: SandboxLimits = SandboxLimits { fuel: 1_000_000, max_memory_bytes: 1 << 20, call_timeout: Duration::from_secs(5) };
+
+    #[tokio::test]
+    async fn echo_plugin_returns_verdict_from_event() {
+        let (mut host, dir) = host_with("echo", &[("echo.wasm", ECHO), ("notes.txt", b"ignored")], LIMITS).await;
+        assert_eq!(host.len(), 1);
+        for (event, verdict) in [(&[2u8, 9, 9][..], PluginVerdict::Block), (&[1], PluginVerdict::Alert), (&[], PluginVerdict::Allow), (&[7], PluginVerdict::Unknown(7))] {
+            let results = host.process_event(event).await;
+            assert_eq!(results[0].1.as_ref().unwrap(), &verdict);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn infinite_loop_runs_out_of_fuel() {
+        let (mut host, dir) = host_with("spin", &[("a_echo.wasm", ECHO), ("b_spin.wasm", include_bytes!("../tests/fixtures/spin_forever.wasm"))], LIMITS).await;
+        for _ in 0..2 { // the spinning plugin stays loaded and fails again; echo keeps working
+            let results = host.process_event(&[2]).await;
+            assert_eq!(results[0].1.as_ref().unwrap(), &PluginVerdict::Block);
+            let err = results[1].1.as_ref().unwrap_err();
+            assert_eq!(err.downcast_ref::<wasmtime::Trap>(), Some(&wasmtime::Trap::OutOfFuel), "{err:?}");
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn memory_growth_past_limit_is_denied() {
+        let (mut host, dir) = host_with("grow", &[("a_echo.wasm", ECHO), ("b_grow.wasm", include_bytes!("../tests/fixtures/grow_memory.wasm"))], LIMITS).await;
+        let results = host.process_event(&[1]).await;
+        assert_eq!(results[0].1.as_ref().unwrap(), &PluginVerdict::Alert);
+        assert!(results[1].1.is_err(), "64 MiB grow must not pass a 1 MiB limit");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn block_verdict_drives_a_pipeline_action() {
+        use crate::modules::{ActionModule, ActionType, BrainModule, CommunicationModule, SensorReading, sensor::SensorType};
+        use crate::pipeline::{Pipeline, PipelineConfig};
+        let (host, dir) = host_with("pipeline", &[("echo.wasm", ECHO)], LIMITS).await;
+        let action = Arc::new(ActionModule::new());
+        let pipeline = Pipeline::start(&PipelineConfig::default(), Arc::new(BrainModule::new()), Arc::new(CommunicationModule::offline("node-test".into())), action.clone(), Some(inspector(host)));
+        let reading = SensorReading { timestamp: 0, sensor_type: SensorType::NetworkTraffic, data: vec![2, 0, 0], metadata: [("source".to_string(), "10.0.0.9".to_string())].into() };
+        pipeline.input().send(reading.clone()).await.unwrap(); // echo plugin: first byte 2 = Block
+        pipeline.input().send(SensorReading { data: vec![0], ..reading }).await.unwrap(); // Allow
+        pipeline.shutdown().await;
+
+        let actions = action.get_active_actions().await;
+        assert_eq!(actions.len(), 1, "only the blocked reading is acted on");
+        assert!(matches!(actions[0].action_type, ActionType::BlockIP));
+        assert_eq!(actions[0].target, "10.0.0.9");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
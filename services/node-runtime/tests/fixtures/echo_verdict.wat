@@ -0,0 +1,14 @@
+;; Test plugin for the node-runtime plugin ABI: returns the first event byte as its verdict.
+(module
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+  ;; bump allocator; the host writes the event at the returned offset
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (global.get $next) (local.get $len)))
+    (local.get $ptr))
+  (func (export "process_event") (param $ptr i32) (param $len i32) (result i32)
+    (if (result i32) (i32.eqz (local.get $len))
+      (then (i32.const 0))
+      (else (i32.load8_u (local.get $ptr))))))
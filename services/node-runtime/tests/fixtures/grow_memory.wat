@@ -0,0 +1,7 @@
+;; Test plugin for sandbox limits: tries to grow memory by 1024 pages (64 MiB) per event.
+(module
+  (memory (export "memory") 1)
+  (func (export "alloc") (param $len i32) (result i32) (i32.const 1024))
+  (func (export "process_event") (param $ptr i32) (param $len i32) (result i32)
+    (drop (memory.grow (i32.const 1024)))
+    (i32.const 0)))
@@ -0,0 +1,7 @@
+;; Test plugin for sandbox limits: never returns from process_event.
+(module
+  (memory (export "memory") 1)
+  (func (export "alloc") (param $len i32) (result i32) (i32.const 1024))
+  (func (export "process_event") (param $ptr i32) (param $len i32) (result i32)
+    (loop $spin (br $spin))
+    (i32.const 0)))
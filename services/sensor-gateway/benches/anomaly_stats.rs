@@ -0,0 +1,24 @@
+use criterion::{criterion_group, criterion_main, Criterion, BenchmarkId, black_box};
+
+#[path = "../src/detection/stats.rs"]
+#[allow(dead_code)]
+mod stats;
+use stats::FeatureStats;
+
+// Per-event Welford updates vs one vectorized batch update over the same rows.
+fn anomaly_stats(c: &mut Criterion) {
+    let mut group = c.benchmark_group("anomaly_stats");
+    for &batch in &[64usize, 512, 4096] {
+        let rows: Vec<Vec<f64>> = (0..batch).map(|i| { let x = i as f64; vec![x, x.sin(), (x * 0.1).cos(), x % 24.0] }).collect();
+        group.bench_with_input(BenchmarkId::new("incremental", batch), &rows, |b, rows| {
+            b.iter(|| { let mut s = FeatureStats::default(); rows.iter().for_each(|r| s.update(r)); black_box(s.count()) })
+        });
+        group.bench_with_input(BenchmarkId::new("batch", batch), &rows, |b, rows| {
+            b.iter(|| { let mut s = FeatureStats::default(); s.update_batch(rows); black_box(s.count()) })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, anomaly_stats);
+criterion_main!(benches);
@@ -0,0 +1,61 @@
+use criterion::{criterion_group, criterion_main, Criterion, BenchmarkId, black_box};
+use swarm_proto::ingestion::RawEvent;
+use prost::Message;
+
+#[path = "../src/buffer_pool.rs"]
+#[allow(dead_code)]
+mod buffer_pool;
+use buffer_pool::{BufferPool, encode_to_bytes};
+
+fn event(payload: Vec<u8>) -> RawEvent {
+    RawEvent { id: "bench-1".into(), observed_ts: 1, source_type: "file".into(), origin: "bench-host".into(), payload, content_type: "text/plain".into() }
+}
+
+// Steady-state encode of one event: fresh Vec allocations vs buffers recycled through the pool
+// vs the thread-local BytesMut path used by process_line (encoded Bytes dropped as NATS would).
+fn buffer_pool(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ingest_buffers");
+    let pool = BufferPool::new(64, 64 * 1024);
+    for &size in &[64usize, 1024, 16 * 1024] {
+        let line = "x".repeat(size);
+        group.bench_with_input(BenchmarkId::new("fresh", size), &line, |b, line| {
+            b.iter(|| {
+                let evt = event(line.as_bytes().to_vec());
+                let mut buf = Vec::with_capacity(evt.encoded_len());
+                evt.encode(&mut buf).unwrap();
+                black_box(buf.len())
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("pooled", size), &line, |b, line| {
+            b.iter(|| {
+                let mut payload = pool.get(line.len());
+                payload.extend_from_slice(line.as_bytes());
+                let mut evt = event(payload);
+                let mut buf = pool.get(evt.encoded_len());
+                evt.encode(&mut buf).unwrap();
+                let len = buf.len();
+                pool.put(std::mem::take(&mut evt.payload));
+                pool.put(buf);
+                black_box(len)
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("bytesmut", size), &line, |b, line| {
+            b.iter(|| {
+                let mut payload = pool.get(line.len());
+                payload.extend_from_slice(line.as_bytes());
+                let mut evt = event(payload);
+                let bytes = encode_to_bytes(&evt);
+                pool.put(std::mem::take(&mut evt.payload));
+                black_box(bytes.len())
+            })
+        });
+    }
+    group.finish();
+    // steady state never allocates: at most a payload and an encode buffer are out at once
+    let (allocations, reuses) = pool.stats();
+    assert!(allocations <= 2, "pool allocated {allocations} buffers");
+    assert!(reuses > 0, "pool was never reused");
+}
+
+criterion_group!(benches, buffer_pool);
+criterion_main!(benches);
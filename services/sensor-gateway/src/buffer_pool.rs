@@ -0,0 +1,124 @@
+//! Reusable byte buffers for the ingest hot path.
+//!
+//! A bounded freelist of `Vec<u8>`: `get` hands out a cleared buffer (allocating only when the
+//! list is empty) and `put` returns it. At most `SENSOR_BUFFER_POOL_SIZE` (default 64) buffers
+//! are kept and buffers grown past `SENSOR_BUFFER_POOL_MAX_BYTES` (default 64 KiB) are dropped,
+//! so one oversized event cannot pin memory. `SENSOR_BUFFER_POOL_SIZE=0` disables pooling.
+//!
+//! Encoded messages go through `encode_to_bytes` instead: a per-thread `BytesMut` whose written
+//! region is split off as `Bytes` and published without a copy; once NATS drops it the
+//! allocation is reclaimed by the next `reserve`.
+
+use bytes::{Bytes, BytesMut};
+use parking_lot::Mutex;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use once_cell::sync::Lazy;
+
+pub static BUFFER_POOL: Lazy<BufferPool> = Lazy::new(|| {
+    let size = std::env::var("SENSOR_BUFFER_POOL_SIZE").ok().and_then(|v| v.parse().ok()).unwrap_or(64);
+    let max_bytes = std::env::var("SENSOR_BUFFER_POOL_MAX_BYTES").ok().and_then(|v| v.parse().ok()).unwrap_or(64 * 1024);
+    BufferPool::new(size, max_bytes)
+});
+
+pub struct BufferPool {
+    free: Mutex<Vec<Vec<u8>>>,
+    max_buffers: usize,
+    max_bytes: usize,
+    allocations: AtomicU64,
+    reuses: AtomicU64,
+}
+
+impl BufferPool {
+    pub fn new(max_buffers: usize, max_bytes: usize) -> Self {
+        Self { free: Mutex::new(Vec::with_capacity(max_buffers)), max_buffers, max_bytes, allocations: AtomicU64::new(0), reuses: AtomicU64::new(0) }
+    }
+
+    /// Empty buffer with at least `capacity` bytes reserved.
+    pub fn get(&self, capacity: usize) -> Vec<u8> {
+        match self.free.lock().pop() {
+            Some(mut buf) => { self.reuses.fetch_add(1, Ordering::Relaxed); buf.reserve(capacity); buf }
+            None => { self.allocations.fetch_add(1, Ordering::Relaxed); Vec::with_capacity(capacity) }
+        }
+    }
+
+    pub fn put(&self, mut buf: Vec<u8>) {
+        if buf.capacity() == 0 || buf.capacity() > self.max_bytes { return; }
+        buf.clear();
+        let mut free = self.free.lock();
+        if free.len() < self.max_buffers { free.push(buf); }
+    }
+
+    /// (fresh allocations, reuses) served by `get`.
+    pub fn stats(&self) -> (u64, u64) { (self.allocations.load(Ordering::Relaxed), self.reuses.load(Ordering::Relaxed)) }
+}
+
+thread_local! {
+    static ENCODE_BUF: RefCell<BytesMut> = RefCell::new(BytesMut::new());
+}
+
+/// Protobuf-encode `msg` into the thread's reusable buffer and return the frozen bytes.
+pub fn encode_to_bytes<M: prost::Message>(msg: &M) -> Bytes {
+    ENCODE_BUF.with(|cell| {
+        let mut buf = cell.borrow_mut();
+        buf.reserve(msg.encoded_len());
+        msg.encode(&mut *buf).expect("reserved encoded_len bytes");
+        buf.split().freeze()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffers_are_reused_cleared_and_bounded() {
+        let pool = BufferPool::new(1, 16);
+        let mut a = pool.get(8);
+        a.extend_from_slice(b"payload");
+        pool.put(a);
+        let b = pool.get(4);
+        assert!(b.is_empty() && b.capacity() >= 8);
+        assert_eq!(pool.stats(), (1, 1));
+        pool.put(vec![0; 32]); // over max_bytes: dropped
+        pool.put(b);
+        pool.put(Vec::with_capacity(8)); // pool full: dropped
+        assert_eq!(pool.free.lock().len(), 1);
+        let disabled = BufferPool::new(0, 16);
+        disabled.put(disabled.get(4));
+        assert_eq!(disabled.stats(), (1, 0));
+        let _ = disabled.get(4);
+        assert_eq!(disabled.stats(), (2, 0));
+    }
+
+    #[test]
+    fn pooled_encoding_matches_fresh_allocation() {
+        use prost::Message;
+        let pool = BufferPool::new(4, 1024);
+        pool.put(b"stale bytes from a previous event".to_vec());
+        pool.put(vec![0xff; 64]);
+        let line = "synthetic-event-7 MALICIOUS";
+        let mut payload = pool.get(line.len());
+        payload.extend_from_slice(line.as_bytes());
+        let evt = swarm_proto::ingestion::RawEvent { id: "1-2".into(), observed_ts: 1, source_type: "file".into(), origin: "host".into(), payload, content_type: "text/plain".into() };
+        let mut buf = pool.get(evt.encoded_len());
+        evt.encode(&mut buf).unwrap();
+        assert_eq!(buf, evt.encode_to_vec());
+        assert_eq!(evt.payload, line.as_bytes());
+        assert_eq!(pool.stats(), (0, 2));
+    }
+
+    #[test]
+    fn thread_local_encoding_is_byte_identical() {
+        use prost::Message;
+        let evt = |i: u64, payload: &str| swarm_proto::ingestion::RawEvent { id: format!("{i}-x"), observed_ts: i, source_type: "file".into(), origin: "host".into(), payload: payload.as_bytes().to_vec(), content_type: "text/plain".into() };
+        let first = evt(1, "a much longer first payload to grow the buffer");
+        let held = encode_to_bytes(&first); // still alive while the next events encode
+        assert_eq!(&held[..], &first.encode_to_vec()[..]);
+        for i in 2..50 {
+            let e = evt(i, &"p".repeat(i as usize));
+            assert_eq!(&encode_to_bytes(&e)[..], &e.encode_to_vec()[..]);
+        }
+        assert_eq!(&held[..], &first.encode_to_vec()[..]);
+    }
+}
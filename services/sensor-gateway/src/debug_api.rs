@@ -0,0 +1,124 @@
+//! Debug HTTP endpoint for live rule stats: `GET /debug/rules`.
+//!
+//! Served on its own port (`DETECTION_DEBUG_PORT`; not started when unset) so it never shares
+//! exposure with `/metrics`. With `DETECTION_DEBUG_API_KEY` set, requests must send it as
+//! `x-api-key` or `Authorization: Bearer`. All requests share a 60s sliding-window limit of
+//! `DETECTION_DEBUG_RATE_LIMIT` (default 30); unauthorized attempts count against it.
+//! The body also carries the anomaly detector's window counts and adaptive baseline.
+
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use axum::{extract::State, http::{HeaderMap, StatusCode}, response::{IntoResponse, Response}, routing::get, Json, Router};
+use parking_lot::Mutex;
+use crate::detection::DetectionEngine;
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+pub struct DebugState {
+    engine: DetectionEngine,
+    api_key: Option<String>,
+    limit: usize,
+    recent: Mutex<VecDeque<Instant>>, // request times inside WINDOW
+}
+
+impl DebugState {
+    pub fn new(engine: DetectionEngine, api_key: Option<String>, limit: usize) -> Self {
+        Self { engine, api_key: api_key.filter(|k| !k.is_empty()), limit, recent: Mutex::new(VecDeque::new()) }
+    }
+
+    pub fn from_env(engine: DetectionEngine) -> Self {
+        let limit = std::env::var("DETECTION_DEBUG_RATE_LIMIT").ok().and_then(|v| v.parse().ok()).unwrap_or(30);
+        Self::new(engine, std::env::var("DETECTION_DEBUG_API_KEY").ok(), limit)
+    }
+
+    fn admit(&self) -> bool {
+        let now = Instant::now();
+        let mut recent = self.recent.lock();
+        while recent.front().is_some_and(|t| now.duration_since(*t) >= WINDOW) { recent.pop_front(); }
+        if recent.len() >= self.limit { return false; }
+        recent.push_back(now);
+        true
+    }
+
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        let Some(expected) = &self.api_key else { return true };
+        let presented = headers.get("x-api-key").and_then(|v| v.to_str().ok())
+            .or_else(|| headers.get("authorization").and_then(|v| v.to_str().ok()).and_then(|v| v.strip_prefix("Bearer ")));
+        presented.is_some_and(|p| constant_time_eq(p.as_bytes(), expected.as_bytes()))
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub fn router(state: Arc<DebugState>) -> Router {
+    Router::new().route("/debug/rules", get(rules_handler)).with_state(state)
+}
+
+async fn rules_handler(State(st): State<Arc<DebugState>>, headers: HeaderMap) -> Response {
+    if !st.admit() { return (StatusCode::TOO_MANY_REQUESTS, "rate limited").into_response(); }
+    if !st.authorized(&headers) { return (StatusCode::UNAUTHORIZED, "missing or invalid api key").into_response(); }
+    let rules = &st.engine.rules;
+    Json(serde_json::json!({
+        "version": rules.version_hash.read().clone(),
+        "loaded_ts": *rules.loaded_ts.read(),
+        "rules": st.engine.rule_stats(),
+        "anomaly": st.engine.anomaly.stats(),
+    })).into_response()
+}
+
+/// Serve `/debug/rules` when `DETECTION_DEBUG_PORT` is set.
+pub async fn spawn_from_env(engine: DetectionEngine) -> anyhow::Result<()> {
+    let Some(port) = std::env::var("DETECTION_DEBUG_PORT").ok().and_then(|v| v.parse::<u16>().ok()) else { return Ok(()) };
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!(?addr, "detection debug endpoint listening");
+    let app = router(Arc::new(DebugState::from_env(engine)));
+    tokio::spawn(async move { if let Err(e) = axum::serve(listener, app).await { tracing::error!(error=?e, "debug endpoint failed"); } });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use tower::ServiceExt;
+    use crate::detection::{RuleSet, AnomalyDetector, anomaly::AnomalyConfig};
+
+    async fn get(app: &Router, key: Option<&str>) -> (StatusCode, serde_json::Value) {
+        let mut req = Request::get("/debug/rules");
+        if let Some(k) = key { req = req.header("x-api-key", k); }
+        let resp = app.clone().oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+        let status = resp.status();
+        let body = to_bytes(resp.into_body(), 1 << 20).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+    }
+
+    #[tokio::test]
+    async fn requires_key_and_returns_ruleset_version() {
+        let rules = RuleSet::new();
+        rules.swap(vec![], "bundle-7f3a".into());
+        let engine = DetectionEngine::new(rules, AnomalyDetector::new(AnomalyConfig::default()), true, false, true);
+        let app = router(Arc::new(DebugState::new(engine, Some("s3cret".into()), 30)));
+
+        assert_eq!(get(&app, None).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(get(&app, Some("wrong")).await.0, StatusCode::UNAUTHORIZED);
+        let (status, body) = get(&app, Some("s3cret")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["version"], "bundle-7f3a");
+        assert!(body["rules"].is_array());
+    }
+
+    #[tokio::test]
+    async fn sliding_window_limits_requests() {
+        let engine = DetectionEngine::new(RuleSet::new(), AnomalyDetector::new(AnomalyConfig::default()), true, false, true);
+        let app = router(Arc::new(DebugState::new(engine, None, 2)));
+        assert_eq!(get(&app, None).await.0, StatusCode::OK);
+        assert_eq!(get(&app, None).await.0, StatusCode::OK);
+        assert_eq!(get(&app, None).await.0, StatusCode::TOO_MANY_REQUESTS);
+    }
+}
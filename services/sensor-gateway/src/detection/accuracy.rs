@@ -0,0 +1,73 @@
+//! Running detection accuracy against the synthetic ground truth (lines containing `MALICIOUS`).
+//!
+//! OTel counters cannot be read back, so TP/FP/FN are also tallied here and the ratio gauges
+//! (`swarm_detection_false_positive_ratio`, `swarm_detection_detection_rate`) are derived from them.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub const GROUND_TRUTH_TOKEN: &str = "MALICIOUS";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome { TruePositive, FalsePositive, FalseNegative }
+
+#[derive(Default)]
+pub struct DetectionAccuracy {
+    tp: AtomicU64,
+    fp: AtomicU64,
+    fn_: AtomicU64,
+}
+
+impl DetectionAccuracy {
+    pub fn new() -> Self { Self::default() }
+
+    /// Classify a scanned line; `None` for a benign line nothing fired on.
+    pub fn record(&self, line: &str, detected: bool) -> Option<Outcome> {
+        let (ctr, outcome) = match (line.contains(GROUND_TRUTH_TOKEN), detected) {
+            (true, true) => (&self.tp, Outcome::TruePositive),
+            (false, true) => (&self.fp, Outcome::FalsePositive),
+            (true, false) => (&self.fn_, Outcome::FalseNegative),
+            (false, false) => return None,
+        };
+        ctr.fetch_add(1, Ordering::Relaxed);
+        Some(outcome)
+    }
+
+    /// (tp, fp, fn)
+    pub fn counts(&self) -> (u64, u64, u64) { (self.tp.load(Ordering::Relaxed), self.fp.load(Ordering::Relaxed), self.fn_.load(Ordering::Relaxed)) }
+
+    /// fp / (fp + tp)
+    pub fn fp_ratio(&self) -> Option<f64> { let (tp, fp, _) = self.counts(); ratio(fp, fp + tp) }
+
+    /// tp / (tp + fn)
+    pub fn detection_rate(&self) -> Option<f64> { let (tp, _, fn_) = self.counts(); ratio(tp, tp + fn_) }
+}
+
+fn ratio(num: u64, den: u64) -> Option<f64> { (den > 0).then(|| num as f64 / den as f64) }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detection::{rules::{CompiledRule, Matcher}, DetectionRule, DetectionEngine, RuleSet, AnomalyDetector, anomaly::AnomalyConfig};
+
+    fn rule(id: &str, pattern: &str) -> CompiledRule {
+        CompiledRule { raw: DetectionRule { id: id.into(), pattern: pattern.into(), severity: Some("high".into()), action: None, shadow: false, match_type: Default::default() }, matcher: Matcher::Regex(regex::Regex::new(pattern).unwrap()) }
+    }
+
+    #[test]
+    fn ratios_follow_classified_lines() {
+        let rules = RuleSet::new();
+        rules.swap(vec![rule("exec", "MALICIOUS exec"), rule("noisy", "login")], "h".into());
+        let engine = DetectionEngine::new(rules, AnomalyDetector::new(AnomalyConfig::default()), true, false, true);
+        let acc = DetectionAccuracy::new();
+        assert_eq!((acc.fp_ratio(), acc.detection_rate()), (None, None));
+
+        let lines = ["MALICIOUS exec 1", "MALICIOUS exec 2", "benign heartbeat", "login from admin", "MALICIOUS exec 3", "MALICIOUS quiet beacon", "benign"];
+        let outcomes: Vec<_> = lines.iter().map(|l| acc.record(l, !engine.scan(l).is_empty())).collect();
+        assert_eq!(outcomes[2], None);
+        assert_eq!(outcomes[3], Some(Outcome::FalsePositive));
+        assert_eq!(outcomes[5], Some(Outcome::FalseNegative));
+        assert_eq!(acc.counts(), (3, 1, 1));
+        assert_eq!(acc.fp_ratio(), Some(0.25));
+        assert_eq!(acc.detection_rate(), Some(0.75));
+    }
+}
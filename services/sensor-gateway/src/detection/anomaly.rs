@@ -1,12 +1,33 @@
+//! Event-rate anomaly detection.
+//!
+//! By default the 1m rate is compared with the 5m average (`threshold_ratio`). With
+//! `ANOMALY_EWMA_HALF_LIFE_MINS` set, the 1m rate is sampled once a minute into an adaptive
+//! `SeasonalBaseline` (hourly buckets unless `ANOMALY_SEASONAL=0`) and flagged when its z-score
+//! reaches `ANOMALY_Z_THRESHOLD` (default 3); the ratio check covers the warm-up.
+//!
+//! Independently of the rate, an event whose feature vector lies at least
+//! `ANOMALY_MAHALANOBIS_THRESHOLD` (default 6; `0` disables) diagonal Mahalanobis units from the
+//! running per-feature mean is flagged. It is scored against the statistics before it is folded in.
+//! `record_batch` does the same for a batch under one lock with a vectorized statistics update.
+//!
+//! Nothing is flagged until `ANOMALY_WARMUP_SAMPLES` (default 100) events have been recorded, so
+//! a cold start does not pollute the detection metrics.
+
 use std::collections::VecDeque;
 use parking_lot::Mutex;
-use std::time::{Instant};
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use super::stats::FeatureStats;
+use super::baseline::{SeasonalBaseline, hour_of_day};
 
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone, Debug, Serialize)]
 pub struct AnomalyStats {
     pub window_1m: u64,
     pub window_5m: u64,
     pub window_15m: u64,
+    pub baseline: Option<f64>, // adaptive baseline (events/min) for the current hour, once warm
+    pub variance: Option<f64>,
+    pub warmed_up: bool,
 }
 
 #[derive(Clone)]
@@ -16,34 +37,116 @@ pub struct AnomalyDetector {
 }
 
 struct Inner {
-    events: VecDeque<(Instant, usize)>, // (time, size)
+    events: VecDeque<Instant>, // arrival times within the 15m window; rates only need timestamps
     last_prune: Instant,
+    stats: FeatureStats, // running per-feature mean/variance over every recorded event
+    baseline: Option<SeasonalBaseline>,
+    last_sample: Instant,
+    seen: u64, // events recorded since start, for the warm-up
 }
 
 #[derive(Clone, Copy)]
 pub struct AnomalyConfig {
     pub threshold_ratio: f64, // ratio vs 5m baseline considered anomaly
     pub min_events: u64,
+    pub ewma_half_life_mins: Option<f64>, // enables the adaptive baseline
+    pub seasonal: bool,
+    pub z_threshold: f64,
+    pub baseline_min_samples: u64,
+    pub warmup_samples: u64,
+    pub mahalanobis_threshold: Option<f64>, // feature-outlier distance; None disables
 }
 
 impl Default for AnomalyConfig {
-    fn default() -> Self { Self { threshold_ratio: 2.5, min_events: 50 } }
+    fn default() -> Self { Self { threshold_ratio: 2.5, min_events: 50, ewma_half_life_mins: None, seasonal: true, z_threshold: 3.0, baseline_min_samples: 30, warmup_samples: 100, mahalanobis_threshold: Some(6.0) } }
+}
+
+impl AnomalyConfig {
+    pub fn from_env() -> Self {
+        let d = Self::default();
+        let var = |k: &str| std::env::var(k).ok();
+        Self {
+            ewma_half_life_mins: var("ANOMALY_EWMA_HALF_LIFE_MINS").and_then(|v| v.parse().ok()).filter(|h: &f64| *h > 0.0),
+            seasonal: var("ANOMALY_SEASONAL").map(|v| v != "0" && !v.eq_ignore_ascii_case("false")).unwrap_or(d.seasonal),
+            z_threshold: var("ANOMALY_Z_THRESHOLD").and_then(|v| v.parse().ok()).unwrap_or(d.z_threshold),
+            warmup_samples: var("ANOMALY_WARMUP_SAMPLES").and_then(|v| v.parse().ok()).unwrap_or(d.warmup_samples),
+            mahalanobis_threshold: match var("ANOMALY_MAHALANOBIS_THRESHOLD").and_then(|v| v.parse::<f64>().ok()) { Some(t) => Some(t).filter(|t| *t > 0.0), None => d.mahalanobis_threshold },
+            ..d
+        }
+    }
 }
 
 impl AnomalyDetector {
     pub fn new(cfg: AnomalyConfig) -> Self {
-        Self { inner: std::sync::Arc::new(Mutex::new(Inner { events: VecDeque::new(), last_prune: Instant::now() })), cfg }
+        let baseline = cfg.ewma_half_life_mins.map(|h| SeasonalBaseline::new(h, cfg.seasonal, cfg.baseline_min_samples));
+        Self { inner: std::sync::Arc::new(Mutex::new(Inner { events: VecDeque::new(), last_prune: Instant::now(), stats: FeatureStats::default(), baseline, last_sample: Instant::now(), seen: 0 })), cfg }
+    }
+
+    /// True once `warmup_samples` events have been recorded.
+    pub fn is_warmed_up(&self) -> bool { self.inner.lock().seen >= self.cfg.warmup_samples }
+
+    /// Current window counts plus the adaptive baseline for this hour (when enabled and warm).
+    pub fn stats(&self) -> AnomalyStats {
+        let guard = self.inner.lock();
+        let mut stats = compute_stats(&guard.events);
+        stats.warmed_up = guard.seen >= self.cfg.warmup_samples;
+        if let Some(b) = guard.baseline.as_ref().and_then(|b| b.current(hour_of_day())) {
+            stats.baseline = Some(b.mean());
+            stats.variance = Some(b.variance());
+        }
+        stats
+    }
+
+    pub fn record(&self, size: usize) -> Option<bool> { self.record_features(vec![size as f64]) }
+
+    pub fn record_features(&self, features: Vec<f64>) -> Option<bool> { // returns Some(is_anomaly)
+        let now = Instant::now();
+        let mut guard = self.inner.lock();
+        let outlier = self.is_outlier(&guard.stats, &features, guard.seen + 1);
+        guard.stats.update(&features);
+        guard.events.push_back(now);
+        guard.seen += 1;
+        self.evaluate(&mut guard, now).map(|rate| rate || outlier)
     }
 
-    pub fn record(&self, size: usize) -> Option<bool> { // returns Some(is_anomaly)
+    /// Record a batch of feature vectors under one lock with a vectorized statistics update.
+    /// Returns is_anomaly per row: the row is a feature outlier against the statistics before the
+    /// batch, or the event rate after the whole batch is anomalous.
+    pub fn record_batch(&self, batch: Vec<Vec<f64>>) -> Vec<bool> {
         let now = Instant::now();
         let mut guard = self.inner.lock();
-        guard.events.push_back((now, size));
+        let seen = guard.seen;
+        let outliers: Vec<bool> = batch.iter().enumerate().map(|(i, f)| self.is_outlier(&guard.stats, f, seen + i as u64 + 1)).collect();
+        guard.stats.update_batch(&batch);
+        guard.seen += batch.len() as u64;
+        guard.events.extend(std::iter::repeat_n(now, batch.len()));
+        let rate = self.evaluate(&mut guard, now).unwrap_or(false);
+        outliers.into_iter().map(|outlier| rate || outlier).collect()
+    }
+
+    /// `features` is at least `mahalanobis_threshold` from the running mean; never during warm-up
+    /// (`seen` counts the event being scored).
+    fn is_outlier(&self, stats: &FeatureStats, features: &[f64], seen: u64) -> bool {
+        let Some(threshold) = self.cfg.mahalanobis_threshold else { return false };
+        seen > self.cfg.warmup_samples && matches!(stats.mahalanobis(features), Some(d) if d >= threshold)
+    }
+
+    fn evaluate(&self, guard: &mut Inner, now: Instant) -> Option<bool> {
         if now.duration_since(guard.last_prune).as_secs() > 10 {
             prune(&mut guard.events, 60*15);
             guard.last_prune = now;
         }
         let stats = compute_stats(&guard.events);
+        if let Some(baseline) = guard.baseline.as_mut() {
+            let (hour, rate) = (hour_of_day(), stats.window_1m as f64);
+            let z = baseline.z_score(hour, rate);
+            if now.duration_since(guard.last_sample) >= Duration::from_secs(60) {
+                baseline.observe(hour, rate);
+                guard.last_sample = now;
+            }
+            if let Some(z) = z { return Some(z >= self.cfg.z_threshold && guard.seen >= self.cfg.warmup_samples); }
+        }
+        if guard.seen < self.cfg.warmup_samples { return Some(false); }
         if stats.window_5m >= self.cfg.min_events {
             let short = stats.window_1m as f64;
             let mid = (stats.window_5m as f64 / 5.0).max(1.0);
@@ -53,20 +156,50 @@ impl AnomalyDetector {
     }
 }
 
-fn prune(events: &mut VecDeque<(Instant, usize)>, horizon_secs: u64) {
+fn prune(events: &mut VecDeque<Instant>, horizon_secs: u64) {
     let cutoff = Instant::now() - std::time::Duration::from_secs(horizon_secs);
-    while let Some((t,_)) = events.front() { if *t < cutoff { events.pop_front(); } else { break; } }
+    while let Some(t) = events.front() { if *t < cutoff { events.pop_front(); } else { break; } }
 }
 
-fn compute_stats(events: &VecDeque<(Instant, usize)>) -> AnomalyStats {
+fn compute_stats(events: &VecDeque<Instant>) -> AnomalyStats {
     let now = Instant::now();
     let mut s1=0; let mut s5=0; let mut s15=0;
-    for (t,_) in events.iter().rev() {
+    for t in events.iter().rev() {
         let age = now.duration_since(*t).as_secs();
         if age <= 60 { s1+=1; }
         if age <= 60*5 { s5+=1; }
         if age <= 60*15 { s15+=1; }
         if age > 60*15 { break; }
     }
-    AnomalyStats { window_1m: s1, window_5m: s5, window_15m: s15 }
+    AnomalyStats { window_1m: s1, window_5m: s5, window_15m: s15, ..Default::default() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_anomalies_until_warmed_up() {
+        // every event lands in the last minute, so the 1m/5m ratio (5.0) trips as soon as it is allowed to
+        let det = AnomalyDetector::new(AnomalyConfig { min_events: 1, warmup_samples: 10, ..Default::default() });
+        for _ in 0..9 { assert_eq!(det.record(100), Some(false)); }
+        assert!(!det.is_warmed_up() && !det.stats().warmed_up);
+        assert_eq!(det.record(100), Some(true));
+        assert!(det.is_warmed_up() && det.stats().warmed_up);
+        assert_eq!(det.record_batch(vec![vec![1.0]; 5]), vec![true; 5]);
+    }
+
+    #[test]
+    fn feature_outliers_are_flagged_per_row() {
+        // min_events out of reach: only the feature distance can flag
+        let det = AnomalyDetector::new(AnomalyConfig { min_events: u64::MAX, warmup_samples: 200, ..Default::default() });
+        for i in 0..200 { assert_eq!(det.record_features(vec![100.0 + (i % 10) as f64, 0.5]), Some(false)); }
+        assert_eq!(det.record_features(vec![104.0, 0.5]), Some(false));
+        assert_eq!(det.record_features(vec![400.0, 0.5]), Some(true));
+        assert_eq!(det.record_batch(vec![vec![103.0, 0.5], vec![5_000.0, 0.5], vec![106.0, 0.5]]), vec![false, true, false]);
+
+        let off = AnomalyDetector::new(AnomalyConfig { min_events: u64::MAX, warmup_samples: 0, mahalanobis_threshold: None, ..Default::default() });
+        for i in 0..20 { off.record_features(vec![(i % 3) as f64]); }
+        assert_eq!(off.record_features(vec![1e9]), Some(false));
+    }
 }
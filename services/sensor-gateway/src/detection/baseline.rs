@@ -0,0 +1,111 @@
+//! Adaptive event-rate baseline for the anomaly detector.
+//!
+//! `Ewma` tracks an exponentially weighted mean/variance whose weight halves every `half_life`
+//! samples. `SeasonalBaseline` keeps one global `Ewma` plus, optionally, one per hour of day, so a
+//! diurnal peak is scored against what that hour normally looks like rather than the daily mean.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Ewma {
+    count: u64,
+    mean: f64,
+    variance: f64,
+}
+
+impl Ewma {
+    pub fn count(&self) -> u64 { self.count }
+    pub fn mean(&self) -> f64 { self.mean }
+    pub fn variance(&self) -> f64 { self.variance }
+
+    /// Fold in `x` with smoothing factor `alpha` (the first sample seeds the mean).
+    pub fn update(&mut self, x: f64, alpha: f64) {
+        self.count += 1;
+        if self.count == 1 { self.mean = x; return; }
+        let diff = x - self.mean;
+        self.mean += alpha * diff;
+        self.variance = (1.0 - alpha) * (self.variance + alpha * diff * diff);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SeasonalBaseline {
+    alpha: f64,
+    min_samples: u64,
+    global: Ewma,
+    hourly: Option<Box<[Ewma; 24]>>,
+}
+
+impl SeasonalBaseline {
+    pub fn new(half_life: f64, seasonal: bool, min_samples: u64) -> Self {
+        let alpha = 1.0 - 0.5f64.powf(1.0 / half_life.max(1.0));
+        Self { alpha, min_samples, global: Ewma::default(), hourly: seasonal.then(|| Box::new([Ewma::default(); 24])) }
+    }
+
+    pub fn observe(&mut self, hour: usize, x: f64) {
+        self.global.update(x, self.alpha);
+        if let Some(h) = &mut self.hourly { h[hour % 24].update(x, self.alpha); }
+    }
+
+    /// Baseline used for `hour`: the hourly bucket once warm, else the global one. `None` while
+    /// neither has `min_samples`.
+    pub fn current(&self, hour: usize) -> Option<Ewma> {
+        let bucket = self.hourly.as_ref().map(|h| h[hour % 24]).filter(|b| b.count >= self.min_samples);
+        bucket.or(Some(self.global).filter(|g| g.count >= self.min_samples))
+    }
+
+    /// z-score of `x` against the baseline for `hour`; std is floored at 1 event so a perfectly
+    /// flat history does not turn a single extra event into an anomaly.
+    pub fn z_score(&self, hour: usize, x: f64) -> Option<f64> {
+        self.current(hour).map(|b| (x - b.mean) / b.variance.sqrt().max(1.0))
+    }
+}
+
+/// UTC hour of day for the seasonal buckets.
+pub fn hour_of_day() -> usize {
+    let secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    ((secs / 3600) % 24) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // per-minute event counts: quiet nights, a sharp business-hours peak, a little jitter
+    fn diurnal(minute: usize) -> f64 {
+        let phase = (minute % 1440) as f64 / 1440.0 * std::f64::consts::TAU;
+        let jitter = ((minute * 7919) % 11) as f64 - 5.0;
+        20.0 + 200.0 * (phase - std::f64::consts::FRAC_PI_2).sin().max(0.0).powi(8) + jitter
+    }
+
+    #[test]
+    fn seasonal_baseline_flags_fewer_peaks_than_static_threshold() {
+        let z_threshold = 3.0;
+        let mut seasonal = SeasonalBaseline::new(30.0, true, 30);
+        let mut flat = SeasonalBaseline::new(30.0 * 24.0, false, 30);
+        let (mut seasonal_hits, mut static_hits) = (0, 0);
+        for minute in 0..1440 * 4 {
+            let (hour, x) = ((minute / 60) % 24, diurnal(minute));
+            if minute >= 1440 * 3 { // score the last day after three days of training
+                if seasonal.z_score(hour, x).is_some_and(|z| z >= z_threshold) { seasonal_hits += 1; }
+                if flat.z_score(hour, x).is_some_and(|z| z >= z_threshold) { static_hits += 1; }
+            }
+            seasonal.observe(hour, x);
+            flat.observe(hour, x);
+        }
+        assert!(static_hits >= 20, "static threshold should misfire at peak, got {static_hits}");
+        assert!(seasonal_hits * 10 < static_hits, "seasonal {seasonal_hits} vs static {static_hits}");
+
+        // a burst at the quiet hour still stands out against that hour's baseline
+        let b = seasonal.current(3).unwrap();
+        assert!(b.mean() < 40.0);
+        assert!(seasonal.z_score(3, 150.0).unwrap() >= z_threshold);
+    }
+
+    #[test]
+    fn cold_buckets_fall_back_to_global() {
+        let mut b = SeasonalBaseline::new(10.0, true, 5);
+        assert!(b.current(0).is_none());
+        for _ in 0..5 { b.observe(0, 10.0); }
+        assert_eq!(b.current(0).unwrap().mean(), 10.0);
+        assert_eq!(b.current(12).unwrap().count(), 5); // hour 12 has no samples: global
+    }
+}
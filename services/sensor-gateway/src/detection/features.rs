@@ -0,0 +1,109 @@
+//! Feature extraction for the anomaly detector.
+//!
+//! A `FeaturePipeline` runs an ordered list of `FeatureExtractor`s over a raw event and yields
+//! the feature vector fed to `AnomalyDetector`. Configured with `ANOMALY_FEATURES`, a comma
+//! separated spec (default `length`):
+//!   `length` payload bytes | `entropy` Shannon entropy (bits/byte) |
+//!   `token:<s>` occurrences of `<s>` | `hour` UTC time of day in fractional hours.
+
+use std::collections::HashMap;
+
+/// Raw event as seen by extractors.
+pub struct EventView<'a> {
+    pub payload: &'a str,
+    pub timestamp_ms: u64,
+}
+
+pub trait FeatureExtractor: Send + Sync {
+    fn name(&self) -> String;
+    fn extract(&self, event: &EventView<'_>) -> f64;
+}
+
+pub struct PayloadLength;
+impl FeatureExtractor for PayloadLength {
+    fn name(&self) -> String { "length".into() }
+    fn extract(&self, event: &EventView<'_>) -> f64 { event.payload.len() as f64 }
+}
+
+pub struct Entropy;
+impl FeatureExtractor for Entropy {
+    fn name(&self) -> String { "entropy".into() }
+    fn extract(&self, event: &EventView<'_>) -> f64 {
+        let bytes = event.payload.as_bytes();
+        if bytes.is_empty() { return 0.0; }
+        let mut counts: HashMap<u8, usize> = HashMap::new();
+        for b in bytes { *counts.entry(*b).or_default() += 1; }
+        let n = bytes.len() as f64;
+        counts.values().map(|c| { let p = *c as f64 / n; -p * p.log2() }).sum()
+    }
+}
+
+pub struct TokenFrequency { pub token: String }
+impl FeatureExtractor for TokenFrequency {
+    fn name(&self) -> String { format!("token:{}", self.token) }
+    fn extract(&self, event: &EventView<'_>) -> f64 { event.payload.matches(self.token.as_str()).count() as f64 }
+}
+
+pub struct TimeOfDay;
+impl FeatureExtractor for TimeOfDay {
+    fn name(&self) -> String { "hour".into() }
+    fn extract(&self, event: &EventView<'_>) -> f64 { (event.timestamp_ms % 86_400_000) as f64 / 3_600_000.0 }
+}
+
+#[derive(Default)]
+pub struct FeaturePipeline {
+    extractors: Vec<Box<dyn FeatureExtractor>>,
+}
+
+impl FeaturePipeline {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn with(mut self, extractor: Box<dyn FeatureExtractor>) -> Self { self.extractors.push(extractor); self }
+
+    /// Build from a spec such as `length,entropy,token:password,hour`.
+    pub fn from_spec(spec: &str) -> Result<Self, String> {
+        let mut pipeline = Self::new();
+        for item in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let extractor: Box<dyn FeatureExtractor> = match item {
+                "length" => Box::new(PayloadLength),
+                "entropy" => Box::new(Entropy),
+                "hour" => Box::new(TimeOfDay),
+                _ => match item.strip_prefix("token:") {
+                    Some(token) if !token.is_empty() => Box::new(TokenFrequency { token: token.to_string() }),
+                    _ => return Err(format!("unknown feature extractor '{item}'")),
+                },
+            };
+            pipeline = pipeline.with(extractor);
+        }
+        Ok(pipeline)
+    }
+
+    /// `ANOMALY_FEATURES` spec; an invalid spec logs and falls back to `length`.
+    pub fn from_env() -> Self {
+        let spec = std::env::var("ANOMALY_FEATURES").unwrap_or_else(|_| "length".into());
+        Self::from_spec(&spec).unwrap_or_else(|e| {
+            tracing::warn!(error=%e, %spec, "invalid ANOMALY_FEATURES - using length only");
+            Self::new().with(Box::new(PayloadLength))
+        })
+    }
+
+    pub fn names(&self) -> Vec<String> { self.extractors.iter().map(|e| e.name()).collect() }
+
+    pub fn extract(&self, event: &EventView<'_>) -> Vec<f64> { self.extractors.iter().map(|e| e.extract(event)).collect() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configured_pipeline_produces_expected_vector() {
+        let pipeline = FeaturePipeline::from_spec("length, entropy, token:ab, hour").unwrap();
+        assert_eq!(pipeline.names(), vec!["length", "entropy", "token:ab", "hour"]);
+        // 2023-11-14T22:13:20Z
+        let v = pipeline.extract(&EventView { payload: "abab", timestamp_ms: 1_700_000_000_000 });
+        assert_eq!(v[..3], [4.0, 1.0, 2.0]);
+        assert!((v[3] - (22.0 + 13.0 / 60.0 + 20.0 / 3600.0)).abs() < 1e-9);
+        assert!(FeaturePipeline::from_spec("length,bogus").is_err());
+    }
+}
@@ -1,7 +1,14 @@
 pub mod rules;
 pub mod anomaly;
 pub mod engine;
+pub mod features;
+pub mod stats;
+pub mod baseline;
+pub mod accuracy;
 
-pub use rules::{RuleSet, DetectionRule, load_rules};
-pub use anomaly::{AnomalyDetector, AnomalyStats};
-pub use engine::{DetectionEngine, DetectionEvent};
+pub use rules::{RuleSet, RuleOverlay, DetectionRule, MatchType, load_rules};
+pub use anomaly::{AnomalyDetector, AnomalyStats, AnomalyConfig};
+pub use engine::{DetectionEngine, DetectionEvent, DetectionAlert, RuleStat, alert_subject};
+pub use features::{FeatureExtractor, FeaturePipeline, EventView};
+pub use stats::FeatureStats;
+pub use accuracy::{DetectionAccuracy, Outcome};
@@ -1,4 +1,5 @@
 use std::{fs, path::Path, time::{SystemTime, UNIX_EPOCH}};
+use std::collections::{HashMap, HashSet};
 use serde::Deserialize;
 use regex::Regex;
 use parking_lot::RwLock;
@@ -12,9 +13,15 @@ pub enum RuleError {
     #[error("io error: {0}")] Io(#[from] std::io::Error),
     #[error("serde error: {0}")] Serde(#[from] serde_yaml::Error),
     #[error("invalid signature")] InvalidSignature,
-    #[error("regex compile failed: {0}")] Regex(String),
+    #[error("rule {0}: {1} pattern failed to compile: {2}")] Regex(String, &'static str, String),
 }
 
+/// How `pattern` is evaluated against a line. Regex is the default so existing bundles keep
+/// their meaning; globs (`*`, `?`, `[...]`) must match the whole line.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchType { Substring, #[default] Regex, Glob }
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct DetectionRule {
     pub id: String,
@@ -22,29 +29,128 @@ pub struct DetectionRule {
     pub severity: Option<String>,
     #[serde(default)]
     pub action: Option<String>,
+    /// Shadow rules are evaluated and counted but never alert (safe rollout of new rules).
+    #[serde(default)]
+    pub shadow: bool,
+    #[serde(default)]
+    pub match_type: MatchType,
 }
 
+#[derive(Clone)]
+pub enum Matcher { Substring(String), Regex(Regex) } // globs are translated to anchored regexes
+
 #[derive(Clone)]
 pub struct CompiledRule {
     pub raw: DetectionRule,
-    pub regex: Regex,
+    pub matcher: Matcher,
+}
+
+impl CompiledRule {
+    /// Compile once at load; an invalid pattern is an error, never a silently dropped rule.
+    pub fn compile(raw: DetectionRule) -> Result<Self, RuleError> {
+        let matcher = match raw.match_type {
+            MatchType::Substring => Matcher::Substring(raw.pattern.clone()),
+            MatchType::Regex => Matcher::Regex(Regex::new(&raw.pattern).map_err(|e| RuleError::Regex(raw.id.clone(), "regex", e.to_string()))?),
+            MatchType::Glob => Matcher::Regex(Regex::new(&glob_to_regex(&raw.pattern)).map_err(|e| RuleError::Regex(raw.id.clone(), "glob", e.to_string()))?),
+        };
+        Ok(Self { raw, matcher })
+    }
+
+    /// Byte span of the first match in `line`.
+    pub fn find(&self, line: &str) -> Option<(usize, usize)> {
+        match &self.matcher {
+            Matcher::Substring(p) => line.find(p.as_str()).map(|start| (start, start + p.len())),
+            Matcher::Regex(re) => re.find(line).map(|m| (m.start(), m.end())),
+        }
+    }
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut re = String::from("^");
+    let mut in_class = false;
+    for c in glob.chars() {
+        match c {
+            '*' if !in_class => re.push_str(".*"),
+            '?' if !in_class => re.push('.'),
+            '[' if !in_class => { in_class = true; re.push('['); }
+            ']' if in_class => { in_class = false; re.push(']'); }
+            '!' if in_class && re.ends_with('[') => re.push('^'),
+            '\\' | '^' if in_class => { re.push('\\'); re.push(c); }
+            c if in_class => re.push(c),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    re
+}
+
+/// Runtime overlay from `DynamicConfig`: disables rule ids / overrides severity without
+/// touching the signed bundle. Kept apart from `rules` so a bundle reload doesn't drop it.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RuleOverlay {
+    pub disabled: HashSet<String>,
+    pub severity: HashMap<String, String>, // rule id -> severity
+}
+
+impl RuleOverlay {
+    pub fn from_config(cfg: &swarm_core::DynamicConfig) -> Self {
+        Self {
+            disabled: cfg.disabled_rules.clone().unwrap_or_default().into_iter().collect(),
+            severity: cfg.rule_severity_overrides.clone().unwrap_or_default(),
+        }
+    }
+
+    /// Local override file (`DETECTION_RULES_OVERRIDE_PATH`): `{rule_id: {enabled, severity}}`.
+    /// Not signed: it can only silence or re-grade rules from the verified bundle, never add any.
+    pub fn from_file(path: &str) -> Result<Self, RuleError> {
+        let entries: HashMap<String, RuleOverride> = serde_yaml::from_str(&fs::read_to_string(path)?)?;
+        let mut overlay = Self::default();
+        for (id, o) in entries {
+            if !o.enabled { overlay.disabled.insert(id.clone()); }
+            if let Some(sev) = o.severity { overlay.severity.insert(id, sev); }
+        }
+        Ok(overlay)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RuleOverride {
+    #[serde(default = "enabled_default")]
+    enabled: bool,
+    #[serde(default)]
+    severity: Option<String>,
 }
 
+fn enabled_default() -> bool { true }
+
 #[derive(Default, Clone)]
 pub struct RuleSet {
     pub rules: Arc<RwLock<Vec<CompiledRule>>>,
     pub version_hash: Arc<RwLock<String>>, // sha256 of file
     pub loaded_ts: Arc<RwLock<u64>>,
+    pub overlay: Arc<RwLock<RuleOverlay>>,
+    pub file_overlay: Arc<RwLock<RuleOverlay>>, // from DETECTION_RULES_OVERRIDE_PATH; `overlay` wins on severity
+    pub hits: Arc<RwLock<HashMap<String, RuleHits>>>, // rule id -> alerting matches; pruned to live ids on swap
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RuleHits {
+    pub hits: u64,
+    pub last_hit_ts: u64, // unix secs
 }
 
 impl RuleSet {
     pub fn new() -> Self { Self::default() }
     pub fn swap(&self, new_rules: Vec<CompiledRule>, hash: String) {
+        let ids: HashSet<&str> = new_rules.iter().map(|r| r.raw.id.as_str()).collect();
+        self.hits.write().retain(|id, _| ids.contains(id.as_str())); // removed ids start from zero if re-added
         *self.rules.write() = new_rules;
         *self.version_hash.write() = hash;
         *self.loaded_ts.write() = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
     }
     pub fn list(&self) -> Vec<String> { self.rules.read().iter().map(|r| r.raw.id.clone()).collect() }
+    pub fn set_overlay(&self, overlay: RuleOverlay) { *self.overlay.write() = overlay; }
+    pub fn set_file_overlay(&self, overlay: RuleOverlay) { *self.file_overlay.write() = overlay; }
 }
 
 #[derive(Debug, Deserialize)]
@@ -85,10 +191,52 @@ pub fn load_rules(path: &str, verify: bool, external_pubkey: Option<&str>) -> Re
         }
     }
 
-    let mut compiled = Vec::new();
-    for r in bundle.rules.into_iter() {
-        let regex = Regex::new(&r.pattern).map_err(|e| RuleError::Regex(e.to_string()))?;
-        compiled.push(CompiledRule { raw: r, regex });
+    bundle.rules.into_iter().map(CompiledRule::compile).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(name: &str, yaml: &str) -> Result<Vec<CompiledRule>, RuleError> {
+        let path = std::env::temp_dir().join(format!("swarm-rules-{}-{name}.yaml", std::process::id()));
+        fs::write(&path, yaml).unwrap();
+        let out = load_rules(path.to_str().unwrap(), false, None);
+        let _ = fs::remove_file(&path);
+        out
+    }
+
+    #[test]
+    fn each_match_type_reports_its_span() {
+        let rules = load("types", r#"
+rules:
+  - { id: sub, pattern: "a.b", match_type: substring }
+  - { id: re, pattern: "cmd\\.exe\\s+/c" }
+  - { id: glob, pattern: "*powershell -[eE]nc*", match_type: glob }
+"#).unwrap();
+        let by_id = |id: &str| rules.iter().find(|r| r.raw.id == id).unwrap();
+        assert_eq!(by_id("re").raw.match_type, MatchType::Regex, "regex stays the default");
+        assert_eq!(by_id("sub").find("xx a.b yy"), Some((3, 6)));
+        assert_eq!(by_id("sub").find("xx aXb yy"), None, "substring is literal");
+        assert_eq!(by_id("re").find("run cmd.exe  /c whoami"), Some((4, 15)));
+        assert_eq!(by_id("glob").find("host1 powershell -Enc AAAA"), Some((0, 26)));
+        assert_eq!(by_id("glob").find("powershell -Xnc"), None);
+        assert!(by_id("glob").find("a.b").is_none());
+    }
+
+    #[test]
+    fn glob_translation_escapes_regex_metacharacters() {
+        assert_eq!(glob_to_regex("*.exe"), "^.*\\.exe$");
+        assert_eq!(glob_to_regex("file?[!0-9]"), "^file.[^0-9]$");
+        let rule = CompiledRule::compile(DetectionRule { id: "g".into(), pattern: "C:\\Temp\\*.ps1".into(), severity: None, action: None, shadow: false, match_type: MatchType::Glob }).unwrap();
+        assert!(rule.find("C:\\Temp\\drop.ps1").is_some());
+        assert!(rule.find("C:\\Temp\\drop.ps1.txt").is_none());
+    }
+
+    #[test]
+    fn invalid_regex_fails_the_load() {
+        let err = load("invalid", "rules:\n  - { id: ok, pattern: fine }\n  - { id: broken, pattern: \"(unclosed\" }\n").err().expect("load must fail");
+        assert!(matches!(&err, RuleError::Regex(id, "regex", _) if id == "broken"), "{err}");
+        assert!(err.to_string().starts_with("rule broken: regex pattern failed to compile"));
     }
-    Ok(compiled)
 }
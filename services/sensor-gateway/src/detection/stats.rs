@@ -0,0 +1,99 @@
+//! Running per-feature statistics (mean / variance / diagonal Mahalanobis distance).
+//!
+//! `update` is Welford's per-event recurrence. `update_batch` reduces a whole batch with flat
+//! column loops the compiler auto-vectorizes, then folds it in with Chan's parallel merge, so
+//! high-rate callers pay one division per feature per batch instead of per event.
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FeatureStats {
+    count: u64,
+    mean: Vec<f64>,
+    m2: Vec<f64>, // sum of squared deviations from the mean
+}
+
+impl FeatureStats {
+    pub fn new(dims: usize) -> Self { Self { count: 0, mean: vec![0.0; dims], m2: vec![0.0; dims] } }
+
+    pub fn count(&self) -> u64 { self.count }
+    pub fn mean(&self) -> &[f64] { &self.mean }
+
+    /// Population variance per feature (zeros until two samples are seen).
+    pub fn variance(&self) -> Vec<f64> {
+        if self.count < 2 { return vec![0.0; self.mean.len()]; }
+        let n = self.count as f64;
+        self.m2.iter().map(|m| m / n).collect()
+    }
+
+    /// Incremental single-event update. A vector of a different width resets the statistics.
+    pub fn update(&mut self, x: &[f64]) {
+        self.ensure_dims(x.len());
+        self.count += 1;
+        let n = self.count as f64;
+        for ((mean, m2), v) in self.mean.iter_mut().zip(self.m2.iter_mut()).zip(x) {
+            let delta = v - *mean;
+            *mean += delta / n;
+            *m2 += delta * (v - *mean);
+        }
+    }
+
+    /// Batch update, equivalent to calling `update` for each row.
+    pub fn update_batch(&mut self, rows: &[Vec<f64>]) {
+        let Some(first) = rows.first() else { return };
+        let dims = first.len();
+        if rows.iter().any(|r| r.len() != dims) { rows.iter().for_each(|r| self.update(r)); return; }
+        self.ensure_dims(dims);
+        let nb = rows.len() as f64;
+        let mut sum = vec![0.0; dims];
+        for row in rows { for (s, v) in sum.iter_mut().zip(row) { *s += v; } }
+        let batch_mean: Vec<f64> = sum.iter().map(|s| s / nb).collect();
+        let mut batch_m2 = vec![0.0; dims];
+        for row in rows {
+            for ((m, v), mu) in batch_m2.iter_mut().zip(row).zip(&batch_mean) { let d = v - mu; *m += d * d; }
+        }
+        let na = self.count as f64;
+        let n = na + nb;
+        for i in 0..dims {
+            let delta = batch_mean[i] - self.mean[i];
+            self.mean[i] += delta * nb / n;
+            self.m2[i] += batch_m2[i] + delta * delta * na * nb / n;
+        }
+        self.count += rows.len() as u64;
+    }
+
+    /// Diagonal-covariance Mahalanobis distance of `x` from the running mean; features with zero
+    /// variance are skipped. None until two samples have been seen or when widths differ.
+    pub fn mahalanobis(&self, x: &[f64]) -> Option<f64> {
+        if self.count < 2 || x.len() != self.mean.len() { return None; }
+        let n = self.count as f64;
+        let d2: f64 = x.iter().zip(&self.mean).zip(&self.m2)
+            .filter(|(_, m2)| **m2 > 0.0)
+            .map(|((v, mu), m2)| (v - mu).powi(2) / (m2 / n))
+            .sum();
+        Some(d2.sqrt())
+    }
+
+    fn ensure_dims(&mut self, dims: usize) {
+        if self.mean.len() != dims { *self = Self::new(dims); }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_matches_incremental() {
+        let rows: Vec<Vec<f64>> = (0..1000).map(|i| { let x = i as f64; vec![x.sin() * 100.0 + 1e6, (x * 0.37).cos(), x % 17.0] }).collect();
+        let mut incremental = FeatureStats::default();
+        rows.iter().for_each(|r| incremental.update(r));
+        let mut batched = FeatureStats::default();
+        batched.update(&rows[0]);
+        for chunk in rows[1..].chunks(64) { batched.update_batch(chunk); }
+        assert_eq!(batched.count(), incremental.count());
+        for (a, b) in batched.mean().iter().zip(incremental.mean()) { assert!((a - b).abs() <= 1e-9 * b.abs().max(1.0), "{a} vs {b}"); }
+        for (a, b) in batched.variance().iter().zip(incremental.variance()) { assert!((a - b).abs() <= 1e-9 * b.abs().max(1.0), "{a} vs {b}"); }
+        let probe = [1e6 + 250.0, 0.0, 8.0];
+        let (a, b) = (batched.mahalanobis(&probe).unwrap(), incremental.mahalanobis(&probe).unwrap());
+        assert!((a - b).abs() < 1e-9 * b);
+    }
+}
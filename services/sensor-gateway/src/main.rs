@@ -4,16 +4,20 @@ use swarm_core::{init_tracing, start_health_server, init_metrics};
 use swarm_proto::ingestion::RawEvent;
 mod detection;
 mod nats_pool;
-use detection::{RuleSet, load_rules, AnomalyDetector, AnomalyConfig, DetectionEngine};
-use nats_pool::NatsPool;
+mod buffer_pool;
+mod shutdown;
+mod debug_api;
+use detection::{RuleSet, RuleOverlay, load_rules, AnomalyDetector, AnomalyConfig, DetectionEngine, DetectionEvent, DetectionAccuracy, Outcome, DetectionAlert, alert_subject};
+use nats_pool::{NatsPool, PoolError};
+use buffer_pool::{BUFFER_POOL, encode_to_bytes};
 use swarm_resilience::{retry_async, CircuitBreaker};
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::path::Path;
 use std::io::Write; // for detection log append
 use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::fs::File;
-use prost::Message;
 use opentelemetry::{global, metrics::{Counter, Histogram}};
 use opentelemetry::metrics::Meter;
 
@@ -23,6 +27,7 @@ pub struct Metrics {
     degraded_total: Counter<u64>,
     encode_latency_ms: Histogram<f64>,
     payload_bytes: Histogram<u64>,
+    publish_dropped_total: Counter<u64>,
 }
 
 pub fn init_metrics() -> Metrics {
@@ -33,7 +38,8 @@ pub fn init_metrics() -> Metrics {
     // Histograms for latency (ms) and payload size (bytes) to baseline throughput & encoding cost.
     let encode_latency_ms = meter.f64_histogram("swarm_ingest_encode_latency_ms").with_description("Time to protobuf-encode a RawEvent in milliseconds").init();
     let payload_bytes = meter.u64_histogram("swarm_ingest_payload_bytes").with_description("Payload size of ingested RawEvent in bytes").init();
-    Metrics { events_total, errors_total, degraded_total, encode_latency_ms, payload_bytes }
+    let publish_dropped_total = meter.u64_counter("swarm_ingest_publish_dropped_total").with_description("Raw events dropped because the NATS in-flight publish limit was reached").init();
+    Metrics { events_total, errors_total, degraded_total, encode_latency_ms, payload_bytes, publish_dropped_total }
 }
 
 #[tokio::main]
@@ -49,7 +55,13 @@ pub async fn run() -> Result<()> {
     let nats_url = std::env::var("NATS_URL").unwrap_or_else(|_| "nats://localhost:4222".into());
     let pool_size = std::env::var("NATS_POOL_SIZE").ok().and_then(|s| s.parse().ok()).unwrap_or(4);
     let mut nats_pool = match NatsPool::new(&nats_url, pool_size).await {
-        Ok(p) => { info!(target:"sensor-gateway", %nats_url, pool_size, "Connected to NATS with connection pool"); Some(Arc::new(p)) },
+        Ok(p) => {
+            info!(target:"sensor-gateway", %nats_url, pool_size, "Connected to NATS with connection pool");
+            let p = Arc::new(p);
+            let health_secs = std::env::var("NATS_POOL_HEALTH_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(10u64).max(1);
+            p.spawn_health_checks(std::time::Duration::from_secs(health_secs));
+            Some(p)
+        },
         Err(e) => { warn!(error=?e, "NATS unavailable - degraded mode"); metrics.degraded_total.add(1, &[]); None }
     };
     // Detection engine setup
@@ -58,77 +70,127 @@ pub async fn run() -> Result<()> {
     let external_pk = std::env::var("DETECTION_RULES_PUBKEY").ok();
     let ruleset = RuleSet::new();
     if let Ok(rules) = load_rules(&rules_path, verify_rules, external_pk.as_deref()) { ruleset.swap(rules, "initial".into()); }
-    let anomaly_cfg = AnomalyConfig::default();
+    let anomaly_cfg = AnomalyConfig::from_env();
     let detection_enabled = std::env::var("DETECTION_ENABLED").ok().map(|v| v=="1" || v.eq_ignore_ascii_case("true")).unwrap_or(true);
     let engine = DetectionEngine::new(ruleset.clone(), AnomalyDetector::new(anomaly_cfg), detection_enabled, true, true);
     if !detection_enabled { info!("detection disabled via DETECTION_ENABLED"); }
+    debug_api::spawn_from_env(engine.clone()).await?;
     // Hot reload watcher
     tokio::spawn(watch_rules(rules_path.clone(), ruleset.clone(), verify_rules, external_pk.clone()));
+    if let Ok(override_path) = std::env::var("DETECTION_RULES_OVERRIDE_PATH") { tokio::spawn(watch_rule_overrides(override_path, ruleset.clone())); }
+    // Runtime rule overlay (disable / severity override) from DynamicConfig, re-applied on config reload
+    let config_updates = swarm_core::subscribe_config();
+    if let Ok(cfg) = swarm_core::load_config("sensor-gateway").await { ruleset.set_overlay(RuleOverlay::from_config(&cfg)); }
+    tokio::spawn(watch_rule_overlay(config_updates, ruleset.clone()));
+    // /ready stays 503 until NATS has a healthy connection and a rule bundle has loaded
+    let ready_pool = nats_pool.clone();
+    swarm_core::register_readiness_check("nats", Box::new(move || ready_pool.as_ref().is_some_and(|p| p.healthy_connections() > 0)));
+    let ready_rules = ruleset.clone();
+    swarm_core::register_readiness_check("rules", Box::new(move || !ready_rules.version_hash.read().is_empty()));
+    swarm_core::mark_ready();
     let cb = CircuitBreaker::new(3, std::time::Duration::from_secs(5));
     if let Some(pool) = &nats_pool { 
         if let Err(e) = pool.publish("ingest.v1.status", b"online").await {
             warn!(error=?e, "failed to publish online status");
         }
     }
+    let token = CancellationToken::new();
+    shutdown::spawn_signal_listener(token.clone());
+    let status_pool = nats_pool.clone();
+    let publish_status = move |status: &'static [u8]| {
+        let pool = status_pool.clone();
+        async move {
+            if let Some(pool) = pool { if let Err(e) = pool.publish("ingest.v1.status", status).await { warn!(error=?e, "failed to publish shutdown status"); } }
+        }
+    };
+    let grace = shutdown::grace_from_env();
     let ingest_file = std::env::var("INGEST_FILE").ok();
-    if let Some(f) = ingest_file { if Path::new(&f).exists() { ingest_file_loop(&f, &mut nats_pool, &metrics, &engine).await?; return Ok(()); } }
+    if let Some(f) = ingest_file { if Path::new(&f).exists() {
+        let res = shutdown::run_with_drain(ingest_file_loop(&f, &mut nats_pool, &metrics, &engine, &token), &token, grace, publish_status).await.unwrap_or(Ok(()));
+        swarm_core::shutdown_observability();
+        return res;
+    } }
     let run_once = std::env::var("SWARM_RUN_ONCE").ok().map(|v| v=="1" || v.eq_ignore_ascii_case("true")).unwrap_or(false);
-    synthetic_loop(&mut nats_pool, &metrics, run_once, &engine).await;
+    shutdown::run_with_drain(synthetic_loop(&mut nats_pool, &metrics, run_once, &engine, &token), &token, grace, publish_status).await;
+    swarm_core::shutdown_observability();
     Ok(())
 }
 
 // Tracing handled by swarm-core
 
-async fn ingest_file_loop(path: &str, nats: &mut Option<Arc<NatsPool>>, metrics: &Metrics, engine: &DetectionEngine) -> Result<()> {
+async fn ingest_file_loop(path: &str, nats: &mut Option<Arc<NatsPool>>, metrics: &Metrics, engine: &DetectionEngine, token: &CancellationToken) -> Result<()> {
     info!(target:"sensor-gateway", %path, "Starting file ingestion loop");
     let file = File::open(path).await.with_context(|| format!("open ingest file {path}"))?;
     let reader = BufReader::new(file);
     let mut lines = reader.lines();
-    while let Ok(Some(line)) = lines.next_line().await {
-        if line.trim().is_empty() { continue; }
-    if let Err(e) = process_line(&line, nats, metrics, engine).await { metrics.errors_total.add(1, &[]); warn!(error=?e, "failed processing line"); }
+    // lines already buffered are scanned together (up to INGEST_BATCH_MAX) so anomaly statistics
+    // are updated once per batch; a lone line is never held back waiting for more
+    let batch_max: usize = std::env::var("INGEST_BATCH_MAX").ok().and_then(|v| v.parse().ok()).unwrap_or(64).max(1);
+    let mut batch = Vec::with_capacity(batch_max);
+    let mut eof = false;
+    while !eof {
+        // stop taking lines once shutdown starts; a batch already in process_line runs to completion
+        let next = tokio::select! { biased; _ = token.cancelled() => break, next = lines.next_line() => next };
+        let Ok(Some(line)) = next else { break };
+        batch.push(line);
+        while batch.len() < batch_max {
+            // zero timeout: take the next line only if it is ready without waiting (next_line is cancel safe)
+            match tokio::time::timeout(std::time::Duration::ZERO, lines.next_line()).await {
+                Ok(Ok(Some(line))) => batch.push(line),
+                Ok(_) => { eof = true; break; }
+                Err(_) => break,
+            }
+        }
+        batch.retain(|l| !l.trim().is_empty());
+        for (line, detections) in batch.iter().zip(engine.scan_batch(&batch)) {
+            if let Err(e) = process_line(line, detections, nats, metrics).await { metrics.errors_total.add(1, &[]); warn!(error=?e, "failed processing line"); }
+        }
+        batch.clear();
     }
     Ok(())
 }
 
-async fn synthetic_loop(nats: &mut Option<Arc<NatsPool>>, metrics: &Metrics, run_once: bool, engine: &DetectionEngine) {
+async fn synthetic_loop(nats: &mut Option<Arc<NatsPool>>, metrics: &Metrics, run_once: bool, engine: &DetectionEngine, token: &CancellationToken) {
     info!(target:"sensor-gateway", "Starting synthetic event generation loop");
     let mut i: u64 = 0;
     loop {
         let payload = format!("synthetic-event-{i}");
-        if let Err(e) = process_line(&payload, nats, metrics, engine).await { metrics.errors_total.add(1, &[]); error!(error=?e, "failed processing synthetic"); }
+        if let Err(e) = process_line(&payload, engine.scan(&payload), nats, metrics).await { metrics.errors_total.add(1, &[]); error!(error=?e, "failed processing synthetic"); }
         i += 1;
         if run_once { break; }
-        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        tokio::select! { _ = token.cancelled() => break, _ = tokio::time::sleep(std::time::Duration::from_millis(500)) => {} }
     }
 }
 
-async fn process_line(line: &str, nats: &mut Option<Arc<NatsPool>>, metrics: &Metrics, engine: &DetectionEngine) -> Result<()> {
+static ACCURACY: once_cell::sync::Lazy<DetectionAccuracy> = once_cell::sync::Lazy::new(DetectionAccuracy::new);
+
+async fn process_line(line: &str, detections: Vec<DetectionEvent>, nats: &mut Option<Arc<NatsPool>>, metrics: &Metrics) -> Result<()> {
     let start_e2e = std::time::Instant::now();
     let ts = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
-    let evt = RawEvent {
+    let mut payload = BUFFER_POOL.get(line.len());
+    payload.extend_from_slice(line.as_bytes());
+    let mut evt = RawEvent {
         id: format!("{}-{}", ts, fxhash::hash32(line.as_bytes())),
         observed_ts: ts,
         source_type: "file".into(),
         origin: hostname::get().ok().and_then(|h| h.into_string().ok()).unwrap_or_else(|| "unknown".into()),
-        payload: line.as_bytes().to_vec(),
+        payload,
         content_type: "text/plain".into(),
     };
-    let mut buf = Vec::with_capacity(evt.encoded_len());
     let start = std::time::Instant::now();
-    evt.encode(&mut buf)?;
+    let buf = encode_to_bytes(&evt);
+    BUFFER_POOL.put(std::mem::take(&mut evt.payload));
     let elapsed = start.elapsed().as_secs_f64() * 1000.0; // ms
     metrics.encode_latency_ms.record(elapsed, &[]);
     metrics.payload_bytes.record(buf.len() as u64, &[]);
     if let Some(pool) = nats { 
-        if let Err(e) = pool.publish("ingest.v1.raw", &buf).await {
-            warn!(error=?e, "failed to publish raw event");
+        match pool.try_publish("ingest.v1.raw", buf).await {
+            Err(e) if matches!(e.downcast_ref::<PoolError>(), Some(PoolError::Saturated)) => metrics.publish_dropped_total.add(1, &[]),
+            Err(e) => warn!(error=?e, "failed to publish raw event"),
+            Ok(()) => {}
         }
     }
-    // Detection
-    let detections = engine.scan(line);
-    // Ground truth heuristic: lines containing token MALICIOUS are considered true threats
-    let gt_positive = line.contains("MALICIOUS");
+    // Detection (scanned by the caller)
     let meter = opentelemetry::global::meter("sensor-gateway");
     let sig_ctr = meter.u64_counter("swarm_detection_signature_total").init();
     let anom_ctr = meter.u64_counter("swarm_detection_anomaly_total").init();
@@ -159,25 +221,32 @@ async fn process_line(line: &str, nats: &mut Option<Arc<NatsPool>>, metrics: &Me
                 _ => {}
             }
         }
-        // Compute TP / FP
-        if gt_positive { tp_ctr.add(1, &[]); } else { fp_ctr.add(1, &[]); }
-    } else if gt_positive {
-        // Missed detection => FN
-        fn_ctr.add(1, &[]);
     }
-    // Approximate ratios using cumulative counters (snapshots not atomic but acceptable for early stage)
-    // In absence of reading counters back (OTel API lacks direct read), emit heuristic based on last classification event
-    if gt_positive && detections.is_empty() { fp_ratio_gauge.record( fp_ctr.as_any().type_id() == fp_ctr.as_any().type_id() /* noop */ as i32 as f64, &[]); }
-    if gt_positive && !detections.is_empty() { detection_rate_gauge.record(1.0, &[]); }
+    // Ground truth heuristic: lines containing token MALICIOUS are considered true threats
+    if let Some(outcome) = ACCURACY.record(line, !detections.is_empty()) {
+        match outcome {
+            Outcome::TruePositive => tp_ctr.add(1, &[]),
+            Outcome::FalsePositive => fp_ctr.add(1, &[]),
+            Outcome::FalseNegative => fn_ctr.add(1, &[]),
+        }
+        if let Some(r) = ACCURACY.fp_ratio() { fp_ratio_gauge.record(r, &[]); }
+        if let Some(r) = ACCURACY.detection_rate() { detection_rate_gauge.record(r, &[]); }
+    }
+    if !detections.is_empty() { swarm_core::CAPACITY.record(swarm_core::capacity::DETECTIONS, detections.len() as u64); }
+    // DETECTION_LOG_PATH gets the versioned alert unless DETECTION_LOG_LEGACY=1 keeps the old DetectionEvent shape
+    let legacy_log = std::env::var("DETECTION_LOG_LEGACY").map(|v| v == "1" || v.eq_ignore_ascii_case("true")).unwrap_or(false);
+    let subject = alert_subject();
     for det in detections {
         if let Some(pool) = nats {
-            if let Ok(json) = serde_json::to_vec(&det) {
-                if let Err(e) = pool.publish("threat.v1.alert.detected", &json).await {
+            let legacy_json = if legacy_log { serde_json::to_vec(&det).ok() } else { None };
+            let alert = DetectionAlert::from(det).with_source(&evt.id, &evt.origin);
+            if let Ok(json) = serde_json::to_vec(&alert).map(bytes::Bytes::from) {
+                if let Err(e) = pool.publish(subject.clone(), json.clone()).await {
                     warn!(error=?e, "failed to publish detection alert");
                 }
                 if let Ok(path) = std::env::var("DETECTION_LOG_PATH") {
                     if let Ok(mut f) = std::fs::OpenOptions::new().create(true).append(true).open(&path) {
-                        let _ = writeln!(f, "{}", String::from_utf8_lossy(&json));
+                        let _ = writeln!(f, "{}", String::from_utf8_lossy(legacy_json.as_deref().unwrap_or(&json[..])));
                     }
                 }
             }
@@ -190,6 +259,8 @@ async fn process_line(line: &str, nats: &mut Option<Arc<NatsPool>>, metrics: &Me
         .with_description("End-to-end latency from ingest to detection publish")
         .init();
     e2e_histogram.record(e2e_elapsed, &[]);
+    swarm_core::CAPACITY.record_latency("ingest_e2e", e2e_elapsed);
+    swarm_core::CAPACITY.record(swarm_core::capacity::EVENTS_INGESTED, 1);
     metrics.events_total.add(1, &[]);
     Ok(())
 }
@@ -198,17 +269,42 @@ use notify::{Watcher, RecommendedWatcher, RecursiveMode, EventKind};
 use std::sync::mpsc::channel;
 
 async fn watch_rules(path: String, ruleset: RuleSet, verify: bool, external_pk: Option<String>) {
+    let watched = path.clone();
+    watch_file(watched, move |initial| {
+        if let Ok(rules) = load_rules(&path, verify, external_pk.as_deref()) { ruleset.swap(rules, if initial { "initial" } else { "reload" }.into()); }
+    }).await;
+}
+
+/// Per-rule enable/severity overrides; unsigned by design (see `RuleOverlay::from_file`).
+async fn watch_rule_overrides(path: String, ruleset: RuleSet) {
+    let watched = path.clone();
+    watch_file(watched, move |_| match RuleOverlay::from_file(&path) {
+        Ok(overlay) => { info!(%path, disabled=overlay.disabled.len(), severity_overrides=overlay.severity.len(), "rule override file loaded"); ruleset.set_file_overlay(overlay); }
+        Err(e) => warn!(%path, error=%e, "rule override file rejected - keeping previous overrides"),
+    }).await;
+}
+
+/// Run `reload(true)` if `path` exists, then `reload(false)` on every modify/create event.
+async fn watch_file(path: String, mut reload: impl FnMut(bool) + Send + 'static) {
     tokio::task::spawn_blocking(move || {
-        if Path::new(&path).exists() { if let Ok(rules) = load_rules(&path, verify, external_pk.as_deref()) { ruleset.swap(rules, "initial".into()); } }
+        if Path::new(&path).exists() { reload(true); }
         let (tx, rx) = channel();
         let mut watcher: RecommendedWatcher = notify::recommended_watcher(move |res| {
             let _ = tx.send(res);
         }).expect("create watcher");
         if watcher.watch(Path::new(&path), RecursiveMode::NonRecursive).is_err() { return; }
         while let Ok(ev) = rx.recv() {
-            if let Ok(event) = ev { if matches!(event.kind, EventKind::Modify(_)|EventKind::Create(_)) {
-                if let Ok(rules) = load_rules(&path, verify, external_pk.as_deref()) { ruleset.swap(rules, "reload".into()); }
-            }}
+            if let Ok(event) = ev { if matches!(event.kind, EventKind::Modify(_)|EventKind::Create(_)) { reload(false); } }
         }
     }).await.ok();
 }
+
+async fn watch_rule_overlay(mut updates: swarm_core::ConfigSubscriber, ruleset: RuleSet) {
+    while let Some(cfg) = updates.recv().await {
+        let overlay = RuleOverlay::from_config(&cfg);
+        if *ruleset.overlay.read() != overlay {
+            info!(disabled=overlay.disabled.len(), severity_overrides=overlay.severity.len(), "detection rule overlay updated");
+            ruleset.set_overlay(overlay);
+        }
+    }
+}
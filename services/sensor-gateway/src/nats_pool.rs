@@ -1,67 +1,218 @@
+//! NATS connection pool for high-throughput publishing.
+//!
+//! Each slot carries a health flag. `check_health` pings every slot and reconnects the ones
+//! that fail (run periodically by `spawn_health_checks`, `NATS_POOL_HEALTH_SECS`, default 10);
+//! a failed publish also marks its slot unhealthy until the next check. `publish` round-robins
+//! over healthy slots only and fails with `PoolError::NoHealthyConnections` when there are none,
+//! so the caller can switch to degraded mode. Healthy count: `swarm_nats_pool_healthy`, one series
+//! per pool labelled `pool` (`with_name`, default `"default"`).
+//!
+//! Outstanding publishes are capped at `NATS_MAX_INFLIGHT` (default: pool size): `publish` waits
+//! for a slot, `try_publish` fails fast with `PoolError::Saturated` so callers can count drops.
+
 use async_nats::Client;
+use bytes::Bytes;
+use once_cell::sync::Lazy;
+use opentelemetry::{metrics::ObservableGauge, KeyValue};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 use tokio::sync::Semaphore;
-use parking_lot::Mutex;
+use parking_lot::{Mutex, RwLock};
+use tracing::{info, warn};
+
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Healthy connections per live pool, by pool name.
+static HEALTHY: Lazy<Mutex<HashMap<String, u64>>> = Lazy::new(Default::default);
+static HEALTHY_GAUGE: Lazy<ObservableGauge<u64>> = Lazy::new(|| {
+    opentelemetry::global::meter("sensor-gateway").u64_observable_gauge("swarm_nats_pool_healthy")
+        .with_description("Pooled NATS connections that passed the last health check")
+        .with_callback(|obs| for (pool, healthy) in HEALTHY.lock().iter() { obs.observe(*healthy, &[KeyValue::new("pool", pool.clone())]); })
+        .init()
+});
+
+#[derive(Debug, thiserror::Error)]
+pub enum PoolError {
+    #[error("no healthy NATS connections in pool")]
+    NoHealthyConnections,
+    #[error("NATS publish in-flight limit reached")]
+    Saturated,
+}
+
+/// What the pool needs from a connection; implemented for `async_nats::Client`.
+pub trait PoolConn: Send + Sync + 'static {
+    fn publish(&self, subject: String, payload: Bytes) -> impl Future<Output = Result<(), async_nats::Error>> + Send;
+    fn ping(&self) -> impl Future<Output = bool> + Send;
+}
+
+impl PoolConn for Client {
+    async fn publish(&self, subject: String, payload: Bytes) -> Result<(), async_nats::Error> {
+        Client::publish(self, subject, payload).await.map_err(Into::into)
+    }
 
-/// NATS connection pool for high-throughput publishing
-pub struct NatsPool {
-    connections: Vec<Arc<Client>>,
-    semaphore: Arc<Semaphore>,
+    async fn ping(&self) -> bool {
+        self.connection_state() == async_nats::connection::State::Connected
+            && matches!(tokio::time::timeout(PING_TIMEOUT, self.flush()).await, Ok(Ok(())))
+    }
+}
+
+pub type Connector<C> = Arc<dyn Fn() -> Pin<Box<dyn Future<Output = Result<C, async_nats::Error>> + Send>> + Send + Sync>;
+
+struct Slot<C> {
+    conn: RwLock<Arc<C>>,
+    healthy: AtomicBool,
+}
+
+pub struct NatsPool<C: PoolConn = Client> {
+    name: String, // `pool` label of the health gauge
+    slots: Vec<Slot<C>>,
+    connector: Connector<C>,
+    semaphore: Arc<Semaphore>, // one permit per in-flight publish
+    max_in_flight: usize,
     next_index: Arc<Mutex<usize>>,
 }
 
-impl NatsPool {
+impl NatsPool<Client> {
     /// Create pool with specified size
     pub async fn new(url: &str, pool_size: usize) -> Result<Self, async_nats::Error> {
-        let mut connections = Vec::with_capacity(pool_size);
+        let url = url.to_string();
+        Self::with_connector(pool_size, Arc::new(move || {
+            let url = url.clone();
+            Box::pin(async move { Ok(async_nats::connect(url).await?) })
+        })).await
+    }
+}
+
+impl<C: PoolConn> NatsPool<C> {
+    /// Open `pool_size` connections through `connector`, which is also used to replace dead ones.
+    pub async fn with_connector(pool_size: usize, connector: Connector<C>) -> Result<Self, async_nats::Error> {
+        let mut slots = Vec::with_capacity(pool_size);
         for _ in 0..pool_size {
-            let client = async_nats::connect(url).await?;
-            connections.push(Arc::new(client));
+            slots.push(Slot { conn: RwLock::new(Arc::new(connector().await?)), healthy: AtomicBool::new(true) });
         }
-        
-        Ok(Self {
-            connections,
-            semaphore: Arc::new(Semaphore::new(pool_size)),
-            next_index: Arc::new(Mutex::new(0)),
-        })
+        let max_in_flight = std::env::var("NATS_MAX_INFLIGHT").ok().and_then(|v| v.parse().ok()).unwrap_or(pool_size).max(1);
+        let pool = Self { name: "default".into(), slots, connector, semaphore: Arc::new(Semaphore::new(max_in_flight)), max_in_flight, next_index: Arc::new(Mutex::new(0)) };
+        pool.report_health();
+        Ok(pool)
+    }
+
+    /// Override the in-flight publish cap (before the pool is shared).
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = max_in_flight.max(1);
+        self.semaphore = Arc::new(Semaphore::new(self.max_in_flight));
+        self
+    }
+
+    /// Label this pool's `swarm_nats_pool_healthy` series (before the pool is shared); names
+    /// should be unique per process.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        HEALTHY.lock().remove(&self.name);
+        self.name = name.into();
+        self.report_health();
+        self
     }
 
-    /// Get next connection using round-robin
-    pub fn get_connection(&self) -> Arc<Client> {
-        let mut index = self.next_index.lock();
-        let conn = self.connections[*index % self.connections.len()].clone();
-        *index = (*index + 1) % self.connections.len();
-        conn
+    /// Publishes currently holding a permit.
+    pub fn in_flight(&self) -> usize { self.max_in_flight - self.semaphore.available_permits() }
+
+    /// Next healthy (slot, connection), round-robin.
+    fn next_healthy(&self) -> Option<(usize, Arc<C>)> {
+        let n = self.slots.len();
+        if n == 0 { return None; }
+        let start = { let mut index = self.next_index.lock(); let s = *index; *index = (s + 1) % n; s };
+        (0..n).map(|k| (start + k) % n).find(|&i| self.slots[i].healthy.load(Ordering::Acquire)).map(|i| (i, self.slots[i].conn.read().clone()))
+    }
+
+    /// Get next healthy connection using round-robin
+    pub fn get_connection(&self) -> Option<Arc<C>> { self.next_healthy().map(|(_, conn)| conn) }
+
+    fn mark_unhealthy(&self, slot: usize) {
+        if self.slots[slot].healthy.swap(false, Ordering::AcqRel) { warn!(slot, "NATS publish failed - connection marked unhealthy"); self.report_health(); }
     }
 
-    /// Publish with automatic connection selection
-    pub async fn publish(&self, subject: impl Into<String>, payload: Vec<u8>) -> Result<(), async_nats::Error> {
+    /// Publish with automatic connection selection. `Bytes` payloads are handed over without copying.
+    pub async fn publish(&self, subject: impl Into<String>, payload: impl Into<Bytes>) -> Result<(), async_nats::Error> {
         let _permit = self.semaphore.acquire().await.unwrap();
-        let conn = self.get_connection();
-        conn.publish(subject.into(), payload.into()).await
+        self.publish_on_healthy(subject.into(), payload.into()).await
+    }
+
+    /// Like `publish` but fails with `PoolError::Saturated` instead of waiting for a permit.
+    pub async fn try_publish(&self, subject: impl Into<String>, payload: impl Into<Bytes>) -> Result<(), async_nats::Error> {
+        let _permit = self.semaphore.try_acquire().map_err(|_| PoolError::Saturated)?;
+        self.publish_on_healthy(subject.into(), payload.into()).await
+    }
+
+    async fn publish_on_healthy(&self, subject: String, payload: Bytes) -> Result<(), async_nats::Error> {
+        let (slot, conn) = self.next_healthy().ok_or(PoolError::NoHealthyConnections)?;
+        let res = conn.publish(subject, payload).await;
+        if res.is_err() { self.mark_unhealthy(slot); }
+        res
     }
 
     /// Publish batch of messages
     pub async fn publish_batch(&self, messages: Vec<(String, Vec<u8>)>) -> Result<(), async_nats::Error> {
         let _permit = self.semaphore.acquire().await.unwrap();
-        let conn = self.get_connection();
-        
+        let (slot, conn) = self.next_healthy().ok_or(PoolError::NoHealthyConnections)?;
         for (subject, payload) in messages {
-            conn.publish(subject, payload.into()).await?;
+            if let Err(e) = conn.publish(subject, payload.into()).await { self.mark_unhealthy(slot); return Err(e); }
         }
-        
         Ok(())
     }
 
     /// Get pool size
     pub fn size(&self) -> usize {
-        self.connections.len()
+        self.slots.len()
+    }
+
+    pub fn healthy_connections(&self) -> usize { self.slots.iter().filter(|s| s.healthy.load(Ordering::Acquire)).count() }
+
+    fn report_health(&self) {
+        HEALTHY.lock().insert(self.name.clone(), self.healthy_connections() as u64);
+        Lazy::force(&HEALTHY_GAUGE);
+    }
+
+    /// Ping every slot and reconnect the ones that fail; returns the healthy count.
+    pub async fn check_health(&self) -> usize {
+        for (i, slot) in self.slots.iter().enumerate() {
+            let conn = slot.conn.read().clone();
+            if conn.ping().await { slot.healthy.store(true, Ordering::Release); continue; }
+            slot.healthy.store(false, Ordering::Release);
+            match (self.connector)().await {
+                Ok(fresh) => { *slot.conn.write() = Arc::new(fresh); slot.healthy.store(true, Ordering::Release); info!(slot=i, "NATS connection replaced"); }
+                Err(e) => warn!(slot=i, error=?e, "NATS reconnect failed"),
+            }
+        }
+        self.report_health();
+        self.healthy_connections()
+    }
+
+    /// Run `check_health` every `interval` until the pool is dropped.
+    pub fn spawn_health_checks(self: &Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let pool = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(interval);
+            tick.tick().await; // first tick is immediate; connections were just opened
+            loop {
+                tick.tick().await;
+                let Some(pool) = pool.upgrade() else { break };
+                pool.check_health().await;
+            }
+        })
     }
 }
 
+impl<C: PoolConn> Drop for NatsPool<C> {
+    fn drop(&mut self) { HEALTHY.lock().remove(&self.name); }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::AtomicUsize;
 
     #[tokio::test]
     #[ignore] // Requires NATS server
@@ -75,14 +226,115 @@ mod tests {
     #[ignore]
     async fn test_round_robin() {
         let pool = NatsPool::new("127.0.0.1:4222", 3).await.unwrap();
-        
+
         // Get connections and verify round-robin
-        let c1 = pool.get_connection();
-        let c2 = pool.get_connection();
-        let c3 = pool.get_connection();
-        let c4 = pool.get_connection();
-        
+        let c1 = pool.get_connection().unwrap();
+        let c2 = pool.get_connection().unwrap();
+        let c3 = pool.get_connection().unwrap();
+        let c4 = pool.get_connection().unwrap();
+
         // c4 should be same as c1 (wrapped around)
         assert!(Arc::ptr_eq(&c1, &c4));
+        assert!(!Arc::ptr_eq(&c2, &c3));
+    }
+
+    const STALL_PERMITS: u32 = 1 << 16;
+
+    struct StubConn { id: usize, alive: Arc<AtomicBool>, published: Arc<Mutex<Vec<usize>>>, stall: Arc<tokio::sync::Semaphore> }
+
+    impl PoolConn for StubConn {
+        async fn publish(&self, _subject: String, _payload: Bytes) -> Result<(), async_nats::Error> {
+            drop(self.stall.acquire().await.unwrap()); // blocks while the test holds every permit (slow broker)
+            if !self.alive.load(Ordering::SeqCst) { return Err("connection closed".into()); }
+            self.published.lock().push(self.id);
+            Ok(())
+        }
+        async fn ping(&self) -> bool { self.alive.load(Ordering::SeqCst) }
+    }
+
+    /// Connector handing out stub connections; `alive[i]` controls connection `i`.
+    struct Stubs { alive: Arc<Mutex<Vec<Arc<AtomicBool>>>>, published: Arc<Mutex<Vec<usize>>>, connect_ok: Arc<AtomicBool>, stall: Arc<tokio::sync::Semaphore> }
+
+    impl Stubs {
+        fn new() -> Self { Self { alive: Arc::default(), published: Arc::default(), connect_ok: Arc::new(AtomicBool::new(true)), stall: Arc::new(tokio::sync::Semaphore::new(STALL_PERMITS as usize)) } }
+        fn connector(&self) -> Connector<StubConn> {
+            let (alive, published, connect_ok, stall, next) = (self.alive.clone(), self.published.clone(), self.connect_ok.clone(), self.stall.clone(), Arc::new(AtomicUsize::new(0)));
+            Arc::new(move || {
+                let (alive, published, connect_ok, stall, next) = (alive.clone(), published.clone(), connect_ok.clone(), stall.clone(), next.clone());
+                Box::pin(async move {
+                    if !connect_ok.load(Ordering::SeqCst) { return Err("connection refused".into()); }
+                    let flag = Arc::new(AtomicBool::new(true));
+                    alive.lock().push(flag.clone());
+                    Ok(StubConn { id: next.fetch_add(1, Ordering::SeqCst), alive: flag, published, stall })
+                })
+            })
+        }
+        fn kill(&self, id: usize) { self.alive.lock()[id].store(false, Ordering::SeqCst); }
+    }
+
+    #[tokio::test]
+    async fn dead_connection_is_skipped_then_replaced() {
+        let stubs = Stubs::new();
+        let pool = NatsPool::with_connector(3, stubs.connector()).await.unwrap();
+        assert_eq!(pool.healthy_connections(), 3);
+
+        stubs.kill(0);
+        stubs.connect_ok.store(false, Ordering::SeqCst); // broker briefly refuses reconnects
+        assert_eq!(pool.check_health().await, 2);
+        for _ in 0..6 { pool.publish("ingest.v1.raw", Bytes::from_static(b"x")).await.unwrap(); }
+        assert!(stubs.published.lock().iter().all(|&id| id == 1 || id == 2), "dead slot used: {:?}", stubs.published.lock());
+
+        stubs.connect_ok.store(true, Ordering::SeqCst);
+        assert_eq!(pool.check_health().await, 3);
+        stubs.published.lock().clear();
+        for _ in 0..3 { pool.publish("ingest.v1.raw", Bytes::from_static(b"x")).await.unwrap(); }
+        assert!(stubs.published.lock().contains(&3), "replacement connection not used");
+    }
+
+    #[tokio::test]
+    async fn try_publish_fails_fast_while_the_only_permit_is_held() {
+        let stubs = Stubs::new();
+        let pool = Arc::new(NatsPool::with_connector(2, stubs.connector()).await.unwrap().with_max_in_flight(1));
+        let held = stubs.stall.acquire_many(STALL_PERMITS).await.unwrap(); // stall the broker
+        let first = tokio::spawn({ let pool = pool.clone(); async move { pool.try_publish("s", Bytes::new()).await } });
+        while pool.in_flight() == 0 { tokio::task::yield_now().await; }
+
+        let err = pool.try_publish("s", Bytes::new()).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<PoolError>(), Some(PoolError::Saturated)), "{err}");
+        assert_eq!(pool.in_flight(), 1);
+
+        drop(held);
+        first.await.unwrap().unwrap();
+        assert_eq!(pool.in_flight(), 0);
+        pool.try_publish("s", Bytes::new()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn publish_failure_marks_slot_and_empty_pool_errors_distinctly() {
+        let stubs = Stubs::new();
+        let pool = NatsPool::with_connector(2, stubs.connector()).await.unwrap();
+        stubs.kill(0);
+        stubs.kill(1);
+        assert!(pool.publish("s", Bytes::new()).await.is_err());
+        assert!(pool.publish("s", Bytes::new()).await.is_err());
+        assert_eq!(pool.healthy_connections(), 0);
+        let err = pool.publish("s", Bytes::new()).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<PoolError>(), Some(PoolError::NoHealthyConnections)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn healthy_gauge_is_kept_per_pool() {
+        let (a, b) = (Stubs::new(), Stubs::new());
+        let pool_a = NatsPool::with_connector(2, a.connector()).await.unwrap().with_name("gauge-a");
+        let pool_b = NatsPool::with_connector(3, b.connector()).await.unwrap().with_name("gauge-b");
+        a.kill(0);
+        a.connect_ok.store(false, Ordering::SeqCst);
+        pool_a.check_health().await;
+        let healthy = |name: &str| HEALTHY.lock().get(name).copied();
+        assert_eq!((healthy("gauge-a"), healthy("gauge-b")), (Some(1), Some(3)), "one pool's health must not overwrite another's");
+        drop(pool_a);
+        assert_eq!(healthy("gauge-a"), None, "dropped pools stop reporting");
+        assert_eq!(pool_b.check_health().await, 3);
+        assert_eq!(healthy("gauge-b"), Some(3));
     }
 }
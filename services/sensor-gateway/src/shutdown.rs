@@ -0,0 +1,110 @@
+//! Graceful shutdown for the ingest loops.
+//!
+//! SIGTERM/Ctrl-C cancels the shared `CancellationToken`: status goes to `draining`, the loops
+//! stop taking new lines, and the line already in `process_line` gets up to `SHUTDOWN_GRACE_SECS`
+//! (default 10) to finish publishing before status goes to `offline`.
+
+use std::future::Future;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+pub const DRAINING: &[u8] = b"draining";
+pub const OFFLINE: &[u8] = b"offline";
+
+pub fn grace_from_env() -> Duration {
+    Duration::from_secs(std::env::var("SHUTDOWN_GRACE_SECS").ok().and_then(|v| v.parse().ok()).unwrap_or(10))
+}
+
+/// Cancel `token` on the first SIGTERM or Ctrl-C.
+pub fn spawn_signal_listener(token: CancellationToken) {
+    tokio::spawn(async move {
+        #[cfg(unix)]
+        {
+            let mut term = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(s) => s,
+                Err(e) => { warn!(error=?e, "SIGTERM handler unavailable"); let _ = tokio::signal::ctrl_c().await; token.cancel(); return; }
+            };
+            tokio::select! { _ = term.recv() => {}, _ = tokio::signal::ctrl_c() => {} }
+        }
+        #[cfg(not(unix))]
+        let _ = tokio::signal::ctrl_c().await;
+        info!("shutdown signal received");
+        token.cancel();
+    });
+}
+
+/// Drive `work` to completion, or on cancellation publish `draining` and give it `grace` to
+/// finish; `offline` is published either way. `None` when the grace period ran out.
+pub async fn run_with_drain<T, S, F>(work: impl Future<Output = T>, token: &CancellationToken, grace: Duration, mut status: S) -> Option<T>
+where S: FnMut(&'static [u8]) -> F, F: Future<Output = ()> {
+    tokio::pin!(work);
+    let out = tokio::select! {
+        out = &mut work => Some(out),
+        _ = token.cancelled() => {
+            status(DRAINING).await;
+            let drained = tokio::time::timeout(grace, &mut work).await.ok();
+            if drained.is_none() { warn!(?grace, "in-flight events not drained before grace period"); }
+            drained
+        }
+    };
+    status(OFFLINE).await;
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+
+    type Published = Arc<Mutex<Vec<&'static [u8]>>>;
+
+    fn recorder() -> (Published, impl FnMut(&'static [u8]) -> std::future::Ready<()>) {
+        let published = Arc::new(Mutex::new(Vec::new()));
+        let sink = published.clone();
+        (published, move |s| { sink.lock().push(s); std::future::ready(()) })
+    }
+
+    #[tokio::test]
+    async fn drains_in_flight_event_then_publishes_offline() {
+        let token = CancellationToken::new();
+        let (published, status) = recorder();
+        let processed = Arc::new(Mutex::new(Vec::new()));
+        let (third_started, started) = tokio::sync::oneshot::channel();
+        let signal = token.clone();
+        tokio::spawn(async move { let _ = started.await; signal.cancel(); }); // SIGTERM while event 2 is publishing
+        let work = {
+            let (token, processed) = (token.clone(), processed.clone());
+            let mut third_started = Some(third_started);
+            async move {
+                for i in 0.. {
+                    if token.is_cancelled() { break; }
+                    if i == 2 { let _ = third_started.take().unwrap().send(()); }
+                    tokio::time::sleep(Duration::from_millis(20)).await; // publish in flight
+                    processed.lock().push(i);
+                }
+            }
+        };
+        assert_eq!(run_with_drain(work, &token, Duration::from_secs(5), status).await, Some(()));
+        assert_eq!(*processed.lock(), vec![0, 1, 2]);
+        assert_eq!(*published.lock(), vec![DRAINING, OFFLINE]);
+    }
+
+    #[tokio::test]
+    async fn grace_period_bounds_a_stuck_publish() {
+        let token = CancellationToken::new();
+        let (published, status) = recorder();
+        token.cancel();
+        let out = run_with_drain(std::future::pending::<()>(), &token, Duration::from_millis(50), status).await;
+        assert_eq!(out, None);
+        assert_eq!(*published.lock(), vec![DRAINING, OFFLINE]);
+    }
+
+    #[tokio::test]
+    async fn completed_work_goes_straight_offline() {
+        let (published, status) = recorder();
+        assert_eq!(run_with_drain(async { 7 }, &CancellationToken::new(), Duration::from_secs(1), status).await, Some(7));
+        assert_eq!(*published.lock(), vec![OFFLINE]);
+    }
+}
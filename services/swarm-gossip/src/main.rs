@@ -1,13 +1,37 @@
+//! Push gossip over NATS with TTL-bounded forwarding, plus periodic anti-entropy: every
+//! `GOSSIP_SYNC_SECS` (default 30) a node sends a random peer the ids of its most recent messages
+//! (at most `GOSSIP_DIGEST_MAX`, default 256) and gets back up to `GOSSIP_REPAIR_MAX` (default 64)
+//! retained messages it lacks. Each node retains the last `GOSSIP_RETAIN` (default 1024) messages.
+//! Envelopes are ed25519-signed by their originator (see `signing`); repaired messages are verified
+//! like live ones.
+//!
+//! Forwarding fanout is `GOSSIP_FANOUT` (default 4), or with `GOSSIP_FANOUT=auto`
+//! `ceil(log2(peers)) + 1` clamped to `GOSSIP_FANOUT_MIN`..=`GOSSIP_FANOUT_MAX` (default 2..=8),
+//! recomputed as peers join; the value in use is exported as `gossip_effective_fanout`.
+//!
+//! Peers are remembered with the time they were last heard from (hello, or any envelope they
+//! signed as `from`); those silent for `GOSSIP_PEER_TTL_SECS` (default 60, four missed hellos)
+//! are pruned. `gossip_peer_count` / `gossip_peers_pruned_total` track membership.
+//!
+//! Fanout targets are sampled with probability proportional to peer reputation
+//! (`swarm_core::ReputationService`); peers below `GOSSIP_MIN_REPUTATION` (unset: none) are never
+//! picked. Only authenticated evidence costs a peer reputation: being pruned as stale, or signing
+//! a hello that announces a different node id. Envelopes failing verification never do, since
+//! their `from` is unauthenticated. Penalties decay back toward neutral over time.
+
 use anyhow::Result;
 use tracing::{info, warn, debug};
-use swarm_core::{init_tracing, start_health_server, init_metrics};
-use std::{collections::{HashSet}, sync::Arc, time::{Duration, Instant}};
+use swarm_core::{init_tracing, start_health_server, init_metrics, ReputationConfig, ReputationService};
+use std::{collections::{HashMap, HashSet, VecDeque}, sync::{atomic::{AtomicU64, Ordering}, Arc}, time::{Duration, Instant}};
 use parking_lot::RwLock;
-use rand::{seq::IteratorRandom, thread_rng};
+use rand::{seq::SliceRandom, thread_rng};
 use serde::{Serialize, Deserialize};
 use opentelemetry::global;
 use sha2::{Sha256, Digest};
 
+mod signing;
+use signing::GossipKeys;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct GossipEnvelope<T> {
     msg_id: String,
@@ -15,64 +39,196 @@ struct GossipEnvelope<T> {
     ts: u64,
     payload: T,
     hops: u8,
+    #[serde(default)]
+    from: String, // originating node_id, the signer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sig: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct GossipHello { node_id: String }
 
+/// Anti-entropy pull: the ids the requester already has.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SyncRequest { from: String, have: Vec<String> }
+
+/// Retained messages missing from the requester's digest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SyncReply { messages: Vec<serde_json::Value> }
+
+fn env_usize(key: &str, default: usize) -> usize { std::env::var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(default) }
+
+static EFFECTIVE_FANOUT: AtomicU64 = AtomicU64::new(0);
+static PEER_COUNT: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FanoutMode { Fixed(usize), Auto { min: usize, max: usize } }
+
+impl FanoutMode {
+    fn from_env() -> Self {
+        match std::env::var("GOSSIP_FANOUT").as_deref() {
+            Ok("auto") => {
+                let min = env_usize("GOSSIP_FANOUT_MIN", 2).max(1);
+                Self::Auto { min, max: env_usize("GOSSIP_FANOUT_MAX", 8).max(min) }
+            }
+            Ok(v) => Self::Fixed(v.parse().unwrap_or(4)),
+            Err(_) => Self::Fixed(4),
+        }
+    }
+
+    fn effective(&self, peers: usize) -> usize {
+        match *self {
+            Self::Fixed(n) => n,
+            Self::Auto { min, max } => {
+                let ceil_log2 = if peers <= 1 { 0 } else { (usize::BITS - (peers - 1).leading_zeros()) as usize };
+                (ceil_log2 + 1).clamp(min, max)
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
-// --- Bloom filter for duplicate suppression (aging) ---
+// --- Bloom filter for duplicate suppression (two generations) ---
+// Lookups check both generations, inserts go to `current`; each `rotate_after` the old generation is
+// dropped and `current` becomes old, so an id stays "seen" for one to two intervals instead of being
+// forgotten the instant a single filter is wiped.
 struct BloomDupFilter {
-    bits: Vec<u8>,
+    current: Vec<u8>,
+    old: Vec<u8>,
     mask: usize,
-    last_reset: Instant,
-    reset_after: Duration,
+    last_rotate: Instant,
+    rotate_after: Duration,
 }
 
 impl BloomDupFilter {
-    fn new(size_pow2: usize, reset_after: Duration) -> Self {
-        let size = size_pow2.next_power_of_two();
-        Self { bits: vec![0; size/8], mask: size - 1, last_reset: Instant::now(), reset_after }
-    }
-    fn maybe_reset(&mut self) {
-        if self.last_reset.elapsed() >= self.reset_after {
-            for b in &mut self.bits { *b = 0; }
-            self.last_reset = Instant::now();
-        }
+    fn new(size_pow2: usize, rotate_after: Duration) -> Self {
+        let size = size_pow2.next_power_of_two().max(8);
+        Self { current: vec![0; size/8], old: vec![0; size/8], mask: size - 1, last_rotate: Instant::now(), rotate_after }
     }
-    fn seen_or_insert(&mut self, id: &str) -> bool { // returns true if new (probabilistic)
-        self.maybe_reset();
+    fn rotate(&mut self) {
+        self.old = std::mem::replace(&mut self.current, vec![0; self.old.len()]);
+        self.last_rotate = Instant::now();
+    }
+    fn maybe_rotate(&mut self) { if self.last_rotate.elapsed() >= self.rotate_after { self.rotate(); } }
+    fn positions(&self, id: &str) -> (usize, usize) {
         use std::hash::{Hash, Hasher};
         let mut h1 = std::collections::hash_map::DefaultHasher::new();
         id.hash(&mut h1);
         let mut h2 = std::collections::hash_map::DefaultHasher::new();
         (0x9e3779b97f4a7c15u64).hash(&mut h2);
         id.as_bytes().iter().rev().for_each(|b| b.hash(&mut h2));
-        let a = (h1.finish() as usize) & self.mask;
-        let b = (h2.finish() as usize) & self.mask;
-        let hit_a = self.set_bit(a);
-        let hit_b = self.set_bit(b);
-        !(hit_a && hit_b)
+        ((h1.finish() as usize) & self.mask, (h2.finish() as usize) & self.mask)
     }
-    fn set_bit(&mut self, idx: usize) -> bool {
-        let byte = idx >> 3; let bit = idx & 7; let m = 1u8 << bit; let prev = self.bits[byte] & m != 0; self.bits[byte] |= m; prev
+    fn seen_or_insert(&mut self, id: &str) -> bool { // returns true if new (probabilistic)
+        self.maybe_rotate();
+        let (a, b) = self.positions(id);
+        let seen = (test_bit(&self.current, a) && test_bit(&self.current, b)) || (test_bit(&self.old, a) && test_bit(&self.old, b));
+        set_bit(&mut self.current, a);
+        set_bit(&mut self.current, b);
+        !seen
     }
 }
 
+fn test_bit(bits: &[u8], idx: usize) -> bool { bits[idx >> 3] & (1u8 << (idx & 7)) != 0 }
+fn set_bit(bits: &mut [u8], idx: usize) { bits[idx >> 3] |= 1u8 << (idx & 7); }
+
 struct GossipState {
-    peers: HashSet<String>,
+    peers: HashMap<String, Instant>, // peer -> last heard from
     dup_filter: BloomDupFilter,
     node_id: String,
+    retained: VecDeque<(String, serde_json::Value)>, // recent messages, oldest first, for repair
+    retained_ids: HashSet<String>,
+    retain_cap: usize,
+    keys: GossipKeys,
+    fanout_mode: FanoutMode,
+    fanout: usize, // effective fanout for the current peer count
+    reputation: ReputationService,
+    min_reputation: Option<f64>, // peers scoring below are never selected
 }
 
 impl GossipState {
-    fn new(node_id: String) -> Self { Self { peers: HashSet::new(), dup_filter: BloomDupFilter::new(1<<17, Duration::from_secs(60)), node_id } }
-    fn add_peer(&mut self, p: String) { if p != self.node_id { self.peers.insert(p); } }
+    fn new(node_id: String) -> Self {
+        Self { peers: HashMap::new(), dup_filter: BloomDupFilter::new(1<<17, Duration::from_secs(60)), node_id, retained: VecDeque::new(), retained_ids: HashSet::new(), retain_cap: env_usize("GOSSIP_RETAIN", 1024).max(1), keys: GossipKeys::default(), fanout_mode: FanoutMode::Fixed(4), fanout: 4, reputation: ReputationService::new(ReputationConfig::default()), min_reputation: std::env::var("GOSSIP_MIN_REPUTATION").ok().and_then(|v| v.parse().ok()) }.with_fanout_mode(FanoutMode::from_env())
+    }
+    fn with_fanout_mode(mut self, mode: FanoutMode) -> Self { self.fanout_mode = mode; self.update_fanout(); self }
+    fn update_fanout(&mut self) {
+        self.fanout = self.fanout_mode.effective(self.peers.len());
+        EFFECTIVE_FANOUT.store(self.fanout as u64, Ordering::Relaxed);
+        PEER_COUNT.store(self.peers.len() as u64, Ordering::Relaxed);
+    }
+    fn add_peer(&mut self, p: String) { self.add_peer_at(p, Instant::now()) }
+    fn add_peer_at(&mut self, p: String, now: Instant) {
+        if p != self.node_id && self.peers.insert(p, now).is_none() { self.update_fanout(); }
+    }
+    /// Refresh a known peer's last-seen time; unknown ids are left to hello-based discovery.
+    fn touch_peer(&mut self, p: &str, now: Instant) { if let Some(seen) = self.peers.get_mut(p) { *seen = now; } }
+    /// Drop peers not heard from within `max_age`; returns how many were removed.
+    fn prune_stale(&mut self, max_age: Duration) -> usize { self.prune_stale_at(max_age, Instant::now()) }
+    fn prune_stale_at(&mut self, max_age: Duration, now: Instant) -> usize {
+        let before = self.peers.len();
+        let reputation = &self.reputation;
+        self.peers.retain(|p, seen| {
+            let live = now.saturating_duration_since(*seen) <= max_age;
+            if !live { reputation.penalize_at(p, now); }
+            live
+        });
+        let pruned = before - self.peers.len();
+        if pruned > 0 { self.update_fanout(); }
+        pruned
+    }
+    /// Add the peer a hello announces; false if its signature fails (no penalty, see module docs).
+    /// With keys enforced, a correctly signed hello naming another node id is the signer's own
+    /// misbehaviour and costs it reputation.
+    fn accept_hello(&mut self, env: &serde_json::Value) -> bool {
+        if !self.keys.verify(env) { return false; }
+        let node_id = env.get("payload").and_then(|p| p.get("node_id")).and_then(|v| v.as_str()).unwrap_or("");
+        let from = env.get("from").and_then(|v| v.as_str()).unwrap_or("");
+        if self.keys.enforcing() && node_id != from {
+            self.reputation.penalize(from);
+            warn!(%from, announced=%node_id, "signed_hello_for_other_node");
+        } else if !node_id.is_empty() {
+            self.add_peer(node_id.to_string());
+        }
+        true
+    }
     fn record(&mut self, id: &str) -> bool { self.dup_filter.seen_or_insert(id) }
+    /// Up to `fanout` distinct peers, sampled with probability proportional to reputation.
     fn random_fanout(&self, fanout: usize) -> Vec<String> {
-        if self.peers.is_empty() { return vec![]; }
+        let candidates: Vec<(&String, f64)> = self.peers.keys().map(|p| (p, self.reputation.score(p)))
+            .filter(|(_, s)| !matches!(self.min_reputation, Some(min) if *s < min)).collect();
+        if candidates.is_empty() { return vec![]; }
         let mut rng = thread_rng();
-        self.peers.iter().cloned().choose_multiple(&mut rng, fanout)
+        match candidates.choose_multiple_weighted(&mut rng, fanout.min(candidates.len()), |(_, s)| s.max(1e-3)) {
+            Ok(chosen) => chosen.map(|(p, _)| (*p).clone()).collect(),
+            Err(_) => candidates.into_iter().take(fanout).map(|(p, _)| p.clone()).collect(),
+        }
+    }
+    fn retain_msg(&mut self, id: &str, msg: serde_json::Value) {
+        if !self.retained_ids.insert(id.to_string()) { return; }
+        self.retained.push_back((id.to_string(), msg));
+        while self.retained.len() > self.retain_cap {
+            if let Some((old, _)) = self.retained.pop_front() { self.retained_ids.remove(&old); }
+        }
+    }
+    /// Ids of the newest `max` retained messages.
+    fn digest(&self, max: usize) -> Vec<String> { self.retained.iter().rev().take(max).map(|(id, _)| id.clone()).collect() }
+    /// Up to `max` retained messages not in `have`, newest first.
+    fn repair_for(&self, have: &[String], max: usize) -> Vec<serde_json::Value> {
+        let have: HashSet<&str> = have.iter().map(String::as_str).collect();
+        self.retained.iter().rev().filter(|(id, _)| !have.contains(id.as_str())).take(max).map(|(_, m)| m.clone()).collect()
+    }
+    /// Take in repaired messages, verified like live ones; returns (new here, dropped for a bad signature).
+    fn apply_repair(&mut self, messages: Vec<serde_json::Value>) -> (usize, usize) {
+        let (mut fresh, mut invalid) = (0, 0);
+        for msg in messages {
+            let Some(id) = msg.get("msg_id").and_then(|v| v.as_str()).map(str::to_string) else { continue };
+            if self.retained_ids.contains(&id) { continue; }
+            if !self.keys.verify(&msg) { invalid += 1; debug!(msg_id=%id, "invalid_signature_in_repair"); continue; }
+            self.record(&id);
+            self.retain_msg(&id, msg);
+            fresh += 1;
+        }
+        (fresh, invalid)
     }
 }
 
@@ -91,6 +247,7 @@ async fn run_gossip_loop(nc: async_nats::Client, state: Arc<RwLock<GossipState>>
     let fwd_counter = meter.u64_counter("gossip_forwarded_total").with_description("Total gossip messages forwarded").init();
     let recv_counter = meter.u64_counter("gossip_received_total").with_description("Total gossip messages received (unique)").init();
     let fanout_hist = meter.i64_histogram("gossip_fanout_size").with_description("Fanout size per forwarded message").init();
+    let invalid_sig = meter.u64_counter("gossip_invalid_signature_total").with_description("Gossip envelopes dropped for a missing or invalid signature").init();
     let start = Instant::now();
     while let Some(msg) = sub.next().await {
         if let Ok(txt) = std::str::from_utf8(&msg.payload) {
@@ -98,14 +255,22 @@ async fn run_gossip_loop(nc: async_nats::Client, state: Arc<RwLock<GossipState>>
                 let id_opt = val.get("msg_id").and_then(|v| v.as_str());
                 if let Some(id) = id_opt {
                     let mut st = state.write();
+                    if !st.keys.verify(&val) {
+                        // no penalty: `from` is unauthenticated, so blaming it would let anyone smear a peer
+                        invalid_sig.add(1, &[]); debug!(msg_id=%id, "invalid_signature_dropped");
+                        continue;
+                    }
                     if !st.record(id) { dup_counter.add(1, &[]); continue; }
                     recv_counter.add(1, &[]);
-                    // forward if hops < ttl
+                    if let Some(from) = val.get("from").and_then(|f| f.as_str()) { st.touch_peer(from, Instant::now()); }
+                    if val.get("kind").and_then(|k| k.as_str()) != Some("hello") { st.retain_msg(id, val.clone()); }
                     let hops = val.get("hops").and_then(|h| h.as_u64()).unwrap_or(0) as u8;
+                    // only first-hop messages: forwarded ones carry relay delay on top of any skew
+                    if hops == 0 { if let Some(ts) = val.get("ts").and_then(|t| t.as_i64()) { swarm_core::CLOCK_HEALTH.observe_peer_timestamp(ts); } }
+                    // forward if hops < ttl
                     let ttl: u8 = std::env::var("GOSSIP_TTL_HOPS").ok().and_then(|v| v.parse().ok()).unwrap_or(8);
                     if hops < ttl { // forward
-                        let fanout_cfg: usize = std::env::var("GOSSIP_FANOUT").ok().and_then(|v| v.parse().ok()).unwrap_or(4);
-                        let targets = st.random_fanout(fanout_cfg);
+                        let targets = st.random_fanout(st.fanout);
                         let mut forwarded = 0;
                         for peer in targets.iter() {
                             let mut clone = val.clone();
@@ -128,25 +293,72 @@ async fn run_peer_listener(nc: async_nats::Client, state: Arc<RwLock<GossipState
     // listen on peer direct subjects
     let pattern = format!("{subject_prefix}.peer.");
     let sub = match nc.subscribe(format!("{subject_prefix}.>")).await { Ok(s) => s, Err(e) => { warn!(error=?e, "subscribe_failed"); return; } };
+    let invalid_sig = global::meter("swarm-gossip").u64_counter("gossip_invalid_signature_total").with_description("Gossip envelopes dropped for a missing or invalid signature").init();
     while let Some(msg) = sub.next().await {
         if let Some(last) = msg.subject.split('.').last() {
             if last == "inbox" { continue; }
             if last != "peer" { // treat last as peer id or message variant
                 // quick heuristic: if payload is hello, add peer
                 if let Ok(txt) = std::str::from_utf8(&msg.payload) {
-                    if let Ok(env) = serde_json::from_str::<serde_json::Value>(txt) { if env.get("kind").and_then(|v| v.as_str()) == Some("hello") {
-                        let node_id = env.get("payload").and_then(|p| p.get("node_id")).and_then(|v| v.as_str()).unwrap_or("");
-                        if !node_id.is_empty() { state.write().add_peer(node_id.to_string()); }
-                    }}
+                    let hello = serde_json::from_str::<serde_json::Value>(txt).ok().filter(|env| env.get("kind").and_then(|v| v.as_str()) == Some("hello"));
+                    if let Some(env) = hello { if !state.write().accept_hello(&env) { invalid_sig.add(1, &[]); } }
                 }
             }
         }
     }
 }
 
+/// Answer peers' sync requests on `{prefix}.sync.{node_id}` with what their digest lacks.
+async fn run_sync_responder(nc: async_nats::Client, state: Arc<RwLock<GossipState>>, subject_prefix: String) {
+    let node_id = state.read().node_id.clone();
+    let mut sub = match nc.subscribe(format!("{subject_prefix}.sync.{node_id}")).await { Ok(s) => s, Err(e) => { warn!(error=?e, "subscribe_failed"); return; } };
+    let sent = global::meter("swarm-gossip").u64_counter("gossip_repair_sent_total").with_description("Gossip messages sent to peers in anti-entropy replies").init();
+    let repair_max = env_usize("GOSSIP_REPAIR_MAX", 64);
+    while let Some(msg) = sub.next().await {
+        let (Some(reply), Ok(req)) = (msg.reply, serde_json::from_slice::<SyncRequest>(&msg.payload)) else { continue };
+        let messages = state.read().repair_for(&req.have, repair_max);
+        if !messages.is_empty() { sent.add(messages.len() as u64, &[]); debug!(peer=%req.from, n=messages.len(), "sync_repair_sent"); }
+        if let Ok(buf) = serde_json::to_vec(&SyncReply { messages }) { let _ = nc.publish(reply, buf.into()).await; }
+    }
+}
+
+/// Periodically pull missed messages from one random peer.
+async fn run_anti_entropy(nc: async_nats::Client, state: Arc<RwLock<GossipState>>, subject_prefix: String) {
+    let received = global::meter("swarm-gossip").u64_counter("gossip_repair_received_total").with_description("Missed gossip messages recovered through anti-entropy").init();
+    let invalid_sig = global::meter("swarm-gossip").u64_counter("gossip_invalid_signature_total").with_description("Gossip envelopes dropped for a missing or invalid signature").init();
+    let digest_max = env_usize("GOSSIP_DIGEST_MAX", 256);
+    let mut interval = tokio::time::interval(Duration::from_secs(env_usize("GOSSIP_SYNC_SECS", 30).max(1) as u64));
+    loop {
+        interval.tick().await;
+        let (peer, req) = {
+            let st = state.read();
+            let Some(peer) = st.random_fanout(1).pop() else { continue };
+            (peer, SyncRequest { from: st.node_id.clone(), have: st.digest(digest_max) })
+        };
+        let Ok(buf) = serde_json::to_vec(&req) else { continue };
+        match tokio::time::timeout(Duration::from_secs(5), nc.request(format!("{subject_prefix}.sync.{peer}"), buf.into())).await {
+            Ok(Ok(resp)) => match serde_json::from_slice::<SyncReply>(&resp.payload) {
+                Ok(reply) => {
+                    let (fresh, invalid) = state.write().apply_repair(reply.messages);
+                    if invalid > 0 { invalid_sig.add(invalid as u64, &[]); warn!(%peer, invalid, "sync_reply_invalid_signatures"); }
+                    if fresh > 0 { received.add(fresh as u64, &[]); info!(%peer, fresh, "sync_repaired"); }
+                }
+                Err(e) => warn!(%peer, error=?e, "sync_reply_invalid"),
+            },
+            Ok(Err(e)) => debug!(%peer, error=?e, "sync_request_failed"),
+            Err(_) => debug!(%peer, "sync_request_timeout"),
+        }
+    }
+}
+
 async fn send_hello(nc: &async_nats::Client, state: &Arc<RwLock<GossipState>>, subject_prefix: &str) {
-    let st = state.read();
-    let env = GossipEnvelope { msg_id: make_msg_id(st.node_id.as_bytes()), kind: "hello".into(), ts: chrono::Utc::now().timestamp_millis() as u64, payload: GossipHello { node_id: st.node_id.clone() }, hops: 0 };
+    let env = {
+        let st = state.read();
+        let (msg_id, ts) = (make_msg_id(st.node_id.as_bytes()), chrono::Utc::now().timestamp_millis() as u64);
+        let payload = GossipHello { node_id: st.node_id.clone() };
+        let sig = serde_json::to_value(&payload).ok().and_then(|p| st.keys.sign(&msg_id, "hello", ts, &p, &st.node_id));
+        GossipEnvelope { msg_id, kind: "hello".into(), ts, payload, hops: 0, from: st.node_id.clone(), sig }
+    };
     publish_gossip(nc, &format!("{subject_prefix}.inbox"), &env).await;
 }
 
@@ -154,17 +366,31 @@ async fn send_hello(nc: &async_nats::Client, state: &Arc<RwLock<GossipState>>, s
 async fn main() -> Result<()> {
     init_tracing("swarm-gossip")?;
     init_metrics()?;
+    let _fanout_gauge = global::meter("swarm-gossip").u64_observable_gauge("gossip_effective_fanout")
+        .with_description("Gossip forwarding fanout currently in use")
+        .with_callback(|obs| obs.observe(EFFECTIVE_FANOUT.load(Ordering::Relaxed), &[]))
+        .init();
+    let _peer_gauge = global::meter("swarm-gossip").u64_observable_gauge("gossip_peer_count")
+        .with_description("Live gossip peers currently known")
+        .with_callback(|obs| obs.observe(PEER_COUNT.load(Ordering::Relaxed), &[]))
+        .init();
     start_health_server(8081).await?;
     let nats_url = std::env::var("NATS_URL").unwrap_or_else(|_| "127.0.0.1:4222".into());
     let node_id = std::env::var("NODE_ID").unwrap_or_else(|_| format!("node-{}", uuid::Uuid::new_v4().simple()));
     let subject_prefix = std::env::var("GOSSIP_SUBJECT_PREFIX").unwrap_or_else(|_| "swarm.gossip".into());
     info!(target: "swarm-gossip", %nats_url, %node_id, %subject_prefix, "Starting swarm-gossip service");
     let nc = async_nats::connect(nats_url).await?;
-    let state = Arc::new(RwLock::new(GossipState::new(node_id.clone())));
+    let mut gossip_state = GossipState::new(node_id.clone());
+    gossip_state.keys = GossipKeys::from_env(&node_id)?;
+    if !gossip_state.keys.enforcing() { warn!("GOSSIP_PEER_KEYS not set: gossip envelopes are not authenticated"); }
+    gossip_state.reputation.spawn_decay_task(Duration::from_secs(60));
+    let state = Arc::new(RwLock::new(gossip_state));
     send_hello(&nc, &state, &subject_prefix).await;
     // spawn loops
     tokio::spawn(run_gossip_loop(nc.clone(), state.clone(), subject_prefix.clone()));
     tokio::spawn(run_peer_listener(nc.clone(), state.clone(), subject_prefix.clone()));
+    tokio::spawn(run_sync_responder(nc.clone(), state.clone(), subject_prefix.clone()));
+    tokio::spawn(run_anti_entropy(nc.clone(), state.clone(), subject_prefix.clone()));
     // periodic hello (keep alive + membership)
     let hello_nc = nc.clone();
     let hello_state = state.clone();
@@ -173,10 +399,147 @@ async fn main() -> Result<()> {
         let mut interval = tokio::time::interval(Duration::from_secs(15));
         loop { interval.tick().await; send_hello(&hello_nc, &hello_state, &hp).await; }
     });
+    // drop peers that stopped saying hello
+    let prune_state = state.clone();
+    let peer_ttl = Duration::from_secs(env_usize("GOSSIP_PEER_TTL_SECS", 60).max(1) as u64);
+    let pruned_counter = global::meter("swarm-gossip").u64_counter("gossip_peers_pruned_total").with_description("Gossip peers removed after exceeding the liveness TTL").init();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval((peer_ttl / 4).max(Duration::from_secs(1)));
+        loop {
+            interval.tick().await;
+            let pruned = prune_state.write().prune_stale(peer_ttl);
+            if pruned > 0 { pruned_counter.add(pruned as u64, &[]); info!(pruned, "stale_peers_pruned"); }
+        }
+    });
     // idle loop until ctrl-c
     tokio::signal::ctrl_c().await?;
     info!("shutdown_signal_received");
+    swarm_core::shutdown_observability();
     Ok(())
 }
 
 // Tracing & metrics handled by swarm-core
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(id: &str) -> serde_json::Value { serde_json::json!({"msg_id": id, "kind": "alert", "ts": 0, "payload": {}, "hops": 1}) }
+
+    #[test]
+    fn auto_fanout_scales_with_peer_count() {
+        let auto = FanoutMode::Auto { min: 2, max: 16 };
+        assert_eq!([2, 16, 1024].map(|n| auto.effective(n)), [2, 5, 11]);
+        assert_eq!(FanoutMode::Auto { min: 2, max: 8 }.effective(1024), 8);
+        assert_eq!(auto.effective(0), 2);
+        assert_eq!(FanoutMode::Fixed(4).effective(1024), 4);
+
+        let mut st = GossipState::new("self".into()).with_fanout_mode(auto);
+        for i in 0..16 { st.add_peer(format!("n{i}")); }
+        assert_eq!(st.fanout, 5);
+    }
+
+    #[test]
+    fn silent_peers_are_pruned_after_ttl() {
+        let mut st = GossipState::new("self".into());
+        let t0 = Instant::now();
+        st.add_peer_at("active".into(), t0);
+        st.add_peer_at("dead".into(), t0);
+        st.touch_peer("active", t0 + Duration::from_secs(50));
+        assert_eq!(st.prune_stale_at(Duration::from_secs(60), t0 + Duration::from_secs(61)), 1);
+        assert_eq!(st.random_fanout(8), vec!["active".to_string()]);
+        st.touch_peer("dead", t0 + Duration::from_secs(62));
+        assert!(!st.peers.contains_key("dead"), "touch must not resurrect a pruned peer");
+    }
+
+    #[test]
+    fn low_reputation_peer_is_selected_less_often() {
+        let mut st = GossipState::new("self".into());
+        st.add_peer("good".into());
+        st.add_peer("bad".into());
+        for _ in 0..8 { st.reputation.penalize("bad"); } // 500 -> 100
+        let (mut good, mut bad) = (0, 0);
+        for _ in 0..6000 { match st.random_fanout(1)[0].as_str() { "good" => good += 1, _ => bad += 1 } }
+        assert!(bad * 3 < good, "bad={bad} good={good}");
+        assert_eq!(st.random_fanout(8).len(), 2, "weighting never drops peers from a wide fanout");
+
+        st.min_reputation = Some(200.0);
+        assert!((0..100).all(|_| st.random_fanout(8) == vec!["good".to_string()]));
+    }
+
+    #[test]
+    fn pruned_peer_loses_reputation() {
+        let mut st = GossipState::new("self".into());
+        let t0 = Instant::now();
+        st.add_peer_at("dead".into(), t0);
+        st.prune_stale_at(Duration::from_secs(60), t0 + Duration::from_secs(61));
+        assert!(st.reputation.score("dead") < st.reputation.config().baseline);
+    }
+
+    #[test]
+    fn id_seen_just_before_rotation_stays_seen() {
+        let mut f = BloomDupFilter::new(1 << 12, Duration::from_secs(3600));
+        assert!(f.seen_or_insert("late"));
+        f.rotate();
+        assert!(!f.seen_or_insert("late"), "still in the old generation");
+        f.rotate();
+        assert!(!f.seen_or_insert("late"), "re-seen ids are carried into the current generation");
+        f.rotate();
+        f.rotate();
+        assert!(f.seen_or_insert("late"), "forgotten after two idle rotations");
+    }
+
+    #[test]
+    fn anti_entropy_round_recovers_missed_message() {
+        let (mut a, mut b) = (GossipState::new("a".into()), GossipState::new("b".into()));
+        for id in ["m1", "m2", "m3"] { a.record(id); a.retain_msg(id, msg(id)); }
+        for id in ["m1", "m3"] { b.record(id); b.retain_msg(id, msg(id)); } // b missed m2
+
+        let repair = a.repair_for(&b.digest(256), 64);
+        assert_eq!(repair, vec![msg("m2")]);
+        assert_eq!(b.apply_repair(repair), (1, 0));
+        assert!(!b.record("m2"), "repaired message must be marked seen");
+        assert!(a.repair_for(&b.digest(256), 64).is_empty());
+        assert_eq!(a.repair_for(&[], 2).len(), 2, "repair payload is bounded");
+    }
+
+    /// Signer for node "a" and a state "b" that trusts only "a".
+    fn trusting_a() -> (signing::GossipKeys, GossipState) {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[7; 32]).unwrap();
+        let public: ed25519_dalek::PublicKey = (&secret).into();
+        let mut b = GossipState::new("b".into());
+        b.keys = signing::GossipKeys::new(None, HashMap::from([("a".to_string(), public)]));
+        (signing::GossipKeys::new(Some(ed25519_dalek::Keypair { secret, public }), HashMap::new()), b)
+    }
+
+    #[test]
+    fn unsigned_repaired_message_is_rejected() {
+        let (origin, mut b) = trusting_a();
+
+        let mut signed = msg("m1");
+        signed["from"] = "a".into();
+        signed["sig"] = origin.sign("m1", "alert", 0, &signed["payload"], "a").unwrap().into();
+        let mut forged = msg("m2");
+        forged["from"] = "a".into(); // claims a trusted origin, carries no signature
+        assert_eq!(b.apply_repair(vec![signed, forged]), (1, 1));
+        assert_eq!(b.digest(256), vec!["m1".to_string()]);
+        assert!(b.record("m2"), "rejected message must not be marked seen");
+    }
+
+    #[test]
+    fn only_authenticated_misbehaviour_costs_reputation() {
+        let (origin, mut b) = trusting_a();
+        b.add_peer("a".into());
+        let baseline = b.reputation.config().baseline;
+        let hello = |node_id: &str, sig: Option<String>| serde_json::json!({"msg_id": "h", "kind": "hello", "ts": 1, "payload": {"node_id": node_id}, "hops": 0, "from": "a", "sig": sig});
+        let sign = |node_id: &str| origin.sign("h", "hello", 1, &serde_json::json!({"node_id": node_id}), "a");
+
+        for _ in 0..20 { assert!(!b.accept_hello(&hello("a", Some("00".repeat(64))))); } // forged in a's name
+        assert_eq!(b.reputation.score("a"), baseline, "unauthenticated `from` must not be penalized");
+
+        assert!(b.accept_hello(&hello("c", sign("c"))));
+        assert!(b.reputation.score("a") < baseline, "a signed a hello for another node");
+        assert!(!b.peers.contains_key("c"));
+        assert!(b.accept_hello(&hello("a", sign("a"))));
+    }
+}
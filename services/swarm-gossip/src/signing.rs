@@ -0,0 +1,104 @@
+//! Ed25519 signatures on gossip envelopes.
+//!
+//! The signature covers `(msg_id, kind, ts, payload, from)` - not `hops`, which relays bump. The
+//! node's secret key is read (hex) from `GOSSIP_NODE_KEY`, trusted peer keys from
+//! `GOSSIP_PEER_KEYS` (JSON `{"node_id": "pubkey hex"}`). Once peer keys are configured, envelopes
+//! that are unsigned, from an unknown node or fail verification are rejected.
+
+use std::collections::HashMap;
+use anyhow::{anyhow, Context, Result};
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signature, Signer, Verifier};
+use serde_json::Value;
+
+#[derive(Default)]
+pub struct GossipKeys {
+    signing: Option<Keypair>,
+    peers: HashMap<String, PublicKey>,
+}
+
+fn signed_bytes(msg_id: &str, kind: &str, ts: u64, payload: &Value, from: &str) -> Vec<u8> {
+    serde_json::to_vec(&(msg_id, kind, ts, payload, from)).unwrap_or_default()
+}
+
+impl GossipKeys {
+    pub fn new(signing: Option<Keypair>, peers: HashMap<String, PublicKey>) -> Self { Self { signing, peers } }
+
+    /// Load keys from `GOSSIP_NODE_KEY` / `GOSSIP_PEER_KEYS`; our own key is trusted for `node_id`.
+    pub fn from_env(node_id: &str) -> Result<Self> {
+        let signing = match std::env::var("GOSSIP_NODE_KEY") {
+            Ok(path) => {
+                let hex_key = std::fs::read_to_string(&path).with_context(|| format!("read GOSSIP_NODE_KEY {path}"))?;
+                let secret = SecretKey::from_bytes(&hex::decode(hex_key.trim())?).map_err(|e| anyhow!("invalid node key: {e}"))?;
+                let public: PublicKey = (&secret).into();
+                Some(Keypair { secret, public })
+            }
+            Err(_) => None,
+        };
+        let mut peers = HashMap::new();
+        if let Ok(path) = std::env::var("GOSSIP_PEER_KEYS") {
+            let raw: HashMap<String, String> = serde_json::from_str(&std::fs::read_to_string(&path).with_context(|| format!("read GOSSIP_PEER_KEYS {path}"))?)?;
+            for (node, pk) in raw {
+                let key = hex::decode(pk.trim()).ok().and_then(|b| PublicKey::from_bytes(&b).ok()).ok_or_else(|| anyhow!("invalid public key for peer {node}"))?;
+                peers.insert(node, key);
+            }
+        }
+        if let (Some(kp), false) = (&signing, peers.is_empty()) { peers.insert(node_id.to_string(), kp.public); }
+        Ok(Self::new(signing, peers))
+    }
+
+    /// Whether envelopes must carry a valid signature.
+    pub fn enforcing(&self) -> bool { !self.peers.is_empty() }
+
+    /// Hex signature for an outgoing envelope; `None` without a node key.
+    pub fn sign(&self, msg_id: &str, kind: &str, ts: u64, payload: &Value, from: &str) -> Option<String> {
+        self.signing.as_ref().map(|kp| hex::encode(kp.sign(&signed_bytes(msg_id, kind, ts, payload, from)).to_bytes()))
+    }
+
+    /// Check a received envelope; always true when not enforcing.
+    pub fn verify(&self, env: &Value) -> bool {
+        if !self.enforcing() { return true; }
+        let field = |k: &str| env.get(k).and_then(|v| v.as_str());
+        let (Some(msg_id), Some(kind), Some(from), Some(sig)) = (field("msg_id"), field("kind"), field("from"), field("sig")) else { return false };
+        let (Some(ts), Some(payload)) = (env.get("ts").and_then(|v| v.as_u64()), env.get("payload")) else { return false };
+        let Some(key) = self.peers.get(from) else { return false };
+        let Some(sig) = hex::decode(sig).ok().and_then(|b| Signature::from_bytes(&b).ok()) else { return false };
+        key.verify(&signed_bytes(msg_id, kind, ts, payload, from), &sig).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keypair(seed: u8) -> Keypair {
+        let secret = SecretKey::from_bytes(&[seed; 32]).unwrap();
+        let public: PublicKey = (&secret).into();
+        Keypair { secret, public }
+    }
+
+    #[test]
+    fn tampered_or_unknown_envelopes_are_rejected() {
+        let (a, mallory) = (keypair(1), keypair(2));
+        let verifier = GossipKeys::new(None, HashMap::from([("a".to_string(), a.public)]));
+        let signer = GossipKeys::new(Some(a), HashMap::new());
+        let payload = serde_json::json!({"node_id": "a"});
+        let sig = signer.sign("m1", "hello", 42, &payload, "a").unwrap();
+        let env = serde_json::json!({"msg_id": "m1", "kind": "hello", "ts": 42, "payload": payload, "hops": 0, "from": "a", "sig": sig});
+        assert!(verifier.verify(&env));
+
+        let mut forwarded = env.clone();
+        forwarded["hops"] = 3.into();
+        assert!(verifier.verify(&forwarded), "hops is not signed");
+        let mut tampered = env.clone();
+        tampered["payload"]["node_id"] = "evil".into();
+        assert!(!verifier.verify(&tampered));
+        let forged_sig = GossipKeys::new(Some(mallory), HashMap::new()).sign("m1", "hello", 42, &payload, "a").unwrap();
+        let mut forged = env.clone();
+        forged["sig"] = forged_sig.into();
+        assert!(!verifier.verify(&forged));
+        let mut unsigned = env;
+        unsigned.as_object_mut().unwrap().remove("sig");
+        assert!(!verifier.verify(&unsigned));
+        assert!(GossipKeys::default().verify(&unsigned), "no peer keys configured: not enforcing");
+    }
+}